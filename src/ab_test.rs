@@ -0,0 +1,169 @@
+use crate::config::Config;
+use crate::session;
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::RandomState;
+use std::fs;
+use std::hash::{BuildHasher, Hasher};
+use std::path::PathBuf;
+
+/// 比較する人格プロンプト (A = 現在の Persona Prompt, B = A/B Test Prompt)
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Variant {
+    A,
+    B,
+}
+
+impl Variant {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Variant::A => "A",
+            Variant::B => "B",
+        }
+    }
+
+    /// ランダムに A / B を選ぶ
+    pub fn random() -> Self {
+        if RandomState::new().build_hasher().finish() & 1 == 0 {
+            Variant::A
+        } else {
+            Variant::B
+        }
+    }
+
+    pub fn prompt<'a>(&self, config: &'a Config) -> &'a str {
+        match self {
+            Variant::A => &config.persona_prompt,
+            Variant::B => &config.ab_prompt_b,
+        }
+    }
+}
+
+/// 1 回分の返答
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AbRecord {
+    pub timestamp: u64,
+    pub variant: Variant,
+    pub user: String,
+    pub reply: String,
+    /// 1 = 👍, -1 = 👎
+    #[serde(default)]
+    pub rating: Option<i8>,
+}
+
+/// バリアントごとの集計
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VariantSummary {
+    pub variant: Variant,
+    pub replies: usize,
+    pub up: usize,
+    pub down: usize,
+}
+
+/// 人格プロンプトの A/B テスト (N ターンの間、返答ごとにランダムにプロンプトを切り替える)
+#[derive(Default)]
+pub struct AbTest {
+    started_at: u64,
+    remaining_turns: usize,
+    pub records: Vec<AbRecord>,
+}
+
+impl AbTest {
+    pub fn start(&mut self, turns: usize) {
+        self.started_at = session::now_unix_secs();
+        self.remaining_turns = turns;
+        self.records.clear();
+    }
+
+    pub fn stop(&mut self) {
+        self.remaining_turns = 0;
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.remaining_turns > 0
+    }
+
+    pub fn remaining_turns(&self) -> usize {
+        self.remaining_turns
+    }
+
+    /// 返答を記録する (ログに書き出すのは save_log)
+    pub fn record(&mut self, variant: Variant, user: String, reply: String) {
+        self.records.push(AbRecord {
+            timestamp: session::now_unix_secs(),
+            variant,
+            user,
+            reply,
+            rating: None,
+        });
+        self.remaining_turns = self.remaining_turns.saturating_sub(1);
+    }
+
+    pub fn rate(&mut self, index: usize, rating: i8) {
+        if let Some(record) = self.records.get_mut(index) {
+            record.rating = Some(rating);
+        }
+    }
+
+    pub fn summary(&self) -> Vec<VariantSummary> {
+        [Variant::A, Variant::B]
+            .into_iter()
+            .map(|variant| {
+                let records = self.records.iter().filter(|r| r.variant == variant);
+                VariantSummary {
+                    variant,
+                    replies: records.clone().count(),
+                    up: records.clone().filter(|r| r.rating == Some(1)).count(),
+                    down: records.filter(|r| r.rating == Some(-1)).count(),
+                }
+            })
+            .collect()
+    }
+
+    /// ログの保存先 (config_dir/ab_tests/<開始時刻>.json)
+    fn log_path(&self) -> Result<PathBuf, String> {
+        let dir = Config::config_dir()?.join("ab_tests");
+        if !dir.exists() {
+            fs::create_dir_all(&dir)
+                .map_err(|e| format!("Failed to create ab_tests directory: {}", e))?;
+        }
+        Ok(dir.join(format!("{}.json", self.started_at)))
+    }
+
+    /// 記録をログに書き出す (プライベートモード中は呼ばない)
+    pub fn save_log(&self) {
+        let result = self.log_path().and_then(|path| {
+            let content = serde_json::to_string_pretty(&self.records)
+                .map_err(|e| format!("Failed to serialize A/B log: {}", e))?;
+            fs::write(&path, content).map_err(|e| format!("Failed to write A/B log: {}", e))
+        });
+        if let Err(e) = result {
+            eprintln!("[A/B] {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summary() {
+        let mut test = AbTest {
+            remaining_turns: 3,
+            ..Default::default()
+        };
+        for (variant, rating) in [(Variant::A, Some(1)), (Variant::B, Some(-1)), (Variant::A, None)] {
+            test.records.push(AbRecord {
+                timestamp: 0,
+                variant,
+                user: String::new(),
+                reply: String::new(),
+                rating,
+            });
+        }
+        let summary = test.summary();
+        assert_eq!(summary[0], VariantSummary { variant: Variant::A, replies: 2, up: 1, down: 0 });
+        assert_eq!(summary[1], VariantSummary { variant: Variant::B, replies: 1, up: 0, down: 1 });
+        assert!(test.is_running());
+    }
+}
@@ -0,0 +1,100 @@
+//! ElizaAgentApp の状態遷移のテスト (設定ディレクトリは一時フォルダに差し替える)
+
+use crate::config::{self, Config};
+use crate::history::HistoryEntry;
+use crate::session::{Session, SessionStats};
+use crate::stats::PersonaStats;
+use crate::test_support::OscSink;
+use crate::{vrchat, ElizaAgentApp};
+use std::time::Instant;
+
+/// 一時フォルダを設定ディレクトリにし、OSC を OscSink に送るアプリ (Agent サーバーなし)
+struct TestApp {
+    app: ElizaAgentApp,
+    sink: OscSink,
+    _dir: tempfile::TempDir,
+}
+
+impl TestApp {
+    fn new() -> Self {
+        let dir = tempfile::tempdir().unwrap();
+        config::TEST_CONFIG_DIR.with(|d| *d.borrow_mut() = Some(dir.path().to_path_buf()));
+        let sink = OscSink::bind();
+        vrchat::TEST_SEND_PORT.with(|p| p.set(Some(sink.port())));
+        let config = Config {
+            agent_server_url: String::new(),
+            pause_when_vrchat_closed: false,
+            ..Config::default()
+        };
+        Self {
+            app: ElizaAgentApp::new(config),
+            sink,
+            _dir: dir,
+        }
+    }
+}
+
+impl Drop for TestApp {
+    fn drop(&mut self) {
+        config::TEST_CONFIG_DIR.with(|d| *d.borrow_mut() = None);
+        vrchat::TEST_SEND_PORT.with(|p| p.set(None));
+    }
+}
+
+#[test]
+fn test_switch_preset_saves_session_under_old_preset() {
+    let mut test = TestApp::new();
+    let app = &mut test.app;
+    app.config.persona_prompt = "default の人格".to_string();
+    app.active_session = Some("朝の会話".to_string());
+    app.conversation_history.push(HistoryEntry::new("You", "おはよう".to_string()));
+
+    app.switch_preset("setting1");
+
+    assert_eq!(app.current_preset, "setting1");
+    let saved = Session::load("朝の会話").unwrap();
+    assert_eq!(saved.preset, "default");
+    assert_eq!(saved.config.persona_prompt, "default の人格");
+}
+
+#[test]
+fn test_load_session_reloads_persona_stats() {
+    let mut test = TestApp::new();
+    let app = &mut test.app;
+    PersonaStats { turns: 7, ..PersonaStats::default() }.save("setting1").unwrap();
+    Session {
+        name: "別の人格".to_string(),
+        saved_at: 0,
+        preset: "setting1".to_string(),
+        config: Config::default(),
+        ui_history: Vec::new(),
+        agent_history: Vec::new(),
+        stats: SessionStats::default(),
+    }
+    .save()
+    .unwrap();
+
+    app.load_session("別の人格");
+
+    assert_eq!(app.current_preset, "setting1");
+    assert_eq!(app.persona_stats.turns, 7);
+}
+
+#[test]
+fn test_safety_stop_drops_pending_chatbox_messages() {
+    for wipe in [false, true] {
+        let mut test = TestApp::new();
+        let app = &mut test.app;
+        app.config.safety_word_wipe_history = wipe;
+        app.conversation_history.push(HistoryEntry::new("Agent", "住所は…".to_string()));
+        app.chatbox_buffer.push("保留した返答".to_string());
+        app.chatbox_retry.push("送れなかった返答".to_string(), Instant::now());
+
+        app.safety_stop(None);
+
+        assert!(app.chatbox_buffer.is_empty());
+        assert!(app.chatbox_retry.is_empty());
+        assert_eq!(app.conversation_history.is_empty(), wipe);
+        assert_eq!(test.sink.chatbox_texts(), vec![String::new()]);
+    }
+}
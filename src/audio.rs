@@ -1,5 +1,6 @@
+use crate::error::AppError;
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
 use std::sync::atomic::{AtomicU32, Ordering};
 use std::time::{Duration, Instant};
@@ -14,10 +15,14 @@ pub struct AudioRecorder {
     current_max_amplitude: Arc<Mutex<f32>>,
     /// VAD判定用: バッファRMSのEMA (ピークより安定)
     current_rms: Arc<Mutex<f32>>,
+    /// ソフトウェアゲイン (VAD判定・録音の前に掛ける)
+    gain: Arc<Mutex<f32>>,
+    /// 最後にクリップ (ゲイン後に ±1.0 を超えた) した時刻
+    last_clip_time: Arc<Mutex<Option<Instant>>>,
 }
 
 impl AudioRecorder {
-    pub fn new(silence_threshold: f32) -> Result<Self, String> {
+    pub fn new(silence_threshold: f32) -> Result<Self, AppError> {
         Ok(Self {
             audio_buffer: Arc::new(Mutex::new(Vec::new())),
             stream: None,
@@ -27,41 +32,43 @@ impl AudioRecorder {
             recording_start_time: Arc::new(Mutex::new(None)),
             current_max_amplitude: Arc::new(Mutex::new(0.0)),
             current_rms: Arc::new(Mutex::new(0.0)),
+            gain: Arc::new(Mutex::new(1.0)),
+            last_clip_time: Arc::new(Mutex::new(None)),
         })
     }
 
+    /// Set the software input gain (録音中でも即座に反映される)
+    pub fn set_gain(&self, gain: f32) {
+        *self.gain.lock().unwrap() = gain.max(0.0);
+    }
+
+    /// 表示用のピーク振幅 (ゲイン適用後)
+    pub fn get_max_amplitude(&self) -> f32 {
+        *self.current_max_amplitude.lock().unwrap()
+    }
+
+    /// 直近1秒以内にクリップしたか
+    pub fn is_clipping(&self) -> bool {
+        self.last_clip_time
+            .lock()
+            .unwrap()
+            .map(|t| t.elapsed() < Duration::from_secs(1))
+            .unwrap_or(false)
+    }
+
     /// VAD判定用RMS振幅 (バッファRMSのEMA)
     pub fn get_rms_amplitude(&self) -> f32 {
         *self.current_rms.lock().unwrap()
     }
 
-    pub fn is_silent(&self, silence_duration_secs: f32) -> bool {
-        // Check if we're still in the grace period (3 seconds after recording starts)
-        let start_time = self.recording_start_time.lock().unwrap();
-        if let Some(start) = *start_time {
-            let elapsed = start.elapsed();
-            if elapsed < Duration::from_secs(3) {
-                // Still in grace period, not silent
-                if elapsed.as_secs() % 10 == 0 {
-                    println!("Grace period: {:.1}s / 3.0s", elapsed.as_secs_f32());
-                }
-                return false;
-            }
+    /// 別スレッドで録音開始・終了を判定するためのハンドル
+    pub fn probe(&self) -> AudioProbe {
+        AudioProbe {
+            audio_buffer: Arc::clone(&self.audio_buffer),
+            current_rms: Arc::clone(&self.current_rms),
+            last_sound_time: Arc::clone(&self.last_sound_time),
+            recording_start_time: Arc::clone(&self.recording_start_time),
         }
-
-        let last_sound = self.last_sound_time.lock().unwrap();
-        let silence_duration = last_sound.elapsed();
-        let is_silent = silence_duration >= Duration::from_secs_f32(silence_duration_secs);
-
-        if is_silent {
-            println!(
-                "SILENT DETECTED: {:.1}s >= {:.1}s",
-                silence_duration.as_secs_f32(),
-                silence_duration_secs
-            );
-        }
-
-        is_silent
     }
 
     pub fn get_silence_duration(&self) -> Duration {
@@ -74,7 +81,7 @@ impl AudioRecorder {
         *last_sound = Instant::now();
     }
 
-    pub fn start_recording_with_device(&mut self, device_name: Option<&str>) -> Result<(), String> {
+    pub fn start_recording_with_device(&mut self, device_name: Option<&str>) -> Result<(), AppError> {
         // Get the default host
         let host = cpal::default_host();
 
@@ -82,13 +89,13 @@ impl AudioRecorder {
         let device = if let Some(name) = device_name {
             // Find device by name
             host.input_devices()
-                .map_err(|e| format!("Failed to get input devices: {}", e))?
+                .map_err(|e| AppError::Audio(format!("Failed to get input devices: {}", e)))?
                 .find(|d| d.name().map(|n| n == name).unwrap_or(false))
-                .ok_or(format!("Input device '{}' not found", name))?
+                .ok_or_else(|| AppError::Config(format!("Input device '{}' not found", name)))?
         } else {
             // Use default device
             host.default_input_device()
-                .ok_or("No input device available")?
+                .ok_or_else(|| AppError::Audio("No input device available".to_string()))?
         };
 
         println!("Using input device: {}", device.name().unwrap_or_default());
@@ -96,7 +103,7 @@ impl AudioRecorder {
         // Get the default input config
         let default_config = device
             .default_input_config()
-            .map_err(|e| format!("Failed to get default input config: {}", e))?;
+            .map_err(|e| AppError::Audio(format!("Failed to get default input config: {}", e)))?;
 
         // Try to force mono (1 channel) recording first
         let mono_config = cpal::StreamConfig {
@@ -118,6 +125,8 @@ impl AudioRecorder {
             *max_amp = 0.0;
         }
 
+        *self.last_clip_time.lock().unwrap() = None;
+
         // Set recording start time for grace period
         {
             let mut start_time = self.recording_start_time.lock().unwrap();
@@ -160,7 +169,7 @@ impl AudioRecorder {
                 current_rms_clone.clone(),
                 threshold,
             ),
-            _ => return Err("Unsupported sample format".to_string()),
+            _ => return Err(AppError::Audio("Unsupported sample format".to_string())),
         };
 
         let (stream, _actual_channels) = match stream_result {
@@ -222,7 +231,7 @@ impl AudioRecorder {
                         threshold,
                         channels,
                     ),
-                    _ => return Err("Unsupported sample format".to_string()),
+                    _ => return Err(AppError::Audio("Unsupported sample format".to_string())),
                 }?;
 
                 (stream, channels)
@@ -231,12 +240,17 @@ impl AudioRecorder {
 
         stream
             .play()
-            .map_err(|e| format!("Failed to play stream: {}", e))?;
+            .map_err(|e| AppError::Audio(format!("Failed to play stream: {}", e)))?;
         self.stream = Some(stream);
 
         Ok(())
     }
 
+    /// それまでに録った音を捨てる (録音は続ける)
+    pub fn clear_buffer(&self) {
+        self.audio_buffer.lock().unwrap().clear();
+    }
+
     pub fn stop_recording(&mut self) -> Vec<f32> {
         // Stop and drop the stream
         self.stream = None;
@@ -264,25 +278,30 @@ impl AudioRecorder {
         current_max_amplitude: Arc<Mutex<f32>>,
         current_rms: Arc<Mutex<f32>>,
         threshold: f32,
-    ) -> Result<cpal::Stream, String>
+    ) -> Result<cpal::Stream, AppError>
     where
         T: cpal::Sample + cpal::SizedSample,
         f32: cpal::FromSample<T>,
     {
         let err_fn = |err| eprintln!("An error occurred on the audio stream: {}", err);
         static BUFFER_COUNT: AtomicU32 = AtomicU32::new(0);
+        let gain = Arc::clone(&self.gain);
+        let last_clip_time = Arc::clone(&self.last_clip_time);
 
         let stream = device
             .build_input_stream(
                 config,
                 move |data: &[T], _: &cpal::InputCallbackInfo| {
+                    let gain = *gain.lock().unwrap();
                     let mut buffer = buffer.lock().unwrap();
                     let mut has_sound = false;
                     let mut max_amplitude = 0.0f32;
                     let mut sum_sq = 0.0f32;
+                    let mut clipped = false;
 
                     for &sample in data.iter() {
-                        let sample_f32: f32 = cpal::Sample::from_sample(sample);
+                        let (sample_f32, clip) = apply_gain(cpal::Sample::from_sample(sample), gain);
+                        clipped |= clip;
                         buffer.push(sample_f32);
 
                         let abs_sample = sample_f32.abs();
@@ -326,11 +345,15 @@ impl AudioRecorder {
                         let mut last_sound = last_sound_time.lock().unwrap();
                         *last_sound = Instant::now();
                     }
+
+                    if clipped {
+                        *last_clip_time.lock().unwrap() = Some(Instant::now());
+                    }
                 },
                 err_fn,
                 None,
             )
-            .map_err(|e| format!("Failed to build input stream: {}", e))?;
+            .map_err(|e| AppError::Audio(format!("Failed to build input stream: {}", e)))?;
 
         Ok(stream)
     }
@@ -345,27 +368,33 @@ impl AudioRecorder {
         current_rms: Arc<Mutex<f32>>,
         threshold: f32,
         channels: u16,
-    ) -> Result<cpal::Stream, String>
+    ) -> Result<cpal::Stream, AppError>
     where
         T: cpal::Sample + cpal::SizedSample,
         f32: cpal::FromSample<T>,
     {
         let err_fn = |err| eprintln!("An error occurred on the audio stream: {}", err);
         static BUFFER_COUNT: AtomicU32 = AtomicU32::new(0);
+        let gain = Arc::clone(&self.gain);
+        let last_clip_time = Arc::clone(&self.last_clip_time);
 
         let stream = device
             .build_input_stream(
                 config,
                 move |data: &[T], _: &cpal::InputCallbackInfo| {
+                    let gain = *gain.lock().unwrap();
                     let mut buffer = buffer.lock().unwrap();
                     let mut has_sound = false;
                     let mut max_amplitude = 0.0f32;
                     let mut sum_sq = 0.0f32;
                     let mut mono_count = 0usize;
+                    let mut clipped = false;
 
                     if channels == 1 {
                         for &sample in data.iter() {
-                            let sample_f32: f32 = cpal::Sample::from_sample(sample);
+                            let (sample_f32, clip) =
+                                apply_gain(cpal::Sample::from_sample(sample), gain);
+                            clipped |= clip;
                             buffer.push(sample_f32);
 
                             let abs_sample = sample_f32.abs();
@@ -385,7 +414,8 @@ impl AudioRecorder {
                                 let sample_f32: f32 = cpal::Sample::from_sample(sample);
                                 sum += sample_f32;
                             }
-                            let mono_sample = sum / channels as f32;
+                            let (mono_sample, clip) = apply_gain(sum / channels as f32, gain);
+                            clipped |= clip;
                             buffer.push(mono_sample);
 
                             let abs_sample = mono_sample.abs();
@@ -434,11 +464,15 @@ impl AudioRecorder {
                         let mut last_sound = last_sound_time.lock().unwrap();
                         *last_sound = Instant::now();
                     }
+
+                    if clipped {
+                        *last_clip_time.lock().unwrap() = Some(Instant::now());
+                    }
                 },
                 err_fn,
                 None,
             )
-            .map_err(|e| format!("Failed to build input stream: {}", e))?;
+            .map_err(|e| AppError::Audio(format!("Failed to build input stream: {}", e)))?;
 
         Ok(stream)
     }
@@ -447,22 +481,26 @@ impl AudioRecorder {
         &self,
         audio_data: &[f32],
         sample_rate: u32,
-    ) -> Result<PathBuf, String> {
-        // Trim leading silence but keep 0.2 seconds
-        let keep_samples = (sample_rate as f32 * 0.2) as usize; // 0.2 seconds worth of samples
+        preroll_secs: f32,
+        work_dir: &Path,
+    ) -> Result<PathBuf, AppError> {
+        // Trim leading silence but keep preroll_secs
+        let keep_samples = (sample_rate as f32 * preroll_secs) as usize;
 
         let trimmed_data = trim_leading_silence(audio_data, self.silence_threshold, keep_samples);
 
         if trimmed_data.is_empty() {
-            return Err("Audio data is empty after trimming".to_string());
+            return Err(AppError::Audio("Audio data is empty after trimming".to_string()));
         }
 
         // Create a temporary file
+        std::fs::create_dir_all(work_dir)
+            .map_err(|e| AppError::Audio(format!("Failed to create work directory {}: {}", work_dir.display(), e)))?;
         let temp_file = tempfile::Builder::new()
-            .prefix("winh_audio_")
+            .prefix(WORK_FILE_PREFIX)
             .suffix(".wav")
-            .tempfile()
-            .map_err(|e| format!("Failed to create temp file: {}", e))?;
+            .tempfile_in(work_dir)
+            .map_err(|e| AppError::Audio(format!("Failed to create temp file: {}", e)))?;
 
         let temp_path = temp_file.path().to_path_buf();
 
@@ -483,7 +521,7 @@ impl AudioRecorder {
         );
 
         let mut writer = hound::WavWriter::create(&temp_path, spec)
-            .map_err(|e| format!("Failed to create WAV writer: {}", e))?;
+            .map_err(|e| AppError::Audio(format!("Failed to create WAV writer: {}", e)))?;
 
         // Write samples
         for &sample in trimmed_data {
@@ -491,12 +529,12 @@ impl AudioRecorder {
             let sample_i16 = (sample * i16::MAX as f32) as i16;
             writer
                 .write_sample(sample_i16)
-                .map_err(|e| format!("Failed to write sample: {}", e))?;
+                .map_err(|e| AppError::Audio(format!("Failed to write sample: {}", e)))?;
         }
 
         writer
             .finalize()
-            .map_err(|e| format!("Failed to finalize WAV file: {}", e))?;
+            .map_err(|e| AppError::Audio(format!("Failed to finalize WAV file: {}", e)))?;
 
         // Keep the file alive by forgetting the tempfile handle
         std::mem::forget(temp_file);
@@ -510,6 +548,126 @@ impl AudioRecorder {
     }
 }
 
+/// 録音中のレコーダーの RMS と無音の長さを、UI スレッド以外から読む
+#[derive(Clone)]
+pub struct AudioProbe {
+    audio_buffer: Arc<Mutex<Vec<f32>>>,
+    current_rms: Arc<Mutex<f32>>,
+    last_sound_time: Arc<Mutex<Instant>>,
+    recording_start_time: Arc<Mutex<Option<Instant>>>,
+}
+
+impl AudioProbe {
+    /// VAD判定用RMS振幅 (バッファRMSのEMA)
+    pub fn rms(&self) -> f32 {
+        *self.current_rms.lock().unwrap()
+    }
+
+    /// ここまでに録った音を取り出す (録音は続く。UI を待たずに録音を区切るため)
+    pub fn take_samples(&self) -> Vec<f32> {
+        std::mem::take(&mut *self.audio_buffer.lock().unwrap())
+    }
+
+    pub fn is_silent(&self, silence_duration_secs: f32) -> bool {
+        // Check if we're still in the grace period (3 seconds after recording starts)
+        let start_time = self.recording_start_time.lock().unwrap();
+        if let Some(start) = *start_time {
+            let elapsed = start.elapsed();
+            if elapsed < Duration::from_secs(3) {
+                // Still in grace period, not silent
+                if elapsed.as_secs() % 10 == 0 {
+                    println!("Grace period: {:.1}s / 3.0s", elapsed.as_secs_f32());
+                }
+                return false;
+            }
+        }
+
+        let last_sound = self.last_sound_time.lock().unwrap();
+        let silence_duration = last_sound.elapsed();
+        let is_silent = silence_duration >= Duration::from_secs_f32(silence_duration_secs);
+
+        if is_silent {
+            println!(
+                "SILENT DETECTED: {:.1}s >= {:.1}s",
+                silence_duration.as_secs_f32(),
+                silence_duration_secs
+            );
+        }
+
+        is_silent
+    }
+}
+
+/// 録音開始の判定。window ごとに 1 フレームとして RMS を見て、閾値超えが confirm_frames 回続いたら発話とみなす
+///
+/// 同じ window の中で何度呼ばれても 1 フレームしか数えないので、画面の再描画が速くても判定は早まらない。
+/// 呼ばれなかった間のフレームは音量を見ていないので数えない
+#[derive(Debug, Default)]
+pub struct VoiceOnsetDetector {
+    count: u32,
+    last_tick: Option<Instant>,
+}
+
+impl VoiceOnsetDetector {
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    /// loud は今の RMS が閾値を超えているか。発話が確定したら true を返し、数え直す
+    pub fn update(&mut self, now: Instant, loud: bool, confirm_frames: u32, window: Duration) -> bool {
+        match self.last_tick {
+            None => self.last_tick = Some(now),
+            Some(last) => {
+                let window = window.max(Duration::from_millis(1));
+                let frames = (now.saturating_duration_since(last).as_millis() / window.as_millis()) as u32;
+                if frames == 0 {
+                    return false;
+                }
+                self.last_tick = Some(last + window * frames);
+            }
+        }
+        if !loud {
+            self.count = 0;
+            return false;
+        }
+        self.count = self.count.saturating_add(1);
+        if self.count >= confirm_frames.max(1) {
+            self.reset();
+            return true;
+        }
+        false
+    }
+}
+
+/// 録音の一時ファイル名の接頭辞 (掃除するときにこのアプリのファイルだけを選ぶ)
+pub const WORK_FILE_PREFIX: &str = "winh_audio_";
+
+/// 録音の一時ファイルを書くディレクトリ (未設定ならシステムの一時フォルダ)
+pub fn work_dir(configured: &str) -> PathBuf {
+    match configured.trim() {
+        "" => std::env::temp_dir(),
+        dir => PathBuf::from(dir),
+    }
+}
+
+/// work_dir にある録音の一時ファイルのうち max_age より古いものを消す。消した数を返す
+pub fn cleanup_work_dir(work_dir: &Path, max_age: Duration) -> usize {
+    let Ok(entries) = std::fs::read_dir(work_dir) else {
+        return 0;
+    };
+    let now = std::time::SystemTime::now();
+    entries
+        .filter_map(|e| e.ok())
+        .filter(|e| {
+            e.file_name().to_string_lossy().starts_with(WORK_FILE_PREFIX)
+                && e.metadata()
+                    .and_then(|m| m.modified())
+                    .is_ok_and(|modified| now.duration_since(modified).unwrap_or_default() >= max_age)
+        })
+        .filter(|e| std::fs::remove_file(e.path()).is_ok())
+        .count()
+}
+
 impl Default for AudioRecorder {
     fn default() -> Self {
         Self::new(0.01).unwrap()
@@ -526,6 +684,61 @@ mod audio_tests {
         assert_eq!(recorder.get_rms_amplitude(), 0.0);
         assert_eq!(recorder.get_max_amplitude(), 0.0);
     }
+
+    #[test]
+    fn test_apply_gain_clips() {
+        assert_eq!(apply_gain(0.25, 2.0), (0.5, false));
+        assert_eq!(apply_gain(0.75, 2.0), (1.0, true));
+        assert_eq!(apply_gain(-0.75, 2.0), (-1.0, true));
+    }
+
+    #[test]
+    fn test_voice_onset_detector() {
+        let window = Duration::from_millis(100);
+        let start = Instant::now();
+        let at = |ms: u64| start + Duration::from_millis(ms);
+
+        let mut detector = VoiceOnsetDetector::default();
+        assert!(!detector.update(at(0), true, 3, window));
+        // 再描画が速くても、同じ window の中では数えない
+        assert!(!detector.update(at(16), true, 3, window));
+        assert!(!detector.update(at(120), true, 3, window));
+        assert!(detector.update(at(210), true, 3, window));
+
+        // 途中で静かになったら数え直す
+        let mut detector = VoiceOnsetDetector::default();
+        assert!(!detector.update(at(0), true, 3, window));
+        assert!(!detector.update(at(100), false, 3, window));
+        assert!(!detector.update(at(200), true, 3, window));
+        // 再描画が遅くても、見ていない間のフレームは数えない
+        assert!(!detector.update(at(500), true, 3, window));
+        assert!(detector.update(at(600), true, 3, window));
+    }
+
+    #[test]
+    fn test_cleanup_work_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let recorder = AudioRecorder::new(0.01).unwrap();
+        let path = recorder.save_audio_to_wav(&[0.5; 160], 16000, 0.0, dir.path()).unwrap();
+        std::fs::write(dir.path().join("other.wav"), b"").unwrap();
+        assert_eq!(work_dir(" "), std::env::temp_dir());
+
+        assert_eq!(cleanup_work_dir(dir.path(), Duration::from_secs(3600)), 0);
+        assert!(path.exists());
+        assert_eq!(cleanup_work_dir(dir.path(), Duration::ZERO), 1);
+        assert!(!path.exists());
+        assert!(dir.path().join("other.wav").exists());
+    }
+}
+
+/// ゲインを掛けて [-1.0, 1.0] に収める。戻り値の bool はクリップしたかどうか
+fn apply_gain(sample: f32, gain: f32) -> (f32, bool) {
+    let amplified = sample * gain;
+    if amplified.abs() >= 1.0 {
+        (amplified.clamp(-1.0, 1.0), true)
+    } else {
+        (amplified, false)
+    }
 }
 
 fn trim_leading_silence(audio_data: &[f32], threshold: f32, keep_samples: usize) -> &[f32] {
@@ -558,11 +771,11 @@ fn trim_leading_silence(audio_data: &[f32], threshold: f32, keep_samples: usize)
 }
 
 /// Get list of available input devices
-pub fn get_input_devices() -> Result<Vec<String>, String> {
+pub fn get_input_devices() -> Result<Vec<String>, AppError> {
     let host = cpal::default_host();
     let devices = host
         .input_devices()
-        .map_err(|e| format!("Failed to get input devices: {}", e))?;
+        .map_err(|e| AppError::Audio(format!("Failed to get input devices: {}", e)))?;
 
     let mut device_names = Vec::new();
     for device in devices {
@@ -0,0 +1,152 @@
+//! 音声まわりの設定 (マイク・閾値・出力デバイス) をキャラクターのプリセットとは別に選ぶ
+//!
+//! 音声プロファイルを選んでいる間は、プリセットを切り替えてもその値を上書きするので、
+//! キャラクターを変えるたびにキャリブレーションし直さなくて済む
+
+use crate::config::Config;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+/// 選択中のプロファイル名を書いておくファイル (プリセットをまたいで共通)
+const ACTIVE_FILE: &str = "active.txt";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AudioProfile {
+    pub input_device_name: Option<String>,
+    pub input_gains: BTreeMap<String, f32>,
+    pub start_threshold: f32,
+    pub silence_threshold: f32,
+    pub silence_duration_secs: f32,
+    pub preroll_secs: f32,
+    pub tts_output_device: String,
+    pub tts_volume: f32,
+    pub monitor_tts_output_device: String,
+    pub monitor_tts_volume: f32,
+    pub sfx_output_device: String,
+    pub sfx_volume: f32,
+    pub notify_output_device: String,
+    pub notify_volume: f32,
+}
+
+impl AudioProfile {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            input_device_name: config.input_device_name.clone(),
+            input_gains: config.input_gains.clone(),
+            start_threshold: config.start_threshold,
+            silence_threshold: config.silence_threshold,
+            silence_duration_secs: config.silence_duration_secs,
+            preroll_secs: config.preroll_secs,
+            tts_output_device: config.tts_output_device.clone(),
+            tts_volume: config.tts_volume,
+            monitor_tts_output_device: config.monitor_tts_output_device.clone(),
+            monitor_tts_volume: config.monitor_tts_volume,
+            sfx_output_device: config.sfx_output_device.clone(),
+            sfx_volume: config.sfx_volume,
+            notify_output_device: config.notify_output_device.clone(),
+            notify_volume: config.notify_volume,
+        }
+    }
+
+    pub fn apply(&self, config: &mut Config) {
+        config.input_device_name = self.input_device_name.clone();
+        config.input_gains = self.input_gains.clone();
+        config.start_threshold = self.start_threshold;
+        config.silence_threshold = self.silence_threshold;
+        config.silence_duration_secs = self.silence_duration_secs;
+        config.preroll_secs = self.preroll_secs;
+        config.tts_output_device = self.tts_output_device.clone();
+        config.tts_volume = self.tts_volume;
+        config.monitor_tts_output_device = self.monitor_tts_output_device.clone();
+        config.monitor_tts_volume = self.monitor_tts_volume;
+        config.sfx_output_device = self.sfx_output_device.clone();
+        config.sfx_volume = self.sfx_volume;
+        config.notify_output_device = self.notify_output_device.clone();
+        config.notify_volume = self.notify_volume;
+    }
+
+    pub fn load(name: &str) -> Result<Self, String> {
+        let path = path_for(name)?;
+        let content = fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read audio profile {}: {}", name, e))?;
+        serde_json::from_str(&content).map_err(|e| format!("Failed to parse audio profile {}: {}", name, e))
+    }
+
+    pub fn save(&self, name: &str) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize audio profile: {}", e))?;
+        fs::write(path_for(name)?, json).map_err(|e| format!("Failed to write audio profile {}: {}", name, e))
+    }
+}
+
+fn profiles_dir() -> Result<PathBuf, String> {
+    let dir = Config::config_dir()?.join("audio_profiles");
+    fs::create_dir_all(&dir).map_err(|e| format!("Failed to create audio profile directory: {}", e))?;
+    Ok(dir)
+}
+
+fn path_for(name: &str) -> Result<PathBuf, String> {
+    let file_name = crate::session::sanitize_name(name);
+    if file_name.is_empty() {
+        return Err("Audio profile name is empty".to_string());
+    }
+    Ok(profiles_dir()?.join(format!("{}.json", file_name)))
+}
+
+/// 保存済みのプロファイル名 (名前順)
+pub fn list() -> Vec<String> {
+    let Ok(dir) = profiles_dir() else {
+        return Vec::new();
+    };
+    let mut names: Vec<String> = fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .filter_map(|e| e.ok())
+                .map(|e| e.path())
+                .filter(|path| path.extension().and_then(|x| x.to_str()) == Some("json"))
+                .filter_map(|path| path.file_stem()?.to_str().map(str::to_string))
+                .collect()
+        })
+        .unwrap_or_default();
+    names.sort();
+    names
+}
+
+/// 選択中のプロファイル (None ならプリセットの値をそのまま使う)
+pub fn load_active() -> Option<String> {
+    let name = fs::read_to_string(profiles_dir().ok()?.join(ACTIVE_FILE)).ok()?;
+    let name = name.trim();
+    (!name.is_empty()).then(|| name.to_string())
+}
+
+pub fn save_active(name: Option<&str>) -> Result<(), String> {
+    fs::write(profiles_dir()?.join(ACTIVE_FILE), name.unwrap_or_default())
+        .map_err(|e| format!("Failed to save active audio profile: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply_keeps_persona_settings() {
+        let calibrated = Config {
+            start_threshold: 0.2,
+            input_device_name: Some("USB Mic".to_string()),
+            ..Config::default()
+        };
+        let profile = AudioProfile::from_config(&calibrated);
+
+        let mut persona = Config {
+            persona_prompt: "You are Lisa.".to_string(),
+            ..Config::default()
+        };
+        profile.apply(&mut persona);
+        assert_eq!(persona.start_threshold, 0.2);
+        assert_eq!(persona.input_device_name.as_deref(), Some("USB Mic"));
+        assert_eq!(persona.persona_prompt, "You are Lisa.");
+        assert_eq!(AudioProfile::from_config(&persona), profile);
+    }
+}
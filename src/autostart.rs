@@ -0,0 +1,70 @@
+//! Windows へのサインイン時に自動で起動する (HKCU の Run キーに登録する)
+
+use std::process::Command;
+
+const RUN_KEY: &str = r"HKCU\Software\Microsoft\Windows\CurrentVersion\Run";
+
+/// Run キーの値の名前 (--instance ごとに別々に登録する)
+fn value_name() -> String {
+    match crate::config::instance() {
+        Some(name) => format!("Eliza Agent [{}]", name),
+        None => "Eliza Agent".to_string(),
+    }
+}
+
+/// 登録するコマンドライン (今の実行ファイルを同じ --instance で起動する)
+fn command_line() -> Result<String, String> {
+    let exe = std::env::current_exe().map_err(|e| format!("Failed to locate executable: {}", e))?;
+    let mut line = format!("\"{}\"", exe.display());
+    if let Some(name) = crate::config::instance() {
+        line.push_str(&format!(" --instance={}", name));
+    }
+    Ok(line)
+}
+
+fn reg(args: &[&str]) -> Result<std::process::Output, String> {
+    #[cfg(windows)]
+    let mut command = {
+        use std::os::windows::process::CommandExt;
+        const CREATE_NO_WINDOW: u32 = 0x08000000;
+        let mut command = Command::new("reg");
+        command.creation_flags(CREATE_NO_WINDOW);
+        command
+    };
+    #[cfg(not(windows))]
+    let mut command = Command::new("reg");
+
+    command
+        .args(args)
+        .output()
+        .map_err(|e| format!("Failed to run reg: {}", e))
+}
+
+/// 自動起動が登録されているか
+pub fn is_enabled() -> bool {
+    cfg!(windows)
+        && reg(&["query", RUN_KEY, "/v", &value_name()]).is_ok_and(|output| output.status.success())
+}
+
+/// 自動起動を登録 / 解除する
+pub fn set_enabled(enabled: bool) -> Result<(), String> {
+    if !cfg!(windows) {
+        return Err("Autostart is only supported on Windows".to_string());
+    }
+    let name = value_name();
+    let output = if enabled {
+        reg(&["add", RUN_KEY, "/v", &name, "/t", "REG_SZ", "/d", &command_line()?, "/f"])?
+    } else if is_enabled() {
+        reg(&["delete", RUN_KEY, "/v", &name, "/f"])?
+    } else {
+        return Ok(());
+    };
+    if !output.status.success() {
+        return Err(format!(
+            "Failed to update autostart: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    println!("[Autostart] {}", if enabled { "Registered" } else { "Unregistered" });
+    Ok(())
+}
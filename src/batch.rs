@@ -0,0 +1,111 @@
+use crate::config::Config;
+use crate::openai::OpenAIClient;
+use crate::stt;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::Sender;
+
+/// 再書き起こしの進捗
+pub enum BatchMessage {
+    Progress { done: usize, total: usize, file: String },
+    Finished(Result<PathBuf, String>),
+}
+
+/// 録音を保存しておくディレクトリ (config_dir/recordings)
+pub fn archive_dir() -> Result<PathBuf, String> {
+    let dir = Config::config_dir()?.join("recordings");
+    if !dir.exists() {
+        fs::create_dir_all(&dir)
+            .map_err(|e| format!("Failed to create recordings directory: {}", e))?;
+    }
+    Ok(dir)
+}
+
+/// 録音を日時のファイル名でアーカイブにコピーする
+pub fn archive_recording(audio_path: &Path) -> Result<PathBuf, String> {
+    let name = chrono::Local::now().format("%Y%m%d_%H%M%S%.3f.wav").to_string();
+    let dest = archive_dir()?.join(name);
+    fs::copy(audio_path, &dest).map_err(|e| format!("Failed to archive recording: {}", e))?;
+    Ok(dest)
+}
+
+/// ディレクトリ内の .wav (ファイル名順)
+pub fn list_recordings(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut files: Vec<PathBuf> = entries
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| {
+            p.extension()
+                .map(|ext| ext.eq_ignore_ascii_case("wav"))
+                .unwrap_or(false)
+        })
+        .collect();
+    files.sort();
+    files
+}
+
+/// dir の録音をすべて backend で書き起こし、transcript_<日時>.txt に書き出す (バックグラウンド)
+pub fn start(dir: PathBuf, backend: String, config: Config, sender: Sender<BatchMessage>) {
+    std::thread::spawn(move || {
+        let result = run(&dir, &backend, &config, &sender);
+        let _ = sender.send(BatchMessage::Finished(result));
+    });
+}
+
+fn run(dir: &Path, backend: &str, config: &Config, sender: &Sender<BatchMessage>) -> Result<PathBuf, String> {
+    let files = list_recordings(dir);
+    if files.is_empty() {
+        return Err(format!("No .wav files in {}", dir.display()));
+    }
+
+    let openai_client = OpenAIClient::new(
+        config.openai_api_key.clone(),
+        config.whisper_model.clone(),
+        config.custom_prompt_for(None).to_string(),
+    )
+    .with_timeout(config.stt_timeout_secs);
+
+    let out_path = dir.join(format!(
+        "transcript_{}.txt",
+        chrono::Local::now().format("%Y%m%d_%H%M%S")
+    ));
+    let mut out = fs::File::create(&out_path)
+        .map_err(|e| format!("Failed to create transcript file: {}", e))?;
+
+    for (i, file) in files.iter().enumerate() {
+        let name = file
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let _ = sender.send(BatchMessage::Progress {
+            done: i,
+            total: files.len(),
+            file: name.clone(),
+        });
+        let line = match stt::transcribe_with(backend, config, &openai_client, file) {
+            Ok(transcription) => format!("[{}] {}", name, transcription.text.trim()),
+            Err(e) => format!("[{}] ❌ {}", name, e),
+        };
+        writeln!(out, "{}", line).map_err(|e| format!("Failed to write transcript: {}", e))?;
+    }
+    println!("[Batch] Transcript written to {:?}", out_path);
+    Ok(out_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_list_recordings() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["b.wav", "a.WAV", "notes.txt"] {
+            fs::write(dir.path().join(name), b"").unwrap();
+        }
+        let files = list_recordings(dir.path());
+        assert_eq!(files, vec![dir.path().join("a.WAV"), dir.path().join("b.wav")]);
+    }
+}
@@ -1,6 +1,53 @@
+use crate::config_crypt;
+use crate::hooks::HookConfig;
+use crate::midi::MidiBinding;
+use crate::scheduler::ScheduledMessage;
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
 use std::fs;
 use std::path::PathBuf;
+use std::sync::{Mutex, OnceLock};
+
+/// `--instance=<name>` で起動したときのインスタンス名 (設定ディレクトリを分ける)
+static INSTANCE: OnceLock<String> = OnceLock::new();
+
+/// 復号できなかったプリセット (既定値で上書きしてしまわないよう保存を断る)
+static UNREADABLE_PRESETS: Mutex<BTreeSet<String>> = Mutex::new(BTreeSet::new());
+
+/// ワールドとプリセットの対応表を書いておくファイル (プリセットをまたいで共通)
+const WORLD_PRESETS_FILE: &str = "world_presets.json";
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct WorldPresets {
+    enabled: bool,
+    presets: BTreeMap<String, String>,
+}
+
+#[cfg(test)]
+thread_local! {
+    /// テスト用の設定ディレクトリ (実際の設定や並列に走る他のテストに影響しないようスレッドごとに持つ)
+    pub static TEST_CONFIG_DIR: std::cell::RefCell<Option<PathBuf>> = const { std::cell::RefCell::new(None) };
+}
+
+/// インスタンス名を設定する (Config を読む前に 1 回だけ)
+pub fn set_instance(name: &str) {
+    let name: String = name
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || *c == '-' || *c == '_')
+        .collect();
+    if !name.is_empty() {
+        let _ = INSTANCE.set(name);
+    }
+}
+
+pub fn instance() -> Option<&'static str> {
+    INSTANCE.get().map(|s| s.as_str())
+}
+
+/// 引数の `--instance=<name>`
+pub fn instance_arg(args: &[String]) -> Option<&str> {
+    args.iter().find_map(|arg| arg.strip_prefix("--instance="))
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
@@ -11,29 +58,714 @@ pub struct Config {
     pub start_threshold: f32,
     pub silence_threshold: f32,
     pub silence_duration_secs: f32,
+    /// 録音の先頭の無音を削るときに、声の手前に残す長さ (秒)
+    #[serde(default = "default_preroll_secs")]
+    pub preroll_secs: f32,
+    /// start_threshold を超えたフレームがこの回数続いたら録音を始める (単発のノイズで始めないため)
+    #[serde(default = "default_voice_confirm_frames")]
+    pub voice_confirm_frames: u32,
+    /// 録音開始の判定の 1 フレームの長さ (ms)
+    #[serde(default = "default_voice_confirm_window_ms")]
+    pub voice_confirm_window_ms: u64,
+    /// 音量で録音を始めず、キー (または MIDI の hold_to_talk) を押している間だけ録音する
+    #[serde(default)]
+    pub hold_to_talk_enabled: bool,
+    /// 押している間録音するキーの名前 (egui のキー名。ウィンドウにフォーカスがあるときだけ効く)
+    #[serde(default = "default_hold_to_talk_key")]
+    pub hold_to_talk_key: String,
 
     // Speech-to-Text settings
     pub whisper_model: String,
     pub custom_prompt: String,
+    /// 言語コード → その言語用の Whisper のプロンプト (なければ custom_prompt を使う)
+    #[serde(default = "default_custom_prompts")]
+    pub custom_prompts: BTreeMap<String, String>,
+    /// 言語別のプロンプトを選ぶ言語 ("auto" なら直前の発話の言語)
+    #[serde(default = "default_custom_prompt_language")]
+    pub custom_prompt_language: String,
+    /// 書き起こしバックエンド (stt::BACKENDS のいずれか)
+    #[serde(default = "default_stt_backend")]
+    pub stt_backend: String,
+    /// メインが失敗・タイムアウトしたときに使うバックエンド ("none" で無効)
+    #[serde(default = "default_stt_fallback_backend")]
+    pub stt_fallback_backend: String,
+    /// 書き起こしのタイムアウト秒数 (0 で無制限)
+    #[serde(default = "default_stt_timeout_secs")]
+    pub stt_timeout_secs: u64,
+    /// これより長い録音は区切って書き起こし、つなげる (0 なら API の 25MB を超えるときだけ)
+    #[serde(default = "default_stt_chunk_secs")]
+    pub stt_chunk_secs: u32,
+    /// whisper.cpp の実行ファイル
+    #[serde(default = "default_local_whisper_path")]
+    pub local_whisper_path: String,
+    /// whisper.cpp の ggml モデルファイル、またはモデルを置いたディレクトリ
+    #[serde(default)]
+    pub local_whisper_model: String,
+    /// local_whisper_model がディレクトリのときに使うモデル (ggml-<size>.bin)
+    #[serde(default = "default_local_whisper_model_size")]
+    pub local_whisper_model_size: String,
+    #[serde(default = "default_local_whisper_language")]
+    pub local_whisper_language: String,
+    /// 推論スレッド数 (0 なら whisper.cpp の既定)
+    #[serde(default = "default_local_whisper_threads")]
+    pub local_whisper_threads: usize,
+    /// 推論中に順番待ちできる発話の数 (超えた分は捨てる)
+    #[serde(default = "default_local_whisper_max_queue")]
+    pub local_whisper_max_queue: usize,
+    /// 直前の発話を Whisper の prompt に付け足す (固有名詞の表記揺れ対策)
+    #[serde(default)]
+    pub whisper_context_enabled: bool,
+    /// 付け足す文脈の最大文字数
+    #[serde(default = "default_whisper_context_chars")]
+    pub whisper_context_chars: usize,
+    /// 直前の Agent の返答も文脈に含める
+    #[serde(default)]
+    pub whisper_context_include_agent: bool,
+
+    // Transcription cleanup (書き起こしを安価な LLM で整えてから使う)
+    #[serde(default)]
+    pub transcript_cleanup_enabled: bool,
+    #[serde(default = "default_transcript_cleanup_model")]
+    pub transcript_cleanup_model: String,
+    #[serde(default = "default_transcript_cleanup_prompt")]
+    pub transcript_cleanup_prompt: String,
+
+    /// 書き起こしの電話番号・メールアドレス・住所を LLM やチャットボックスに送る前に伏せ字にする
+    #[serde(default)]
+    pub pii_redaction_enabled: bool,
+    /// 指示のような発話の扱い (injection_guard::INJECTION_GUARD_MODES のいずれか)
+    #[serde(default = "default_injection_guard")]
+    pub injection_guard: String,
 
     // Eliza settings
     pub agent_server_url: String,
     pub agent_model: String,
     pub max_length_of_conversation_history: usize,
+    /// 履歴が上限を超えたときのまとめ方 (eliza::COMPACTION_STRATEGIES のいずれか)
+    #[serde(default = "default_history_compaction")]
+    pub history_compaction: String,
+    /// まとめたあとに残す件数
+    #[serde(default = "default_history_compact_size")]
+    pub history_compact_size: usize,
+    /// Agent の返答を待つ秒数 (0 なら待ち続ける)
+    #[serde(default = "default_agent_timeout_secs")]
+    pub agent_timeout_secs: u64,
+    /// 返答をストリーミング (SSE) で受け取り、届いた分から表示する
+    #[serde(default)]
+    pub agent_streaming_enabled: bool,
+    /// 追加の人格プロンプト (system メッセージとして送る。空ならサーバー側の設定のみ)
+    #[serde(default)]
+    pub persona_prompt: String,
+    /// 返答の temperature
+    #[serde(default)]
+    pub agent_temperature: f32,
+    /// 気まぐれさ。毎ターン temperature を ± この幅でランダムに揺らす (0 = 揺らさない)
+    #[serde(default)]
+    pub agent_spontaneity: f32,
+    /// 返答の最大トークン数 (0 = 制限しない)
+    #[serde(default)]
+    pub agent_max_tokens: u32,
+    /// 最近の 👍/👎 の評価を system プロンプトに添える
+    #[serde(default)]
+    pub feedback_note_enabled: bool,
+    /// 添える評価の件数
+    #[serde(default = "default_feedback_note_count")]
+    pub feedback_note_count: usize,
+    /// A/B テストで Persona Prompt と比べるもう一方のプロンプト
+    #[serde(default)]
+    pub ab_prompt_b: String,
+    /// A/B テストを続けるターン数
+    #[serde(default = "default_ab_test_turns")]
+    pub ab_test_turns: usize,
 
     // UI settings
     #[serde(default)]
     pub input_device_name: Option<String>,
+    /// デバイス名 → ソフトウェア入力ゲイン (倍率)。登録のないデバイスは 1.0
+    #[serde(default)]
+    pub input_gains: BTreeMap<String, f32>,
+    /// 録音を config_dir/recordings に残す (あとで再書き起こしできるように)
+    #[serde(default)]
+    pub archive_recordings: bool,
+    /// 録音の一時ファイルを書くディレクトリ (空ならシステムの一時フォルダ、RAM ディスクも可)
+    #[serde(default)]
+    pub audio_work_dir: String,
+    /// この分数より古い一時ファイルを消す (0 なら消さない)
+    #[serde(default = "default_audio_cleanup_minutes")]
+    pub audio_cleanup_minutes: u32,
 
     // VRChat mute detection
     #[serde(default = "default_true")]
     pub use_vrchat_mute_detection: bool,
+    /// VRChat の AFK (ヘッドセットを外したときなど) の間はモニタリングを止め、戻ったら再開する
+    #[serde(default)]
+    pub afk_pause_enabled: bool,
+    /// VRChat の OSC 受信ポート (こちらからの送信先)。2 つ目の VRChat を --osc で別ポートにした場合に変える
+    #[serde(default = "default_osc_send_port")]
+    pub osc_send_port: u16,
+    /// VRChat からの OSC を受けるポート
+    #[serde(default = "default_osc_listen_port")]
+    pub osc_listen_port: u16,
+    /// VRChat の Voice パラメータで自分の声かどうかを判定し、他の人の声は "Others" として記録する
+    #[serde(default)]
+    pub speaker_attribution_enabled: bool,
+    /// 録音中は true にする Bool パラメータ (聞いていることをアバターで見せる)。空なら送らない
+    #[serde(default)]
+    pub listening_osc_address: String,
+    /// Agent アカウントモード: このアプリが操作するのは人格用の別アカウント。
+    /// オペレーターの入力はチャットボックスに引用せず、返答だけを送る
+    #[serde(default)]
+    pub agent_account_mode: bool,
+    /// ローカルの操作用 API (POST http://127.0.0.1:<port>/say) でテキストを受け付ける
+    #[serde(default)]
+    pub control_api_enabled: bool,
+    #[serde(default = "default_control_api_port")]
+    pub control_api_port: u16,
+    /// 操作用 API に必要なトークン (Authorization: Bearer <token>)。空なら API を有効にしたときに作る
+    #[serde(default)]
+    pub control_api_token: String,
+    /// VRChat のログから他のプレイヤーのチャットを拾い、返答の参考にする (プライバシーのため既定はオフ)
+    #[serde(default)]
+    pub world_chat_context_enabled: bool,
+    /// ログの行からチャットを拾う正規表現 (name / text の名前付きグループ)
+    #[serde(default = "default_world_chat_pattern")]
+    pub world_chat_pattern: String,
+    /// 何分前までのチャットを添えるか
+    #[serde(default = "default_world_chat_minutes")]
+    pub world_chat_minutes: u64,
+    /// VRChat のログで入室を検知したらあいさつする
+    #[serde(default)]
+    pub greet_joins_enabled: bool,
+    /// 1 分あたりのあいさつの上限
+    #[serde(default = "default_greet_per_minute")]
+    pub greet_per_minute: usize,
+    /// あいさつしないプレイヤー名 (カンマ区切り、自分の名前もここに入れる)
+    #[serde(default)]
+    pub greet_blocklist: String,
+    /// 今のインスタンスにいるプレイヤーのメモを返答の参考にする
+    #[serde(default)]
+    pub player_notes_enabled: bool,
+    /// プレイヤー名 → メモ
+    #[serde(default)]
+    pub player_notes: BTreeMap<String, String>,
+    /// VRChat のログで入ったワールドに合わせてプリセットを切り替える
+    /// (プリセットをまたいで共通なので world_presets.json に保存する。プリセットからは古い値を読むだけ)
+    #[serde(default, skip_serializing)]
+    pub world_presets_enabled: bool,
+    /// ワールド名 → プリセット ("*" はどれにも当てはまらないワールド)
+    #[serde(default, skip_serializing)]
+    pub world_presets: BTreeMap<String, String>,
+
+    /// VRChat が起動していない間はチャットボックス送信を止めて返答を保留する
+    #[serde(default = "default_true")]
+    pub pause_when_vrchat_closed: bool,
+    /// チャットボックス送信に失敗したら間隔を空けて再送する
+    #[serde(default = "default_true")]
+    pub chatbox_retry_enabled: bool,
+    /// 再送を諦めるまでの秒数
+    #[serde(default = "default_chatbox_retry_secs")]
+    pub chatbox_retry_secs: u64,
+    /// チャットボックスに収まらない返答は何ページかに分けて順に送る
+    #[serde(default = "default_true")]
+    pub chatbox_paginate_enabled: bool,
+    /// ページを切り替えるまでの読む速さ (文字/秒)
+    #[serde(default = "default_chatbox_reading_cps")]
+    pub chatbox_reading_cps: f32,
+    /// 返答を送ってしばらくしたらチャットボックスを空にする
+    #[serde(default)]
+    pub chatbox_clear_enabled: bool,
+    /// 空にするまでの秒数 (最後のページを送ってから数える)
+    #[serde(default = "default_chatbox_clear_secs")]
+    pub chatbox_clear_secs: u64,
+    /// チャットボックスに収まらない返答は全文を paste サービスに上げて URL を添える
+    #[serde(default)]
+    pub paste_long_replies_enabled: bool,
+    /// 全文のアップロード先 (本文を POST すると URL を返すサービス)
+    #[serde(default = "default_paste_endpoint")]
+    pub paste_endpoint: String,
+    /// 返答の頻度を制限し、超えた分はチャットボックスに送らない
+    #[serde(default)]
+    pub reply_limit_enabled: bool,
+    /// 1 分あたりの返答数の上限 (0 = 無制限)
+    #[serde(default = "default_reply_limit_per_minute")]
+    pub reply_limit_per_minute: usize,
+    /// 返答どうしの最小間隔 (秒)
+    #[serde(default = "default_reply_cooldown_secs")]
+    pub reply_cooldown_secs: u64,
+    /// 書き起こしの引用方法 (QUOTE_MODES のいずれか)
+    #[serde(default = "default_quote_mode")]
+    pub quote_mode: String,
+    #[serde(default = "default_quote_prefix")]
+    pub quote_prefix: String,
+    #[serde(default)]
+    pub quote_suffix: String,
+    /// dialogue モードでの発話者名
+    #[serde(default = "default_dialogue_user_label")]
+    pub dialogue_user_label: String,
+    /// dialogue モードでの Agent 名 (空なら Agent Names の先頭)
+    #[serde(default)]
+    pub dialogue_agent_label: String,
+    /// 書き起こしを単語のタイミングに合わせて少しずつチャットボックスに出す (whisper 系モデルのみ)
+    #[serde(default)]
+    pub karaoke_enabled: bool,
+    /// カラオケ表示にかける最大秒数 (発話がこれより長ければ早回しする)
+    #[serde(default = "default_karaoke_replay_secs")]
+    pub karaoke_replay_secs: f32,
+
+    // Intent filter: Agent に向けた発話にだけ返答する
+    #[serde(default)]
+    pub intent_filter_enabled: bool,
+    /// Agent の呼び名 (カンマ区切り)。空なら名前による判定はしない
+    #[serde(default)]
+    pub agent_names: String,
+    /// ローカル判定に加えて LLM で判定する
+    #[serde(default)]
+    pub intent_use_llm: bool,
+    #[serde(default = "default_transcript_cleanup_model")]
+    pub intent_model: String,
+
+    /// 停止・おやすみ時に会話を要約して保存し、次回の会話の冒頭に添える
+    #[serde(default)]
+    pub session_summary_enabled: bool,
+
+    // Mood: 会話の感情で気分が変わり、プロンプトとアバターの表情に反映される
+    #[serde(default)]
+    pub mood_enabled: bool,
+    /// 気分を送る Int パラメータ (0=ふつう, 1=上機嫌, 2=不機嫌, 3=ハイテンション)。空なら送らない
+    #[serde(default)]
+    pub mood_osc_address: String,
+
+    // Silence follow-up: 会話の途中で沈黙が続いたら、直前の話題について Agent から質問する
+    /// 最後の返答のあとこの秒数だれも話さなければ質問する (0 = しない)
+    #[serde(default)]
+    pub silence_follow_up_secs: u64,
+    #[serde(default = "default_silence_follow_up_prompt")]
+    pub silence_follow_up_prompt: String,
+
+    // Status topic line (N ターンごとに会話の話題を生成して表示)
+    #[serde(default)]
+    pub status_topic_enabled: bool,
+    #[serde(default = "default_status_topic_interval_turns")]
+    pub status_topic_interval_turns: usize,
+    /// 空ならチャットボックスの先頭行に表示、指定すればその OSC アドレスへ文字列で送信
+    #[serde(default)]
+    pub status_topic_osc_address: String,
+
+    // Language routing (言語コード → プリセット名, 例: "en" → "setting1")
+    #[serde(default)]
+    pub language_routing_enabled: bool,
+    #[serde(default)]
+    pub language_routes: BTreeMap<String, String>,
+    /// 話しかけられた言語で返答するよう Agent に指示する
+    #[serde(default)]
+    pub auto_reply_language_enabled: bool,
+    /// accepted_languages 以外の言語と判定された発話は聞き流す (近くのテレビや音楽で反応しないように)
+    #[serde(default)]
+    pub language_filter_enabled: bool,
+    /// 受け付ける言語コード (カンマ区切り, 例: "ja" / "ja,en")
+    #[serde(default = "default_accepted_languages")]
+    pub accepted_languages: String,
+
+    // Safety word (空なら無効)。認識されたら即停止してチャットボックスを消す
+    #[serde(default)]
+    pub safety_word: String,
+    #[serde(default)]
+    pub safety_word_wipe_history: bool,
+
+    /// Do-not-disturb キーワード (カンマ区切り)。発話か返答に含まれていたらチャットボックスに送らない
+    #[serde(default)]
+    pub dnd_keywords: String,
+
+    // Voice commands ("感度を上げて/下げて")
+    #[serde(default = "default_true")]
+    pub voice_commands_enabled: bool,
+    /// 1回のコマンドで閾値を変える割合 (0.2 = 20%)
+    #[serde(default = "default_sensitivity_step")]
+    pub sensitivity_step: f32,
+
+    // Compare mode: 同じメッセージを 2 つ目のバックエンドにも送って並べて表示する
+    #[serde(default)]
+    pub compare_mode_enabled: bool,
+    /// 空なら agent_server_url と同じサーバーを使う
+    #[serde(default)]
+    pub compare_server_url: String,
+    #[serde(default)]
+    pub compare_model: String,
+    /// true なら B (比較側) の返答を VRChat に送る
+    #[serde(default)]
+    pub compare_use_secondary: bool,
+    /// テキストだけで会話する (マイクを開かない)。Ctrl+T で切り替える
+    #[serde(default)]
+    pub text_mode: bool,
+
+    // Soundboard: 返答中の [sfx:name] タグで効果音を鳴らす
+    #[serde(default)]
+    pub soundboard_enabled: bool,
+    /// タグ名 → 音声ファイルのパス
+    #[serde(default)]
+    pub sfx_map: BTreeMap<String, String>,
+    /// 空なら既定の出力デバイス
+    #[serde(default)]
+    pub sfx_output_device: String,
+    #[serde(default = "default_sfx_volume")]
+    pub sfx_volume: f32,
+
+    // TTS: 返答を OpenAI の音声合成で読み上げる
+    #[serde(default)]
+    pub tts_enabled: bool,
+    /// 読み上げの方式 (tts::TTS_BACKENDS のいずれか)
+    #[serde(default = "default_tts_backend")]
+    pub tts_backend: String,
+    #[serde(default = "default_tts_voice")]
+    pub tts_voice: String,
+    /// VOICEVOX エンジンの URL
+    #[serde(default = "default_voicevox_url")]
+    pub voicevox_url: String,
+    /// VOICEVOX の話者スタイル ID (/speakers の styles[].id)
+    #[serde(default = "default_voicevox_style_id")]
+    pub voicevox_style_id: u32,
+    /// 話速 (1.0 が標準)
+    #[serde(default = "default_voicevox_speed")]
+    pub voicevox_speed: f32,
+    /// 音高 (0.0 が標準)
+    #[serde(default)]
+    pub voicevox_pitch: f32,
+    #[serde(default)]
+    pub elevenlabs_api_key: String,
+    /// ElevenLabs の声 (/v1/voices の voice_id)
+    #[serde(default = "default_elevenlabs_voice_id")]
+    pub elevenlabs_voice_id: String,
+    /// 空なら既定の出力デバイス (仮想ケーブルにすれば Agent アカウントのマイクになる)
+    #[serde(default)]
+    pub tts_output_device: String,
+    #[serde(default = "default_tts_volume")]
+    pub tts_volume: f32,
+    /// 返答を自分のヘッドホンでも読み上げる (VR 中に画面を見なくて済むように。読み上げの有効/無効とは別)
+    #[serde(default)]
+    pub monitor_tts_enabled: bool,
+    /// 空なら既定の出力デバイス
+    #[serde(default)]
+    pub monitor_tts_output_device: String,
+    #[serde(default = "default_tts_volume")]
+    pub monitor_tts_volume: f32,
+    /// 読み上げ中に話し始めたら読み上げを止めて録音を優先する
+    #[serde(default = "default_true")]
+    pub barge_in_enabled: bool,
+    /// 読み上げ前に数字・日付・単位を読みやすく書き換える
+    #[serde(default = "default_true")]
+    pub tts_normalize_enabled: bool,
+    /// 正規化の言語 (text_normalize::NORMALIZE_LOCALES のいずれか)
+    #[serde(default = "default_tts_normalize_locale")]
+    pub tts_normalize_locale: String,
+
+    // Notification sounds: 録音開始・返答送信・エラーを音で知らせる (VR 中にデスクトップを見なくて済むように)
+    #[serde(default)]
+    pub notify_recording_enabled: bool,
+    /// 空なら内蔵のビープ音
+    #[serde(default)]
+    pub notify_recording_sound: String,
+    #[serde(default)]
+    pub notify_reply_enabled: bool,
+    #[serde(default)]
+    pub notify_reply_sound: String,
+    #[serde(default)]
+    pub notify_error_enabled: bool,
+    #[serde(default)]
+    pub notify_error_sound: String,
+    /// 空なら既定の出力デバイス
+    #[serde(default)]
+    pub notify_output_device: String,
+    #[serde(default = "default_notify_volume")]
+    pub notify_volume: f32,
+
+    // Quick replies (ボタンで送る定型文)
+    #[serde(default = "default_quick_replies")]
+    pub quick_replies: Vec<String>,
+    /// 決まった時刻にチャットボックスへ送るメッセージ
+    #[serde(default)]
+    pub scheduled_messages: Vec<ScheduledMessage>,
+    /// true なら Agent を通さずそのままチャットボックスに送る
+    #[serde(default = "default_true")]
+    pub quick_replies_bypass_agent: bool,
+    /// プリセットファイルの暗号化 ("none" / "dpapi" / "passphrase")
+    #[serde(default = "default_config_encryption")]
+    pub config_encryption: String,
+
+    // Event hooks (外部コマンド / Webhook)
+    #[serde(default)]
+    pub hooks: Vec<HookConfig>,
+    /// すべてのイベントを OSC でも送る (配信ツールのアラートやシーン切り替え用)
+    #[serde(default)]
+    pub activity_osc_enabled: bool,
+    /// 送り先 (host:port)
+    #[serde(default = "default_activity_osc_target")]
+    pub activity_osc_target: String,
+
+    // MIDI controller
+    #[serde(default)]
+    pub midi_enabled: bool,
+    /// 空なら最初に見つかったポート
+    #[serde(default)]
+    pub midi_port_name: String,
+    #[serde(default)]
+    pub midi_bindings: Vec<MidiBinding>,
 }
 
 fn default_true() -> bool {
     true
 }
 
+fn default_stt_backend() -> String {
+    "openai".to_string()
+}
+
+fn default_stt_fallback_backend() -> String {
+    "none".to_string()
+}
+
+fn default_accepted_languages() -> String {
+    "ja".to_string()
+}
+
+fn default_stt_timeout_secs() -> u64 {
+    30
+}
+
+fn default_stt_chunk_secs() -> u32 {
+    120
+}
+
+fn default_local_whisper_path() -> String {
+    "whisper-cli".to_string()
+}
+
+fn default_local_whisper_model_size() -> String {
+    "base".to_string()
+}
+
+fn default_local_whisper_language() -> String {
+    "ja".to_string()
+}
+
+fn default_local_whisper_threads() -> usize {
+    4
+}
+
+fn default_local_whisper_max_queue() -> usize {
+    1
+}
+
+fn default_whisper_context_chars() -> usize {
+    200
+}
+
+fn default_transcript_cleanup_model() -> String {
+    "gpt-4o-mini".to_string()
+}
+
+fn default_injection_guard() -> String {
+    "off".to_string()
+}
+
+fn default_transcript_cleanup_prompt() -> String {
+    "音声認識の結果を整えてください。句読点を補い、えー・あのー等のフィラーを除き、明らかな誤認識だけを直してください。意味や口調は変えず、整えた文章だけを出力してください。".to_string()
+}
+
+fn default_feedback_note_count() -> usize {
+    5
+}
+
+fn default_ab_test_turns() -> usize {
+    20
+}
+
+fn default_agent_timeout_secs() -> u64 {
+    60
+}
+
+fn default_osc_send_port() -> u16 {
+    crate::vrchat::DEFAULT_SEND_PORT
+}
+
+fn default_osc_listen_port() -> u16 {
+    crate::vrchat::DEFAULT_LISTEN_PORT
+}
+
+fn default_control_api_port() -> u16 {
+    8765
+}
+
+/// ワールドのチャットのログ行 (例: `[Chat] Alice: こんばんは`)
+pub const DEFAULT_WORLD_CHAT_PATTERN: &str = r"\[(?:Chat|Chatbox|ChatBox)\]\s*(?P<name>[^:]+?):\s*(?P<text>.+)$";
+
+fn default_world_chat_pattern() -> String {
+    DEFAULT_WORLD_CHAT_PATTERN.to_string()
+}
+
+fn default_world_chat_minutes() -> u64 {
+    5
+}
+
+fn default_greet_per_minute() -> usize {
+    2
+}
+
+fn default_reply_limit_per_minute() -> usize {
+    3
+}
+
+fn default_reply_cooldown_secs() -> u64 {
+    10
+}
+
+fn default_chatbox_retry_secs() -> u64 {
+    60
+}
+
+fn default_chatbox_clear_secs() -> u64 {
+    30
+}
+
+fn default_chatbox_reading_cps() -> f32 {
+    8.0
+}
+
+fn default_paste_endpoint() -> String {
+    crate::paste::DEFAULT_PASTE_ENDPOINT.to_string()
+}
+
+/// 書き起こしをチャットボックスに引用する方法
+pub const QUOTE_MODES: [(&str, &str); 4] = [
+    ("separate", "返答の前に別メッセージで送る"),
+    ("merged", "返答と 1 つのメッセージにまとめる"),
+    ("dialogue", "\"You: … / Lisa: …\" の形でまとめる"),
+    ("off", "引用しない"),
+];
+
+fn default_quote_mode() -> String {
+    "separate".to_string()
+}
+
+fn default_quote_prefix() -> String {
+    "> ".to_string()
+}
+
+fn default_dialogue_user_label() -> String {
+    "You".to_string()
+}
+
+fn default_karaoke_replay_secs() -> f32 {
+    3.0
+}
+
+fn default_tts_voice() -> String {
+    "alloy".to_string()
+}
+
+fn default_history_compaction() -> String {
+    "keep_last".to_string()
+}
+
+fn default_history_compact_size() -> usize {
+    crate::eliza::DEFAULT_COMPACT_SIZE
+}
+
+fn default_tts_backend() -> String {
+    "openai".to_string()
+}
+
+fn default_voicevox_url() -> String {
+    crate::voicevox::DEFAULT_VOICEVOX_URL.to_string()
+}
+
+fn default_voicevox_style_id() -> u32 {
+    // ずんだもん (ノーマル)
+    3
+}
+
+fn default_voicevox_speed() -> f32 {
+    1.0
+}
+
+fn default_elevenlabs_voice_id() -> String {
+    crate::elevenlabs::DEFAULT_VOICE_ID.to_string()
+}
+
+fn default_tts_normalize_locale() -> String {
+    "ja".to_string()
+}
+
+fn default_tts_volume() -> f32 {
+    1.0
+}
+
+fn default_sfx_volume() -> f32 {
+    0.8
+}
+
+fn default_notify_volume() -> f32 {
+    0.5
+}
+
+fn default_quick_replies() -> Vec<String> {
+    vec!["ちょっと待ってね".to_string(), "AFKです".to_string()]
+}
+
+fn default_voice_confirm_frames() -> u32 {
+    2
+}
+
+fn default_voice_confirm_window_ms() -> u64 {
+    100
+}
+
+fn default_hold_to_talk_key() -> String {
+    "F9".to_string()
+}
+
+fn default_silence_follow_up_prompt() -> String {
+    "(しばらく沈黙が続いています。直前の話題に関連した短い質問をひとつして、会話を続けてください)".to_string()
+}
+
+fn default_status_topic_interval_turns() -> usize {
+    3
+}
+
+fn default_sensitivity_step() -> f32 {
+    0.2
+}
+
+fn default_activity_osc_target() -> String {
+    "127.0.0.1:9100".to_string()
+}
+
+fn default_config_encryption() -> String {
+    "none".to_string()
+}
+
+fn default_custom_prompt() -> String {
+    "{setting:{language:[JP,EN,ZH],situation:a man is speaking, goal:transcribe it}}".to_string()
+}
+
+fn default_custom_prompts() -> BTreeMap<String, String> {
+    BTreeMap::from([
+        ("en".to_string(), "A casual English conversation in VRChat. Transcribe it with punctuation.".to_string()),
+        ("ja".to_string(), "VRChat での日本語の会話です。句読点を付けて書き起こしてください。".to_string()),
+    ])
+}
+
+fn default_custom_prompt_language() -> String {
+    "auto".to_string()
+}
+
+fn default_audio_cleanup_minutes() -> u32 {
+    60
+}
+
+fn default_preroll_secs() -> f32 {
+    0.2
+}
+
 impl Default for Config {
     fn default() -> Self {
         Self {
@@ -41,22 +773,275 @@ impl Default for Config {
             start_threshold: 0.09,
             silence_threshold: 0.06,
             silence_duration_secs: 1.5,
+            preroll_secs: default_preroll_secs(),
+            voice_confirm_frames: default_voice_confirm_frames(),
+            voice_confirm_window_ms: default_voice_confirm_window_ms(),
+            hold_to_talk_enabled: false,
+            hold_to_talk_key: default_hold_to_talk_key(),
             whisper_model: "gpt-4o-transcribe".to_string(),
-            custom_prompt: "{setting:{language:[JP,EN,ZH],situation:a man is speaking, goal:transcribe it}}".to_string(),
+            custom_prompt: default_custom_prompt(),
+            custom_prompts: default_custom_prompts(),
+            custom_prompt_language: default_custom_prompt_language(),
+            stt_backend: default_stt_backend(),
+            stt_fallback_backend: default_stt_fallback_backend(),
+            stt_timeout_secs: default_stt_timeout_secs(),
+            stt_chunk_secs: default_stt_chunk_secs(),
+            local_whisper_path: default_local_whisper_path(),
+            local_whisper_model: String::new(),
+            local_whisper_model_size: default_local_whisper_model_size(),
+            local_whisper_language: default_local_whisper_language(),
+            local_whisper_threads: default_local_whisper_threads(),
+            local_whisper_max_queue: default_local_whisper_max_queue(),
+            whisper_context_enabled: false,
+            whisper_context_chars: default_whisper_context_chars(),
+            whisper_context_include_agent: false,
+            transcript_cleanup_enabled: false,
+            pii_redaction_enabled: false,
+            injection_guard: default_injection_guard(),
+            transcript_cleanup_model: default_transcript_cleanup_model(),
+            transcript_cleanup_prompt: default_transcript_cleanup_prompt(),
             agent_server_url: "http://localhost:9096".to_string(),
             agent_model: "grok-4-1-fast".to_string(),
             max_length_of_conversation_history: 20,
+            history_compaction: default_history_compaction(),
+            history_compact_size: default_history_compact_size(),
+            agent_timeout_secs: default_agent_timeout_secs(),
+            agent_streaming_enabled: false,
+            persona_prompt: String::new(),
+            agent_temperature: 0.0,
+            agent_spontaneity: 0.0,
+            agent_max_tokens: 0,
+            feedback_note_enabled: false,
+            feedback_note_count: default_feedback_note_count(),
+            ab_prompt_b: String::new(),
+            ab_test_turns: default_ab_test_turns(),
             input_device_name: None,
+            input_gains: BTreeMap::new(),
+            archive_recordings: false,
+            audio_work_dir: String::new(),
+            audio_cleanup_minutes: default_audio_cleanup_minutes(),
             use_vrchat_mute_detection: true,
+            afk_pause_enabled: false,
+            osc_send_port: default_osc_send_port(),
+            osc_listen_port: default_osc_listen_port(),
+            speaker_attribution_enabled: false,
+            listening_osc_address: String::new(),
+            agent_account_mode: false,
+            control_api_enabled: false,
+            control_api_port: default_control_api_port(),
+            control_api_token: String::new(),
+            world_chat_context_enabled: false,
+            world_chat_pattern: default_world_chat_pattern(),
+            world_chat_minutes: default_world_chat_minutes(),
+            greet_joins_enabled: false,
+            greet_per_minute: default_greet_per_minute(),
+            greet_blocklist: String::new(),
+            player_notes_enabled: false,
+            player_notes: BTreeMap::new(),
+            world_presets_enabled: false,
+            world_presets: BTreeMap::new(),
+            pause_when_vrchat_closed: true,
+            chatbox_retry_enabled: true,
+            chatbox_retry_secs: default_chatbox_retry_secs(),
+            chatbox_paginate_enabled: true,
+            chatbox_reading_cps: default_chatbox_reading_cps(),
+            chatbox_clear_enabled: false,
+            chatbox_clear_secs: default_chatbox_clear_secs(),
+            paste_long_replies_enabled: false,
+            paste_endpoint: default_paste_endpoint(),
+            reply_limit_enabled: false,
+            reply_limit_per_minute: default_reply_limit_per_minute(),
+            reply_cooldown_secs: default_reply_cooldown_secs(),
+            quote_mode: default_quote_mode(),
+            quote_prefix: default_quote_prefix(),
+            quote_suffix: String::new(),
+            dialogue_user_label: default_dialogue_user_label(),
+            dialogue_agent_label: String::new(),
+            karaoke_enabled: false,
+            karaoke_replay_secs: default_karaoke_replay_secs(),
+            session_summary_enabled: false,
+            mood_enabled: false,
+            mood_osc_address: String::new(),
+            silence_follow_up_secs: 0,
+            silence_follow_up_prompt: default_silence_follow_up_prompt(),
+            intent_filter_enabled: false,
+            agent_names: String::new(),
+            intent_use_llm: false,
+            intent_model: default_transcript_cleanup_model(),
+            status_topic_enabled: false,
+            status_topic_interval_turns: default_status_topic_interval_turns(),
+            status_topic_osc_address: String::new(),
+            language_routing_enabled: false,
+            language_routes: BTreeMap::new(),
+            auto_reply_language_enabled: false,
+            language_filter_enabled: false,
+            accepted_languages: default_accepted_languages(),
+            safety_word: String::new(),
+            safety_word_wipe_history: false,
+            dnd_keywords: String::new(),
+            voice_commands_enabled: true,
+            sensitivity_step: default_sensitivity_step(),
+            compare_mode_enabled: false,
+            compare_server_url: String::new(),
+            compare_model: String::new(),
+            compare_use_secondary: false,
+            text_mode: false,
+            soundboard_enabled: false,
+            sfx_map: BTreeMap::new(),
+            sfx_output_device: String::new(),
+            sfx_volume: default_sfx_volume(),
+            tts_enabled: false,
+            tts_backend: default_tts_backend(),
+            tts_voice: default_tts_voice(),
+            voicevox_url: default_voicevox_url(),
+            voicevox_style_id: default_voicevox_style_id(),
+            voicevox_speed: default_voicevox_speed(),
+            voicevox_pitch: 0.0,
+            elevenlabs_api_key: String::new(),
+            elevenlabs_voice_id: default_elevenlabs_voice_id(),
+            tts_output_device: String::new(),
+            tts_volume: default_tts_volume(),
+            monitor_tts_enabled: false,
+            monitor_tts_output_device: String::new(),
+            monitor_tts_volume: default_tts_volume(),
+            barge_in_enabled: true,
+            tts_normalize_enabled: true,
+            tts_normalize_locale: default_tts_normalize_locale(),
+            notify_recording_enabled: false,
+            notify_recording_sound: String::new(),
+            notify_reply_enabled: false,
+            notify_reply_sound: String::new(),
+            notify_error_enabled: false,
+            notify_error_sound: String::new(),
+            notify_output_device: String::new(),
+            notify_volume: default_notify_volume(),
+            quick_replies: default_quick_replies(),
+            scheduled_messages: Vec::new(),
+            quick_replies_bypass_agent: true,
+            config_encryption: default_config_encryption(),
+            hooks: Vec::new(),
+            activity_osc_enabled: false,
+            activity_osc_target: default_activity_osc_target(),
+            midi_enabled: false,
+            midi_port_name: String::new(),
+            midi_bindings: Vec::new(),
         }
     }
 }
 
+/// "key=value" の行リストをマップに変換する (空行・不正な行は無視)
+pub fn parse_mapping_lines(text: &str) -> BTreeMap<String, String> {
+    text.lines()
+        .filter_map(|line| {
+            let (key, value) = line.split_once('=')?;
+            let (key, value) = (key.trim(), value.trim());
+            if key.is_empty() || value.is_empty() {
+                None
+            } else {
+                Some((key.to_string(), value.to_string()))
+            }
+        })
+        .collect()
+}
+
+/// 設定に書かれたプリセット名として受け付けるか (パスの区切りや ".." を含むものはファイル名に使わない)
+pub fn is_valid_preset_name(name: &str) -> bool {
+    !name.is_empty() && !name.contains(['/', '\\']) && !name.contains("..")
+}
+
+/// マップを "key=value" の行リストに変換する
+pub fn format_mapping_lines(map: &BTreeMap<String, String>) -> String {
+    map.iter()
+        .map(|(k, v)| format!("{}={}", k, v))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
 impl Config {
+    /// 選択中の入力デバイスのゲイン (既定デバイスは "Windows既定" で登録)
+    pub fn input_gain(&self) -> f32 {
+        let device_name = self.input_device_name.as_deref().unwrap_or("Windows既定");
+        self.input_gains.get(device_name).copied().unwrap_or(1.0)
+    }
+
+    /// ワールドに割り当てたプリセット (名前は "setting1" でも "設定1" でもよい)
+    pub fn preset_for_world(&self, world: &str) -> Option<String> {
+        let target = self
+            .world_presets
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(world))
+            .or_else(|| self.world_presets.get_key_value("*"))
+            .map(|(_, preset)| preset.trim())?;
+        Self::list_presets()
+            .into_iter()
+            .find(|preset| preset == target || Self::preset_display_name(preset) == target)
+    }
+
+    /// 話した言語に割り当てたプリセット (名前は "setting1" でも "設定1" でもよい)
+    pub fn preset_for_language(&self, language: &str) -> Option<String> {
+        let target = self.language_routes.get(language).map(|preset| preset.trim())?;
+        if !is_valid_preset_name(target) {
+            return None;
+        }
+        Self::list_presets()
+            .into_iter()
+            .find(|preset| preset == target || Self::preset_display_name(preset) == target)
+    }
+
+    /// 書き起こしに使うプロンプト。言語別のものがあればそれを、なければ custom_prompt を使う
+    /// (language は直前の発話の言語。custom_prompt_language を決めていればそちらを優先する)
+    ///
+    /// 自動判定のときは、custom_prompt を自分で書いていれば組み込みの言語別プロンプトでは置き換えない
+    pub fn custom_prompt_for(&self, language: Option<&str>) -> &str {
+        let (language, auto) = match self.custom_prompt_language.trim() {
+            "" | "auto" => (language, true),
+            fixed => (Some(fixed), false),
+        };
+        let own_prompt = self.custom_prompt != default_custom_prompt();
+        language
+            .and_then(|language| {
+                let prompt = self.custom_prompts.get(language)?;
+                let built_in = default_custom_prompts().get(language) == Some(prompt);
+                (!(auto && built_in && own_prompt)).then_some(prompt)
+            })
+            .unwrap_or(&self.custom_prompt)
+    }
+
+    /// 言語の制限で受け付ける言語か (判定できなかった発話は受け付ける)
+    pub fn accepts_language(&self, language: Option<&str>) -> bool {
+        let (true, Some(language)) = (self.language_filter_enabled, language) else {
+            return true;
+        };
+        self.accepted_languages
+            .split(',')
+            .any(|accepted| accepted.trim().eq_ignore_ascii_case(language))
+    }
+
+    /// 書き起こしを引用の形にする
+    pub fn quote(&self, text: &str) -> String {
+        format!("{}{}{}", self.quote_prefix, text, self.quote_suffix)
+    }
+
+    /// 実際に使う引用方法 (Agent アカウントモードではオペレーターの入力を引用しない)
+    pub fn effective_quote_mode(&self) -> &str {
+        if self.agent_account_mode {
+            "off"
+        } else {
+            &self.quote_mode
+        }
+    }
+
     /// Get the config directory
     pub fn config_dir() -> Result<PathBuf, String> {
+        #[cfg(test)]
+        if let Some(dir) = TEST_CONFIG_DIR.with(|d| d.borrow().clone()) {
+            return Ok(dir);
+        }
         let config_dir = dirs::config_dir().ok_or("Failed to get config directory")?;
-        let app_config_dir = config_dir.join("eliza-agent");
+        let app_config_dir = match instance() {
+            Some(name) => config_dir.join(format!("eliza-agent-{}", name)),
+            None => config_dir.join("eliza-agent"),
+        };
 
         if !app_config_dir.exists() {
             fs::create_dir_all(&app_config_dir)
@@ -125,11 +1110,17 @@ impl Config {
 
     /// Load config from a specific preset
     pub fn load_preset(preset_name: &str) -> Self {
+        let mut config = Self::read_preset(preset_name);
+        config.apply_world_presets();
+        config
+    }
+
+    fn read_preset(preset_name: &str) -> Self {
         match Self::config_path_for_preset(preset_name) {
             Ok(path) => {
                 if path.exists() {
-                    match fs::read_to_string(&path) {
-                        Ok(content) => match serde_json::from_str(&content) {
+                    match fs::read_to_string(&path).map(|content| config_crypt::open(&content)) {
+                        Ok(Ok(content)) => match serde_json::from_str(&content) {
                             Ok(config) => {
                                 println!("Config loaded from: {:?}", path);
                                 return config;
@@ -138,6 +1129,12 @@ impl Config {
                                 eprintln!("Failed to parse config: {}", e);
                             }
                         },
+                        Ok(Err(e)) => {
+                            eprintln!("{}", e);
+                            if let Ok(mut unreadable) = UNREADABLE_PRESETS.lock() {
+                                unreadable.insert(preset_name.to_string());
+                            }
+                        }
                         Err(e) => {
                             eprintln!("Failed to read config file: {}", e);
                         }
@@ -155,16 +1152,95 @@ impl Config {
         Self::default()
     }
 
+    /// 共通のワールド対応表で上書きする
+    /// (まだ無ければ、このプリセットに残っている古い対応表を共通のものとして書き出す)
+    pub fn apply_world_presets(&mut self) {
+        let Ok(path) = Self::config_dir().map(|dir| dir.join(WORLD_PRESETS_FILE)) else {
+            return;
+        };
+        match fs::read_to_string(&path) {
+            Ok(content) => match serde_json::from_str::<WorldPresets>(&content) {
+                Ok(shared) => {
+                    self.world_presets_enabled = shared.enabled;
+                    self.world_presets = shared.presets;
+                }
+                Err(e) => eprintln!("Failed to parse {:?}: {}", path, e),
+            },
+            Err(_) if self.world_presets_enabled || !self.world_presets.is_empty() => {
+                if let Err(e) = self.save_world_presets() {
+                    eprintln!("{}", e);
+                }
+            }
+            Err(_) => {}
+        }
+    }
+
+    /// ワールド対応表を共通のファイルに書く
+    pub fn save_world_presets(&self) -> Result<(), String> {
+        let shared = WorldPresets {
+            enabled: self.world_presets_enabled,
+            presets: self.world_presets.clone(),
+        };
+        let json = serde_json::to_string_pretty(&shared)
+            .map_err(|e| format!("Failed to serialize world presets: {}", e))?;
+        fs::write(Self::config_dir()?.join(WORLD_PRESETS_FILE), json)
+            .map_err(|e| format!("Failed to save world presets: {}", e))
+    }
+
     /// Save config to a specific preset
     pub fn save_preset(&self, preset_name: &str) -> Result<(), String> {
+        if UNREADABLE_PRESETS.lock().is_ok_and(|unreadable| unreadable.contains(preset_name)) {
+            return Err(format!(
+                "{} could not be decrypted, so it is not overwritten",
+                Self::preset_display_name(preset_name)
+            ));
+        }
         let path = Self::config_path_for_preset(preset_name)?;
         let json = serde_json::to_string_pretty(self)
             .map_err(|e| format!("Failed to serialize config: {}", e))?;
-        fs::write(&path, json).map_err(|e| format!("Failed to write config file: {}", e))?;
+        let content = config_crypt::seal(&json, &self.config_encryption)?;
+        fs::write(&path, content).map_err(|e| format!("Failed to write config file: {}", e))?;
         println!("Config saved to: {:?}", path);
         Ok(())
     }
 
+    /// API キー・トークン・フック (Webhook の URL に鍵が入ることがある) を外した写し。
+    /// セッションファイルのように暗号化しない保存先に書くときに使う
+    pub fn without_secrets(&self) -> Config {
+        let mut config = self.clone();
+        config.restore_secrets(&Config::default());
+        config
+    }
+
+    /// without_secrets で外した値を from のもので埋める
+    pub fn restore_secrets(&mut self, from: &Config) {
+        self.openai_api_key = from.openai_api_key.clone();
+        self.elevenlabs_api_key = from.elevenlabs_api_key.clone();
+        self.control_api_token = from.control_api_token.clone();
+        self.hooks = from.hooks.clone();
+    }
+
+    /// 保存済みのプリセットをすべて mode の暗号化で保存し直す (平文からの移行用)。保存し直した数を返す
+    pub fn reencrypt_presets(mode: &str) -> Result<usize, String> {
+        let mut count = 0;
+        for preset in Self::list_presets() {
+            let path = Self::config_path_for_preset(&preset)?;
+            let Ok(content) = fs::read_to_string(&path) else {
+                continue;
+            };
+            let current = config_crypt::encryption_of(&content).unwrap_or_else(|| "none".to_string());
+            if current == mode {
+                continue;
+            }
+            let mut config: Config = serde_json::from_str(&config_crypt::open(&content)?)
+                .map_err(|e| format!("Failed to parse {}: {}", path.display(), e))?;
+            config.config_encryption = mode.to_string();
+            config.save_preset(&preset)?;
+            count += 1;
+        }
+        Ok(count)
+    }
+
     /// Apply command line arguments
     pub fn apply_args(&mut self, args: &[String]) {
         for arg in args {
@@ -178,3 +1254,139 @@ impl Config {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_without_secrets() {
+        let config = Config {
+            openai_api_key: "sk-test".to_string(),
+            elevenlabs_api_key: "el-test".to_string(),
+            control_api_token: "token".to_string(),
+            hooks: vec![HookConfig::default()],
+            agent_model: "model".to_string(),
+            ..Config::default()
+        };
+        let snapshot = config.without_secrets();
+        let json = serde_json::to_string(&snapshot).unwrap();
+        assert!(!json.contains("sk-test") && !json.contains("el-test") && !json.contains("\"token\""));
+        assert!(snapshot.hooks.is_empty());
+        assert_eq!(snapshot.agent_model, "model");
+
+        let mut restored = snapshot;
+        restored.restore_secrets(&config);
+        assert_eq!(restored.openai_api_key, "sk-test");
+        assert_eq!(restored.hooks.len(), 1);
+    }
+
+    #[test]
+    fn test_instance_arg() {
+        let args = ["eliza-agent".to_string(), "--instance=alt".to_string()];
+        assert_eq!(instance_arg(&args), Some("alt"));
+        assert_eq!(instance_arg(&args[..1]), None);
+    }
+
+    #[test]
+    fn test_mapping_lines_roundtrip() {
+        let map = parse_mapping_lines("ja = default\n\nen=setting1\ninvalid\nko=");
+        assert_eq!(map.len(), 2);
+        assert_eq!(map.get("ja"), Some(&"default".to_string()));
+        assert_eq!(format_mapping_lines(&map), "en=setting1\nja=default");
+    }
+
+    #[test]
+    fn test_preset_for_language() {
+        let config = Config {
+            language_routes: parse_mapping_lines("ja=default\nen=setting1\nko=../../evil\nzh=setting1/..\nfr=unknown"),
+            ..Config::default()
+        };
+        assert_eq!(config.preset_for_language("ja"), Some("default".to_string()));
+        assert_eq!(config.preset_for_language("en"), Some("setting1".to_string()));
+        assert_eq!(config.preset_for_language("ko"), None);
+        assert_eq!(config.preset_for_language("zh"), None);
+        assert_eq!(config.preset_for_language("fr"), None);
+        assert!(!is_valid_preset_name("..\\config"));
+        assert!(is_valid_preset_name("setting1"));
+    }
+
+    #[test]
+    fn test_custom_prompt_for() {
+        let mut config = Config {
+            custom_prompt: "fallback".to_string(),
+            custom_prompts: parse_mapping_lines("ja=日本語\nen=English"),
+            ..Config::default()
+        };
+        assert_eq!(config.custom_prompt_for(Some("en")), "English");
+        assert_eq!(config.custom_prompt_for(Some("ko")), "fallback");
+        assert_eq!(config.custom_prompt_for(None), "fallback");
+        config.custom_prompt_language = "ja".to_string();
+        assert_eq!(config.custom_prompt_for(Some("en")), "日本語");
+
+        // 組み込みの言語別プロンプトは、自分で書いた custom_prompt を置き換えない
+        let mut config = Config {
+            custom_prompt: "固有名詞: ミライ".to_string(),
+            ..Config::default()
+        };
+        assert_eq!(config.custom_prompt_for(Some("ja")), "固有名詞: ミライ");
+        config.custom_prompt_language = "ja".to_string();
+        assert_eq!(config.custom_prompt_for(None), default_custom_prompts()["ja"]);
+        let config = Config::default();
+        assert_eq!(config.custom_prompt_for(Some("ja")), default_custom_prompts()["ja"]);
+    }
+
+    #[test]
+    fn test_accepts_language() {
+        let mut config = Config {
+            accepted_languages: "ja, en".to_string(),
+            ..Config::default()
+        };
+        assert!(config.accepts_language(Some("ko")));
+        config.language_filter_enabled = true;
+        assert!(config.accepts_language(Some("ja")));
+        assert!(config.accepts_language(Some("en")));
+        assert!(!config.accepts_language(Some("ko")));
+        assert!(config.accepts_language(None));
+    }
+
+    #[test]
+    fn test_preset_for_world() {
+        let config = Config {
+            world_presets: BTreeMap::from([
+                ("Study Room".to_string(), "setting1".to_string()),
+                ("Party Hall".to_string(), "設定2".to_string()),
+                ("Old World".to_string(), "nowhere".to_string()),
+            ]),
+            ..Config::default()
+        };
+        assert_eq!(config.preset_for_world("study room"), Some("setting1".to_string()));
+        assert_eq!(config.preset_for_world("Party Hall"), Some("setting2".to_string()));
+        assert_eq!(config.preset_for_world("Old World"), None);
+        assert_eq!(config.preset_for_world("Home"), None);
+
+        let with_fallback = Config {
+            world_presets: BTreeMap::from([("*".to_string(), "default".to_string())]),
+            ..config
+        };
+        assert_eq!(with_fallback.preset_for_world("Home"), Some("default".to_string()));
+    }
+
+    #[test]
+    fn test_world_presets_not_saved_per_preset() {
+        let config = Config {
+            world_presets_enabled: true,
+            world_presets: BTreeMap::from([("Study Room".to_string(), "setting1".to_string())]),
+            ..Config::default()
+        };
+        let json = serde_json::to_string(&config).unwrap();
+        assert!(!json.contains("world_presets"));
+
+        // 古いプリセットに残っている対応表は読める (共通のファイルに移すため)
+        let legacy = json.replacen('{', r#"{"world_presets_enabled":true,"world_presets":{"*":"default"},"#, 1);
+        let loaded: Config = serde_json::from_str(&legacy).unwrap();
+        assert!(loaded.world_presets_enabled);
+        assert_eq!(loaded.world_presets.get("*").map(String::as_str), Some("default"));
+    }
+
+}
@@ -0,0 +1,135 @@
+//! プリセットの JSON ファイルの暗号化 (PC を共有するとき API キーなどを平文で置かないため)
+//!
+//! 暗号化したファイルは `{"encryption": 方式, "data": base64}` の形で保存し、読み込むときに方式を見て復号する。
+//! 暗号処理は Windows 標準の PowerShell (.NET) に任せる
+
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/// 暗号化の方式 (設定値, 表示名)
+pub const ENCRYPTION_MODES: [(&str, &str); 3] = [
+    ("none", "しない"),
+    ("dpapi", "Windows アカウント (DPAPI)"),
+    ("passphrase", "パスフレーズ"),
+];
+
+/// パスフレーズ方式のパスフレーズを渡す環境変数
+pub const PASSPHRASE_ENV: &str = "ELIZA_CONFIG_PASSPHRASE";
+
+#[derive(Serialize, Deserialize)]
+struct Envelope {
+    encryption: String,
+    data: String,
+}
+
+const PREAMBLE: &str = "$ErrorActionPreference = 'Stop'; \
+    Add-Type -AssemblyName System.Security; \
+    [Console]::InputEncoding = New-Object Text.UTF8Encoding $false; \
+    [Console]::OutputEncoding = New-Object Text.UTF8Encoding $false; \
+    $in = [Console]::In.ReadToEnd();";
+
+const DPAPI_PROTECT: &str = "$bytes = [Text.Encoding]::UTF8.GetBytes($in); \
+    [Convert]::ToBase64String([Security.Cryptography.ProtectedData]::Protect($bytes, $null, 'CurrentUser'))";
+
+const DPAPI_UNPROTECT: &str = "$bytes = [Security.Cryptography.ProtectedData]::Unprotect([Convert]::FromBase64String($in.Trim()), $null, 'CurrentUser'); \
+    [Text.Encoding]::UTF8.GetString($bytes)";
+
+// salt (16) + IV (16) + AES-256-CBC の暗号文。鍵は PBKDF2 (SHA-256, 100000 回) で作る
+const PASSPHRASE_PROTECT: &str = "$salt = New-Object byte[] 16; \
+    [Security.Cryptography.RandomNumberGenerator]::Create().GetBytes($salt); \
+    $kdf = New-Object Security.Cryptography.Rfc2898DeriveBytes($env:ELIZA_CONFIG_PASSPHRASE, $salt, 100000, [Security.Cryptography.HashAlgorithmName]::SHA256); \
+    $aes = [Security.Cryptography.Aes]::Create(); $aes.Key = $kdf.GetBytes(32); $aes.GenerateIV(); \
+    $bytes = [Text.Encoding]::UTF8.GetBytes($in); \
+    $cipher = $aes.CreateEncryptor().TransformFinalBlock($bytes, 0, $bytes.Length); \
+    [Convert]::ToBase64String($salt + $aes.IV + $cipher)";
+
+const PASSPHRASE_UNPROTECT: &str = "$all = [Convert]::FromBase64String($in.Trim()); \
+    $kdf = New-Object Security.Cryptography.Rfc2898DeriveBytes($env:ELIZA_CONFIG_PASSPHRASE, [byte[]]$all[0..15], 100000, [Security.Cryptography.HashAlgorithmName]::SHA256); \
+    $aes = [Security.Cryptography.Aes]::Create(); $aes.Key = $kdf.GetBytes(32); $aes.IV = [byte[]]$all[16..31]; \
+    $bytes = $aes.CreateDecryptor().TransformFinalBlock($all, 32, $all.Length - 32); \
+    [Text.Encoding]::UTF8.GetString($bytes)";
+
+/// 保存する内容を作る (mode が "none" ならそのまま)
+pub fn seal(json: &str, mode: &str) -> Result<String, String> {
+    let script = match mode {
+        "dpapi" => DPAPI_PROTECT,
+        "passphrase" => PASSPHRASE_PROTECT,
+        _ => return Ok(json.to_string()),
+    };
+    let envelope = Envelope {
+        encryption: mode.to_string(),
+        data: run_powershell(script, json, mode == "passphrase")?.trim().to_string(),
+    };
+    serde_json::to_string_pretty(&envelope).map_err(|e| format!("Failed to serialize encrypted config: {}", e))
+}
+
+/// 暗号化されたファイルなら方式を返す
+pub fn encryption_of(content: &str) -> Option<String> {
+    serde_json::from_str::<Envelope>(content).ok().map(|e| e.encryption)
+}
+
+/// 読み込んだ内容を JSON に戻す (暗号化されていなければそのまま)
+pub fn open(content: &str) -> Result<String, String> {
+    let Ok(envelope) = serde_json::from_str::<Envelope>(content) else {
+        return Ok(content.to_string());
+    };
+    let script = match envelope.encryption.as_str() {
+        "dpapi" => DPAPI_UNPROTECT,
+        "passphrase" => PASSPHRASE_UNPROTECT,
+        other => return Err(format!("Unknown config encryption: {}", other)),
+    };
+    run_powershell(script, &envelope.data, envelope.encryption == "passphrase")
+        .map_err(|e| format!("Failed to decrypt config ({}): {}", envelope.encryption, e))
+}
+
+/// PowerShell に input を標準入力で渡してスクリプトを実行し、標準出力を返す
+fn run_powershell(script: &str, input: &str, needs_passphrase: bool) -> Result<String, String> {
+    if needs_passphrase && std::env::var(PASSPHRASE_ENV).map_or(true, |p| p.is_empty()) {
+        return Err(format!("{} is not set", PASSPHRASE_ENV));
+    }
+    let mut command = Command::new("powershell");
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+        const CREATE_NO_WINDOW: u32 = 0x08000000;
+        command.creation_flags(CREATE_NO_WINDOW);
+    }
+    let mut child = command
+        .args(["-NoProfile", "-NonInteractive", "-Command", &format!("{} {}", PREAMBLE, script)])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to run PowerShell: {}", e))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(input.as_bytes())
+            .map_err(|e| format!("Failed to write to PowerShell: {}", e))?;
+    }
+    let output = child
+        .wait_with_output()
+        .map_err(|e| format!("Failed to run PowerShell: {}", e))?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    Ok(stdout.trim_start_matches('\u{feff}').to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plaintext_passthrough() {
+        let json = r#"{"openai_api_key": "sk-test"}"#;
+        assert_eq!(seal(json, "none").unwrap(), json);
+        assert_eq!(open(json).unwrap(), json);
+        assert_eq!(encryption_of(json), None);
+
+        let sealed = r#"{"encryption": "dpapi", "data": "AQID"}"#;
+        assert_eq!(encryption_of(sealed).as_deref(), Some("dpapi"));
+        assert!(open(r#"{"encryption": "rot13", "data": ""}"#).is_err());
+    }
+}
@@ -0,0 +1,231 @@
+use crate::error::AppError;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// 受け付ける本文の上限
+const MAX_BODY_BYTES: usize = 64 * 1024;
+
+/// ローカルの操作用 API (`POST /say` の本文を Agent に送るテキストとして渡す)
+///
+/// オペレーターが別アカウントやスクリプト・Discord bot などからテキストを送るためのもの。
+/// 127.0.0.1 でのみ待ち受け、drop すると止まる。
+/// ブラウザで開いたページからのクロスサイトリクエストで話させられないように、
+/// トークンを必須にし、Origin 付き・JSON 以外のリクエストは受け付けない
+pub struct ControlServer {
+    pub port: u16,
+    pub token: String,
+    stop: Arc<AtomicBool>,
+}
+
+/// 推測できないトークン (32 バイトの乱数の 16 進)
+pub fn generate_token() -> Result<String, String> {
+    let mut bytes = [0u8; 32];
+    getrandom::getrandom(&mut bytes).map_err(|e| format!("Failed to generate control API token: {}", e))?;
+    Ok(bytes.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+impl ControlServer {
+    pub fn start(port: u16, token: String, sender: Sender<String>) -> Result<Self, AppError> {
+        if token.is_empty() {
+            return Err(AppError::Config("Control API token is not set".to_string()));
+        }
+        let listener = TcpListener::bind(("127.0.0.1", port))
+            .map_err(|e| AppError::Config(format!("Failed to bind control API port {}: {}", port, e)))?;
+        listener
+            .set_nonblocking(true)
+            .map_err(|e| AppError::Other(format!("Failed to configure control API socket: {}", e)))?;
+        println!("[Control API] Listening on http://127.0.0.1:{}/say", port);
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = stop.clone();
+        let thread_token = token.clone();
+        std::thread::spawn(move || {
+            while !thread_stop.load(Ordering::Relaxed) {
+                match listener.accept() {
+                    Ok((stream, _)) => {
+                        if let Err(e) = handle(stream, &thread_token, &sender) {
+                            eprintln!("[Control API] {}", e);
+                        }
+                    }
+                    Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                        std::thread::sleep(Duration::from_millis(100));
+                    }
+                    Err(e) => {
+                        eprintln!("[Control API] accept error: {}", e);
+                        break;
+                    }
+                }
+            }
+            println!("[Control API] Stopped");
+        });
+        Ok(Self { port, token, stop })
+    }
+}
+
+impl Drop for ControlServer {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+/// リクエストのうち、受け付けるかどうかの判断に使うヘッダー
+#[derive(Debug, Default)]
+struct Headers {
+    content_length: usize,
+    content_type: Option<String>,
+    authorization: Option<String>,
+    /// ブラウザはクロスサイトの POST に必ず付ける
+    has_origin: bool,
+}
+
+impl Headers {
+    fn parse_line(&mut self, line: &str) {
+        let Some((name, value)) = line.split_once(':') else {
+            return;
+        };
+        let value = value.trim();
+        match name.trim().to_ascii_lowercase().as_str() {
+            "content-length" => self.content_length = value.parse().unwrap_or(0),
+            "content-type" => self.content_type = Some(value.to_string()),
+            "authorization" => self.authorization = Some(value.to_string()),
+            "origin" => self.has_origin = true,
+            _ => {}
+        }
+    }
+}
+
+/// 受け付けないリクエストならステータスと理由を返す
+fn check_headers(headers: &Headers, token: &str) -> Result<(), (&'static str, &'static str)> {
+    if headers.has_origin {
+        return Err(("403 Forbidden", "requests from web pages are not accepted"));
+    }
+    let presented = headers
+        .authorization
+        .as_deref()
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(str::trim);
+    if !presented.is_some_and(|presented| token_matches(presented, token)) {
+        return Err(("401 Unauthorized", "missing or wrong token"));
+    }
+    let is_json = headers
+        .content_type
+        .as_deref()
+        .and_then(|value| value.split(';').next())
+        .is_some_and(|media| media.trim().eq_ignore_ascii_case("application/json"));
+    if !is_json {
+        return Err(("415 Unsupported Media Type", "use Content-Type: application/json"));
+    }
+    Ok(())
+}
+
+/// 長さ以外で早く打ち切らない比較 (応答時間からトークンを推測されないように)
+fn token_matches(presented: &str, token: &str) -> bool {
+    presented.len() == token.len()
+        && presented
+            .bytes()
+            .zip(token.bytes())
+            .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+fn handle(stream: TcpStream, token: &str, sender: &Sender<String>) -> Result<(), String> {
+    stream.set_nonblocking(false).ok();
+    stream.set_read_timeout(Some(Duration::from_secs(5))).ok();
+    let mut reader = BufReader::new(&stream);
+
+    let mut request_line = String::new();
+    reader
+        .read_line(&mut request_line)
+        .map_err(|e| format!("Failed to read request: {}", e))?;
+    let mut headers = Headers::default();
+    loop {
+        let mut line = String::new();
+        reader
+            .read_line(&mut line)
+            .map_err(|e| format!("Failed to read headers: {}", e))?;
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        headers.parse_line(line);
+    }
+
+    let (status, message) = if !request_line.starts_with("POST /say ") {
+        ("404 Not Found", "use POST /say".to_string())
+    } else if let Err((status, reason)) = check_headers(&headers, token) {
+        eprintln!("[Control API] Rejected: {}", reason);
+        (status, reason.to_string())
+    } else if headers.content_length > MAX_BODY_BYTES {
+        ("413 Payload Too Large", "body too large".to_string())
+    } else {
+        let mut body = vec![0; headers.content_length];
+        reader
+            .read_exact(&mut body)
+            .map_err(|e| format!("Failed to read body: {}", e))?;
+        match parse_say_body(&String::from_utf8_lossy(&body)) {
+            Some(text) => {
+                println!("[Control API] Received: {}", text);
+                let _ = sender.send(text);
+                ("202 Accepted", "ok".to_string())
+            }
+            None => ("400 Bad Request", "expected {\"text\": \"...\"}".to_string()),
+        }
+    };
+
+    let mut stream = &stream;
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        message.len(),
+        message
+    )
+    .map_err(|e| format!("Failed to write response: {}", e))
+}
+
+/// 本文は `{"text": "..."}`
+pub fn parse_say_body(body: &str) -> Option<String> {
+    let value = serde_json::from_str::<serde_json::Value>(body).ok()?;
+    let text = value.get("text")?.as_str()?.trim();
+    (!text.is_empty()).then(|| text.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_say_body() {
+        assert_eq!(parse_say_body(r#"{"text": " こんにちは "}"#), Some("こんにちは".to_string()));
+        assert_eq!(parse_say_body("やあ\n"), None);
+        assert_eq!(parse_say_body(r#"{"message": "x"}"#), None);
+        assert_eq!(parse_say_body(r#"{"text": "  "}"#), None);
+    }
+
+    #[test]
+    fn test_check_headers() {
+        let headers = |lines: &[&str]| {
+            let mut headers = Headers::default();
+            lines.iter().for_each(|line| headers.parse_line(line));
+            headers
+        };
+        let ok = headers(&["Content-Type: application/json; charset=utf-8", "Authorization: Bearer secret"]);
+        assert_eq!(check_headers(&ok, "secret"), Ok(()));
+        assert_eq!(check_headers(&ok, "other").unwrap_err().0, "401 Unauthorized");
+        let no_token = headers(&["Content-Type: application/json"]);
+        assert_eq!(check_headers(&no_token, "secret").unwrap_err().0, "401 Unauthorized");
+        let from_browser = headers(&[
+            "Content-Type: application/json",
+            "Authorization: Bearer secret",
+            "Origin: https://example.com",
+        ]);
+        assert_eq!(check_headers(&from_browser, "secret").unwrap_err().0, "403 Forbidden");
+        let form = headers(&["Content-Type: text/plain", "Authorization: Bearer secret"]);
+        assert_eq!(check_headers(&form, "secret").unwrap_err().0, "415 Unsupported Media Type");
+        assert_eq!(generate_token().unwrap().len(), 64);
+    }
+}
@@ -0,0 +1,29 @@
+/// Do-not-disturb キーワード (カンマ区切り) を分ける
+pub fn parse_keywords(text: &str) -> Vec<String> {
+    text.split([',', '、', '\n'])
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
+/// text に含まれる最初のキーワード (大文字小文字は区別しない)
+pub fn find_keyword(keywords: &str, text: &str) -> Option<String> {
+    let text = text.to_lowercase();
+    parse_keywords(keywords)
+        .into_iter()
+        .find(|keyword| text.contains(&keyword.to_lowercase()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_keyword() {
+        let keywords = "住所, 電話番号、Password";
+        assert_eq!(find_keyword(keywords, "うちの住所はね"), Some("住所".to_string()));
+        assert_eq!(find_keyword(keywords, "my password is"), Some("Password".to_string()));
+        assert_eq!(find_keyword(keywords, "こんにちは"), None);
+        assert_eq!(find_keyword("", "住所"), None);
+    }
+}
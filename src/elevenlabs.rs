@@ -0,0 +1,114 @@
+//! ElevenLabs の音声合成 (生成しながら再生できるストリーミング API)
+
+use crate::error::AppError;
+use serde::Deserialize;
+use std::time::Duration;
+
+const ELEVENLABS_API_URL: &str = "https://api.elevenlabs.io";
+/// 多言語対応のモデル (日本語以外の人格でも自然に読める)
+const ELEVENLABS_MODEL: &str = "eleven_multilingual_v2";
+/// ストリーミングで受け取る PCM のサンプルレート
+pub const STREAM_SAMPLE_RATE: u32 = 22050;
+/// 既定の声 (Rachel)
+pub const DEFAULT_VOICE_ID: &str = "21m00Tcm4TlvDq8ikWAM";
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Voice {
+    pub voice_id: String,
+    pub name: String,
+}
+
+#[derive(Deserialize)]
+struct VoicesResponse {
+    voices: Vec<Voice>,
+}
+
+pub struct ElevenLabsClient {
+    api_key: String,
+    base_url: String,
+}
+
+impl ElevenLabsClient {
+    pub fn new(api_key: &str) -> Self {
+        Self {
+            api_key: api_key.to_string(),
+            base_url: ELEVENLABS_API_URL.to_string(),
+        }
+    }
+
+    #[cfg(test)]
+    fn with_base_url(mut self, base_url: &str) -> Self {
+        self.base_url = base_url.to_string();
+        self
+    }
+
+    /// アカウントで使える声の一覧
+    pub fn voices(&self) -> Result<Vec<Voice>, AppError> {
+        let response = reqwest::blocking::Client::new()
+            .get(format!("{}/v1/voices", self.base_url))
+            .header("xi-api-key", &self.api_key)
+            .timeout(Duration::from_secs(15))
+            .send()
+            .map_err(|e| AppError::Network(format!("Failed to connect to ElevenLabs: {}", e)))?;
+        let status = response.status();
+        if !status.is_success() {
+            return Err(AppError::http(status.as_u16(), format!("ElevenLabs returned status {}", status)));
+        }
+        response
+            .json::<VoicesResponse>()
+            .map(|r| r.voices)
+            .map_err(|e| AppError::Other(format!("Failed to parse ElevenLabs voices: {}", e)))
+    }
+
+    /// text の音声を 16bit モノラル PCM (STREAM_SAMPLE_RATE Hz) のストリームとして受け取る
+    pub fn stream(&self, text: &str, voice_id: &str) -> Result<reqwest::blocking::Response, AppError> {
+        let request = serde_json::json!({
+            "text": text,
+            "model_id": ELEVENLABS_MODEL,
+        });
+        // 本文を読み終えるまでがリクエストなので、全体のタイムアウトは付けない
+        let response = reqwest::blocking::Client::builder()
+            .connect_timeout(Duration::from_secs(10))
+            .timeout(None)
+            .build()
+            .map_err(|e| AppError::Other(format!("Failed to build HTTP client: {}", e)))?
+            .post(format!("{}/v1/text-to-speech/{}/stream", self.base_url, voice_id))
+            .query(&[("output_format", format!("pcm_{}", STREAM_SAMPLE_RATE))])
+            .header("xi-api-key", &self.api_key)
+            .json(&request)
+            .send()
+            .map_err(|e| AppError::Network(format!("Failed to connect to ElevenLabs: {}", e)))?;
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().unwrap_or_default();
+            return Err(AppError::http(status.as_u16(), format!("ElevenLabs returned status {}: {}", status, body)));
+        }
+        Ok(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{FakeAgentServer, FakeResponse};
+
+    #[test]
+    fn test_voices() {
+        let server = FakeAgentServer::start(vec![
+            FakeResponse::json(
+                200,
+                r#"{"voices":[{"voice_id":"abc","name":"Rachel","category":"premade"},{"voice_id":"def","name":"Adam"}]}"#,
+            ),
+            FakeResponse::json(401, r#"{"detail":"invalid api key"}"#),
+        ]);
+        let client = ElevenLabsClient::new("key").with_base_url(&server.url);
+        assert_eq!(
+            client.voices().unwrap(),
+            vec![
+                Voice { voice_id: "abc".to_string(), name: "Rachel".to_string() },
+                Voice { voice_id: "def".to_string(), name: "Adam".to_string() },
+            ]
+        );
+        assert_eq!(client.voices().unwrap_err().category(), "auth");
+    }
+}
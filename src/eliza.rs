@@ -1,5 +1,11 @@
+use crate::mock;
+use crate::snippets;
 use serde::{Deserialize, Serialize};
+use std::collections::hash_map::RandomState;
 use std::collections::VecDeque;
+use std::hash::{BuildHasher, Hasher};
+use std::io::{BufRead, BufReader};
+use std::time::Instant;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Message {
@@ -13,6 +19,8 @@ struct ChatRequest {
     messages: Vec<Message>,
     stream: bool,
     temperature: f32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_tokens: Option<u32>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -25,30 +33,237 @@ struct ChatResponse {
     tool: Option<Vec<(serde_json::Value, Option<serde_json::Value>)>>,
 }
 
-#[derive(Debug)]
+/// --mock のときの返答 (最後の user メッセージに台本で返す)
+fn mock_response(messages: &[Message]) -> ChatResponse {
+    let user = messages
+        .iter()
+        .rev()
+        .find(|m| m.role == "user")
+        .map(|m| m.content.as_str())
+        .unwrap_or_default();
+    ChatResponse {
+        message: Message {
+            role: "assistant".to_string(),
+            content: mock::reply(user),
+        },
+        sleep: false,
+        tool: None,
+    }
+}
+
+/// 履歴が上限を超えたときに残す件数 (既定値)
+pub const DEFAULT_COMPACT_SIZE: usize = 5;
+
+/// 履歴が上限を超えたときのまとめ方
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CompactionStrategy {
+    /// 記憶を保存してから直近 compact_size 件だけ残す
+    KeepLast,
+    /// 捨てる部分を Agent に要約させて補足に残し、直近 compact_size 件だけ残す
+    SummarizeThenKeep,
+    /// 上限に収まるまで古いやりとりを 1 往復ずつ捨てる (記憶は保存しない)
+    DropOldestPairs,
+}
+
+/// 設定値, 表示名
+pub const COMPACTION_STRATEGIES: [(&str, &str); 3] = [
+    ("keep_last", "記憶を保存して直近だけ残す"),
+    ("summarize", "要約してから直近だけ残す"),
+    ("drop_oldest_pairs", "古いやりとりから順に捨てる"),
+];
+
+impl CompactionStrategy {
+    /// 設定値から (知らない値なら KeepLast)
+    pub fn from_name(name: &str) -> Self {
+        match name {
+            "summarize" => CompactionStrategy::SummarizeThenKeep,
+            "drop_oldest_pairs" => CompactionStrategy::DropOldestPairs,
+            _ => CompactionStrategy::KeepLast,
+        }
+    }
+}
+
+/// 会話履歴の状態 (UI 表示用)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HistoryStats {
+    pub length: usize,
+    pub max_length: usize,
+    /// system プロンプトと補足を含めて送るトークン数の見積もり
+    pub estimated_tokens: usize,
+    /// 次のやりとりで記憶の保存と履歴の圧縮が起きる
+    pub compacts_next: bool,
+}
+
+/// トークン数のおおまかな見積もり (ASCII は 4 文字で 1 トークン、それ以外は 1 文字で 1 トークン)
+pub fn estimate_tokens(text: &str) -> usize {
+    let ascii = text.chars().filter(|c| c.is_ascii()).count();
+    let other = text.chars().count() - ascii;
+    ascii.div_ceil(4) + other
+}
+
+/// SSE の data 1 行分から (追加された本文, sleep) を取り出す。
+/// {"message": {"content": ...}} と OpenAI 形式の {"choices": [{"delta": ...}]} に対応し、
+/// JSON でなければ data をそのまま本文とみなす
+fn parse_stream_chunk(data: &str) -> (String, bool) {
+    let Ok(value) = serde_json::from_str::<serde_json::Value>(data) else {
+        return (data.to_string(), false);
+    };
+    let delta = value["message"]["content"]
+        .as_str()
+        .or_else(|| value["choices"][0]["delta"]["content"].as_str())
+        .or_else(|| value["content"].as_str())
+        .unwrap_or_default();
+    let sleep = value["sleep"].as_bool().unwrap_or(false);
+    (delta.to_string(), sleep)
+}
+
+/// GET /memory の応答をテキストにする。
+/// JSON なら文字列・配列・{"memory": ...} などから本文を取り出し、そうでなければそのまま使う
+fn parse_memory_response(body: &str) -> String {
+    fn collect(value: &serde_json::Value, out: &mut Vec<String>) {
+        match value {
+            serde_json::Value::String(s) if !s.trim().is_empty() => out.push(s.trim().to_string()),
+            serde_json::Value::Array(items) => items.iter().for_each(|v| collect(v, out)),
+            serde_json::Value::Object(map) => {
+                for key in ["memory", "memories", "messages", "content", "text"] {
+                    if let Some(v) = map.get(key) {
+                        collect(v, out);
+                        return;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    match serde_json::from_str::<serde_json::Value>(body) {
+        Ok(value) => {
+            let mut lines = Vec::new();
+            collect(&value, &mut lines);
+            lines.join("\n")
+        }
+        Err(_) => body.trim().to_string(),
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
 pub enum ElizaError {
+    #[error("Network error: {0}")]
     NetworkError(String),
+    #[error("API error: {0}")]
     ApiError(String),
+    /// サーバーがエラーの HTTP ステータスを返した (ステータスコード, メッセージ)
+    #[error("API error: {1}")]
+    HttpStatus(u16, String),
+    #[error("Parse error: {0}")]
     ParseError(String),
 }
 
-impl std::fmt::Display for ElizaError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            ElizaError::NetworkError(msg) => write!(f, "Network error: {}", msg),
-            ElizaError::ApiError(msg) => write!(f, "API error: {}", msg),
-            ElizaError::ParseError(msg) => write!(f, "Parse error: {}", msg),
+/// 記憶の保存を諦めるまでの時間 (遅いサーバーで終了や履歴の消去が止まらないように)
+const MEMORY_SAVE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(15);
+
+/// /memory に保存する会話履歴の写し
+pub struct MemorySnapshot {
+    server_url: String,
+    model: String,
+    messages: Vec<Message>,
+}
+
+impl MemorySnapshot {
+    /// Save to the /memory endpoint (gives up after MEMORY_SAVE_TIMEOUT)
+    pub fn save(self) -> Result<(), ElizaError> {
+        if self.messages.is_empty() || mock::is_enabled() {
+            return Ok(());
         }
+
+        let request = ChatRequest {
+            model: self.model,
+            messages: self.messages,
+            stream: false,
+            temperature: 0.0,
+            max_tokens: None,
+        };
+
+        let url = format!(
+            "{}/memory",
+            self.server_url.trim_end_matches("/chat")
+        );
+
+        println!("Saving memory to: {}", url);
+        let client = reqwest::blocking::Client::builder()
+            .timeout(MEMORY_SAVE_TIMEOUT)
+            .build()
+            .map_err(|e| ElizaError::NetworkError(format!("Failed to build client: {}", e)))?;
+        let response = client
+            .post(&url)
+            .header("Content-Type", "application/json")
+            .json(&request)
+            .send()
+            .map_err(|e| ElizaError::NetworkError(format!("Failed to save memory: {}", e)))?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().unwrap_or_default();
+            return Err(ElizaError::HttpStatus(
+                status.as_u16(),
+                format!("Memory API returned status {}: {}", status, body),
+            ));
+        }
+
+        println!("Memory saved successfully");
+        Ok(())
     }
 }
 
-impl std::error::Error for ElizaError {}
+/// temperature を ± spontaneity の範囲で揺らす (unit は 0..1 の乱数)。API が受け付ける 0..=2 に収める
+fn jitter_temperature(temperature: f32, spontaneity: f32, unit: f32) -> f32 {
+    (temperature + spontaneity * (unit * 2.0 - 1.0)).clamp(0.0, 2.0)
+}
+
+/// Drop the oldest messages as the compaction strategy does (without saving or summarizing)
+fn trim_history(
+    history: &mut VecDeque<Message>,
+    compaction: CompactionStrategy,
+    max_history_length: usize,
+    compact_size: usize,
+) {
+    if compaction == CompactionStrategy::DropOldestPairs {
+        while history.len() > max_history_length {
+            history.pop_front();
+            history.pop_front();
+        }
+        return;
+    }
+    // 上限より多く残すと毎回圧縮が起きるので、上限までに抑える
+    let keep = compact_size.min(max_history_length);
+    while history.len() > keep {
+        history.pop_front();
+    }
+}
 
 pub struct ElizaClient {
     server_url: String,
     model: String,
     conversation_history: VecDeque<Message>,
     max_history_length: usize,
+    /// 追加の人格プロンプト (空ならサーバー側のプロンプトのみ)
+    system_prompt: String,
+    /// 人格プロンプトとは別に添える補足 (key, 本文)。前回の会話の要約、気分など
+    context_notes: Vec<(String, String)>,
+    /// 次の 1 回のリクエストだけ履歴の後ろに付ける指示 (沈黙への問いかけなど。履歴には残さない)
+    transient_note: Option<String>,
+    turn_count: usize,
+    topic: Option<String>,
+    last_reply_at: Option<Instant>,
+    timeout: Option<std::time::Duration>,
+    compaction: CompactionStrategy,
+    compact_size: usize,
+    temperature: f32,
+    spontaneity: f32,
+    /// 返答の最大トークン数 (None なら制限しない。要約などの補助の呼び出しには使わない)
+    max_tokens: Option<u32>,
+    /// 履歴が上限を超えたときにサーバーの記憶へ保存するか (プライベートモードでは保存しない)
+    memory_saves: bool,
 }
 
 impl ElizaClient {
@@ -58,14 +273,198 @@ impl ElizaClient {
             model,
             conversation_history: VecDeque::new(),
             max_history_length,
+            system_prompt: String::new(),
+            context_notes: Vec::new(),
+            transient_note: None,
+            turn_count: 0,
+            topic: None,
+            last_reply_at: None,
+            timeout: None,
+            compaction: CompactionStrategy::KeepLast,
+            compact_size: DEFAULT_COMPACT_SIZE,
+            temperature: 0.0,
+            spontaneity: 0.0,
+            max_tokens: None,
+            memory_saves: true,
         }
     }
 
-    /// Send a message to Eliza and get a response.
+    /// 履歴が上限を超えたときのまとめ方と残す件数
+    pub fn with_compaction(mut self, strategy: CompactionStrategy, compact_size: usize) -> Self {
+        self.set_compaction(strategy, compact_size);
+        self
+    }
+
+    pub fn set_compaction(&mut self, strategy: CompactionStrategy, compact_size: usize) {
+        self.compaction = strategy;
+        self.compact_size = compact_size;
+    }
+
+    pub fn set_memory_saves(&mut self, enabled: bool) {
+        self.memory_saves = enabled;
+    }
+
+    /// 返答の temperature と、毎ターンそれを揺らす幅 (0 なら揺らさない)
+    pub fn with_temperature(mut self, temperature: f32, spontaneity: f32) -> Self {
+        self.set_temperature(temperature, spontaneity);
+        self
+    }
+
+    pub fn set_temperature(&mut self, temperature: f32, spontaneity: f32) {
+        self.temperature = temperature;
+        self.spontaneity = spontaneity;
+    }
+
+    /// 返答の最大トークン数 (0 なら制限しない)
+    pub fn with_max_tokens(mut self, max_tokens: u32) -> Self {
+        self.set_max_tokens(max_tokens);
+        self
+    }
+
+    pub fn set_max_tokens(&mut self, max_tokens: u32) {
+        self.max_tokens = (max_tokens > 0).then_some(max_tokens);
+    }
+
+    /// このターンに使う temperature
+    fn turn_temperature(&self) -> f32 {
+        if self.spontaneity <= 0.0 {
+            return self.temperature;
+        }
+        let unit = (RandomState::new().build_hasher().finish() % 10_000) as f32 / 10_000.0;
+        jitter_temperature(self.temperature, self.spontaneity, unit)
+    }
+
+    /// /chat へのリクエストを secs 秒で打ち切る (0 なら待ち続ける)
+    pub fn with_timeout(mut self, secs: u64) -> Self {
+        self.set_timeout(secs);
+        self
+    }
+
+    pub fn set_timeout(&mut self, secs: u64) {
+        self.timeout = (secs > 0).then(|| std::time::Duration::from_secs(secs));
+    }
+
+    /// Change the agent server and model on a live client (history is kept)
+    pub fn set_server(&mut self, server_url: String, model: String) {
+        self.server_url = server_url;
+        self.model = model;
+    }
+
+    /// Change the history limit on a live client. If the history is now over the limit,
+    /// it is compacted on the next exchange (before the request is sent)
+    pub fn set_max_history_length(&mut self, max_history_length: usize) {
+        self.max_history_length = max_history_length;
+    }
+
+    fn http_client(&self) -> Result<reqwest::blocking::Client, ElizaError> {
+        let mut builder = reqwest::blocking::Client::builder();
+        if let Some(timeout) = self.timeout {
+            builder = builder.timeout(timeout);
+        }
+        builder
+            .build()
+            .map_err(|e| ElizaError::NetworkError(format!("Failed to build client: {}", e)))
+    }
+
+    /// Set the persona prompt sent as a system message before the history
+    /// (`{{include:name}}` is replaced with the snippet file)
+    pub fn with_system_prompt(mut self, system_prompt: String) -> Self {
+        self.system_prompt = snippets::expand(&system_prompt);
+        self
+    }
+
+    /// Replace the persona prompt on a live client (history is kept)
+    pub fn set_system_prompt(&mut self, system_prompt: String) {
+        self.system_prompt = snippets::expand(&system_prompt);
+    }
+
+    /// Set a note sent as an extra system message (e.g. summary of the previous session)
+    pub fn with_context_note(mut self, key: &str, note: String) -> Self {
+        self.set_context_note(key, note);
+        self
+    }
+
+    /// Replace the note with the given key (an empty note removes it)
+    pub fn set_context_note(&mut self, key: &str, note: String) {
+        self.context_notes.retain(|(k, _)| k != key);
+        if !note.trim().is_empty() {
+            self.context_notes.push((key.to_string(), note));
+        }
+    }
+
+    /// Add `note` as a system message after the history for the next request only
+    /// (it is not kept in the history, so later turns do not see it)
+    pub fn set_transient_note(&mut self, note: String) {
+        self.transient_note = (!note.trim().is_empty()).then_some(note);
+    }
+
+    /// System prompt and context notes (if any) followed by the conversation history
+    /// and the transient note
+    fn request_messages(&self) -> Vec<Message> {
+        let mut messages = self.messages_with_history(&self.conversation_history);
+        if let Some(note) = &self.transient_note {
+            messages.push(Message {
+                role: "system".to_string(),
+                content: note.clone(),
+            });
+        }
+        messages
+    }
+
+    fn messages_with_history(&self, history: &VecDeque<Message>) -> Vec<Message> {
+        let mut messages = Vec::with_capacity(history.len() + self.context_notes.len() + 1);
+        let notes = self.context_notes.iter().map(|(_, note)| note);
+        for content in std::iter::once(&self.system_prompt).chain(notes) {
+            if !content.trim().is_empty() {
+                messages.push(Message {
+                    role: "system".to_string(),
+                    content: content.clone(),
+                });
+            }
+        }
+        messages.extend(history.iter().cloned());
+        messages
+    }
+
+    /// Messages the next exchange would send: `user_message` is added and the history trimmed to
+    /// the limit. With summarize_then_keep the new summary is not made here, only the trimming is shown
+    pub fn next_request_preview(&self, user_message: &str) -> Vec<Message> {
+        let mut history = self.conversation_history.clone();
+        history.push_back(Message {
+            role: "user".to_string(),
+            content: user_message.to_string(),
+        });
+        if history.len() > self.max_history_length {
+            trim_history(&mut history, self.compaction, self.max_history_length, self.compact_size);
+        }
+        self.messages_with_history(&history)
+    }
+
+    /// Send a message to Eliza and get a response (an empty message asks for a reply without a user turn,
+    /// e.g. after set_transient_note).
     /// Returns (response_text, sleep) where sleep=true means the user wants to sleep.
     pub fn send_message(&mut self, user_message: &str) -> Result<(String, bool), ElizaError> {
+        self.exchange(user_message, None)
+    }
+
+    /// Same as send_message, but requests `stream: true` and calls `on_delta` with each chunk
+    pub fn send_message_streaming(
+        &mut self,
+        user_message: &str,
+        on_delta: &mut dyn FnMut(&str),
+    ) -> Result<(String, bool), ElizaError> {
+        self.exchange(user_message, Some(on_delta))
+    }
+
+    fn exchange(
+        &mut self,
+        user_message: &str,
+        on_delta: Option<&mut dyn FnMut(&str)>,
+    ) -> Result<(String, bool), ElizaError> {
         // Add user message to history
-        self.add_message("user".to_string(), user_message.to_string());
+        if !user_message.is_empty() {
+            self.add_message("user".to_string(), user_message.to_string());
+        }
 
         println!("Sending message to Eliza: {}", user_message);
         println!(
@@ -73,28 +472,45 @@ impl ElizaClient {
             self.conversation_history.len()
         );
 
-        // Prepare messages
-        let messages: Vec<Message> = self.conversation_history.iter().cloned().collect();
+        // Prepare messages (the transient note is used only once, even if the request fails)
+        let messages = self.request_messages();
+        self.transient_note = None;
+        let chat_response = match on_delta {
+            Some(on_delta) => self.post_chat_stream(messages, on_delta)?,
+            None => self.post_chat(messages, self.max_tokens)?,
+        };
+
+        let assistant_message = chat_response.message.content.clone();
+        let sleep = chat_response.sleep;
+
+        // Add assistant message to history
+        self.add_message("assistant".to_string(), assistant_message.clone());
+        self.turn_count += 1;
+        self.last_reply_at = Some(Instant::now());
+
+        println!("Eliza response: {} (sleep={})", assistant_message, sleep);
+        Ok((assistant_message, sleep))
+    }
+
+    /// POST messages to /chat and parse the response (history is not modified)
+    fn post_chat(&self, messages: Vec<Message>, max_tokens: Option<u32>) -> Result<ChatResponse, ElizaError> {
+        if mock::is_enabled() {
+            return Ok(mock_response(&messages));
+        }
 
         // Prepare request
         let request = ChatRequest {
             model: self.model.clone(),
             messages,
             stream: false,
-            temperature: 0.0,
-        };
-
-        // Build the full URL (server_url + /chat if not already included)
-        let url = if self.server_url.ends_with("/chat") {
-            self.server_url.clone()
-        } else {
-            format!("{}/chat", self.server_url)
+            temperature: self.turn_temperature(),
+            max_tokens,
         };
 
         // Send request
-        let client = reqwest::blocking::Client::new();
+        let client = self.http_client()?;
         let response = client
-            .post(&url)
+            .post(self.chat_url())
             .header("Content-Type", "application/json")
             .json(&request)
             .send()
@@ -106,91 +522,381 @@ impl ElizaClient {
             .map_err(|e| ElizaError::NetworkError(format!("Failed to read response: {}", e)))?;
 
         if !status.is_success() {
-            return Err(ElizaError::ApiError(format!(
-                "API returned status {}: {}",
-                status, response_text
-            )));
+            return Err(ElizaError::HttpStatus(
+                status.as_u16(),
+                format!("API returned status {}: {}", status, response_text),
+            ));
         }
 
         // Parse response
-        let chat_response: ChatResponse = serde_json::from_str(&response_text).map_err(|e| {
+        serde_json::from_str(&response_text).map_err(|e| {
             ElizaError::ParseError(format!(
                 "Failed to parse response: {}. Response was: {}",
                 e, response_text
             ))
-        })?;
+        })
+    }
 
-        let assistant_message = chat_response.message.content.clone();
-        let sleep = chat_response.sleep;
+    /// POST messages to /chat with `stream: true` and read the SSE response chunk by chunk.
+    /// Falls back to a normal JSON response if the server does not stream
+    fn post_chat_stream(
+        &self,
+        messages: Vec<Message>,
+        on_delta: &mut dyn FnMut(&str),
+    ) -> Result<ChatResponse, ElizaError> {
+        if mock::is_enabled() {
+            // 少しずつ届くように見せる
+            let response = mock_response(&messages);
+            let chars: Vec<char> = response.message.content.chars().collect();
+            for chunk in chars.chunks(4) {
+                std::thread::sleep(std::time::Duration::from_millis(80));
+                on_delta(&chunk.iter().collect::<String>());
+            }
+            return Ok(response);
+        }
 
-        // Add assistant message to history
-        self.add_message("assistant".to_string(), assistant_message.clone());
+        let request = ChatRequest {
+            model: self.model.clone(),
+            messages,
+            stream: true,
+            temperature: self.turn_temperature(),
+            max_tokens: self.max_tokens,
+        };
 
-        println!("Eliza response: {} (sleep={})", assistant_message, sleep);
-        Ok((assistant_message, sleep))
+        let client = self.http_client()?;
+        let response = client
+            .post(self.chat_url())
+            .header("Content-Type", "application/json")
+            .header("Accept", "text/event-stream")
+            .json(&request)
+            .send()
+            .map_err(|e| ElizaError::NetworkError(format!("Failed to send request: {}", e)))?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().unwrap_or_default();
+            return Err(ElizaError::HttpStatus(
+                status.as_u16(),
+                format!("API returned status {}: {}", status, body),
+            ));
+        }
+
+        let is_event_stream = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.starts_with("text/event-stream"))
+            .unwrap_or(false);
+        if !is_event_stream {
+            println!("Server did not stream, reading the whole response");
+            let response_text = response
+                .text()
+                .map_err(|e| ElizaError::NetworkError(format!("Failed to read response: {}", e)))?;
+            let chat_response: ChatResponse = serde_json::from_str(&response_text).map_err(|e| {
+                ElizaError::ParseError(format!(
+                    "Failed to parse response: {}. Response was: {}",
+                    e, response_text
+                ))
+            })?;
+            on_delta(&chat_response.message.content);
+            return Ok(chat_response);
+        }
+
+        let mut content = String::new();
+        let mut sleep = false;
+        for line in BufReader::new(response).lines() {
+            let line = line
+                .map_err(|e| ElizaError::NetworkError(format!("Failed to read stream: {}", e)))?;
+            let Some(data) = line.strip_prefix("data:") else {
+                continue;
+            };
+            let data = data.trim();
+            if data == "[DONE]" {
+                break;
+            }
+            let (delta, chunk_sleep) = parse_stream_chunk(data);
+            sleep |= chunk_sleep;
+            if !delta.is_empty() {
+                content.push_str(&delta);
+                on_delta(&delta);
+            }
+        }
+
+        Ok(ChatResponse {
+            message: Message {
+                role: "assistant".to_string(),
+                content,
+            },
+            sleep,
+            tool: None,
+        })
+    }
+
+    /// server_url + /chat if not already included
+    fn chat_url(&self) -> String {
+        if self.server_url.ends_with("/chat") {
+            self.server_url.clone()
+        } else {
+            format!("{}/chat", self.server_url)
+        }
+    }
+
+    /// Messages that would be sent if `user_message` were sent now
+    pub fn pending_messages(&self, user_message: &str) -> Vec<Message> {
+        let mut messages = self.request_messages();
+        if !user_message.is_empty() {
+            messages.push(Message {
+                role: "user".to_string(),
+                content: user_message.to_string(),
+            });
+        }
+        messages
+    }
+
+    /// One-shot completion for the given messages (history is not modified)
+    pub fn complete(&self, messages: Vec<Message>) -> Result<String, ElizaError> {
+        let chat_response = self.post_chat(messages, None)?;
+        Ok(chat_response.message.content)
+    }
+
+    /// Replace the content of the last assistant message (e.g. when another reply was chosen)
+    pub fn replace_last_reply(&mut self, content: &str) {
+        if let Some(last) = self
+            .conversation_history
+            .iter_mut()
+            .rev()
+            .find(|m| m.role == "assistant")
+        {
+            last.content = content.to_string();
+        }
+    }
+
+    /// History length, token estimate and whether the next exchange triggers compaction
+    pub fn history_stats(&self) -> HistoryStats {
+        let length = self.conversation_history.len();
+        HistoryStats {
+            length,
+            max_length: self.max_history_length,
+            estimated_tokens: self
+                .request_messages()
+                .iter()
+                .map(|m| estimate_tokens(&m.content))
+                .sum(),
+            // 発話と返答の 2 件が追加される
+            compacts_next: length + 2 > self.max_history_length,
+        }
+    }
+
+    /// Number of completed user/assistant exchanges
+    pub fn turn_count(&self) -> usize {
+        self.turn_count
+    }
+
+    /// Seconds since the last reply (None if nothing has been replied yet)
+    pub fn secs_since_last_reply(&self) -> Option<u64> {
+        self.last_reply_at.map(|t| t.elapsed().as_secs())
+    }
+
+    /// Current conversation topic (set by update_topic)
+    pub fn topic(&self) -> Option<&str> {
+        self.topic.as_deref()
+    }
+
+    /// Ask the agent for a one-line topic of the current conversation.
+    /// The request is not added to the conversation history.
+    pub fn update_topic(&mut self) -> Result<String, ElizaError> {
+        if self.conversation_history.is_empty() {
+            return Err(ElizaError::ApiError("No conversation to summarize".to_string()));
+        }
+
+        let mut messages = self.request_messages();
+        messages.push(Message {
+            role: "user".to_string(),
+            content: "ここまでの会話の話題を、20文字以内の短い一行で答えてください。説明や前置きは不要です。"
+                .to_string(),
+        });
+
+        let chat_response = self.post_chat(messages, None)?;
+        let topic = chat_response
+            .message
+            .content
+            .lines()
+            .map(|l| l.trim())
+            .find(|l| !l.is_empty())
+            .unwrap_or_default()
+            .to_string();
+
+        println!("Conversation topic: {}", topic);
+        self.topic = Some(topic.clone());
+        Ok(topic)
+    }
+
+    /// Ask the agent for a short summary of the whole conversation.
+    /// The request is not added to the conversation history.
+    pub fn summarize(&self) -> Result<String, ElizaError> {
+        if self.conversation_history.is_empty() {
+            return Err(ElizaError::ApiError("No conversation to summarize".to_string()));
+        }
+
+        let mut messages = self.request_messages();
+        messages.push(Message {
+            role: "user".to_string(),
+            content: "ここまでの会話を、次回の会話の冒頭で思い出せるように3文以内で要約してください。要約だけを出力してください。"
+                .to_string(),
+        });
+
+        let summary = self.complete(messages)?.trim().to_string();
+        println!("Conversation summary: {}", summary);
+        Ok(summary)
+    }
+
+    fn save_memory_before_compaction(&self) {
+        if !self.memory_saves {
+            println!("Privacy mode: memory not saved before compaction");
+            return;
+        }
+        if let Err(e) = self.save_memory() {
+            eprintln!("Failed to save memory (max length reached): {}", e);
+        }
     }
 
     /// Add a message to conversation history and maintain max length
     fn add_message(&mut self, role: String, content: String) {
         self.conversation_history.push_back(Message { role, content });
 
-        // Save memory and compact history if it exceeds max length
         if self.conversation_history.len() > self.max_history_length {
-            const COMPACT_SIZE: usize = 5;
-            if let Err(e) = self.save_memory() {
-                eprintln!("Failed to save memory (max length reached): {}", e);
-            }
-            while self.conversation_history.len() > COMPACT_SIZE {
-                self.conversation_history.pop_front();
+            self.compact_history();
+        }
+    }
+
+    /// Shrink the history according to the compaction strategy
+    fn compact_history(&mut self) {
+        match self.compaction {
+            CompactionStrategy::KeepLast => self.save_memory_before_compaction(),
+            CompactionStrategy::SummarizeThenKeep => {
+                self.save_memory_before_compaction();
+                let keep = self.compact_size.min(self.max_history_length);
+                let dropped = self.conversation_history.len().saturating_sub(keep);
+                match self.summarize_oldest(dropped) {
+                    Ok(summary) => self.set_context_note("compacted", format!("これより前の会話の要約: {}", summary)),
+                    Err(e) => eprintln!("Failed to summarize dropped history: {}", e),
+                }
             }
+            CompactionStrategy::DropOldestPairs => {}
         }
+        trim_history(
+            &mut self.conversation_history,
+            self.compaction,
+            self.max_history_length,
+            self.compact_size,
+        );
     }
 
-    /// Save conversation history to /memory endpoint
-    pub fn save_memory(&self) -> Result<(), ElizaError> {
-        if self.conversation_history.is_empty() {
-            return Ok(());
+    /// Ask the agent to summarize the oldest `count` messages (with the previous summary, if any)
+    fn summarize_oldest(&self, count: usize) -> Result<String, ElizaError> {
+        let mut transcript = String::new();
+        if let Some((_, previous)) = self.context_notes.iter().find(|(k, _)| k == "compacted") {
+            transcript.push_str(previous);
+            transcript.push('\n');
         }
+        for message in self.conversation_history.iter().take(count) {
+            transcript.push_str(&format!("{}: {}\n", message.role, message.content));
+        }
+        let messages = vec![Message {
+            role: "user".to_string(),
+            content: format!(
+                "次の会話を、あとで続きを話すときに思い出せるように3文以内で要約してください。要約だけを出力してください。\n\n{}",
+                transcript
+            ),
+        }];
+        Ok(self.complete(messages)?.trim().to_string())
+    }
 
-        let messages: Vec<Message> = self.conversation_history.iter().cloned().collect();
+    /// Save conversation history to /memory endpoint
+    pub fn save_memory(&self) -> Result<(), ElizaError> {
+        self.memory_snapshot().save()
+    }
 
-        let request = ChatRequest {
+    /// Copy of what save_memory sends, so it can be saved off the UI thread
+    pub fn memory_snapshot(&self) -> MemorySnapshot {
+        MemorySnapshot {
+            server_url: self.server_url.clone(),
             model: self.model.clone(),
-            messages,
-            stream: false,
-            temperature: 0.0,
-        };
+            messages: self.conversation_history.iter().cloned().collect(),
+        }
+    }
 
+    /// Fetch the server-side memory from the /memory endpoint as plain text
+    pub fn fetch_memory(&self) -> Result<String, ElizaError> {
+        if mock::is_enabled() {
+            return Ok("(モック) ユーザーは映画が好き".to_string());
+        }
         let url = format!(
             "{}/memory",
             self.server_url.trim_end_matches("/chat")
         );
 
-        println!("Saving memory to: {}", url);
+        println!("Fetching memory from: {}", url);
         let client = reqwest::blocking::Client::new();
         let response = client
-            .post(&url)
-            .header("Content-Type", "application/json")
-            .json(&request)
+            .get(&url)
             .send()
-            .map_err(|e| ElizaError::NetworkError(format!("Failed to save memory: {}", e)))?;
+            .map_err(|e| ElizaError::NetworkError(format!("Failed to fetch memory: {}", e)))?;
 
         let status = response.status();
+        let body = response
+            .text()
+            .map_err(|e| ElizaError::NetworkError(format!("Failed to read response: {}", e)))?;
         if !status.is_success() {
-            let body = response.text().unwrap_or_default();
-            return Err(ElizaError::ApiError(format!(
-                "Memory API returned status {}: {}",
-                status, body
-            )));
+            return Err(ElizaError::HttpStatus(
+                status.as_u16(),
+                format!("Memory API returned status {}: {}", status, body),
+            ));
         }
 
-        println!("Memory saved successfully");
-        Ok(())
+        let memory = parse_memory_response(&body);
+        println!("Memory fetched: {} chars", memory.chars().count());
+        Ok(memory)
+    }
+
+    /// Get a copy of the conversation history
+    pub fn history(&self) -> Vec<Message> {
+        self.conversation_history.iter().cloned().collect()
+    }
+
+    /// Restore conversation state (e.g. from a saved session)
+    pub fn restore(&mut self, history: Vec<Message>, turn_count: usize, topic: Option<String>) {
+        self.conversation_history = history.into();
+        self.turn_count = turn_count;
+        self.topic = topic;
+        println!(
+            "Conversation restored: {} messages, {} turns",
+            self.conversation_history.len(),
+            self.turn_count
+        );
+    }
+
+    /// Restore a conversation from an exported file, keeping only the most recent messages that fit
+    /// (older ones are dropped without saving memory or summarizing)
+    pub fn import_history(&mut self, history: Vec<Message>) {
+        let turn_count = history.iter().filter(|m| m.role == "assistant").count();
+        let mut history: VecDeque<Message> = history.into();
+        while history.len() > self.max_history_length {
+            history.pop_front();
+        }
+        // 返答から始まらないようにする
+        while history.front().is_some_and(|m| m.role != "user") {
+            history.pop_front();
+        }
+        self.restore(history.into(), turn_count, None);
     }
 
     /// Clear conversation history
     pub fn clear_history(&mut self) {
         self.conversation_history.clear();
+        self.turn_count = 0;
+        self.topic = None;
+        self.last_reply_at = None;
         println!("Conversation history cleared");
     }
 
@@ -223,6 +929,74 @@ mod tests {
         assert_eq!(client.conversation_history[0].content, "Response 1");
     }
 
+    #[test]
+    fn test_import_history() {
+        let mut client = ElizaClient::new("http://localhost:9095".to_string(), "m".to_string(), 4);
+        let history = (0..7)
+            .map(|i| Message {
+                role: if i % 2 == 0 { "user" } else { "assistant" }.to_string(),
+                content: format!("{}", i),
+            })
+            .collect();
+        client.import_history(history);
+        let contents: Vec<String> = client.history().into_iter().map(|m| m.content).collect();
+        assert_eq!(contents, vec!["4", "5", "6"]);
+        assert_eq!(client.turn_count(), 3);
+    }
+
+    #[test]
+    fn test_compaction_strategies() {
+        let mut keep_last = ElizaClient::new("http://localhost:9095".to_string(), "m".to_string(), 6)
+            .with_compaction(CompactionStrategy::KeepLast, 2);
+        let mut drop_pairs = ElizaClient::new("http://localhost:9095".to_string(), "m".to_string(), 6)
+            .with_compaction(CompactionStrategy::DropOldestPairs, 2);
+        for i in 0..7 {
+            let role = if i % 2 == 0 { "user" } else { "assistant" };
+            keep_last.add_message(role.to_string(), format!("{}", i));
+            drop_pairs.add_message(role.to_string(), format!("{}", i));
+        }
+        let contents = |client: &ElizaClient| {
+            client
+                .conversation_history
+                .iter()
+                .map(|m| m.content.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(contents(&keep_last), vec!["5", "6"]);
+        assert_eq!(contents(&drop_pairs), vec!["2", "3", "4", "5", "6"]);
+        assert_eq!(CompactionStrategy::from_name("summarize"), CompactionStrategy::SummarizeThenKeep);
+
+        let server = crate::test_support::FakeAgentServer::start(vec![crate::test_support::FakeResponse::reply("犬の話をした")]);
+        let mut summarize = ElizaClient::new(server.url, "m".to_string(), 3)
+            .with_compaction(CompactionStrategy::SummarizeThenKeep, 2);
+        for i in 0..4 {
+            summarize.add_message("user".to_string(), format!("{}", i));
+        }
+        assert_eq!(contents(&summarize), vec!["2", "3"]);
+        assert_eq!(
+            summarize.context_notes,
+            vec![("compacted".to_string(), "これより前の会話の要約: 犬の話をした".to_string())]
+        );
+        assert_eq!(CompactionStrategy::from_name("unknown"), CompactionStrategy::KeepLast);
+    }
+
+    #[test]
+    fn test_compaction_without_memory_saves() {
+        use crate::test_support::{FakeAgentServer, FakeResponse};
+        // 記憶の保存が先に走ると 1 つ目の応答を使ってしまい、要約が 2 つ目になる
+        let server = FakeAgentServer::start(vec![FakeResponse::reply("1 つ目"), FakeResponse::reply("2 つ目")]);
+        let mut client = ElizaClient::new(server.url, "m".to_string(), 3)
+            .with_compaction(CompactionStrategy::SummarizeThenKeep, 2);
+        client.set_memory_saves(false);
+        for i in 0..4 {
+            client.add_message("user".to_string(), format!("{}", i));
+        }
+        assert_eq!(
+            client.context_notes,
+            vec![("compacted".to_string(), "これより前の会話の要約: 1 つ目".to_string())]
+        );
+    }
+
     #[test]
     fn test_clear_history() {
         let mut client = ElizaClient::new(
@@ -236,4 +1010,161 @@ mod tests {
         client.clear_history();
         assert_eq!(client.conversation_history.len(), 0);
     }
+
+    #[test]
+    fn test_history_stats() {
+        let mut client = ElizaClient::new(
+            "http://localhost:9095".to_string(),
+            "grok-beta".to_string(),
+            3,
+        );
+        client.conversation_history.push_back(Message {
+            role: "user".to_string(),
+            content: "こんにちは hello!".to_string(),
+        });
+        let stats = client.history_stats();
+        assert_eq!(stats.length, 1);
+        assert_eq!(stats.estimated_tokens, 5 + 2);
+        assert!(!stats.compacts_next);
+
+        client.conversation_history.push_back(Message {
+            role: "assistant".to_string(),
+            content: "やあ".to_string(),
+        });
+        assert!(client.history_stats().compacts_next);
+    }
+
+    #[test]
+    fn test_settings_keep_history() {
+        let mut client = ElizaClient::new("http://localhost:9095".to_string(), "grok-beta".to_string(), 10);
+        for content in ["1", "2", "3", "4"] {
+            client.conversation_history.push_back(Message {
+                role: "user".to_string(),
+                content: content.to_string(),
+            });
+        }
+        client.set_server("http://localhost:9096".to_string(), "grok-2".to_string());
+        client.set_timeout(30);
+        client.set_compaction(CompactionStrategy::DropOldestPairs, 2);
+        client.set_max_history_length(2);
+        assert_eq!(client.chat_url(), "http://localhost:9096/chat");
+        assert_eq!(client.model, "grok-2");
+        assert_eq!(client.conversation_history.len(), 4);
+        // 次のリクエストで新しい上限まで削られる
+        assert_eq!(client.next_request_preview("5").len(), 1);
+    }
+
+    #[test]
+    fn test_next_request_preview() {
+        let mut client = ElizaClient::new("http://localhost:9095".to_string(), "grok-beta".to_string(), 4)
+            .with_system_prompt("You are Lisa.".to_string());
+        for (role, content) in [("user", "1"), ("assistant", "2"), ("user", "3"), ("assistant", "4")] {
+            client.conversation_history.push_back(Message {
+                role: role.to_string(),
+                content: content.to_string(),
+            });
+        }
+        let preview: Vec<String> = client
+            .next_request_preview("5")
+            .into_iter()
+            .map(|m| format!("{}:{}", m.role, m.content))
+            .collect();
+        assert_eq!(preview, vec!["system:You are Lisa.", "assistant:2", "user:3", "assistant:4", "user:5"]);
+        // 履歴そのものは変わらない
+        assert_eq!(client.conversation_history.len(), 4);
+    }
+
+    #[test]
+    fn test_parse_stream_chunk() {
+        assert_eq!(
+            parse_stream_chunk(r#"{"message": {"role": "assistant", "content": "こん"}}"#),
+            ("こん".to_string(), false)
+        );
+        assert_eq!(
+            parse_stream_chunk(r#"{"choices": [{"delta": {"content": "hi"}}]}"#),
+            ("hi".to_string(), false)
+        );
+        assert_eq!(parse_stream_chunk(r#"{"sleep": true}"#), (String::new(), true));
+        assert_eq!(parse_stream_chunk("plain"), ("plain".to_string(), false));
+    }
+
+    #[test]
+    fn test_parse_memory_response() {
+        assert_eq!(parse_memory_response(r#"{"memory": "猫が好き"}"#), "猫が好き");
+        assert_eq!(
+            parse_memory_response(r#"{"messages": [{"role": "user", "content": "a"}, {"content": "b"}]}"#),
+            "a\nb"
+        );
+        assert_eq!(parse_memory_response(r#"["a", "", "b"]"#), "a\nb");
+        assert_eq!(parse_memory_response(" plain text\n"), "plain text");
+    }
+
+    #[test]
+    fn test_system_prompt_is_prepended() {
+        let client = ElizaClient::new(
+            "http://localhost:9095".to_string(),
+            "grok-beta".to_string(),
+            5,
+        )
+        .with_system_prompt("You are Lisa.".to_string());
+
+        let messages = client.pending_messages("Hi");
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].role, "system");
+        assert_eq!(messages[1].content, "Hi");
+    }
+
+    #[test]
+    fn test_transient_note() {
+        use crate::test_support::{FakeAgentServer, FakeResponse};
+        let server = FakeAgentServer::start(vec![FakeResponse::reply("どうしたの?")]);
+        let mut client = ElizaClient::new(server.url, "m".to_string(), 5);
+        client.set_memory_saves(false);
+        client.add_message("user".to_string(), "こんにちは".to_string());
+        client.set_transient_note("話しかけて".to_string());
+
+        let messages = client.pending_messages("");
+        assert_eq!(messages.last().map(|m| (m.role.as_str(), m.content.as_str())), Some(("system", "話しかけて")));
+
+        client.send_message("").unwrap();
+        let roles: Vec<&str> = client.conversation_history.iter().map(|m| m.role.as_str()).collect();
+        assert_eq!(roles, vec!["user", "assistant"]);
+        assert!(client.pending_messages("").iter().all(|m| m.content != "話しかけて"));
+    }
+
+    #[test]
+    fn test_max_tokens() {
+        let mut client = ElizaClient::new(String::new(), String::new(), 10).with_max_tokens(150);
+        assert_eq!(client.max_tokens, Some(150));
+        client.set_max_tokens(0);
+        assert_eq!(client.max_tokens, None);
+
+        let request = |max_tokens| ChatRequest {
+            model: "m".to_string(),
+            messages: Vec::new(),
+            stream: false,
+            temperature: 0.0,
+            max_tokens,
+        };
+        let json = serde_json::to_value(request(Some(150))).unwrap();
+        assert_eq!(json["max_tokens"], 150);
+        let json = serde_json::to_value(request(None)).unwrap();
+        assert!(json.get("max_tokens").is_none());
+    }
+
+    #[test]
+    fn test_jitter_temperature() {
+        assert_eq!(jitter_temperature(0.7, 0.0, 0.9), 0.7);
+        assert!((jitter_temperature(0.7, 0.2, 0.0) - 0.5).abs() < 1e-6);
+        assert!((jitter_temperature(0.7, 0.2, 1.0) - 0.9).abs() < 1e-6);
+        assert_eq!(jitter_temperature(0.1, 0.5, 0.0), 0.0);
+        assert_eq!(jitter_temperature(1.9, 0.5, 1.0), 2.0);
+
+        let client = ElizaClient::new(String::new(), String::new(), 10).with_temperature(0.8, 0.3);
+        for _ in 0..20 {
+            let t = client.turn_temperature();
+            assert!((0.5..=1.1).contains(&t), "{}", t);
+        }
+    }
+
 }
@@ -0,0 +1,156 @@
+//! 画面に出すエラー。種類ごとに「どうすれば直るか」の一言を添える
+//!
+//! 各モジュールのエラー (ElizaError, OpenAIError, VRChatError) は From で種類に振り分ける。
+//! 外部のサービス (VOICEVOX, ElevenLabs, paste) とデバイス (マイク, 再生, MIDI, ローカル Whisper) の API は
+//! 直接 AppError を返す。設定やセッションなどのファイルの読み書きは直し方を添えられないので String のままで、
+//! report_error に渡すと Other になる
+
+use crate::eliza::ElizaError;
+use crate::openai::OpenAIError;
+use crate::vrchat::VRChatError;
+use thiserror::Error;
+
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum AppError {
+    /// API キーが無い・間違っている (401/403)
+    #[error("{0}")]
+    Auth(String),
+    /// サーバーに届かない・タイムアウト
+    #[error("{0}")]
+    Network(String),
+    /// 設定の値が足りない・間違っている
+    #[error("{0}")]
+    Config(String),
+    /// マイク・録音ファイル
+    #[error("{0}")]
+    Audio(String),
+    #[error("{0}")]
+    Other(String),
+}
+
+impl AppError {
+    /// 種類の名前 (ログやフック向け)
+    pub fn category(&self) -> &'static str {
+        match self {
+            AppError::Auth(_) => "auth",
+            AppError::Network(_) => "network",
+            AppError::Config(_) => "config",
+            AppError::Audio(_) => "audio",
+            AppError::Other(_) => "other",
+        }
+    }
+
+    /// ユーザーにしてもらうこと
+    pub fn remediation(&self) -> Option<&'static str> {
+        match self {
+            AppError::Auth(_) => Some("Settings で API キーを確認してください"),
+            AppError::Network(_) => Some("ネットワーク接続と Settings のサーバー URL を確認してください"),
+            AppError::Config(_) => Some("Settings の値を見直してください"),
+            AppError::Audio(_) => Some("マイクの接続と Settings の入力デバイスを確認してください"),
+            AppError::Other(_) => None,
+        }
+    }
+
+    /// エラーの HTTP ステータスを返したリクエスト (401/403 は API キーの問題)
+    pub fn http(status: u16, message: String) -> Self {
+        match status {
+            401 | 403 => AppError::Auth(message),
+            _ => AppError::Other(message),
+        }
+    }
+
+    /// 先頭に何をしていたかを付ける ("Eliza failed: ...")
+    pub fn context(self, context: &str) -> Self {
+        self.map(|message| format!("{}: {}", context, message))
+    }
+
+    /// トーストやステータスに出す文 (直し方があれば 2 行目に)
+    pub fn user_message(&self) -> String {
+        match self.remediation() {
+            Some(hint) => format!("{}\n💡 {}", self, hint),
+            None => self.to_string(),
+        }
+    }
+
+    fn map(self, f: impl FnOnce(String) -> String) -> Self {
+        match self {
+            AppError::Auth(m) => AppError::Auth(f(m)),
+            AppError::Network(m) => AppError::Network(f(m)),
+            AppError::Config(m) => AppError::Config(f(m)),
+            AppError::Audio(m) => AppError::Audio(f(m)),
+            AppError::Other(m) => AppError::Other(f(m)),
+        }
+    }
+}
+
+impl From<String> for AppError {
+    fn from(message: String) -> Self {
+        AppError::Other(message)
+    }
+}
+
+impl From<&str> for AppError {
+    fn from(message: &str) -> Self {
+        AppError::Other(message.to_string())
+    }
+}
+
+impl From<ElizaError> for AppError {
+    fn from(e: ElizaError) -> Self {
+        let message = e.to_string();
+        match e {
+            ElizaError::NetworkError(_) => AppError::Network(message),
+            ElizaError::HttpStatus(status, _) => AppError::http(status, message),
+            ElizaError::ApiError(_) | ElizaError::ParseError(_) => AppError::Other(message),
+        }
+    }
+}
+
+impl From<OpenAIError> for AppError {
+    fn from(e: OpenAIError) -> Self {
+        let message = e.to_string();
+        match e {
+            OpenAIError::NetworkError(_) => AppError::Network(message),
+            OpenAIError::HttpStatus(status, _) => AppError::http(status, message),
+            OpenAIError::FileError(_) => AppError::Audio(message),
+            OpenAIError::ParseError(_) => AppError::Other(message),
+        }
+    }
+}
+
+impl From<VRChatError> for AppError {
+    fn from(e: VRChatError) -> Self {
+        // OSC は同じ PC への UDP なので、届かないのはポートの設定くらい
+        AppError::Config(e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify() {
+        let auth = AppError::from(OpenAIError::HttpStatus(
+            401,
+            "API returned status 401 Unauthorized: invalid key".to_string(),
+        ));
+        assert_eq!(auth.category(), "auth");
+        assert_eq!(
+            auth.clone().context("Transcription failed").user_message(),
+            "Transcription failed: API error: API returned status 401 Unauthorized: invalid key\n💡 Settings で API キーを確認してください"
+        );
+
+        let network = AppError::from(ElizaError::NetworkError("connection refused".to_string()));
+        assert_eq!(network.category(), "network");
+        let server = AppError::from(ElizaError::HttpStatus(500, "API returned status 500: oops".to_string()));
+        assert_eq!(server.category(), "other");
+        assert_eq!(server.user_message(), "API error: API returned status 500: oops");
+        // 本文に "status 401" とあっても、ステータスで判定する
+        let body = AppError::from(ElizaError::ApiError("status 401 in the text".to_string()));
+        assert_eq!(body.category(), "other");
+        assert_eq!(AppError::http(403, "forbidden".to_string()).category(), "auth");
+        assert_eq!(AppError::from(OpenAIError::FileError("missing".to_string())).category(), "audio");
+        assert_eq!(AppError::from("plain").category(), "other");
+    }
+}
@@ -0,0 +1,95 @@
+use crate::config::Config;
+use crate::session;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+
+/// 返答への評価 1 件 (feedback/<preset>.jsonl に 1 行ずつ追記する)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FeedbackEntry {
+    pub timestamp: u64,
+    /// 1 = 👍, -1 = 👎
+    pub rating: i8,
+    pub message: String,
+    /// 評価した返答の直前の会話 ("You: ..." の形式)
+    #[serde(default)]
+    pub context: Vec<String>,
+}
+
+impl FeedbackEntry {
+    pub fn new(rating: i8, message: String, context: Vec<String>) -> Self {
+        Self {
+            timestamp: session::now_unix_secs(),
+            rating,
+            message,
+            context,
+        }
+    }
+}
+
+fn feedback_path(preset: &str) -> Result<PathBuf, String> {
+    let dir = Config::config_dir()?.join("feedback");
+    if !dir.exists() {
+        fs::create_dir_all(&dir)
+            .map_err(|e| format!("Failed to create feedback directory: {}", e))?;
+    }
+    Ok(dir.join(format!("{}.jsonl", preset)))
+}
+
+/// プリセット (人格) ごとのフィードバックログに追記する
+pub fn append(preset: &str, entry: &FeedbackEntry) -> Result<(), String> {
+    let line = serde_json::to_string(entry)
+        .map_err(|e| format!("Failed to serialize feedback: {}", e))?;
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(feedback_path(preset)?)
+        .map_err(|e| format!("Failed to open feedback log: {}", e))?;
+    writeln!(file, "{}", line).map_err(|e| format!("Failed to write feedback: {}", e))
+}
+
+/// 直近 count 件のフィードバック (古い順)
+pub fn load_recent(preset: &str, count: usize) -> Vec<FeedbackEntry> {
+    let Some(content) = feedback_path(preset)
+        .ok()
+        .and_then(|path| fs::read_to_string(path).ok())
+    else {
+        return Vec::new();
+    };
+    let entries: Vec<FeedbackEntry> = content
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect();
+    entries[entries.len().saturating_sub(count)..].to_vec()
+}
+
+/// system プロンプトに添える「最近の評価」(評価がなければ空)
+pub fn feedback_note(entries: &[FeedbackEntry]) -> String {
+    if entries.is_empty() {
+        return String::new();
+    }
+    let mut note = "ユーザーによる最近の返答の評価です。👍 の返答の調子に寄せ、👎 の返答のような言い方は避けてください。"
+        .to_string();
+    for entry in entries {
+        let mark = if entry.rating > 0 { "👍" } else { "👎" };
+        note.push_str(&format!("\n{} {}", mark, entry.message));
+    }
+    note
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_feedback_note() {
+        assert_eq!(feedback_note(&[]), "");
+        let entries = [
+            FeedbackEntry::new(1, "いいね".to_string(), Vec::new()),
+            FeedbackEntry::new(-1, "知りません".to_string(), Vec::new()),
+        ];
+        let note = feedback_note(&entries);
+        assert!(note.ends_with("\n👍 いいね\n👎 知りません"));
+    }
+}
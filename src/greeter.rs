@@ -0,0 +1,102 @@
+//! インスタンスに入ってきたプレイヤーへの自動あいさつ (ホスト役)
+
+use crate::dnd;
+use crate::limiter::ReplyLimiter;
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+/// 同じプレイヤーが入り直してもこの間はあいさつしない
+const REGREET_AFTER: Duration = Duration::from_secs(30 * 60);
+/// あいさつ同士の最小間隔 (同時に何人も入ってきたとき連投しない)
+const GREET_COOLDOWN: Duration = Duration::from_secs(10);
+/// あいさつを待つ人数の上限 (これを超えたら古い順に諦める)
+const MAX_PENDING: usize = 20;
+
+pub struct Greeter {
+    greeted: HashMap<String, Instant>,
+    /// 入ってきたがまだあいさつしていないプレイヤー (古い順)
+    pending: VecDeque<String>,
+    limiter: ReplyLimiter,
+}
+
+impl Greeter {
+    pub fn new() -> Self {
+        Self {
+            greeted: HashMap::new(),
+            pending: VecDeque::new(),
+            limiter: ReplyLimiter::new(),
+        }
+    }
+
+    /// あいさつを待つ列に加える
+    pub fn push(&mut self, name: String) {
+        if self.pending.contains(&name) {
+            return;
+        }
+        self.pending.push_back(name);
+        while self.pending.len() > MAX_PENDING {
+            self.pending.pop_front();
+        }
+    }
+
+    /// 次にあいさつするプレイヤー。ブロックリストの人・最近あいさつした人・もういない人は列から外し、
+    /// 連投の制限中なら列に残したまま None (次の呼び出しで改めて見る)
+    pub fn next(&mut self, now: Instant, blocklist: &str, max_per_minute: usize, present: &[String]) -> Option<String> {
+        let blocked = dnd::parse_keywords(blocklist);
+        self.pending.retain(|name| {
+            let keep = !blocked.iter().any(|b| b.eq_ignore_ascii_case(name))
+                && self.greeted.get(name).is_none_or(|at| now.duration_since(*at) >= REGREET_AFTER)
+                && present.contains(name);
+            if !keep {
+                println!("[Greeting] skip {}", name);
+            }
+            keep
+        });
+        self.pending.front()?;
+        self.limiter.check(now, max_per_minute, GREET_COOLDOWN).ok()?;
+        let name = self.pending.pop_front()?;
+        self.greeted.insert(name.clone(), now);
+        Some(name)
+    }
+}
+
+/// Agent に送るあいさつの依頼 (会話履歴には残さない)
+pub fn greeting_prompt(name: &str) -> String {
+    format!(
+        "今、{} さんがインスタンスに入ってきました。ホストとして名前を呼んで、一言で短く歓迎のあいさつをしてください。あいさつの文だけを出力してください。",
+        name
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_greeter_queue() {
+        let start = Instant::now();
+        let at = |secs: u64| start + Duration::from_secs(secs);
+        let present: Vec<String> = ["Alice", "Bob", "Carol", "Dave"].map(String::from).to_vec();
+        let mut greeter = Greeter::new();
+        for name in ["Alice", "Bob", "Carol", "Alice"] {
+            greeter.push(name.to_string());
+        }
+        // ブロックリスト (大文字小文字は区別しない) の人は外す
+        assert_eq!(greeter.next(start, "bob", 2, &present), Some("Alice".to_string()));
+        // 連投しないが、待っている人は捨てない
+        assert_eq!(greeter.next(at(5), "bob", 2, &present), None);
+        assert_eq!(greeter.next(at(20), "bob", 2, &present), Some("Carol".to_string()));
+        // 1 分あたりの上限を過ぎてからあいさつする
+        greeter.push("Dave".to_string());
+        assert_eq!(greeter.next(at(40), "", 2, &present), None);
+        assert_eq!(greeter.next(at(90), "", 2, &present), Some("Dave".to_string()));
+        // 入り直しても 30 分はあいさつしない
+        greeter.push("Alice".to_string());
+        assert_eq!(greeter.next(at(200), "", 2, &present), None);
+        greeter.push("Alice".to_string());
+        assert_eq!(greeter.next(start + REGREET_AFTER, "", 2, &present), Some("Alice".to_string()));
+        // もういない人にはあいさつしない
+        greeter.push("Eve".to_string());
+        assert_eq!(greeter.next(start + REGREET_AFTER * 2, "", 2, &present), None);
+    }
+}
@@ -0,0 +1,131 @@
+//! バックエンドごとの応答時間と失敗の記録 (遅いのがネットワークか中継サーバーか VRChat かを見分けるため)
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// バックエンドごとに覚えておく件数
+pub const MAX_SAMPLES: usize = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Backend {
+    Stt,
+    Chat,
+    Osc,
+}
+
+impl Backend {
+    pub const ALL: [Backend; 3] = [Backend::Stt, Backend::Chat, Backend::Osc];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Backend::Stt => "書き起こし (STT)",
+            Backend::Chat => "Agent (チャット)",
+            Backend::Osc => "VRChat (OSC)",
+        }
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// 1 回の呼び出しの結果
+#[derive(Debug, Clone, Copy)]
+pub struct Sample {
+    pub at: Instant,
+    pub latency: Duration,
+    pub ok: bool,
+}
+
+static SAMPLES: Mutex<[VecDeque<Sample>; 3]> = Mutex::new([VecDeque::new(), VecDeque::new(), VecDeque::new()]);
+
+pub fn record(backend: Backend, latency: Duration, ok: bool) {
+    let mut samples = SAMPLES.lock().unwrap_or_else(|e| e.into_inner());
+    let samples = &mut samples[backend.index()];
+    samples.push_back(Sample {
+        at: Instant::now(),
+        latency,
+        ok,
+    });
+    while samples.len() > MAX_SAMPLES {
+        samples.pop_front();
+    }
+}
+
+/// 計った時間と結果を記録して、そのまま結果を返す
+pub fn measure<T, E>(backend: Backend, f: impl FnOnce() -> Result<T, E>) -> Result<T, E> {
+    let started = Instant::now();
+    let result = f();
+    record(backend, started.elapsed(), result.is_ok());
+    result
+}
+
+/// 古い順
+pub fn samples(backend: Backend) -> Vec<Sample> {
+    SAMPLES.lock().unwrap_or_else(|e| e.into_inner())[backend.index()]
+        .iter()
+        .copied()
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Summary {
+    pub count: usize,
+    pub error_rate: f32,
+    /// 成功した呼び出しの平均 (ミリ秒)
+    pub avg_ms: Option<u64>,
+    /// 成功した呼び出しの 90 パーセンタイル (ミリ秒)
+    pub p90_ms: Option<u64>,
+}
+
+pub fn summarize(samples: &[Sample]) -> Option<Summary> {
+    if samples.is_empty() {
+        return None;
+    }
+    let mut latencies: Vec<u64> = samples
+        .iter()
+        .filter(|s| s.ok)
+        .map(|s| s.latency.as_millis() as u64)
+        .collect();
+    latencies.sort_unstable();
+    let errors = samples.len() - latencies.len();
+    let avg_ms = (!latencies.is_empty()).then(|| latencies.iter().sum::<u64>() / latencies.len() as u64);
+    let p90_ms = (!latencies.is_empty()).then(|| latencies[(latencies.len() * 9 / 10).min(latencies.len() - 1)]);
+    Some(Summary {
+        count: samples.len(),
+        error_rate: errors as f32 / samples.len() as f32,
+        avg_ms,
+        p90_ms,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summarize() {
+        assert_eq!(summarize(&[]), None);
+
+        let now = Instant::now();
+        let sample = |ms: u64, ok: bool| Sample {
+            at: now,
+            latency: Duration::from_millis(ms),
+            ok,
+        };
+        let mut samples: Vec<Sample> = (1..=10).map(|i| sample(i * 100, true)).collect();
+        samples.push(sample(30_000, false));
+        samples.push(sample(30_000, false));
+
+        let summary = summarize(&samples).unwrap();
+        assert_eq!(summary.count, 12);
+        assert!((summary.error_rate - 2.0 / 12.0).abs() < 1e-6);
+        assert_eq!(summary.avg_ms, Some(550));
+        assert_eq!(summary.p90_ms, Some(1000));
+
+        let failed = summarize(&[sample(10, false)]).unwrap();
+        assert_eq!(failed.avg_ms, None);
+        assert_eq!(failed.error_rate, 1.0);
+    }
+}
@@ -0,0 +1,257 @@
+use chrono::{DateTime, Local, NaiveDate};
+use serde::{Deserialize, Serialize};
+use crate::eliza::Message;
+use std::path::{Path, PathBuf};
+
+/// UI に表示する会話の 1 件
+///
+/// 古いセッションファイルの `[role, message]` 形式も読めるように、
+/// timestamp は省略可能 (0 = 不明) にしている
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub role: String,
+    pub message: String,
+    /// Unix time (秒)
+    #[serde(default)]
+    pub timestamp: u64,
+    /// 返答への評価 (1 = 👍, -1 = 👎)
+    #[serde(default)]
+    pub rating: Option<i8>,
+    /// チャットボックスに送らず UI だけに表示した理由
+    #[serde(default)]
+    pub suppressed: Option<String>,
+    /// そのターンの付随情報 (古い履歴やテキスト入力の発話にはない)
+    #[serde(default)]
+    pub meta: Option<TurnMeta>,
+}
+
+/// 1 ターンの付随情報 (ホバーで表示し、書き出しや統計、再生に使う)
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TurnMeta {
+    #[serde(default)]
+    pub preset: String,
+    /// 書き起こしまたは返答に使ったモデル
+    #[serde(default)]
+    pub model: String,
+    /// 書き起こしにかかった時間 (ミリ秒)
+    #[serde(default)]
+    pub stt_ms: Option<u64>,
+    /// Agent の返答にかかった時間 (ミリ秒)
+    #[serde(default)]
+    pub agent_ms: Option<u64>,
+    /// 送ったトークン数の見積もり (system プロンプトと履歴を含む)
+    #[serde(default)]
+    pub tokens_in: Option<usize>,
+    /// 返答のトークン数の見積もり
+    #[serde(default)]
+    pub tokens_out: Option<usize>,
+    /// 元の録音 (アーカイブしていなければ消えていることがある)
+    #[serde(default)]
+    pub audio_path: Option<PathBuf>,
+    /// 書き起こした発話の言語 (ISO 639-1)
+    #[serde(default)]
+    pub language: Option<String>,
+    /// 録音中に VRChat が自分の声を検出していた割合 (0.0..=1.0)
+    #[serde(default)]
+    pub self_voice_ratio: Option<f32>,
+}
+
+impl TurnMeta {
+    /// ホバー表示用の複数行テキスト
+    pub fn describe(&self) -> String {
+        let mut lines = Vec::new();
+        if !self.preset.is_empty() {
+            lines.push(format!("プリセット: {}", self.preset));
+        }
+        if !self.model.is_empty() {
+            lines.push(format!("モデル: {}", self.model));
+        }
+        if let Some(language) = &self.language {
+            lines.push(format!("言語: {}", language));
+        }
+        if let Some(ratio) = self.self_voice_ratio {
+            lines.push(format!("自分の声: {:.0}%", ratio * 100.0));
+        }
+        if let Some(ms) = self.stt_ms {
+            lines.push(format!("書き起こし: {:.1}s", ms as f64 / 1000.0));
+        }
+        if let Some(ms) = self.agent_ms {
+            lines.push(format!("返答: {:.1}s", ms as f64 / 1000.0));
+        }
+        if self.tokens_in.is_some() || self.tokens_out.is_some() {
+            let count = |n: Option<usize>| n.map(|n| n.to_string()).unwrap_or_else(|| "-".to_string());
+            lines.push(format!(
+                "トークン (見積もり): 送信 {} / 返答 {}",
+                count(self.tokens_in),
+                count(self.tokens_out)
+            ));
+        }
+        if let Some(path) = &self.audio_path {
+            lines.push(format!("録音: {}", path.display()));
+        }
+        lines.join("\n")
+    }
+}
+
+impl HistoryEntry {
+    pub fn new(role: &str, message: String) -> Self {
+        Self {
+            role: role.to_string(),
+            message,
+            timestamp: crate::session::now_unix_secs(),
+            rating: None,
+            suppressed: None,
+            meta: None,
+        }
+    }
+
+    pub fn with_meta(mut self, meta: TurnMeta) -> Self {
+        self.meta = Some(meta);
+        self
+    }
+
+    fn local_time(&self) -> Option<DateTime<Local>> {
+        if self.timestamp == 0 {
+            return None;
+        }
+        DateTime::from_timestamp(self.timestamp as i64, 0).map(|t| t.with_timezone(&Local))
+    }
+
+    /// ローカル時刻での日付 (日付区切りの表示用)
+    pub fn local_date(&self) -> Option<NaiveDate> {
+        self.local_time().map(|t| t.date_naive())
+    }
+
+    /// 絶対時刻 (例: 2024-05-01 21:03:15)
+    pub fn absolute_time(&self) -> String {
+        self.local_time()
+            .map(|t| t.format("%Y-%m-%d %H:%M:%S").to_string())
+            .unwrap_or_else(|| "時刻不明".to_string())
+    }
+
+    /// 相対時刻 (例: 3分前)
+    pub fn relative_time(&self, now: u64) -> String {
+        if self.timestamp == 0 {
+            return String::new();
+        }
+        format_relative(now.saturating_sub(self.timestamp))
+    }
+}
+
+/// 会話を JSON ファイルに書き出す (再インストール後などに読み込んで続きから話せるように)
+pub fn export(entries: &[HistoryEntry], path: &Path) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(entries)
+        .map_err(|e| format!("Failed to serialize history: {}", e))?;
+    std::fs::write(path, json).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+/// 書き出したファイルを読む (セッションファイルを渡された場合はその会話を読む)
+pub fn import(path: &Path) -> Result<Vec<HistoryEntry>, String> {
+    #[derive(Deserialize)]
+    struct SessionFile {
+        ui_history: Vec<HistoryEntry>,
+    }
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    serde_json::from_str::<Vec<HistoryEntry>>(&content)
+        .or_else(|_| serde_json::from_str::<SessionFile>(&content).map(|s| s.ui_history))
+        .map_err(|e| format!("Failed to parse {}: {}", path.display(), e))
+}
+
+/// Agent に渡す履歴に戻す (自分の発話と返答だけ。無視した発話や他の人の声は含めない)
+pub fn agent_messages(entries: &[HistoryEntry]) -> Vec<Message> {
+    entries
+        .iter()
+        .filter_map(|entry| {
+            let role = match entry.role.as_str() {
+                "You" => "user",
+                "Agent" => "assistant",
+                _ => return None,
+            };
+            Some(Message {
+                role: role.to_string(),
+                content: entry.message.clone(),
+            })
+        })
+        .collect()
+}
+
+/// 経過秒数を「3分前」のような表記にする
+pub fn format_relative(elapsed_secs: u64) -> String {
+    match elapsed_secs {
+        0..=59 => "たった今".to_string(),
+        60..=3599 => format!("{}分前", elapsed_secs / 60),
+        3600..=86399 => format!("{}時間前", elapsed_secs / 3600),
+        _ => format!("{}日前", elapsed_secs / 86400),
+    }
+}
+
+/// 日付区切りの見出し (例: 2024-05-01 (水))
+pub fn format_day_separator(date: NaiveDate) -> String {
+    const WEEKDAYS: [&str; 7] = ["月", "火", "水", "木", "金", "土", "日"];
+    let weekday = WEEKDAYS[chrono::Datelike::weekday(&date).num_days_from_monday() as usize];
+    format!("{} ({})", date.format("%Y-%m-%d"), weekday)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_relative() {
+        assert_eq!(format_relative(5), "たった今");
+        assert_eq!(format_relative(180), "3分前");
+        assert_eq!(format_relative(7200), "2時間前");
+        assert_eq!(format_relative(86400 * 3), "3日前");
+    }
+
+    #[test]
+    fn test_legacy_tuple_entry_is_readable() {
+        let entries: Vec<HistoryEntry> =
+            serde_json::from_str(r#"[["You", "こんにちは"], ["Agent", "やあ"]]"#).unwrap();
+        assert_eq!(entries[1].role, "Agent");
+        assert_eq!(entries[1].message, "やあ");
+        assert_eq!(entries[1].timestamp, 0);
+        assert_eq!(entries[1].relative_time(100), "");
+        assert_eq!(entries[1].meta, None);
+    }
+
+    #[test]
+    fn test_turn_meta_describe() {
+        let meta = TurnMeta {
+            preset: "default".to_string(),
+            model: "gpt-4o-mini".to_string(),
+            agent_ms: Some(1300),
+            tokens_in: Some(320),
+            tokens_out: Some(42),
+            ..TurnMeta::default()
+        };
+        assert_eq!(
+            meta.describe(),
+            "プリセット: default\nモデル: gpt-4o-mini\n返答: 1.3s\nトークン (見積もり): 送信 320 / 返答 42"
+        );
+        assert_eq!(TurnMeta::default().describe(), "");
+    }
+
+    #[test]
+    fn test_export_and_import() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history.json");
+        let entries = vec![
+            HistoryEntry::new("You", "こんにちは".to_string()),
+            HistoryEntry::new("Ignored", "えーと".to_string()),
+            HistoryEntry::new("Agent", "やあ".to_string()),
+        ];
+        export(&entries, &path).unwrap();
+        let imported = import(&path).unwrap();
+        assert_eq!(imported.len(), 3);
+        assert_eq!(imported[2].message, "やあ");
+
+        let messages = agent_messages(&imported);
+        let roles: Vec<&str> = messages.iter().map(|m| m.role.as_str()).collect();
+        assert_eq!(roles, vec!["user", "assistant"]);
+
+        std::fs::write(&path, r#"{"name": "s", "ui_history": [["You", "もしもし"]]}"#).unwrap();
+        assert_eq!(import(&path).unwrap()[0].message, "もしもし");
+    }
+}
@@ -0,0 +1,247 @@
+use crate::vrchat::VRChatClient;
+use rosc::OscType;
+use serde::{Deserialize, Serialize};
+use std::process::Command;
+
+/// フックを起動するイベント
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HookEvent {
+    RecordingStarted,
+    RecordingStopped,
+    TranscriptionComplete,
+    ReplySent,
+    Sleep,
+    Error,
+}
+
+impl HookEvent {
+    pub const ALL: [HookEvent; 6] = [
+        HookEvent::RecordingStarted,
+        HookEvent::RecordingStopped,
+        HookEvent::TranscriptionComplete,
+        HookEvent::ReplySent,
+        HookEvent::Sleep,
+        HookEvent::Error,
+    ];
+
+    /// 設定ファイルとテンプレートで使う名前
+    pub fn name(&self) -> &'static str {
+        match self {
+            HookEvent::RecordingStarted => "recording_start",
+            HookEvent::RecordingStopped => "recording_stop",
+            HookEvent::TranscriptionComplete => "transcription",
+            HookEvent::ReplySent => "reply",
+            HookEvent::Sleep => "sleep",
+            HookEvent::Error => "error",
+        }
+    }
+}
+
+/// ユーザーが設定するフック 1 つ分
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HookConfig {
+    /// HookEvent::name() のいずれか
+    pub event: String,
+    /// true なら target に HTTP POST、false なら target をコマンドとして実行
+    #[serde(default)]
+    pub webhook: bool,
+    /// コマンドライン または URL (テンプレート可)
+    pub target: String,
+    /// Webhook の body (テンプレート可)。空なら JSON で event と text を送る
+    #[serde(default)]
+    pub payload: String,
+}
+
+impl Default for HookConfig {
+    fn default() -> Self {
+        Self {
+            event: HookEvent::ReplySent.name().to_string(),
+            webhook: false,
+            target: String::new(),
+            payload: String::new(),
+        }
+    }
+}
+
+/// `{event}` `{text}` `{preset}` `{time}` を置き換える
+/// (escape_json が true なら値を JSON 文字列の中身としてエスケープする)
+pub fn render_template(template: &str, vars: &[(&str, &str)], escape_json: bool) -> String {
+    let mut rendered = template.to_string();
+    for (key, value) in vars {
+        let value = if escape_json {
+            let quoted = serde_json::to_string(value).unwrap_or_default();
+            quoted[1..quoted.len() - 1].to_string()
+        } else {
+            value.to_string()
+        };
+        rendered = rendered.replace(&format!("{{{}}}", key), &value);
+    }
+    rendered
+}
+
+/// テンプレートの変数を渡す環境変数の名前 (text → ELIZA_TEXT)
+fn env_name(key: &str) -> String {
+    format!("ELIZA_{}", key.to_ascii_uppercase())
+}
+
+/// コマンドラインの `{text}` などを環境変数の参照に置き換える
+///
+/// 話した言葉をそのままコマンドラインに埋め込むと、マイクの近くで話すだけで
+/// 任意のコマンドを差し込めてしまう。値は環境変数で渡し、シェルには参照だけを見せる
+/// (cmd は遅延展開 `!VAR!`、sh は `"$VAR"`。どちらも展開後の値は構文として解釈されない)
+fn command_template(template: &str, vars: &[(&str, &str)], windows: bool) -> String {
+    let mut rendered = template.to_string();
+    for (key, _) in vars {
+        let reference = if windows {
+            format!("!{}!", env_name(key))
+        } else {
+            format!("\"${}\"", env_name(key))
+        };
+        rendered = rendered.replace(&format!("{{{}}}", key), &reference);
+    }
+    rendered
+}
+
+/// イベントに対応するフックをバックグラウンドで実行する
+pub fn fire(hooks: &[HookConfig], event: HookEvent, text: &str, preset: &str) {
+    let time = chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+    for hook in hooks.iter().filter(|h| h.event == event.name()) {
+        if hook.target.trim().is_empty() {
+            continue;
+        }
+        let vars = [
+            ("event", event.name()),
+            ("text", text),
+            ("preset", preset),
+            ("time", time.as_str()),
+        ];
+        let hook = hook.clone();
+        let target = if hook.webhook {
+            render_template(&hook.target, &vars, false)
+        } else {
+            command_template(&hook.target, &vars, cfg!(windows))
+        };
+        let payload = if hook.payload.trim().is_empty() {
+            default_payload(event, text, preset, &time)
+        } else {
+            render_template(&hook.payload, &vars, true)
+        };
+        let env: Vec<(String, String)> = vars.iter().map(|(key, value)| (env_name(key), value.to_string())).collect();
+
+        std::thread::spawn(move || {
+            let result = if hook.webhook {
+                run_webhook(&target, payload)
+            } else {
+                run_command(&target, &env)
+            };
+            match result {
+                Ok(_) => println!("[Hook] {} → {}", event.name(), target),
+                Err(e) => eprintln!("[Hook] {} → {} failed: {}", event.name(), target, e),
+            }
+        });
+    }
+}
+
+/// 既定の JSON の本文
+fn default_payload(event: HookEvent, text: &str, preset: &str, time: &str) -> String {
+    serde_json::json!({ "event": event.name(), "text": text, "preset": preset, "time": time }).to_string()
+}
+
+/// 配信ツール向けに、イベントを OSC で `/eliza/<event>` に送る (引数は既定の JSON の本文 1 つ)
+pub fn send_osc(target: &str, event: HookEvent, text: &str, preset: &str) {
+    let time = chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+    let client = VRChatClient {
+        target_addr: target.trim().to_string(),
+    };
+    let args = vec![OscType::String(default_payload(event, text, preset, &time))];
+    if let Err(e) = client.send_osc(&format!("/eliza/{}", event.name()), args) {
+        eprintln!("[Hook] {} → OSC {} failed: {}", event.name(), target, e);
+    }
+}
+
+fn run_webhook(url: &str, payload: String) -> Result<(), String> {
+    let response = reqwest::blocking::Client::new()
+        .post(url)
+        .header("Content-Type", "application/json")
+        .body(payload)
+        .send()
+        .map_err(|e| format!("Failed to send webhook: {}", e))?;
+    if !response.status().is_success() {
+        return Err(format!("Webhook returned status {}", response.status()));
+    }
+    Ok(())
+}
+
+/// コマンドを実行する。値は環境変数 ELIZA_EVENT / ELIZA_TEXT / ELIZA_PRESET / ELIZA_TIME で渡す
+fn run_command(command_line: &str, env: &[(String, String)]) -> Result<(), String> {
+    #[cfg(windows)]
+    let mut command = {
+        use std::os::windows::process::CommandExt;
+        const CREATE_NO_WINDOW: u32 = 0x08000000;
+        let mut command = Command::new("cmd");
+        // /V:ON で !VAR! (遅延展開) を使えるようにする
+        command.args(["/V:ON", "/C", command_line]).creation_flags(CREATE_NO_WINDOW);
+        command
+    };
+    #[cfg(not(windows))]
+    let mut command = {
+        let mut command = Command::new("sh");
+        command.args(["-c", command_line]);
+        command
+    };
+
+    let status = command
+        .envs(env.iter().map(|(key, value)| (key, value)))
+        .status()
+        .map_err(|e| format!("Failed to run command: {}", e))?;
+    if !status.success() {
+        return Err(format!("Command exited with {}", status));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_template() {
+        let vars = [("event", "reply"), ("text", "say \"hi\"")];
+        assert_eq!(
+            render_template("{event}: {text}", &vars, false),
+            "reply: say \"hi\""
+        );
+        assert_eq!(
+            render_template(r#"{"msg":"{text}"}"#, &vars, true),
+            r#"{"msg":"say \"hi\""}"#
+        );
+    }
+
+    #[test]
+    fn test_command_template_does_not_embed_values() {
+        let vars = [("event", "reply"), ("text", "hi; rm -rf ~ & del *")];
+        assert_eq!(
+            command_template("notify-send {event} {text}", &vars, false),
+            "notify-send \"$ELIZA_EVENT\" \"$ELIZA_TEXT\""
+        );
+        assert_eq!(
+            command_template("msg * {text}", &vars, true),
+            "msg * !ELIZA_TEXT!"
+        );
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_run_command_passes_text_as_data() {
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("out.txt");
+        let marker = dir.path().join("injected");
+        let vars = [("text", format!("x; touch {}", marker.display()))];
+        let vars: Vec<(&str, &str)> = vars.iter().map(|(k, v)| (*k, v.as_str())).collect();
+        let command_line = command_template(&format!("printf %s {{text}} > {}", out.display()), &vars, false);
+        let env = vec![(env_name("text"), vars[0].1.to_string())];
+        run_command(&command_line, &env).unwrap();
+        assert_eq!(std::fs::read_to_string(&out).unwrap(), vars[0].1);
+        assert!(!marker.exists());
+    }
+}
@@ -0,0 +1,113 @@
+/// 書き起こしが誰に向けた発話か
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Intent {
+    /// Agent に話しかけている → 返答する
+    Directed,
+    /// 周りの人と話している → 返答しない
+    Others,
+    /// 雑音・誤認識 → 返答しない
+    Noise,
+    /// 指示を書き換えようとする発話 (injection_guard) → 返答しない
+    Injection,
+    /// 受け付ける言語以外の発話 (language_filter_enabled) → 返答しない
+    OtherLanguage,
+}
+
+impl Intent {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Intent::Directed => "directed",
+            Intent::Others => "others",
+            Intent::Noise => "noise",
+            Intent::Injection => "injection",
+            Intent::OtherLanguage => "language",
+        }
+    }
+}
+
+/// 無音や雑音で Whisper がよく出す定型文
+const HALLUCINATIONS: [&str; 3] = [
+    "ご視聴ありがとうございました",
+    "チャンネル登録",
+    "Thank you for watching",
+];
+
+/// この秒数以内に返答していれば、名前を呼ばれなくても会話の続きとみなす
+pub const FOLLOW_UP_SECS: u64 = 30;
+
+/// ローカルの簡易判定
+/// - 句読点を除いて 2 文字以下、または定型の誤認識 → Noise
+/// - Agent の名前を含む、または直前に返答している → Directed
+/// - 名前が設定されていなければ常に Directed
+pub fn classify_local(text: &str, agent_names: &[String], in_conversation: bool) -> Intent {
+    let meaningful = text.chars().filter(|c| c.is_alphanumeric()).count();
+    if meaningful <= 2 || HALLUCINATIONS.iter().any(|h| text.contains(h)) {
+        return Intent::Noise;
+    }
+
+    if agent_names.is_empty() || in_conversation {
+        return Intent::Directed;
+    }
+
+    let lower = text.to_lowercase();
+    if agent_names
+        .iter()
+        .any(|name| lower.contains(&name.to_lowercase()))
+    {
+        Intent::Directed
+    } else {
+        Intent::Others
+    }
+}
+
+/// LLM の出力 ("directed" など) を Intent にする。判定できなければ Directed
+pub fn parse_intent(label: &str) -> Intent {
+    let lower = label.trim().to_lowercase();
+    if lower.contains("noise") {
+        Intent::Noise
+    } else if lower.contains("others") {
+        Intent::Others
+    } else {
+        Intent::Directed
+    }
+}
+
+/// "リサ, Lisa" のようなカンマ区切りの名前リスト
+pub fn parse_agent_names(text: &str) -> Vec<String> {
+    text.split([',', '、'])
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
+/// LLM に判定させるときの指示
+pub fn classifier_instruction(agent_names: &[String]) -> String {
+    let names = if agent_names.is_empty() {
+        "AI アシスタント".to_string()
+    } else {
+        agent_names.join(" / ")
+    };
+    format!(
+        "あなたは VRChat の音声認識結果を分類します。話者が {} に話しかけているなら directed、\
+         周りの他の人と話しているなら others、意味のない雑音や誤認識なら noise と、\
+         ラベルだけを 1 語で答えてください。",
+        names
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify_local() {
+        let names = parse_agent_names("リサ, Lisa");
+        assert_eq!(classify_local("リサ、今日の天気は？", &names, false), Intent::Directed);
+        assert_eq!(classify_local("hey lisa", &names, false), Intent::Directed);
+        assert_eq!(classify_local("それでさ、昨日のワールドが", &names, false), Intent::Others);
+        assert_eq!(classify_local("それでさ、昨日のワールドが", &names, true), Intent::Directed);
+        assert_eq!(classify_local("えっ", &names, true), Intent::Noise);
+        assert_eq!(classify_local("ご視聴ありがとうございました", &[], false), Intent::Noise);
+        assert_eq!(parse_intent(" Others\n"), Intent::Others);
+    }
+}
@@ -0,0 +1,102 @@
+use crate::openai::WordTimestamp;
+use crate::vrchat::VRChatClient;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// チャットボックスの更新間隔の下限 (VRChat のスパム制限に掛からないように)
+const MIN_FRAME_INTERVAL: f32 = 0.5;
+
+/// 単語の時刻から、チャットボックスに順に出す (表示開始までの秒数, 表示するテキスト) を作る
+///
+/// 実際の発話時間が replay_secs より長ければ replay_secs に縮める。
+/// 最後のフレームは常に text 全体 (清書で単語と一致しなくなった場合も含む)
+pub fn build_frames(text: &str, words: &[WordTimestamp], replay_secs: f32) -> Vec<(f32, String)> {
+    let total = words.last().map(|w| w.end).unwrap_or(0.0);
+    let scale = if total > replay_secs && total > 0.0 {
+        replay_secs / total
+    } else {
+        1.0
+    };
+
+    let mut frames: Vec<(f32, String)> = Vec::new();
+    let mut cursor = 0;
+    for word in words {
+        let token = word.word.trim();
+        if token.is_empty() {
+            continue;
+        }
+        // 単語を本文の中で探して、そこまでを表示する (空白や句読点は本文のまま)
+        let Some(pos) = text[cursor..].find(token) else {
+            continue;
+        };
+        cursor += pos + token.len();
+
+        let at = word.start * scale;
+        match frames.last_mut() {
+            // 間隔が詰まりすぎるなら直前のフレームにまとめる
+            Some(last) if at - last.0 < MIN_FRAME_INTERVAL => last.1 = text[..cursor].to_string(),
+            _ => frames.push((at, text[..cursor].to_string())),
+        }
+    }
+
+    match frames.last_mut() {
+        Some(last) if last.1.len() == text.len() => {}
+        Some(last) if total * scale - last.0 < MIN_FRAME_INTERVAL => last.1 = text.to_string(),
+        _ => frames.push((total * scale, text.to_string())),
+    }
+    frames
+}
+
+/// フレームを時刻どおりにチャットボックスへ送る。返答の送信と重ならないよう JoinHandle を返す
+pub fn play(frames: Vec<(f32, String)>, format: impl Fn(&str) -> String + Send + 'static) -> JoinHandle<()> {
+    std::thread::spawn(move || {
+        let vrchat = VRChatClient::new();
+        let started = Instant::now();
+        for (i, (at, text)) in frames.iter().enumerate() {
+            if let Some(wait) = Duration::from_secs_f32(at.max(0.0)).checked_sub(started.elapsed()) {
+                std::thread::sleep(wait);
+            }
+            let message = format(text);
+            // 通知音は最初の 1 回だけ
+            let result = if i == 0 {
+                vrchat.send_message(&message)
+            } else {
+                vrchat.update_message(&message)
+            };
+            if let Err(e) = result {
+                eprintln!("✗ VRChat karaoke send failed: {}", e);
+                return;
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn word(word: &str, start: f32, end: f32) -> WordTimestamp {
+        WordTimestamp {
+            word: word.to_string(),
+            start,
+            end,
+        }
+    }
+
+    #[test]
+    fn test_build_frames() {
+        let words = [
+            word("hello", 0.0, 0.4),
+            word("big", 0.6, 0.8),
+            word("world", 0.9, 1.4),
+        ];
+        let frames = build_frames("Hello, big world!", &words, 10.0);
+        // "hello" は大文字違いで見つからないので飛ばされ、big と world は 0.5 秒未満なのでまとまる
+        assert_eq!(frames, vec![(0.6, "Hello, big world".to_string()), (1.4, "Hello, big world!".to_string())]);
+
+        // replay_secs に縮める
+        let words = [word("あ", 0.0, 1.0), word("い", 4.0, 6.0)];
+        let frames = build_frames("あい", &words, 3.0);
+        assert_eq!(frames, vec![(0.0, "あ".to_string()), (2.0, "あい".to_string())]);
+    }
+}
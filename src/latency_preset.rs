@@ -0,0 +1,99 @@
+//! 応答の速さと精度をまとめて切り替えるプリセット (モデルや補助の LLM 呼び出しを知らなくても選べるように)
+
+use crate::config::Config;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LatencyPreset {
+    pub label: &'static str,
+    pub description: &'static str,
+    pub whisper_model: &'static str,
+    pub agent_streaming_enabled: bool,
+    pub max_length_of_conversation_history: usize,
+    /// 返答の最大トークン数 (0 = 制限しない)
+    pub agent_max_tokens: u32,
+    pub whisper_context_enabled: bool,
+    pub transcript_cleanup_enabled: bool,
+    pub intent_use_llm: bool,
+}
+
+pub const PRESETS: [LatencyPreset; 3] = [
+    LatencyPreset {
+        label: "速さ優先",
+        description: "軽い書き起こしモデルと短い履歴・短めの返答で、返答を届いた分から表示します",
+        whisper_model: "gpt-4o-mini-transcribe",
+        agent_streaming_enabled: true,
+        max_length_of_conversation_history: 10,
+        agent_max_tokens: 150,
+        whisper_context_enabled: false,
+        transcript_cleanup_enabled: false,
+        intent_use_llm: false,
+    },
+    LatencyPreset {
+        label: "バランス",
+        description: "標準の書き起こしモデルに直前の発話を添え、返答はストリーミングで受け取ります",
+        whisper_model: "gpt-4o-transcribe",
+        agent_streaming_enabled: true,
+        max_length_of_conversation_history: 20,
+        agent_max_tokens: 300,
+        whisper_context_enabled: true,
+        transcript_cleanup_enabled: false,
+        intent_use_llm: false,
+    },
+    LatencyPreset {
+        label: "精度優先",
+        description: "書き起こしの清書や LLM による宛先判定も使い、長めの履歴で返答します",
+        whisper_model: "gpt-4o-transcribe",
+        agent_streaming_enabled: false,
+        max_length_of_conversation_history: 40,
+        agent_max_tokens: 0,
+        whisper_context_enabled: true,
+        transcript_cleanup_enabled: true,
+        intent_use_llm: true,
+    },
+];
+
+impl LatencyPreset {
+    pub fn apply(&self, config: &mut Config) {
+        config.whisper_model = self.whisper_model.to_string();
+        config.agent_streaming_enabled = self.agent_streaming_enabled;
+        config.max_length_of_conversation_history = self.max_length_of_conversation_history;
+        config.agent_max_tokens = self.agent_max_tokens;
+        config.whisper_context_enabled = self.whisper_context_enabled;
+        config.transcript_cleanup_enabled = self.transcript_cleanup_enabled;
+        config.intent_use_llm = self.intent_use_llm;
+    }
+
+    fn matches(&self, config: &Config) -> bool {
+        config.whisper_model == self.whisper_model
+            && config.agent_streaming_enabled == self.agent_streaming_enabled
+            && config.max_length_of_conversation_history == self.max_length_of_conversation_history
+            && config.agent_max_tokens == self.agent_max_tokens
+            && config.whisper_context_enabled == self.whisper_context_enabled
+            && config.transcript_cleanup_enabled == self.transcript_cleanup_enabled
+            && config.intent_use_llm == self.intent_use_llm
+    }
+}
+
+/// 今の設定と一致するプリセット (手で調整していれば None)
+pub fn current(config: &Config) -> Option<&'static LatencyPreset> {
+    PRESETS.iter().find(|p| p.matches(config))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply_and_current() {
+        let mut config = Config::default();
+        PRESETS[0].apply(&mut config);
+        assert_eq!(current(&config), Some(&PRESETS[0]));
+        assert_eq!(config.whisper_model, "gpt-4o-mini-transcribe");
+        assert_eq!(config.agent_max_tokens, 150);
+
+        PRESETS[2].apply(&mut config);
+        assert_eq!(current(&config).map(|p| p.label), Some("精度優先"));
+        config.agent_streaming_enabled = true;
+        assert_eq!(current(&config), None);
+    }
+}
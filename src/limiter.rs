@@ -0,0 +1,70 @@
+use crate::config::Config;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+const WINDOW: Duration = Duration::from_secs(60);
+
+/// チャットボックスへの返答の頻度制限 (直近 1 分の送信時刻を覚えておく)
+pub struct ReplyLimiter {
+    sent: VecDeque<Instant>,
+}
+
+impl ReplyLimiter {
+    pub const fn new() -> Self {
+        Self {
+            sent: VecDeque::new(),
+        }
+    }
+
+    /// 送ってよければ記録して Ok、制限中なら理由を返す
+    pub fn check(&mut self, now: Instant, max_per_minute: usize, cooldown: Duration) -> Result<(), String> {
+        self.sent.retain(|t| now.duration_since(*t) < WINDOW);
+        if let Some(last) = self.sent.back() {
+            let elapsed = now.duration_since(*last);
+            if elapsed < cooldown {
+                return Err(format!(
+                    "クールダウン中 (あと {} 秒)",
+                    (cooldown - elapsed).as_secs_f32().ceil()
+                ));
+            }
+        }
+        if max_per_minute > 0 && self.sent.len() >= max_per_minute {
+            return Err(format!("1 分あたり {} 回の上限", max_per_minute));
+        }
+        self.sent.push_back(now);
+        Ok(())
+    }
+}
+
+static LIMITER: Mutex<ReplyLimiter> = Mutex::new(ReplyLimiter::new());
+
+/// 設定に従って返答をチャットボックスに送ってよいか判定する
+pub fn allow_reply(config: &Config) -> Result<(), String> {
+    if !config.reply_limit_enabled {
+        return Ok(());
+    }
+    LIMITER.lock().unwrap_or_else(|e| e.into_inner()).check(
+        Instant::now(),
+        config.reply_limit_per_minute,
+        Duration::from_secs(config.reply_cooldown_secs),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reply_limiter() {
+        let start = Instant::now();
+        let mut limiter = ReplyLimiter::new();
+        let cooldown = Duration::from_secs(5);
+        assert!(limiter.check(start, 2, cooldown).is_ok());
+        assert!(limiter.check(start + Duration::from_secs(3), 2, cooldown).is_err());
+        assert!(limiter.check(start + Duration::from_secs(6), 2, cooldown).is_ok());
+        assert!(limiter.check(start + Duration::from_secs(20), 2, cooldown).is_err());
+        // 1 分経てば最初の送信は数えない
+        assert!(limiter.check(start + Duration::from_secs(61), 2, cooldown).is_ok());
+    }
+}
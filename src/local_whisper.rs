@@ -0,0 +1,156 @@
+use crate::error::AppError;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// 選べるモデルサイズ (ggml-<size>.bin)
+pub const MODEL_SIZES: [&str; 6] = ["tiny", "base", "small", "medium", "large-v3-turbo", "large-v3"];
+
+/// 同時に動かす推論は 1 つだけ (VRChat の CPU/GPU を奪わないように)
+static INFERENCE_LOCK: Mutex<()> = Mutex::new(());
+/// 実行中 + 順番待ちの推論の数
+static IN_FLIGHT: AtomicUsize = AtomicUsize::new(0);
+
+struct InFlightGuard;
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        IN_FLIGHT.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// model がディレクトリなら、その中の ggml-<size>.bin を使う
+pub fn resolve_model_path(model: &str, size: &str) -> PathBuf {
+    let path = PathBuf::from(model);
+    if path.is_dir() {
+        path.join(format!("ggml-{}.bin", size))
+    } else {
+        path
+    }
+}
+
+/// whisper.cpp の CLI (whisper-cli) をローカルで実行して書き起こす
+pub struct LocalWhisper {
+    /// 実行ファイル (PATH 上なら名前だけでよい)
+    pub exe_path: String,
+    /// ggml モデルファイル
+    pub model_path: PathBuf,
+    /// 言語コード ("auto" で自動判定)
+    pub language: String,
+    /// 0 なら待ち続ける
+    pub timeout_secs: u64,
+    /// 推論スレッド数 (0 なら whisper.cpp の既定)
+    pub threads: usize,
+    /// 実行中の推論があるときに順番待ちできる数。これを超えた発話は捨てる
+    pub max_queue: usize,
+}
+
+impl LocalWhisper {
+    pub fn transcribe(&self, audio_path: &Path) -> Result<String, AppError> {
+        if self.model_path.as_os_str().is_empty() {
+            return Err(AppError::Config("Local whisper model is not configured".to_string()));
+        }
+
+        let in_flight = IN_FLIGHT.fetch_add(1, Ordering::SeqCst) + 1;
+        let _guard = InFlightGuard;
+        if in_flight > self.max_queue + 1 {
+            return Err(AppError::Other(format!("Local whisper is busy ({} in queue)", in_flight - 1)));
+        }
+        let _lock = INFERENCE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        println!("[LocalWhisper] Transcribing {:?} with {:?}", audio_path, self.model_path);
+
+        let mut command = Command::new(&self.exe_path);
+        command
+            .arg("-m")
+            .arg(&self.model_path)
+            .arg("-f")
+            .arg(audio_path)
+            .arg("-l")
+            .arg(if self.language.is_empty() { "auto" } else { &self.language })
+            .arg("--no-timestamps")
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null());
+        if self.threads > 0 {
+            command.arg("-t").arg(self.threads.to_string());
+        }
+        #[cfg(windows)]
+        {
+            use std::os::windows::process::CommandExt;
+            const CREATE_NO_WINDOW: u32 = 0x08000000;
+            // VRChat より優先度を下げる
+            const BELOW_NORMAL_PRIORITY_CLASS: u32 = 0x00004000;
+            command.creation_flags(CREATE_NO_WINDOW | BELOW_NORMAL_PRIORITY_CLASS);
+        }
+
+        let mut child = command
+            .spawn()
+            .map_err(|e| AppError::Config(format!("Failed to run {}: {}", self.exe_path, e)))?;
+
+        // 出力が詰まらないよう別スレッドで読み続ける
+        let mut stdout = child
+            .stdout
+            .take()
+            .ok_or_else(|| AppError::Other("Failed to capture local whisper output".to_string()))?;
+        let reader = std::thread::spawn(move || {
+            let mut buf = Vec::new();
+            let _ = stdout.read_to_end(&mut buf);
+            buf
+        });
+
+        // タイムアウトしたら打ち切る
+        let started = Instant::now();
+        let status = loop {
+            match child.try_wait() {
+                Ok(Some(status)) => break status,
+                Ok(None) => {
+                    if self.timeout_secs > 0 && started.elapsed() > Duration::from_secs(self.timeout_secs) {
+                        let _ = child.kill();
+                        let _ = child.wait();
+                        return Err(AppError::Other(format!("Local whisper timed out after {}s", self.timeout_secs)));
+                    }
+                    std::thread::sleep(Duration::from_millis(50));
+                }
+                Err(e) => return Err(AppError::Other(format!("Failed to wait for local whisper: {}", e))),
+            }
+        };
+
+        let stdout = reader.join().unwrap_or_default();
+        if !status.success() {
+            return Err(AppError::Config(format!("Local whisper exited with {}", status)));
+        }
+        let text = parse_output(&String::from_utf8_lossy(&stdout));
+        println!("[LocalWhisper] Result: {}", text);
+        Ok(text)
+    }
+}
+
+/// whisper-cli の標準出力 (1 行 1 セグメント) を 1 つの文にする
+pub fn parse_output(stdout: &str) -> String {
+    stdout
+        .lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty() && *line != "[BLANK_AUDIO]")
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_output() {
+        assert_eq!(parse_output(" こんにちは。\n\n 元気？\n"), "こんにちは。 元気？");
+        assert_eq!(parse_output("[BLANK_AUDIO]\n"), "");
+    }
+
+    #[test]
+    fn test_resolve_model_path() {
+        let dir = std::env::temp_dir();
+        assert_eq!(resolve_model_path(dir.to_str().unwrap(), "base"), dir.join("ggml-base.bin"));
+        assert_eq!(resolve_model_path("models/ggml-small.bin", "base"), PathBuf::from("models/ggml-small.bin"));
+    }
+}
@@ -1,23 +1,143 @@
+/// ログの出力。`--rpc` では標準出力を JSON-RPC 専用にするため、標準エラー出力に書く
+/// (mod より前に定義して、すべてのモジュールの println! をこれにする)
+macro_rules! println {
+    ($($arg:tt)*) => {
+        if $crate::rpc::is_enabled() {
+            std::eprintln!($($arg)*)
+        } else {
+            std::println!($($arg)*)
+        }
+    };
+}
+
+mod ab_test;
+#[cfg(test)]
+mod app_tests;
 mod audio;
+mod audio_profile;
+mod autostart;
+mod batch;
 mod config;
+mod config_crypt;
+mod control;
+mod dnd;
+mod elevenlabs;
 mod eliza;
+mod error;
+mod feedback;
+mod greeter;
+mod health;
+mod history;
+mod hooks;
+mod injection_guard;
+mod intent;
+mod local_whisper;
+mod karaoke;
+mod latency_preset;
+mod limiter;
+mod midi;
+mod mock;
+mod mood;
+mod notify_sound;
 mod openai;
+mod osc_log;
+mod paste;
+mod persona;
+mod pipeline_controller;
+#[cfg(test)]
+mod pipeline_tests;
+mod retry_queue;
+mod rpc;
+mod scheduler;
+mod playback;
+mod redact;
+mod replay;
+mod session;
+mod single_instance;
+mod snippets;
+mod soundboard;
+mod stats;
+mod stt;
+#[cfg(test)]
+mod test_support;
+mod text_normalize;
+mod toast;
+mod tts;
+mod ui;
+mod vad;
+mod vad_preset;
+mod voice_command;
+mod voicevox;
 mod vrchat;
+mod vrchat_log;
+mod worker;
 
+use ab_test::{AbTest, Variant};
 use audio::AudioRecorder;
+use audio_profile::AudioProfile;
 use config::Config;
 use eframe::egui;
-use eliza::ElizaClient;
+use eliza::{CompactionStrategy, ElizaClient, ElizaError, HistoryStats};
+use error::AppError;
+use health::Backend;
+use history::{HistoryEntry, TurnMeta};
+use hooks::HookEvent;
+use intent::Intent;
+use latency_preset::LatencyPreset;
+use midi::MidiEvent;
+use mood::MoodState;
+use notify_sound::SoundEvent;
 use openai::OpenAIClient;
+use retry_queue::RetryQueue;
+use persona::PersonaTemplate;
+use pipeline_controller::{AppState, Effect, PipelineController, PipelineEvent};
+use rosc::OscType;
+use session::{Session, SessionStats};
+use stats::PersonaStats;
+use ui::calibration::CalibrationState;
+use ui::mic_check::MicCheckState;
+use ui::replay::ReplayState;
+use ui::persona_editor::PersonaEditorState;
+use ui::settings::SettingsState;
+use vad::{VadEvent, VadParams, VadThread};
+use vad_preset::VadPreset;
+use voice_command::VoiceCommand;
+use std::collections::HashMap;
 use std::path::PathBuf;
 use std::sync::mpsc::{channel, Receiver, Sender};
-use vrchat::{VRChatClient, start_mute_listener};
+use vrchat::{VRChatClient, VRChatError, start_mute_listener};
 
 fn main() -> eframe::Result<()> {
     // Load config
     let args: Vec<String> = std::env::args().collect();
+    if args.iter().any(|arg| arg == "--rpc") {
+        rpc::enable();
+    }
+    if let Some(name) = config::instance_arg(&args) {
+        config::set_instance(name);
+    }
+    if args.iter().any(|arg| arg == "--mock") {
+        mock::enable();
+    }
     let mut config = Config::load();
     config.apply_args(&args);
+    if let Some(profile) = audio_profile::load_active().and_then(|name| AudioProfile::load(&name).ok()) {
+        profile.apply(&mut config);
+    }
+
+    // 同じ --instance が既に動いていれば、そちらを前に出して終わる (OSC のポートを取り合わない)
+    let instance_lock =
+        match Config::config_dir().and_then(|dir| single_instance::acquire(&dir.join("instance.lock"))) {
+            Ok(Some(lock)) => Some(lock),
+            Ok(None) => {
+                println!("Already running; focused the existing window");
+                return Ok(());
+            }
+            Err(e) => {
+                eprintln!("{}", e);
+                None
+            }
+        };
 
     let options = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default()
@@ -26,8 +146,15 @@ fn main() -> eframe::Result<()> {
         ..Default::default()
     };
 
+    let mut title = match config::instance() {
+        Some(name) => format!("Eliza Agent [{}] - VRChat Voice Chat", name),
+        None => "Eliza Agent - VRChat Voice Chat".to_string(),
+    };
+    if mock::is_enabled() {
+        title.push_str(" (mock)");
+    }
     eframe::run_native(
-        "Eliza Agent - VRChat Voice Chat",
+        &title,
         options,
         Box::new(move |cc| {
             // Setup Japanese font
@@ -49,83 +176,230 @@ fn main() -> eframe::Result<()> {
                 .or_default()
                 .insert(0, "japanese".to_owned());
             cc.egui_ctx.set_fonts(fonts);
+            if let Some(lock) = instance_lock {
+                lock.listen(cc.egui_ctx.clone());
+            }
 
-            Ok(Box::new(ElizaAgentApp::new(config)))
+            let mut app = ElizaAgentApp::new(config);
+            app.egui_ctx = Some(cc.egui_ctx.clone());
+            Ok(Box::new(app))
         }),
     )
 }
 
-#[derive(Debug, Clone, PartialEq)]
-enum AppState {
-    Idle,
-    Monitoring,
-    Recording,
-    Processing,
-    CalibratingSilence, // キャリブレーション: 無音フェーズ (2秒)
-    CalibratingVoice,   // キャリブレーション: 発話フェーズ (2秒以上)
-}
-
 enum ProcessingMessage {
     TranscriptionInProgress,
-    TranscriptionComplete(String),
+    TranscriptionComplete(String, TurnMeta),
     ElizaInProgress,
-    ElizaComplete(String, bool), // response text, sleep flag
+    ElizaPartial(String), // ストリーミング中の返答 (ここまでの全文)
+    ElizaComplete(String, bool, TurnMeta), // response text, sleep flag
+    TopicUpdated(String),
+    RouteToPreset(String, String, Option<ElizaClient>), // preset, transcribed text, client
+    SafetyStop(Option<ElizaClient>), // Safety word detected
+    VoiceCommand(VoiceCommand, Option<ElizaClient>),
+    CompareComplete(Comparison),
+    ChatboxBuffered(String), // VRChat が起動していないため送らずに保留した返答
+    ChatboxFailed(String),   // 送信に失敗したので再送待ちにする返答
+    ChatboxSuppressed(String), // チャットボックスに送らなかった理由 (返答は UI のみ)
+    Ignored(String, Intent, Option<ElizaClient>), // Agent 宛てではない発話
+    MoodChanged(MoodState),
     Complete(Option<ElizaClient>), // Processing complete, return ElizaClient
-    Error(String, Option<ElizaClient>), // Error with ElizaClient (to preserve history)
+    Error(AppError, Option<ElizaClient>), // Error with ElizaClient (to preserve history)
+    Cancelled(Option<ElizaClient>), // 始まる前に取り消された (ElizaClient だけ返す)
+}
+
+impl ProcessingMessage {
+    /// 返ってきた ElizaClient (結果を捨てるときも履歴は取り戻す)
+    fn into_client(self) -> Option<ElizaClient> {
+        match self {
+            ProcessingMessage::RouteToPreset(_, _, client)
+            | ProcessingMessage::SafetyStop(client)
+            | ProcessingMessage::VoiceCommand(_, client)
+            | ProcessingMessage::Ignored(_, _, client)
+            | ProcessingMessage::Complete(client)
+            | ProcessingMessage::Error(_, client)
+            | ProcessingMessage::Cancelled(client) => client,
+            _ => None,
+        }
+    }
+}
+
+/// 終了処理の進み具合
+struct ShutdownProgress {
+    started_at: std::time::Instant,
+    /// 再送待ちだったチャットボックスのメッセージを送り終えたら届く
+    chatbox_flush: Option<Receiver<()>>,
+    /// 会話の保存と記憶の保存を始めたか (処理中の返答が ElizaClient を返してから)
+    saved: bool,
+}
+
+/// 閉じるときに自動で保存するセッション名
+const AUTOSAVE_SESSION_NAME: &str = "autosave";
+
+/// 録音中に自分の声が検出されていた割合がこれ未満なら他の人の発話として記録する
+const MIN_SELF_VOICE_RATIO: f32 = 0.2;
+
+/// Compare mode の結果 (A = メイン, B = 比較側)
+struct Comparison {
+    model_a: String,
+    reply_a: String,
+    model_b: String,
+    reply_b: String,
+    sent_b: bool,
 }
 
 struct ElizaAgentApp {
-    state: AppState,
+    pipeline: PipelineController,
     config: Config,
     current_preset: String,
     status_message: String,
     recording_info: String,
+    toasts: toast::Toasts,
 
     // Audio
     audio_recorder: Option<AudioRecorder>,
     audio_file_path: Option<PathBuf>,
+    /// 直前の発話の言語 (次の書き起こしで言語別の Whisper プロンプトを選ぶ)
+    last_language: Option<String>,
+    /// 今の録音が始まった時刻 (話者の判定に使う)
+    recording_started_at: Option<std::time::Instant>,
+    /// VAD のスレッドが無音を検出した時点で切り出した録音 (ProcessRecording で使う)
+    vad_cut_samples: Option<Vec<f32>>,
+    /// 処理中の録音で VRChat が自分の声を検出していた割合
+    recording_self_voice: Option<f32>,
+    /// 最後のユーザーの発話のあと、沈黙への質問をまだしていない
+    silence_follow_up_armed: bool,
 
     // Clients
     eliza_client: Option<ElizaClient>,
+    // 言語ルーティングで切り替えたプリセットの会話を保持する
+    parked_clients: HashMap<String, ElizaClient>,
+    // VRChat が起動していない間に保留した返答
+    chatbox_buffer: Vec<String>,
+    chatbox_retry: RetryQueue,
+    // 返答以外 (予約メッセージ・あいさつ) をチャットボックスに送ったときの保留・再送・抑制
+    chatbox_sender: Sender<ProcessingMessage>,
+    chatbox_receiver: Receiver<ProcessingMessage>,
+    // 直近の返答 (reply フック用)
+    last_reply: String,
+    // キャラクターの気分 (pipeline に渡して更新してもらう)
+    mood: MoodState,
+    // 会話の要約 (停止・おやすみ時に生成)
+    last_summary: Option<String>,
+    summary_receiver: Option<Receiver<Result<String, String>>>,
+    /// バックグラウンドで保存中の記憶 (終わったら結果が届く)
+    memory_save_receivers: Vec<Receiver<Result<(), String>>>,
+    /// サーバーから読み込み中の記憶
+    memory_load_receiver: Option<Receiver<Result<String, ElizaError>>>,
+    /// safety_stop で結果を捨てた処理 (ElizaClient が返ってきたら取り戻す) と、履歴を消すか
+    discarded_receivers: Vec<(Receiver<ProcessingMessage>, bool)>,
+    /// 発話・テキストのパイプラインを順に実行する常駐スレッド
+    worker: worker::PipelineWorker,
+    /// プライベートモード: このセッションの間は録音・記憶・要約・評価・セッションをディスクやサーバーに残さない
+    privacy_mode: bool,
+    /// 終了処理中 (閉じるボタンを押してから、保存と送信が終わるまで)
+    shutdown: Option<ShutdownProgress>,
+    summarized_turns: usize,
+    greeter: greeter::Greeter,
+    /// (名前, 送ったあいさつ。送らなかったときは空)
+    greeting_receiver: Option<Receiver<Result<(String, String), AppError>>>,
 
     // Background processing
     processing_receiver: Option<Receiver<ProcessingMessage>>,
 
     // VRChat mute state detection
     mute_receiver: Option<Receiver<bool>>,
+    /// 最後に受け取った MuteSelf (ミュート中は VRChat が声を検出しないので話者を判定しない)
+    vrchat_muted: bool,
+    /// AFK でモニタリングを止めている (戻ったら再開する)
+    afk_paused: bool,
+
+    // 操作用 API (drop すると止まる) と、処理中に届いて順番待ちのテキスト
+    control_server: Option<control::ControlServer>,
+    control_receiver: Option<Receiver<String>>,
+    pending_control_texts: std::collections::VecDeque<String>,
+
+    // MIDI controller (接続は drop すると閉じる)
+    midi_connection: Option<midir::MidiInputConnection<()>>,
+    midi_receiver: Option<Receiver<MidiEvent>>,
+    last_midi_event: Option<MidiEvent>,
+    /// hold_to_talk を割り当てた MIDI パッドを押しているか
+    midi_talk_held: bool,
 
     // VAD: 単発ノイズスパイクで誤検出しないよう連続カウント
-    voice_detection_count: u32,
-
-    // Calibration
-    calib_start_time: Option<std::time::Instant>,
-    calib_rms_samples: Vec<f32>,
-
-    // Settings UI
-    show_settings: bool,
-    settings_openai_key: String,
-    settings_agent_server_url: String,
-    settings_start_threshold: f32,
-    settings_silence_threshold: f32,
-    settings_silence_duration: f32,
-    settings_whisper_model: String,
-    settings_custom_prompt: String,
-    settings_agent_model: String,
-    settings_max_history: usize,
-    settings_use_vrchat_mute_detection: bool,
+    /// 録音開始・終了を判定するスレッド (マイクを開いている間だけ)
+    vad: Option<VadThread>,
+    /// 判定スレッドから再描画を起こすため
+    egui_ctx: Option<egui::Context>,
+
+    calibration: CalibrationState,
+    mic_check: MicCheckState,
+    replay: ReplayState,
+
+    settings: SettingsState,
 
     // Device management
     available_devices: Vec<String>,
     selected_device_index: usize,
 
     // Conversation history display
-    conversation_history: Vec<(String, String)>, // (role, message)
+    conversation_history: Vec<HistoryEntry>,
+    ab_test: AbTest,
+    /// 処理中の返答に使った A/B テストのプロンプト
+    ab_variant: Option<Variant>,
+    show_ab_report: bool,
+    /// ストリーミング受信中の返答
+    streaming_reply: Option<String>,
+    /// 最後に確認した履歴の状態 (処理中は client がスレッドにあるので保持しておく)
+    history_stats: Option<HistoryStats>,
 
     // Text input for direct text sending
     text_input: String,
 
     // Sleep: set to true when Eliza detects user wants to sleep
     pending_sleep: bool,
+
+    // Status topic line (N ターンごとに更新)
+    current_topic: Option<String>,
+
+    // Compare mode: 直近の比較結果
+    last_comparison: Option<Comparison>,
+
+    // Persona library
+    show_persona_library: bool,
+    persona_library: Vec<PersonaTemplate>,
+    selected_persona_index: usize,
+    persona_editor: PersonaEditorState,
+    /// 今のプリセットの通算の記録
+    persona_stats: PersonaStats,
+    show_persona_stats: bool,
+
+    // Session save/load
+    show_sessions: bool,
+    // Batch re-transcription window
+    show_batch: bool,
+    show_osc_console: bool,
+    show_diagnostics: bool,
+    show_scheduler: bool,
+    show_context_preview: bool,
+    osc_console: osc_log::ConsoleState,
+    batch_dir: String,
+    batch_backend: String,
+    batch_receiver: Option<Receiver<batch::BatchMessage>>,
+    batch_status: String,
+    session_name_input: String,
+    /// 会話の書き出し / 読み込みに使うファイル
+    history_file_input: String,
+    saved_sessions: Vec<String>,
+    /// モニタリングを続けている名前付きの会話 (止めるたびにこの名前で保存する)
+    active_session: Option<String>,
+    /// 新しく始める会話の名前の入力欄
+    new_session_name: String,
+    /// 選択中の音声プロファイル (None ならプリセットの音声設定を使う)
+    audio_profile: Option<String>,
+    audio_profiles: Vec<String>,
+    new_audio_profile_name: String,
+    session_started_at: u64,
 }
 
 impl ElizaAgentApp {
@@ -146,61 +420,405 @@ impl ElizaAgentApp {
             0
         };
 
+        vrchat::start_process_watcher();
+        vrchat::set_send_port(config.osc_send_port);
+
         // Start VRChat mute listener if enabled
-        let mute_receiver = if config.use_vrchat_mute_detection {
+        let mute_receiver = if config.use_vrchat_mute_detection
+            || config.speaker_attribution_enabled
+            || config.afk_pause_enabled
+        {
             let (tx, rx) = channel::<bool>();
-            start_mute_listener(tx);
+            start_mute_listener(tx, config.osc_listen_port);
             Some(rx)
         } else {
             None
         };
 
-        Self {
-            state: AppState::Idle,
+        let (chatbox_sender, chatbox_receiver) = channel();
+        let scheduler_sender = chatbox_sender.clone();
+        let mut app = Self {
+            pipeline: PipelineController::default(),
             current_preset: "default".to_string(),
             status_message: "Ready. Press Start to begin monitoring.".to_string(),
             recording_info: String::new(),
+            toasts: toast::Toasts::default(),
             audio_recorder: None,
             audio_file_path: None,
+            last_language: None,
+            recording_started_at: None,
+            vad_cut_samples: None,
+            recording_self_voice: None,
+            silence_follow_up_armed: false,
             eliza_client: None,
+            parked_clients: HashMap::new(),
             processing_receiver: None,
             mute_receiver,
-            voice_detection_count: 0,
-            calib_start_time: None,
-            calib_rms_samples: Vec::new(),
-            show_settings: false,
-            settings_openai_key: config.openai_api_key.clone(),
-            settings_agent_server_url: config.agent_server_url.clone(),
-            settings_start_threshold: config.start_threshold,
-            settings_silence_threshold: config.silence_threshold,
-            settings_silence_duration: config.silence_duration_secs,
-            settings_whisper_model: config.whisper_model.clone(),
-            settings_custom_prompt: config.custom_prompt.clone(),
-            settings_agent_model: config.agent_model.clone(),
-            settings_max_history: config.max_length_of_conversation_history,
-            settings_use_vrchat_mute_detection: config.use_vrchat_mute_detection,
+            vrchat_muted: false,
+            afk_paused: false,
+            control_server: None,
+            control_receiver: None,
+            pending_control_texts: std::collections::VecDeque::new(),
+            midi_connection: None,
+            midi_receiver: None,
+            last_midi_event: None,
+            midi_talk_held: false,
+            vad: None,
+            egui_ctx: None,
+            calibration: CalibrationState::default(),
+            mic_check: MicCheckState::default(),
+            replay: ReplayState::default(),
+            settings: SettingsState::new(&config),
             available_devices,
             selected_device_index,
             conversation_history: Vec::new(),
+            ab_test: AbTest::default(),
+            ab_variant: None,
+            show_ab_report: false,
+            streaming_reply: None,
+            history_stats: None,
             text_input: String::new(),
             pending_sleep: false,
+            current_topic: None,
+            last_comparison: None,
+            chatbox_buffer: Vec::new(),
+            chatbox_retry: RetryQueue::default(),
+            chatbox_sender,
+            chatbox_receiver,
+            last_reply: String::new(),
+            mood: MoodState::default(),
+            last_summary: Session::load_summary("default"),
+            summary_receiver: None,
+            memory_save_receivers: Vec::new(),
+            memory_load_receiver: None,
+            discarded_receivers: Vec::new(),
+            worker: worker::PipelineWorker::start(),
+            privacy_mode: false,
+            shutdown: None,
+            summarized_turns: 0,
+            greeter: greeter::Greeter::new(),
+            greeting_receiver: None,
+            show_persona_library: false,
+            persona_library: Vec::new(),
+            persona_stats: PersonaStats::load("default"),
+            show_persona_stats: false,
+            selected_persona_index: 0,
+            persona_editor: PersonaEditorState::default(),
+            show_sessions: false,
+            show_batch: false,
+            show_osc_console: false,
+            show_diagnostics: false,
+            show_scheduler: false,
+            show_context_preview: false,
+            osc_console: osc_log::ConsoleState::default(),
+            batch_dir: batch::archive_dir()
+                .map(|d| d.display().to_string())
+                .unwrap_or_default(),
+            batch_backend: config.stt_backend.clone(),
+            batch_receiver: None,
+            batch_status: String::new(),
+            session_name_input: String::new(),
+            history_file_input: dirs::document_dir()
+                .map(|dir| dir.join("eliza-history.json").display().to_string())
+                .unwrap_or_default(),
+            saved_sessions: Session::list(),
+            active_session: None,
+            new_session_name: String::new(),
+            audio_profile: audio_profile::load_active(),
+            audio_profiles: audio_profile::list(),
+            new_audio_profile_name: String::new(),
+            session_started_at: session::now_unix_secs(),
             config,
+        };
+        app.restart_midi();
+        app.restart_control_api();
+        app.start_world_chat_watcher();
+        app.sync_scheduler();
+        scheduler::start(move |config, text| {
+            if let Err(e) = send_reply_to_chatbox(config, text, text, "", &scheduler_sender) {
+                eprintln!("[Scheduler] Failed to send: {}", e);
+            }
+        });
+        app
+    }
+
+    /// Create a new ElizaClient from the current config
+    /// Agent サーバーが設定されているか (--mock ではサーバーなしで動かす)
+    fn agent_configured(&self) -> bool {
+        !self.config.agent_server_url.is_empty() || mock::is_enabled()
+    }
+
+    fn new_eliza_client(&self) -> ElizaClient {
+        let client = ElizaClient::new(
+            self.config.agent_server_url.clone(),
+            self.config.agent_model.clone(),
+            self.config.max_length_of_conversation_history,
+        )
+        .with_timeout(self.config.agent_timeout_secs)
+        .with_temperature(self.config.agent_temperature, self.config.agent_spontaneity)
+        .with_max_tokens(self.config.agent_max_tokens)
+        .with_compaction(
+            CompactionStrategy::from_name(&self.config.history_compaction),
+            self.config.history_compact_size,
+        )
+        .with_system_prompt(self.config.persona_prompt.clone())
+        .with_context_note("feedback", self.feedback_note());
+        match Session::load_summary(&self.summary_key()) {
+            Some(summary) if self.config.session_summary_enabled => {
+                client.with_context_note("summary", format!("前回の会話の要約: {}", summary))
+            }
+            _ => client,
+        }
+    }
+
+    /// 表示中の会話の続きから話す ElizaClient を作る (Agent を設定する前に読み込んだ会話も引き継ぐ)
+    fn resume_eliza_client(&mut self) {
+        let mut client = self.new_eliza_client();
+        client.import_history(history::agent_messages(&self.conversation_history));
+        self.eliza_client = Some(client);
+    }
+
+    /// Summarize the conversation in the background (on stop / sleep)
+    /// 要約を保存する単位 (名前付きの会話ならその会話、なければプリセット)
+    fn summary_key(&self) -> String {
+        match &self.active_session {
+            Some(name) => format!("session_{}", name),
+            None => self.current_preset.clone(),
+        }
+    }
+
+    /// モニタリング開始時: 名前付きの会話がなければ日時の名前で始める (今の会話はそのまま続ける)
+    fn ensure_active_session(&mut self) {
+        if self.active_session.is_some() {
+            return;
+        }
+        let name = chrono::Local::now().format("%Y-%m-%d %H-%M").to_string();
+        println!("Starting session: {}", name);
+        self.session_name_input = name.clone();
+        self.active_session = Some(name);
+        if !self.privacy_mode {
+            self.persona_stats.record_session();
+            self.save_persona_stats();
+        }
+    }
+
+    /// 使うプリセットを変える (プリセットごとに保存する人格の記録も読み直す)
+    fn set_current_preset(&mut self, preset_name: &str) {
+        self.current_preset = preset_name.to_string();
+        self.persona_stats = PersonaStats::load(preset_name);
+    }
+
+    fn save_persona_stats(&mut self) {
+        if let Err(e) = self.persona_stats.save(&self.current_preset) {
+            eprintln!("[Stats] {}", e);
+        }
+    }
+
+    /// モニタリング停止時: 名前付きの会話を保存する
+    fn save_active_session(&mut self) {
+        // リプレイ中の履歴は使い捨て (元の会話はリプレイを止めたときに戻る)
+        if self.is_replaying() {
+            return;
+        }
+        if let Some(name) = self.active_session.clone() {
+            if !self.conversation_history.is_empty() {
+                self.save_session_as(name);
+            }
+        }
+    }
+
+    /// 会話を切り替える前に止めて、今の名前付きの会話を保存する (停止したときに保存済みなら保存し直さない)
+    fn stop_and_save_session(&mut self) {
+        if self.dispatch(PipelineEvent::StopPressed) != Some(Effect::CloseMicrophone) {
+            self.save_active_session();
+        }
+    }
+
+    /// 新しい名前付きの会話を始める (同じ名前の会話があればそれを開く)
+    fn start_named_session(&mut self, name: &str) {
+        if self.saved_sessions.iter().any(|s| s == name) {
+            self.load_session(name);
+            return;
+        }
+        if self.processing_receiver.is_some() {
+            self.status_message = "処理中は会話を切り替えられません".to_string();
+            return;
+        }
+        self.save_active_session();
+        self.save_memory_in_background();
+        self.active_session = Some(name.to_string());
+        self.session_name_input = name.to_string();
+        self.conversation_history.clear();
+        self.current_topic = None;
+        self.session_started_at = session::now_unix_secs();
+        self.summarized_turns = 0;
+        self.mood = MoodState::default();
+        self.last_summary = Session::load_summary(&self.summary_key());
+        self.eliza_client = self.agent_configured().then(|| self.new_eliza_client());
+        self.status_message = format!("会話「{}」を始めました", name);
+    }
+
+    fn start_session_summary(&mut self) {
+        if !self.config.session_summary_enabled || self.summary_receiver.is_some() || self.privacy_blocks("会話の要約") {
+            return;
+        }
+        let Some(client) = &self.eliza_client else {
+            return;
+        };
+        if client.turn_count() == 0 || client.turn_count() == self.summarized_turns {
+            return;
+        }
+        self.summarized_turns = client.turn_count();
+
+        let mut summarizer = self.new_eliza_client();
+        summarizer.restore(client.history(), client.turn_count(), None);
+        let (sender, receiver) = channel();
+        self.summary_receiver = Some(receiver);
+        std::thread::spawn(move || {
+            let _ = sender.send(summarizer.summarize().map_err(|e| e.to_string()));
+        });
+    }
+
+    /// Greet players who joined the instance (one at a time, rate-limited)
+    fn greet_joined_players(&mut self) {
+        let joins = vrchat_log::take_joins();
+        if !self.config.greet_joins_enabled || !self.agent_configured() {
+            return;
+        }
+        for name in joins {
+            self.greeter.push(name);
+        }
+        if self.greeting_receiver.is_some() {
+            return;
+        }
+        let Some(name) = self.greeter.next(
+            std::time::Instant::now(),
+            &self.config.greet_blocklist,
+            self.config.greet_per_minute,
+            &vrchat_log::present_players(),
+        ) else {
+            return;
+        };
+        let mut greeter_client = self.new_eliza_client();
+        if let Some(client) = &self.eliza_client {
+            greeter_client.restore(client.history(), client.turn_count(), None);
+        }
+        let config = self.pipeline_config();
+        let chatbox_sender = self.chatbox_sender.clone();
+        let (sender, receiver) = channel();
+        self.greeting_receiver = Some(receiver);
+        std::thread::spawn(move || {
+            let messages = greeter_client.pending_messages(&greeter::greeting_prompt(&name));
+            let result = greeter_client
+                .complete(messages)
+                .map_err(AppError::from)
+                .and_then(|greeting| {
+                    let greeting = greeting.trim().to_string();
+                    // 入ってきた直後でなければ意味がないので、VRChat が閉じている間は保留せずに捨てる
+                    if greeting.is_empty() || chatbox_paused(&config) {
+                        return Ok(String::new());
+                    }
+                    send_reply_to_chatbox(&config, &greeting, &greeting, "", &chatbox_sender)
+                        .map_err(|e| AppError::from(e).context("VRChat failed"))?;
+                    Ok(greeting)
+                })
+                .map(|greeting| (name, greeting));
+            let _ = sender.send(result);
+        });
+    }
+
+    /// 返答以外をチャットボックスに送った結果を反映する (返答と同じく、VRChat が閉じていれば保留し、失敗すれば再送を待つ)
+    fn poll_chatbox_messages(&mut self) {
+        while let Ok(message) = self.chatbox_receiver.try_recv() {
+            match message {
+                ProcessingMessage::ChatboxBuffered(text) => {
+                    self.status_message = format!("VRChat が起動していないので保留しました: {}", text);
+                    self.chatbox_buffer.push(text);
+                }
+                ProcessingMessage::ChatboxFailed(text) => self.chatbox_retry.push(text, std::time::Instant::now()),
+                ProcessingMessage::ChatboxSuppressed(reason) => {
+                    self.status_message = format!("チャットボックスに送りませんでした ({})", reason);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Feed an event to the pipeline state machine and carry out the resulting effect
+    fn dispatch(&mut self, event: PipelineEvent) -> Option<Effect> {
+        // テキストモードではマイクを開かない (ミュート連動や MIDI などからの開始も含めて)
+        if self.config.text_mode
+            && matches!(event, PipelineEvent::StartPressed | PipelineEvent::CalibrationRequested)
+        {
+            self.status_message = "⌨ テキストモード中です (Ctrl+T で音声モードに戻ります)".to_string();
+            return None;
+        }
+        let was_idle = self.pipeline.is_idle();
+        let was_recording = self.pipeline.state() == AppState::Recording;
+        let effect = self.pipeline.handle(event);
+        match (was_recording, self.pipeline.state() == AppState::Recording) {
+            (false, true) => {
+                self.recording_started_at = Some(std::time::Instant::now());
+                self.set_listening_parameter(true);
+                self.fire_hook(HookEvent::RecordingStarted, "");
+            }
+            (true, false) => {
+                self.set_listening_parameter(false);
+                self.fire_hook(HookEvent::RecordingStopped, "");
+            }
+            _ => {}
+        }
+        match effect {
+            Some(Effect::OpenMicrophone) => {
+                if was_idle {
+                    self.ensure_active_session();
+                }
+                self.start_monitoring();
+            }
+            Some(Effect::CloseMicrophone) => {
+                self.silence_follow_up_armed = false;
+                self.stop_monitoring();
+                self.save_active_session();
+            }
+            Some(Effect::ProcessRecording) => self.stop_recording_and_process(),
+            Some(Effect::OpenCalibrationMicrophone) => self.start_calibration(),
+            Some(Effect::FinishCalibration) => self.finish_calibration(),
+            None => {}
+        }
+        if let Some(vad) = &self.vad {
+            vad.set_state(self.pipeline.state());
+        }
+        effect
+    }
+
+    /// 録音中かどうかをアバターのパラメータに送る
+    fn set_listening_parameter(&self, listening: bool) {
+        if self.config.listening_osc_address.is_empty() {
+            return;
+        }
+        if let Err(e) = VRChatClient::new().send_osc(
+            &self.config.listening_osc_address,
+            vec![OscType::Bool(listening)],
+        ) {
+            eprintln!("✗ VRChat listening parameter send failed: {}", e);
         }
     }
 
     fn start_monitoring(&mut self) {
         println!("Starting monitoring mode");
-        self.state = AppState::Monitoring;
-        self.status_message = "Monitoring... Speak to start recording.".to_string();
+        self.status_message = if self.config.hold_to_talk_enabled {
+            format!(
+                "Monitoring... {} を押している間だけ録音します (このウィンドウが前面にあるときだけ)",
+                self.config.hold_to_talk_key
+            )
+        } else {
+            "Monitoring... Speak to start recording.".to_string()
+        };
 
         // Initialize ElizaClient only if not already initialized
-        if self.eliza_client.is_none() && !self.config.agent_server_url.is_empty() {
+        if self.eliza_client.is_none() && self.agent_configured() {
             println!("Creating new ElizaClient");
-            self.eliza_client = Some(ElizaClient::new(
-                self.config.agent_server_url.clone(),
-                self.config.agent_model.clone(),
-                self.config.max_length_of_conversation_history,
-            ));
+            self.resume_eliza_client();
         } else if self.eliza_client.is_some() {
             println!("Reusing existing ElizaClient with conversation history");
         }
@@ -208,6 +826,7 @@ impl ElizaAgentApp {
         // Start audio monitoring
         match AudioRecorder::new(self.config.silence_threshold) {
             Ok(mut recorder) => {
+                recorder.set_gain(self.config.input_gain());
                 let device_name = self
                     .config
                     .input_device_name
@@ -218,238 +837,1832 @@ impl ElizaAgentApp {
                 match recorder.start_recording_with_device(device_name) {
                     Ok(_) => {
                         self.audio_recorder = Some(recorder);
+                        self.restart_vad();
                     }
                     Err(e) => {
-                        self.status_message = format!("Error: {}", e);
-                        self.state = AppState::Idle;
+                        self.report_error(e.context("Error"));
+                        self.dispatch(PipelineEvent::MicrophoneFailed);
                     }
                 }
             }
             Err(e) => {
-                self.status_message = format!("Error: {}", e);
-                self.state = AppState::Idle;
+                self.report_error(e.context("Error"));
+                self.dispatch(PipelineEvent::MicrophoneFailed);
             }
         }
     }
 
+    /// 録音の開始・終了を判定するスレッドを今の設定で作り直す (Hold-to-talk では使わない)
+    fn restart_vad(&mut self) {
+        self.vad = match &self.audio_recorder {
+            Some(recorder) if !self.config.hold_to_talk_enabled => Some(VadThread::start(
+                recorder.probe(),
+                VadParams::from_config(&self.config),
+                self.egui_ctx.clone(),
+            )),
+            _ => None,
+        };
+        if let Some(vad) = &self.vad {
+            vad.set_state(self.pipeline.state());
+        }
+    }
+
+    /// Show an error in the status bar and as a toast (with a hint on how to fix it)
+    fn report_error(&mut self, error: impl Into<AppError>) {
+        let error = error.into();
+        self.toasts.error(error.user_message());
+        self.fire_hook(HookEvent::Error, &error.to_string());
+        notify_sound::play(&self.config, SoundEvent::Error);
+        self.status_message = error.user_message();
+    }
+
+    fn fire_hook(&self, event: HookEvent, text: &str) {
+        hooks::fire(&self.config.hooks, event, text, &self.current_preset);
+        rpc::notify_event(event.name(), text, &self.current_preset);
+        if self.config.activity_osc_enabled {
+            hooks::send_osc(&self.config.activity_osc_target, event, text, &self.current_preset);
+        }
+    }
+
     fn stop_monitoring(&mut self) {
         println!("Stopping monitoring mode");
         if let Some(mut recorder) = self.audio_recorder.take() {
             recorder.stop_recording();
         }
-        self.state = AppState::Idle;
         self.status_message = "Stopped.".to_string();
         self.recording_info.clear();
-        self.voice_detection_count = 0;
-    }
-
-    fn start_calibration(&mut self) {
-        println!("Starting calibration: silence phase");
-        // マイクを起動（silence_threshold=0 で全サンプル拾う）
-        match AudioRecorder::new(0.0) {
-            Ok(mut recorder) => {
-                let device_name = self
-                    .config
-                    .input_device_name
-                    .as_ref()
-                    .filter(|name| name.as_str() != "Windows既定")
-                    .map(|s| s.as_str());
-                match recorder.start_recording_with_device(device_name) {
-                    Ok(_) => {
-                        self.audio_recorder = Some(recorder);
-                        self.state = AppState::CalibratingSilence;
-                        self.calib_start_time = Some(std::time::Instant::now());
-                        self.calib_rms_samples = Vec::new();
-                        self.status_message = "キャリブレーション: 静かにしてください... (2秒)".to_string();
-                    }
-                    Err(e) => {
-                        self.status_message = format!("Error: {}", e);
-                    }
-                }
-            }
-            Err(e) => {
-                self.status_message = format!("Error: {}", e);
-            }
-        }
+        self.vad = None;
     }
 
     fn switch_preset(&mut self, preset_name: &str) {
         println!("Switching to preset: {}", preset_name);
 
-        // Stop monitoring if active
-        if self.state != AppState::Idle {
-            self.stop_monitoring();
-        }
+        // Stop monitoring if active and keep the current conversation and memory
+        self.stop_and_save_session();
+        self.save_memory_in_background();
 
         // Load new config
         self.config = Config::load_preset(preset_name);
-        self.current_preset = preset_name.to_string();
+        self.apply_audio_profile();
+        self.set_current_preset(preset_name);
 
         // Update settings UI
-        self.settings_openai_key = self.config.openai_api_key.clone();
-        self.settings_agent_server_url = self.config.agent_server_url.clone();
-        self.settings_start_threshold = self.config.start_threshold;
-        self.settings_silence_threshold = self.config.silence_threshold;
-        self.settings_silence_duration = self.config.silence_duration_secs;
-        self.settings_whisper_model = self.config.whisper_model.clone();
-        self.settings_custom_prompt = self.config.custom_prompt.clone();
-        self.settings_agent_model = self.config.agent_model.clone();
-        self.settings_max_history = self.config.max_length_of_conversation_history;
-        self.settings_use_vrchat_mute_detection = self.config.use_vrchat_mute_detection;
-
-        // Restart mute listener for new preset
-        if self.config.use_vrchat_mute_detection {
-            let (tx, rx) = channel::<bool>();
-            start_mute_listener(tx);
-            self.mute_receiver = Some(rx);
-        } else {
-            self.mute_receiver = None;
+        self.load_settings_from_config();
+        if self.persona_editor.open {
+            self.persona_editor.open_with(&self.config.persona_prompt);
         }
-
-        // Update device selection
-
-        self.selected_device_index = if let Some(ref device_name) = self.config.input_device_name {
-            self.available_devices
-                .iter()
-                .position(|d| d == device_name)
-                .unwrap_or(0)
-        } else {
-            0
-        };
+        self.restart_mute_listener();
+        self.restart_midi();
+        self.restart_control_api();
+        self.start_world_chat_watcher();
 
         // Clear ElizaClient to force re-initialization
         self.eliza_client = None;
+        self.parked_clients.clear();
         self.conversation_history.clear();
+        self.current_topic = None;
+        self.session_started_at = session::now_unix_secs();
+        self.active_session = None;
+        self.last_summary = Session::load_summary(preset_name);
+        self.summarized_turns = 0;
+        self.mood = MoodState::default();
 
         self.status_message = format!("Switched to {}", Config::preset_display_name(preset_name));
     }
 
-    fn start_recording(&mut self) {
-        println!("Voice detected! Starting recording...");
-        self.state = AppState::Recording;
-        self.status_message = "Recording... Speak now!".to_string();
+    /// 選択中の音声プロファイルの値で今の設定を上書きする
+    fn apply_audio_profile(&mut self) {
+        let Some(name) = &self.audio_profile else {
+            return;
+        };
+        match AudioProfile::load(name) {
+            Ok(profile) => profile.apply(&mut self.config),
+            Err(e) => self.toasts.error(e),
+        }
     }
 
-    fn stop_recording_and_process(&mut self) {
-        println!("Silence detected. Processing...");
-        self.state = AppState::Processing;
-        self.status_message = "Processing audio...".to_string();
-
-        if let Some(mut recorder) = self.audio_recorder.take() {
-            let audio_data = recorder.stop_recording();
-            let sample_rate = recorder.get_sample_rate();
+    /// 今の音声設定を選択中の音声プロファイルに書く (選んでいなければ何もしない)
+    fn save_audio_profile(&mut self) {
+        let Some(name) = &self.audio_profile else {
+            return;
+        };
+        if let Err(e) = AudioProfile::from_config(&self.config).save(name) {
+            self.report_error(e);
+        }
+    }
 
-            if audio_data.is_empty() {
-                self.status_message = "No audio recorded".to_string();
-                self.start_monitoring();
-                return;
-            }
+    /// 音声プロファイルを切り替える (None ならプリセットの音声設定に戻す)
+    fn select_audio_profile(&mut self, name: Option<String>) {
+        self.dispatch(PipelineEvent::StopPressed);
+        if let Err(e) = audio_profile::save_active(name.as_deref()) {
+            self.report_error(e);
+        }
+        self.audio_profile = name;
+        if self.audio_profile.is_some() {
+            self.apply_audio_profile();
+        } else {
+            let preset = Config::load_preset(&self.current_preset);
+            AudioProfile::from_config(&preset).apply(&mut self.config);
+        }
+        self.load_settings_from_config();
+        self.status_message = match &self.audio_profile {
+            Some(name) => format!("音声プロファイル「{}」に切り替えました", name),
+            None => "プリセットの音声設定に戻しました".to_string(),
+        };
+    }
 
-            // Save audio to WAV
-            match recorder.save_audio_to_wav(&audio_data, sample_rate) {
-                Ok(path) => {
-                    self.audio_file_path = Some(path.clone());
-                    self.start_background_processing(path);
-                }
-                Err(e) => {
-                    self.status_message = format!("Failed to save audio: {}", e);
-                    self.start_monitoring();
-                }
-            }
+    /// 今の音声設定を新しいプロファイルとして保存して選ぶ
+    fn create_audio_profile(&mut self, name: &str) {
+        if let Err(e) = AudioProfile::from_config(&self.config).save(name) {
+            self.report_error(e);
+            return;
         }
+        self.audio_profiles = audio_profile::list();
+        self.select_audio_profile(Some(name.to_string()));
     }
 
-    fn start_background_processing(&mut self, audio_path: PathBuf) {
-        let (sender, receiver) = channel();
-        self.processing_receiver = Some(receiver);
+    /// 言語ルーティング: 検出した言語に対応するプリセットに切り替えて返答させる
+    /// (各プリセットの会話履歴は parked_clients に保持する)
+    fn route_to_preset(
+        &mut self,
+        preset_name: &str,
+        text: String,
+        eliza_client: Option<ElizaClient>,
+    ) {
+        println!("Routing to preset {} by detected language", preset_name);
+        if let Some(client) = eliza_client {
+            self.parked_clients.insert(self.current_preset.clone(), client);
+        }
+
+        // ルーティング設定は切り替え元のものを引き継ぐ (切り替え先から戻ってこられるように)
+        let routing_enabled = self.config.language_routing_enabled;
+        let routes = self.config.language_routes.clone();
+        let osc = (
+            self.config.use_vrchat_mute_detection,
+            self.config.osc_send_port,
+            self.config.osc_listen_port,
+        );
+        self.config = Config::load_preset(preset_name);
+        self.apply_audio_profile();
+        self.config.language_routing_enabled = routing_enabled;
+        self.config.language_routes = routes;
+        self.set_current_preset(preset_name);
+        self.load_settings_from_config();
+        if (
+            self.config.use_vrchat_mute_detection,
+            self.config.osc_send_port,
+            self.config.osc_listen_port,
+        ) != osc
+        {
+            self.restart_mute_listener();
+        }
 
-        let openai_key = self.config.openai_api_key.clone();
-        let whisper_model = self.config.whisper_model.clone();
-        let custom_prompt = self.config.custom_prompt.clone();
+        self.eliza_client = self.parked_clients.remove(preset_name);
+        if self.eliza_client.is_none() && self.agent_configured() {
+            self.eliza_client = Some(self.new_eliza_client());
+        }
+        self.current_topic = self
+            .eliza_client
+            .as_ref()
+            .and_then(|c| c.topic().map(|t| t.to_string()));
 
-        // Take ownership of eliza_client to use in the thread
-        let eliza_client = self.eliza_client.take();
+        self.conversation_history.push(HistoryEntry::new("You", text.clone()));
+        self.status_message = format!(
+            "🌐 {} で返答します...",
+            Config::preset_display_name(preset_name)
+        );
 
-        std::thread::spawn(move || {
-            let _returned_client = process_pipeline(
-                audio_path,
-                openai_key,
-                whisper_model,
-                custom_prompt,
-                eliza_client,
-                sender,
-            );
-            // ElizaClient is returned via ProcessingMessage::Complete
+        let (sender, receiver) = channel();
+        self.processing_receiver = Some(receiver);
+        self.apply_ab_variant();
+        let config = self.pipeline_config();
+        let eliza_client = self.take_eliza_client();
+        let mood = self.mood;
+        self.submit_pipeline(eliza_client, sender, move |eliza_client, sender| {
+            text_pipeline(text, config, eliza_client, mood, sender, false);
         });
     }
 
-    fn send_text_message(&mut self, text: String) {
-        if text.trim().is_empty() {
+    /// 最近の評価から作る補足 (無効なら空)
+    fn feedback_note(&self) -> String {
+        if !self.config.feedback_note_enabled {
+            return String::new();
+        }
+        let entries = feedback::load_recent(&self.current_preset, self.config.feedback_note_count);
+        feedback::feedback_note(&entries)
+    }
+
+    /// Rate an agent message in the history and append it to the feedback log
+    fn rate_history_entry(&mut self, index: usize, rating: i8) {
+        let Some(entry) = self.conversation_history.get_mut(index) else {
+            return;
+        };
+        entry.rating = Some(rating);
+        let message = entry.message.clone();
+        let context = self.conversation_history[index.saturating_sub(4)..index]
+            .iter()
+            .map(|e| format!("{}: {}", e.role, e.message))
+            .collect();
+        if self.privacy_blocks("評価") {
             return;
         }
+        let entry = feedback::FeedbackEntry::new(rating, message, context);
+        if let Err(e) = feedback::append(&self.current_preset, &entry) {
+            self.report_error(e);
+            return;
+        }
+        let note = self.feedback_note();
+        if let Some(client) = &mut self.eliza_client {
+            client.set_context_note("feedback", note);
+        }
+    }
 
-        // Ensure ElizaClient is initialized
-        if self.eliza_client.is_none() && !self.config.agent_server_url.is_empty() {
-            self.eliza_client = Some(ElizaClient::new(
-                self.config.agent_server_url.clone(),
-                self.config.agent_model.clone(),
-                self.config.max_length_of_conversation_history,
-            ));
+    /// A/B テスト中ならこのターンのプロンプトをランダムに選ぶ (それ以外は Persona Prompt に戻す)
+    fn apply_ab_variant(&mut self) {
+        let variant = self.ab_test.is_running().then(Variant::random);
+        if let Some(client) = &mut self.eliza_client {
+            let prompt = variant.unwrap_or(Variant::A).prompt(&self.config);
+            client.set_system_prompt(prompt.to_string());
         }
+        self.ab_variant = variant;
+    }
 
-        // Add to conversation history immediately
-        self.conversation_history.push(("You".to_string(), text.clone()));
-        self.status_message = "Sending to Eliza...".to_string();
+    /// 人格ライブラリのプロンプトを現在のプリセットに適用して保存する
+    fn apply_persona(&mut self, persona: PersonaTemplate) {
+        self.config.persona_prompt = persona.prompt.clone();
+        self.settings.draft.persona_prompt = persona.prompt.clone();
+        if let Some(ref mut eliza_client) = self.eliza_client {
+            eliza_client.set_system_prompt(persona.prompt);
+        }
+        match self.config.save_preset(&self.current_preset) {
+            Ok(_) => {
+                self.status_message = format!(
+                    "人格「{}」を {} に適用しました",
+                    persona.name,
+                    Config::preset_display_name(&self.current_preset)
+                )
+            }
+            Err(e) => self.report_error(format!("Failed to save: {}", e)),
+        }
+    }
 
-        let (sender, receiver) = channel();
-        self.processing_receiver = Some(receiver);
+    /// Copy current config values into the settings UI fields
+    fn load_settings_from_config(&mut self) {
+        self.settings.load(&self.config);
+        self.sync_scheduler();
 
-        let eliza_client = self.eliza_client.take();
+        // Restore device index
+        self.selected_device_index = if let Some(ref device_name) = self.config.input_device_name {
+            self.available_devices
+                .iter()
+                .position(|d| d == device_name)
+                .unwrap_or(0)
+        } else {
+            0
+        };
+    }
 
-        std::thread::spawn(move || {
-            text_pipeline(text, eliza_client, sender);
-        });
+    /// Apply the OSC ports and (re)start or stop the mute listener according to config
+    fn restart_mute_listener(&mut self) {
+        vrchat::set_send_port(self.config.osc_send_port);
+        if self.config.use_vrchat_mute_detection
+            || self.config.speaker_attribution_enabled
+            || self.config.afk_pause_enabled
+        {
+            let (tx, rx) = channel::<bool>();
+            start_mute_listener(tx, self.config.osc_listen_port);
+            self.mute_receiver = Some(rx);
+        } else {
+            self.mute_receiver = None;
+            self.vrchat_muted = false;
+        }
     }
-}
+
+    /// AFK になったらモニタリングを止め、戻ったら (AFK で止めたときだけ) 再開する
+    fn handle_afk(&mut self) {
+        let Some(afk) = vrchat::take_afk_change() else {
+            return;
+        };
+        if !self.config.afk_pause_enabled {
+            return;
+        }
+        if afk && !self.pipeline.is_idle() {
+            println!("VRChat AFK → pause monitoring");
+            self.dispatch(PipelineEvent::StopPressed);
+            self.afk_paused = true;
+            self.status_message = "💤 AFK のあいだモニタリングを止めています".to_string();
+        } else if !afk && self.afk_paused {
+            self.afk_paused = false;
+            if self.pipeline.is_idle() {
+                println!("VRChat back from AFK → resume monitoring");
+                self.dispatch(PipelineEvent::StartPressed);
+            }
+        }
+    }
+
+    /// `--rpc` の標準入力から届いたリクエストを処理する
+    fn handle_rpc_requests(&mut self) {
+        for request in rpc::take_requests() {
+            let result = match request.method.as_str() {
+                "say" => request.str_param("text").map(|text| {
+                    self.pending_control_texts.push_back(text);
+                    serde_json::json!({"queued": self.pending_control_texts.len()})
+                }),
+                "start" => {
+                    if self.pipeline.is_idle() {
+                        self.dispatch(PipelineEvent::StartPressed);
+                    }
+                    Ok(serde_json::json!({"state": format!("{:?}", self.pipeline.state())}))
+                }
+                "stop" => {
+                    if !self.pipeline.is_idle() {
+                        self.dispatch(PipelineEvent::StopPressed);
+                    }
+                    Ok(serde_json::json!({"state": format!("{:?}", self.pipeline.state())}))
+                }
+                "status" => Ok(serde_json::json!({
+                    "state": format!("{:?}", self.pipeline.state()),
+                    "preset": self.current_preset,
+                    "session": self.active_session,
+                    "processing": self.processing_receiver.is_some(),
+                    "last_reply": self.last_reply,
+                })),
+                "switch_preset" => request.str_param("name").and_then(|name| {
+                    if !Config::list_presets().contains(&name) {
+                        return Err((rpc::INVALID_PARAMS, format!("Unknown preset: {}", name)));
+                    }
+                    self.switch_preset(&name);
+                    Ok(serde_json::json!({"preset": self.current_preset}))
+                }),
+                method => Err((rpc::METHOD_NOT_FOUND, format!("Method not found: {}", method))),
+            };
+            rpc::respond(&request, result);
+        }
+    }
+
+    /// (Re)start or stop the control API according to config
+    fn restart_control_api(&mut self) {
+        if self.config.control_api_enabled && self.config.control_api_token.is_empty() {
+            match control::generate_token() {
+                Ok(token) => {
+                    self.config.control_api_token = token;
+                    self.settings.draft.control_api_token = self.config.control_api_token.clone();
+                    if let Err(e) = self.config.save_preset(&self.current_preset) {
+                        eprintln!("Failed to save control API token: {}", e);
+                    }
+                }
+                Err(e) => {
+                    self.toasts.error(e);
+                    return;
+                }
+            }
+        }
+        let port = self.config.control_api_port;
+        if self.config.control_api_enabled
+            && self
+                .control_server
+                .as_ref()
+                .is_some_and(|s| s.port == port && s.token == self.config.control_api_token)
+        {
+            return;
+        }
+        // 古いサーバーを止めてポートを空ける
+        self.control_server = None;
+        self.control_receiver = None;
+        if !self.config.control_api_enabled {
+            return;
+        }
+        let (tx, rx) = channel();
+        match control::ControlServer::start(port, self.config.control_api_token.clone(), tx) {
+            Ok(server) => {
+                self.control_server = Some(server);
+                self.control_receiver = Some(rx);
+            }
+            Err(e) => self.toasts.error(e.user_message()),
+        }
+    }
+
+    /// 入ったワールドに割り当てたプリセットに切り替える (処理中なら終わるまで待つ)
+    fn switch_preset_for_world(&mut self) {
+        if self.processing_receiver.is_some() {
+            return;
+        }
+        let Some(world) = vrchat_log::take_world() else {
+            return;
+        };
+        if !self.config.world_presets_enabled {
+            return;
+        }
+        let Some(preset) = self.config.preset_for_world(&world) else {
+            return;
+        };
+        if preset == self.current_preset {
+            return;
+        }
+        let was_active = self.pipeline.state() != AppState::Idle;
+        self.switch_preset(&preset);
+        if was_active {
+            self.dispatch(PipelineEvent::StartPressed);
+        }
+        let message = format!(
+            "🌐 {} に入ったので {} に切り替えました",
+            world,
+            Config::preset_display_name(&preset)
+        );
+        self.toasts.info(message.clone());
+        self.status_message = message;
+    }
+
+    /// Start watching the VRChat log for world chat or players when enabled (the pattern can change later)
+    fn start_world_chat_watcher(&mut self) {
+        if !self.config.world_chat_context_enabled
+            && !self.config.greet_joins_enabled
+            && !self.config.player_notes_enabled
+            && !self.config.world_presets_enabled
+        {
+            return;
+        }
+        if self.config.world_chat_context_enabled {
+            if let Err(e) = vrchat_log::set_pattern(&self.config.world_chat_pattern) {
+                self.toasts.error(e);
+                return;
+            }
+        }
+        match vrchat_log::default_log_dir() {
+            Some(dir) => vrchat_log::start_watcher(dir),
+            None => self.toasts.error("VRChat log directory not found"),
+        }
+    }
+
+    /// (Re)connect the MIDI controller according to the current config
+    fn restart_midi(&mut self) {
+        self.midi_connection = None;
+        self.midi_receiver = None;
+        if !self.config.midi_enabled {
+            return;
+        }
+        let (tx, rx) = channel::<MidiEvent>();
+        match midi::connect(&self.config.midi_port_name, tx) {
+            Ok(connection) => {
+                self.midi_connection = Some(connection);
+                self.midi_receiver = Some(rx);
+            }
+            Err(e) => self.toasts.error(e.user_message()),
+        }
+    }
+
+    /// Run the action bound to a MIDI pad / knob
+    fn handle_midi_event(&mut self, event: MidiEvent) {
+        let Some(binding) = self
+            .config
+            .midi_bindings
+            .iter()
+            .find(|b| b.trigger == event.key())
+            .cloned()
+        else {
+            return;
+        };
+        if let MidiEvent::NoteOff(_) = event {
+            // 離したことを使うのは hold_to_talk だけ (他のアクションは押したときに一度だけ)
+            if binding.action == "hold_to_talk" {
+                self.midi_talk_held = false;
+            }
+            return;
+        }
+        println!("[MIDI] {} → {}", event.key(), binding.action);
+
+        match binding.action.as_str() {
+            "toggle" => {
+                if self.pipeline.is_idle() {
+                    self.dispatch(PipelineEvent::StartPressed);
+                } else {
+                    self.dispatch(PipelineEvent::StopPressed);
+                }
+            }
+            "next_preset" | "prev_preset" => {
+                let presets = Config::list_presets();
+                let index = presets.iter().position(|p| *p == self.current_preset).unwrap_or(0);
+                let next = if binding.action == "next_preset" {
+                    (index + 1) % presets.len()
+                } else {
+                    (index + presets.len() - 1) % presets.len()
+                };
+                let preset = presets[next].clone();
+                self.switch_preset(&preset);
+            }
+            "sensitivity_up" => self.apply_voice_command(VoiceCommand::SensitivityUp),
+            "sensitivity_down" => self.apply_voice_command(VoiceCommand::SensitivityDown),
+            "threshold_knob" => {
+                if let MidiEvent::Control(_, value) = event {
+                    self.config.start_threshold = midi::knob_to_threshold(value);
+                    self.settings.draft.start_threshold = self.config.start_threshold;
+                    self.status_message =
+                        format!("🎚 Start Threshold: {:.4}", self.config.start_threshold);
+                }
+            }
+            "send_text" => self.send_direct_to_chatbox(binding.text),
+            "hold_to_talk" => self.midi_talk_held = true,
+            _ => {}
+        }
+    }
+
+    fn save_session(&mut self) {
+        let name = self.session_name_input.trim().to_string();
+        self.save_session_as(name);
+    }
+
+    fn save_session_as(&mut self, name: String) {
+        if self.privacy_blocks("セッション") {
+            return;
+        }
+        let (agent_history, turn_count) = match &self.eliza_client {
+            Some(client) => (client.history(), client.turn_count()),
+            None => (Vec::new(), 0),
+        };
+        let session = Session {
+            name,
+            saved_at: session::now_unix_secs(),
+            preset: self.current_preset.clone(),
+            config: self.config.without_secrets(),
+            ui_history: self.conversation_history.clone(),
+            agent_history,
+            stats: SessionStats {
+                turn_count,
+                topic: self.current_topic.clone(),
+                started_at: self.session_started_at,
+            },
+        };
+        match session.save() {
+            Ok(_) => {
+                self.status_message = format!("Session saved: {}", session.name);
+                self.saved_sessions = Session::list();
+            }
+            Err(e) => self.report_error(format!("Failed to save session: {}", e)),
+        }
+    }
+
+    fn load_session(&mut self, name: &str) {
+        if self.processing_receiver.is_some() {
+            self.status_message = "処理中はセッションを読み込めません".to_string();
+            return;
+        }
+        // 先に今の会話を保存する (同じ会話を開き直すときは保存したものを読む)
+        self.stop_and_save_session();
+        let session = match Session::load(name) {
+            Ok(session) => session,
+            Err(e) => {
+                self.report_error(format!("Failed to load session: {}", e));
+                return;
+            }
+        };
+
+        // Restore preset snapshot (not written back to the preset file).
+        // Sessions are saved without secrets, so the current keys are kept
+        let mut config = session.config;
+        config.restore_secrets(&self.config);
+        config.apply_world_presets();
+        self.config = config;
+        self.apply_audio_profile();
+        self.set_current_preset(&session.preset);
+        self.active_session = Some(session.name.clone());
+        self.last_summary = Session::load_summary(&self.summary_key());
+        self.load_settings_from_config();
+        self.restart_mute_listener();
+
+        // Restore conversation
+        let mut client = self.new_eliza_client();
+        client.restore(
+            session.agent_history,
+            session.stats.turn_count,
+            session.stats.topic.clone(),
+        );
+        self.eliza_client = Some(client);
+        self.conversation_history = session.ui_history;
+        self.current_topic = session.stats.topic;
+        self.session_started_at = session.stats.started_at;
+        self.session_name_input = session.name;
+
+        self.status_message = format!("Session loaded: {}", self.session_name_input);
+    }
+
+    /// 表示中の会話をファイルに書き出す
+    fn export_history(&mut self) {
+        if self.privacy_blocks("会話の書き出し") {
+            return;
+        }
+        let path = PathBuf::from(self.history_file_input.trim());
+        match history::export(&self.conversation_history, &path) {
+            Ok(_) => self.status_message = format!("会話を書き出しました: {}", path.display()),
+            Err(e) => self.report_error(e),
+        }
+    }
+
+    /// 書き出した会話を読み込んで、その続きから話せるようにする (プリセットはそのまま)。
+    /// 今の会話は先に保存し、読み込んだ会話は名前のない新しい会話として始める
+    fn import_history(&mut self) {
+        if self.processing_receiver.is_some() {
+            self.status_message = "処理中は会話を読み込めません".to_string();
+            return;
+        }
+        let path = PathBuf::from(self.history_file_input.trim());
+        let entries = match history::import(&path) {
+            Ok(entries) => entries,
+            Err(e) => {
+                self.report_error(e);
+                return;
+            }
+        };
+        self.stop_and_save_session();
+        self.save_memory_in_background();
+        self.active_session = None;
+        self.session_name_input.clear();
+        self.session_started_at = session::now_unix_secs();
+        self.last_summary = Session::load_summary(&self.summary_key());
+        self.status_message = format!("会話を読み込みました: {} 件", entries.len());
+        self.conversation_history = entries;
+        self.current_topic = None;
+        self.summarized_turns = 0;
+        self.mood = MoodState::default();
+        // Agent が未設定なら、あとで作るときに読み込んだ会話を引き継ぐ
+        self.eliza_client = None;
+        if self.agent_configured() {
+            self.resume_eliza_client();
+        }
+    }
+
+    fn stop_recording_and_process(&mut self) {
+        println!("Silence detected. Processing...");
+        self.status_message = "Processing audio...".to_string();
+
+        self.vad = None;
+        if let Some(mut recorder) = self.audio_recorder.take() {
+            let recorded = recorder.stop_recording();
+            // VAD が無音で切り出していれば、その後に録れた音は送らない
+            let audio_data = self.vad_cut_samples.take().unwrap_or(recorded);
+            let sample_rate = recorder.get_sample_rate();
+
+            if audio_data.is_empty() {
+                self.status_message = "No audio recorded".to_string();
+                self.dispatch(PipelineEvent::RecordingDiscarded);
+                return;
+            }
+
+            self.recording_self_voice = if self.config.speaker_attribution_enabled && !self.vrchat_muted {
+                self.recording_started_at
+                    .and_then(|start| vrchat::self_voice_ratio(start, std::time::Instant::now()))
+            } else {
+                None
+            };
+
+            // Save audio to WAV
+            let work_dir = audio::work_dir(&self.config.audio_work_dir);
+            if self.config.audio_cleanup_minutes > 0 {
+                let max_age = std::time::Duration::from_secs(self.config.audio_cleanup_minutes as u64 * 60);
+                let removed = audio::cleanup_work_dir(&work_dir, max_age);
+                if removed > 0 {
+                    println!("Removed {} old audio files from {}", removed, work_dir.display());
+                }
+            }
+            match recorder.save_audio_to_wav(&audio_data, sample_rate, self.config.preroll_secs, &work_dir) {
+                Ok(path) => {
+                    let archived = if self.config.archive_recordings && !self.privacy_mode {
+                        batch::archive_recording(&path)
+                            .map_err(|e| eprintln!("{}", e))
+                            .ok()
+                    } else {
+                        None
+                    };
+                    // 会話履歴から辿れるのはアーカイブした方 (一時ファイルは上書きされる)
+                    self.audio_file_path = Some(archived.unwrap_or_else(|| path.clone()));
+                    self.start_background_processing(path);
+                }
+                Err(e) => {
+                    self.report_error(e.context("Failed to save audio"));
+                    self.dispatch(PipelineEvent::RecordingDiscarded);
+                }
+            }
+        }
+    }
+
+    /// 録音を始める (VAD が声を検出した / Hold-to-talk のキーが押された)
+    fn begin_recording(&mut self) {
+        if self.config.barge_in_enabled && tts::stop_speaking() {
+            println!("Barge-in: TTS stopped");
+        }
+        self.dispatch(PipelineEvent::VoiceDetected);
+        notify_sound::play(&self.config, SoundEvent::RecordingStarted);
+    }
+
+    /// Hold-to-talk: キーか MIDI パッドを押している間だけ録音し、離したら送る
+    fn update_hold_to_talk(&mut self, ctx: &egui::Context) {
+        let key_held = egui::Key::from_name(&self.config.hold_to_talk_key)
+            .is_some_and(|key| ctx.input(|i| i.key_down(key)));
+        match pipeline_controller::hold_to_talk_event(self.pipeline.state(), key_held || self.midi_talk_held) {
+            Some(PipelineEvent::VoiceDetected) => {
+                println!("Hold-to-talk pressed. Starting recording...");
+                // 押す前に拾った音は送らない
+                if let Some(recorder) = &self.audio_recorder {
+                    recorder.clear_buffer();
+                }
+                self.begin_recording();
+                self.status_message = "Recording... 離すと送ります".to_string();
+            }
+            Some(event) => {
+                self.dispatch(event);
+            }
+            None => {}
+        }
+    }
+
+    /// 録音中なら無音を待たずに、ここまでの録音を送る
+    fn send_recording_now(&mut self) {
+        if self.dispatch(PipelineEvent::SendNowPressed).is_some() {
+            println!("Send now pressed");
+        }
+    }
+
+    fn start_background_processing(&mut self, audio_path: PathBuf) {
+        let (sender, receiver) = channel();
+        self.processing_receiver = Some(receiver);
+
+        self.apply_ab_variant();
+        let mut config = self.pipeline_config();
+        config.custom_prompt = self.config.custom_prompt_for(self.last_language.as_deref()).to_string();
+        let current_preset = self.current_preset.clone();
+
+        // Take ownership of eliza_client to use in the thread
+        let eliza_client = self.take_eliza_client();
+
+        let mood = self.mood;
+        let privacy_mode = self.privacy_mode;
+        self.submit_pipeline(eliza_client, sender, move |eliza_client, sender| {
+            let _returned_client =
+                process_pipeline(audio_path.clone(), config, current_preset, eliza_client, mood, sender);
+            // ElizaClient is returned via ProcessingMessage::Complete
+            if privacy_mode {
+                // 一時ファイルの掃除を待たずに録音を消す
+                let _ = std::fs::remove_file(&audio_path);
+            }
+        });
+    }
+
+    /// パイプラインを順番待ちに入れる。始まる前に取り消されたら ElizaClient をそのまま返す
+    fn submit_pipeline(
+        &mut self,
+        eliza_client: Option<ElizaClient>,
+        sender: Sender<ProcessingMessage>,
+        job: impl FnOnce(Option<ElizaClient>, Sender<ProcessingMessage>) + Send + 'static,
+    ) {
+        self.worker.submit(move |cancelled| {
+            if cancelled {
+                let _ = sender.send(ProcessingMessage::Cancelled(eliza_client));
+            } else {
+                job(eliza_client, sender);
+            }
+        });
+    }
+
+    /// safety_stop で結果を捨てた処理から ElizaClient を取り戻す (返答などは捨てる。
+    /// 止めたときに履歴を消す設定なら消してから戻す。その間に新しい会話を始めていれば戻さない)
+    fn poll_discarded_processing(&mut self) {
+        let mut returned = Vec::new();
+        self.discarded_receivers.retain(|(receiver, wipe)| loop {
+            match receiver.try_recv() {
+                Ok(message) => {
+                    if let Some(client) = message.into_client() {
+                        returned.push((client, *wipe));
+                    }
+                }
+                Err(std::sync::mpsc::TryRecvError::Empty) => break true,
+                Err(std::sync::mpsc::TryRecvError::Disconnected) => break false,
+            }
+        });
+        for (mut client, wipe) in returned {
+            if wipe {
+                client.clear_history();
+            }
+            if self.eliza_client.is_none() && self.processing_receiver.is_none() {
+                self.eliza_client = Some(client);
+            }
+        }
+    }
+
+    /// Safety word: 即座に停止し、チャットボックスを消す (設定により履歴も消去)
+    fn safety_stop(&mut self, eliza_client: Option<ElizaClient>) {
+        println!("Safety word detected! Halting.");
+        // 処理中のリクエストの結果は破棄し、順番待ちのものは実行しない (ElizaClient は後で取り戻す)
+        if let Some(receiver) = self.processing_receiver.take() {
+            self.discarded_receivers.push((receiver, self.config.safety_word_wipe_history));
+        }
+        self.worker.cancel_all();
+        self.pending_sleep = false;
+        if eliza_client.is_some() {
+            self.eliza_client = eliza_client;
+        }
+        self.dispatch(PipelineEvent::StopPressed);
+
+        // 止める前に保留・再送待ちになっていた返答も、VRChat に戻ったあとで送らない
+        self.chatbox_buffer.clear();
+        self.chatbox_retry.clear();
+        while self.chatbox_receiver.try_recv().is_ok() {}
+        if let Err(e) = VRChatClient::new().clear_chatbox() {
+            self.toasts.error(format!("Failed to clear chatbox: {}", e));
+        }
+
+        if self.config.safety_word_wipe_history {
+            self.conversation_history.clear();
+            self.current_topic = None;
+            self.parked_clients.clear();
+            if let Some(ref mut eliza_client) = self.eliza_client {
+                // memory にも残さない
+                eliza_client.clear_history();
+            }
+            self.status_message = "🛑 停止しました (履歴を消去)".to_string();
+        } else {
+            self.status_message = "🛑 停止しました".to_string();
+        }
+    }
+
+    /// 音声コマンドを実行する (閾値の変更は calibration 同様プリセットには保存しない。
+    /// 音声プロファイルを選んでいれば、プリセットを切り替えても戻らないようにプロファイルに書く)
+    fn apply_voice_command(&mut self, command: VoiceCommand) {
+        let step = self.config.sensitivity_step;
+        self.config.start_threshold =
+            voice_command::adjust_threshold(self.config.start_threshold, command, step);
+        self.config.silence_threshold =
+            voice_command::adjust_threshold(self.config.silence_threshold, command, step);
+        self.settings.draft.start_threshold = self.config.start_threshold;
+        self.settings.draft.silence_threshold = self.config.silence_threshold;
+        self.save_audio_profile();
+
+        let label = match command {
+            VoiceCommand::SensitivityUp => "感度を上げました",
+            VoiceCommand::SensitivityDown => "感度を下げました",
+        };
+        println!(
+            "Voice command {:?}: start={:.4}, silence={:.4}",
+            command, self.config.start_threshold, self.config.silence_threshold
+        );
+        self.status_message = format!(
+            "🎚 {} (start={:.4}, silence={:.4})",
+            label, self.config.start_threshold, self.config.silence_threshold
+        );
+    }
+
+    /// Switch the VAD thresholds to a preset and save it to the current preset file
+    /// (and to the selected audio profile, which would otherwise override it on the next preset switch)
+    fn apply_vad_preset(&mut self, preset: &VadPreset) {
+        preset.apply(&mut self.config);
+        preset.apply(&mut self.settings.draft);
+        println!("VAD preset: {}", preset.label);
+        self.save_audio_profile();
+        match self.config.save_preset(&self.current_preset) {
+            Ok(_) => self.status_message = format!("🎚 感度: {}", preset.label),
+            Err(e) => self.report_error(format!("Failed to save: {}", e)),
+        }
+    }
+
+    /// Switch the speed/accuracy bundle and save it to the current preset file
+    /// (the history length and reply length also apply to the live client, keeping its history)
+    fn apply_latency_preset(&mut self, preset: &LatencyPreset) {
+        preset.apply(&mut self.config);
+        preset.apply(&mut self.settings.draft);
+        if let Some(client) = &mut self.eliza_client {
+            client.set_max_history_length(self.config.max_length_of_conversation_history);
+            client.set_max_tokens(self.config.agent_max_tokens);
+        }
+        println!("Latency preset: {}", preset.label);
+        match self.config.save_preset(&self.current_preset) {
+            Ok(_) => self.status_message = format!("⚡ 応答: {}", preset.label),
+            Err(e) => self.report_error(format!("Failed to save: {}", e)),
+        }
+    }
+
+    /// 音声モードとテキストモードを切り替えて今のプリセットに保存する (テキストモードに入るときはマイクを閉じる)
+    fn set_text_mode(&mut self, enabled: bool) {
+        if enabled
+            && !self.pipeline.is_idle()
+            && self.dispatch(PipelineEvent::StopPressed) == Some(Effect::CloseMicrophone)
+        {
+            self.start_session_summary();
+        }
+        self.config.text_mode = enabled;
+        self.settings.draft.text_mode = enabled;
+        match self.config.save_preset(&self.current_preset) {
+            Ok(_) => {
+                self.status_message = if enabled {
+                    "⌨ テキストモード (マイクは使いません)".to_string()
+                } else {
+                    "🎤 音声モード".to_string()
+                }
+            }
+            Err(e) => self.report_error(format!("Failed to save: {}", e)),
+        }
+    }
+
+    /// Send text straight to the chatbox without asking the agent
+    fn send_direct_to_chatbox(&mut self, text: String) {
+        if text.trim().is_empty() {
+            return;
+        }
+        if chatbox_paused(&self.config) {
+            self.chatbox_buffer.push(text);
+            return;
+        }
+        match VRChatClient::new().send_message(&text) {
+            Ok(_) => {
+                self.status_message = format!("Sent: {}", text);
+                self.conversation_history.push(HistoryEntry::new("You", text));
+            }
+            Err(e) if self.config.chatbox_retry_enabled => {
+                eprintln!("VRChat send failed, queued for retry: {}", e);
+                self.chatbox_retry.push(text, std::time::Instant::now());
+            }
+            Err(e) => self.report_error(AppError::from(e).context("VRChat failed")),
+        }
+    }
+
+    /// プライベートモード中なら、保存しなかったことを表示して true を返す
+    fn privacy_blocks(&mut self, what: &str) -> bool {
+        if self.privacy_mode {
+            println!("Privacy mode: {} not saved", what);
+            self.status_message = format!("🕶 プライベートモード中は{}を保存しません", what);
+        }
+        self.privacy_mode
+    }
+
+    /// 処理スレッドに渡す設定 (プライベートモード中は返答の全文を paste サービスに上げない)
+    fn pipeline_config(&self) -> Config {
+        let mut config = self.config.clone();
+        if self.privacy_mode {
+            config.paste_long_replies_enabled = false;
+        }
+        // リプレイの返答は読み上げない
+        if self.is_replaying() {
+            config.tts_enabled = false;
+            config.monitor_tts_enabled = false;
+        }
+        config
+    }
+
+    /// 処理スレッドに渡す Agent (プライベートモード中とリプレイ中は履歴を縮めるときも記憶に保存しない)
+    fn take_eliza_client(&mut self) -> Option<ElizaClient> {
+        let mut client = self.eliza_client.take();
+        if let Some(client) = &mut client {
+            client.set_memory_saves(!self.privacy_mode && !self.is_replaying());
+        }
+        client
+    }
+
+    /// Save the current history to the server-side memory without blocking the UI.
+    /// The result is reported by poll_memory_saves.
+    fn save_memory_in_background(&mut self) {
+        if self.privacy_blocks("記憶") || self.is_replaying() {
+            return;
+        }
+        let Some(client) = &self.eliza_client else {
+            return;
+        };
+        let snapshot = client.memory_snapshot();
+        let (sender, receiver) = channel();
+        self.memory_save_receivers.push(receiver);
+        self.status_message = "🧠 記憶を保存中...".to_string();
+        std::thread::spawn(move || {
+            let _ = sender.send(snapshot.save().map_err(|e| e.to_string()));
+        });
+    }
+
+    /// Report finished background memory saves
+    fn poll_memory_saves(&mut self) {
+        let mut results = Vec::new();
+        self.memory_save_receivers.retain(|receiver| match receiver.try_recv() {
+            Ok(result) => {
+                results.push(result);
+                false
+            }
+            Err(std::sync::mpsc::TryRecvError::Empty) => true,
+            Err(std::sync::mpsc::TryRecvError::Disconnected) => false,
+        });
+        for result in results {
+            match result {
+                Ok(_) => self.status_message = "🧠 記憶を保存しました".to_string(),
+                Err(e) => self.report_error(format!("Failed to save memory: {}", e)),
+            }
+        }
+    }
+
+    /// 終了処理を始める: 順番待ちのリクエストを取り消し、録音と読み上げを止め、
+    /// 再送待ちのチャットボックスを送り切る (会話と記憶の保存は drive_shutdown で続ける)
+    fn begin_shutdown(&mut self) {
+        println!("Shutting down...");
+        self.worker.cancel_all();
+        self.pending_sleep = false;
+        self.dispatch(PipelineEvent::StopPressed);
+        tts::stop_speaking();
+
+        let texts = self.chatbox_retry.drain();
+        let chatbox_flush = if texts.is_empty() || chatbox_paused(&self.config) {
+            None
+        } else {
+            let (sender, receiver) = channel();
+            std::thread::spawn(move || {
+                let client = VRChatClient::new();
+                for (i, text) in texts.iter().enumerate() {
+                    if i > 0 {
+                        std::thread::sleep(retry_queue::SEND_INTERVAL);
+                    }
+                    if let Err(e) = client.send_message(text) {
+                        eprintln!("Failed to flush chatbox message on exit: {}", e);
+                        break;
+                    }
+                }
+                let _ = sender.send(());
+            });
+            Some(receiver)
+        };
+        self.shutdown = Some(ShutdownProgress {
+            started_at: std::time::Instant::now(),
+            chatbox_flush,
+            saved: false,
+        });
+        self.drive_shutdown();
+    }
+
+    /// 終了処理を進める。処理中のリクエストが終わって ElizaClient が戻ってから、
+    /// 会話をセッションに保存し、そのあとで記憶を保存する
+    fn drive_shutdown(&mut self) {
+        let in_flight = self.processing_receiver.is_some();
+        let Some(shutdown) = &mut self.shutdown else {
+            return;
+        };
+        if let Some(receiver) = &shutdown.chatbox_flush {
+            if !matches!(receiver.try_recv(), Err(std::sync::mpsc::TryRecvError::Empty)) {
+                shutdown.chatbox_flush = None;
+            }
+        }
+        if shutdown.saved || in_flight {
+            return;
+        }
+        shutdown.saved = true;
+        if !self.conversation_history.is_empty() {
+            self.save_session_as(AUTOSAVE_SESSION_NAME.to_string());
+        }
+        self.save_memory_in_background();
+    }
+
+    /// 終了処理でまだ待っているもの (なければ空)
+    fn shutdown_pending(&self) -> Vec<&'static str> {
+        let Some(shutdown) = &self.shutdown else {
+            return Vec::new();
+        };
+        let mut pending = Vec::new();
+        if self.processing_receiver.is_some() {
+            pending.push("処理中のリクエスト");
+        }
+        if shutdown.chatbox_flush.is_some() {
+            pending.push("再送待ちのチャットボックス");
+        }
+        if self.summary_receiver.is_some() {
+            pending.push("会話の要約");
+        }
+        if !shutdown.saved || !self.memory_save_receivers.is_empty() {
+            pending.push("記憶の保存");
+        }
+        pending
+    }
+
+    /// 閉じるボタンが押されたら、残っている処理を終えてから閉じる (待つ間は進捗を出す)
+    fn handle_close_request(&mut self, ctx: &egui::Context) {
+        if ctx.input(|i| i.viewport().close_requested()) && self.shutdown.is_none() {
+            self.begin_shutdown();
+            if !self.shutdown_pending().is_empty() {
+                ctx.send_viewport_cmd(egui::ViewportCommand::CancelClose);
+            }
+        }
+        let Some(started_at) = self.shutdown.as_ref().map(|s| s.started_at) else {
+            return;
+        };
+        self.drive_shutdown();
+        let pending = self.shutdown_pending();
+        if pending.is_empty() || started_at.elapsed() >= SHUTDOWN_WAIT {
+            ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+            return;
+        }
+        egui::Window::new("終了中…")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.spinner();
+                    ui.label("終了の前に保存と送信をしています");
+                });
+                for item in pending {
+                    ui.weak(format!("・{}", item));
+                }
+            });
+    }
+
+    /// Fetch the server-side memory and pass it to the agent as a context note
+    fn load_memory(&mut self) {
+        if self.memory_load_receiver.is_some() || !self.agent_configured() {
+            return;
+        }
+        let fetcher = self.new_eliza_client();
+        let (sender, receiver) = channel();
+        self.memory_load_receiver = Some(receiver);
+        self.status_message = "📥 記憶を読み込み中...".to_string();
+        std::thread::spawn(move || {
+            let _ = sender.send(fetcher.fetch_memory());
+        });
+    }
+
+    /// 読み込んだ記憶を Agent に渡す (処理中は Agent が戻ってくるまで待つ)
+    fn poll_memory_load(&mut self) {
+        if self.processing_receiver.is_some() {
+            return;
+        }
+        let Some(receiver) = &self.memory_load_receiver else {
+            return;
+        };
+        let result = match receiver.try_recv() {
+            Ok(result) => result,
+            Err(std::sync::mpsc::TryRecvError::Empty) => return,
+            Err(std::sync::mpsc::TryRecvError::Disconnected) => {
+                self.memory_load_receiver = None;
+                return;
+            }
+        };
+        self.memory_load_receiver = None;
+        match result {
+            Ok(memory) if memory.is_empty() => {
+                self.status_message = "サーバーに記憶はありません".to_string();
+            }
+            Ok(memory) => {
+                let note = format!("以前の会話で覚えていること:\n{}", memory);
+                if self.eliza_client.is_none() {
+                    self.resume_eliza_client();
+                }
+                if let Some(client) = &mut self.eliza_client {
+                    client.set_context_note("memory", note);
+                }
+                self.status_message = format!("📥 記憶を読み込みました ({} 文字)", memory.chars().count());
+            }
+            Err(e) => self.report_error(e),
+        }
+    }
+
+    /// Resend chatbox messages that failed earlier (backoff, give up after chatbox_retry_secs)
+    fn retry_chatbox_messages(&mut self) {
+        let now = std::time::Instant::now();
+        let max_age = std::time::Duration::from_secs(self.config.chatbox_retry_secs);
+        for text in self.chatbox_retry.expire(now, max_age) {
+            self.report_error(format!("VRChat への再送を諦めました: {}", text));
+        }
+        if chatbox_paused(&self.config) {
+            return;
+        }
+        let Some(text) = self.chatbox_retry.due(now) else {
+            return;
+        };
+        match VRChatClient::new().send_message(text) {
+            Ok(_) => {
+                println!("✓ VRChat retry succeeded");
+                self.chatbox_retry.mark_sent(now);
+            }
+            Err(e) => {
+                eprintln!("✗ VRChat retry failed: {}", e);
+                self.chatbox_retry.mark_failed(now);
+            }
+        }
+    }
+
+    /// Quick reply button: send directly, or through the agent when configured
+    fn send_quick_reply(&mut self, text: String) {
+        if self.config.quick_replies_bypass_agent {
+            self.send_direct_to_chatbox(text);
+        } else if self.processing_receiver.is_none() {
+            self.send_text_message(text);
+        }
+    }
+
+    fn send_text_message(&mut self, text: String) {
+        if text.trim().is_empty() {
+            return;
+        }
+        let text = if self.config.pii_redaction_enabled {
+            redact::redact(&text).0
+        } else {
+            text
+        };
+
+        if matches_safety_word(&text, &self.config.safety_word) {
+            self.safety_stop(None);
+            return;
+        }
+
+        // Ensure ElizaClient is initialized
+        if self.eliza_client.is_none() && self.agent_configured() {
+            self.resume_eliza_client();
+        }
+
+        // Add to conversation history immediately
+        self.conversation_history.push(HistoryEntry::new("You", text.clone()));
+        self.silence_follow_up_armed = true;
+        self.status_message = "Sending to Eliza...".to_string();
+
+        let (sender, receiver) = channel();
+        self.processing_receiver = Some(receiver);
+
+        self.apply_ab_variant();
+        let config = self.pipeline_config();
+        let eliza_client = self.take_eliza_client();
+        let mood = self.mood;
+
+        self.submit_pipeline(eliza_client, sender, move |eliza_client, sender| {
+            text_pipeline(text, config, eliza_client, mood, sender, false);
+        });
+    }
+
+    /// 会話の途中で沈黙が続いていたら、直前の話題について Agent から質問する
+    fn check_silence_follow_up(&mut self) {
+        if !self.silence_follow_up_armed
+            || self.pipeline.state() != AppState::Monitoring
+            || self.processing_receiver.is_some()
+        {
+            return;
+        }
+        let (Some(recorder), Some(client)) = (&self.audio_recorder, &self.eliza_client) else {
+            return;
+        };
+        if !silence_follow_up_due(
+            self.config.silence_follow_up_secs,
+            recorder.get_silence_duration(),
+            client.secs_since_last_reply(),
+        ) {
+            return;
+        }
+        // 1 回聞いて返事がなければ、次に話しかけられるまで待つ
+        self.silence_follow_up_armed = false;
+        println!("Silence for {}s, asking a follow-up question", self.config.silence_follow_up_secs);
+        self.status_message = "沈黙が続いたので話しかけています...".to_string();
+
+        let (sender, receiver) = channel();
+        self.processing_receiver = Some(receiver);
+
+        let prompt = self.config.silence_follow_up_prompt.clone();
+        let config = self.pipeline_config();
+        let mood = self.mood;
+        let eliza_client = self.take_eliza_client();
+        self.submit_pipeline(eliza_client, sender, move |eliza_client, sender| {
+            text_pipeline(prompt, config, eliza_client, mood, sender, true);
+        });
+    }
+}
+
+/// 沈黙への質問をする頃か (secs が 0 なら無効。返答の読み上げ中などは沈黙に数えない)
+fn silence_follow_up_due(secs: u64, silence: std::time::Duration, secs_since_reply: Option<u64>) -> bool {
+    secs > 0 && silence.as_secs() >= secs && secs_since_reply.is_some_and(|elapsed| elapsed >= secs)
+}
+
+/// 句読点・空白・大文字小文字を無視して safety word を含むか判定する
+fn matches_safety_word(text: &str, safety_word: &str) -> bool {
+    fn normalize(s: &str) -> String {
+        s.chars()
+            .filter(|c| c.is_alphanumeric())
+            .flat_map(|c| c.to_lowercase())
+            .collect()
+    }
+    let word = normalize(safety_word);
+    !word.is_empty() && normalize(text).contains(&word)
+}
+
+/// Send the message to the agent. In compare mode the same context is also sent
+/// to the second backend in parallel and the selected reply is kept in history.
+fn ask_agent(
+    config: &Config,
+    client: &mut ElizaClient,
+    text: &str,
+    sender: &Sender<ProcessingMessage>,
+) -> Result<(String, bool), ElizaError> {
+    if !config.compare_mode_enabled || config.compare_model.is_empty() {
+        if config.agent_streaming_enabled {
+            return stream_agent_reply(config, client, text, sender);
+        }
+        return client.send_message(text);
+    }
+
+    let compare_url = if config.compare_server_url.is_empty() {
+        config.agent_server_url.clone()
+    } else {
+        config.compare_server_url.clone()
+    };
+    let compare_client = ElizaClient::new(
+        compare_url,
+        config.compare_model.clone(),
+        config.max_length_of_conversation_history,
+    );
+    let messages = client.pending_messages(text);
+
+    let (primary, secondary) = std::thread::scope(|scope| {
+        let handle = scope.spawn(|| compare_client.complete(messages));
+        let primary = client.send_message(text);
+        let secondary = handle.join().unwrap_or_else(|_| {
+            Err(ElizaError::NetworkError("Compare request panicked".to_string()))
+        });
+        (primary, secondary)
+    });
+
+    let (reply_a, sleep) = primary?;
+    let (reply_b, sent_b) = match secondary {
+        Ok(reply) => (reply, config.compare_use_secondary),
+        Err(e) => {
+            eprintln!("Compare backend failed: {}", e);
+            (format!("❌ {}", e), false)
+        }
+    };
+    if sent_b {
+        client.replace_last_reply(&reply_b);
+    }
+
+    let _ = sender.send(ProcessingMessage::CompareComplete(Comparison {
+        model_a: config.agent_model.clone(),
+        reply_a: reply_a.clone(),
+        model_b: config.compare_model.clone(),
+        reply_b: reply_b.clone(),
+        sent_b,
+    }));
+
+    Ok((if sent_b { reply_b } else { reply_a }, sleep))
+}
+
+/// Stream the reply: show it in the UI as it arrives and raise the typing indicator early
+fn stream_agent_reply(
+    config: &Config,
+    client: &mut ElizaClient,
+    text: &str,
+    sender: &Sender<ProcessingMessage>,
+) -> Result<(String, bool), ElizaError> {
+    const PARTIAL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(150);
+
+    let vrchat = VRChatClient::new();
+    let show_typing = !chatbox_paused(config);
+    let mut typing = false;
+    let mut partial = String::new();
+    let mut last_partial = std::time::Instant::now();
+    let result = client.send_message_streaming(text, &mut |delta| {
+        if show_typing && !typing {
+            typing = vrchat.set_typing(true).is_ok();
+        }
+        partial.push_str(delta);
+        if last_partial.elapsed() >= PARTIAL_INTERVAL {
+            let _ = sender.send(ProcessingMessage::ElizaPartial(partial.clone()));
+            last_partial = std::time::Instant::now();
+        }
+    });
+    // 返答を送るときは typing=false も同じパケットで送る
+    if typing && result.is_err() {
+        let _ = vrchat.set_typing(false);
+    }
+    result
+}
+
+/// Prefix the reply with the topic line when the topic is displayed in the chatbox
+fn chatbox_reply_text(config: &Config, client: &ElizaClient, reply: &str, transcribed: &str) -> String {
+    let topic_line = match client.topic() {
+        Some(topic) if config.status_topic_enabled && config.status_topic_osc_address.is_empty() => {
+            Some(format!("📌 {}", topic))
+        }
+        _ => None,
+    };
+    let body = match config.effective_quote_mode() {
+        _ if transcribed.is_empty() => reply.to_string(),
+        "merged" => format!("{}\n{}", config.quote(transcribed), reply),
+        "dialogue" => {
+            let budget = vrchat::CHATBOX_MAX_CHARS
+                .saturating_sub(topic_line.as_ref().map(|l| l.chars().count() + 1).unwrap_or(0));
+            vrchat::format_dialogue(&config.dialogue_user_label, transcribed, &dialogue_agent_label(config), reply, budget)
+        }
+        _ => reply.to_string(),
+    };
+    match topic_line {
+        Some(line) => format!("{}\n{}", line, body),
+        None => body,
+    }
+}
+
+/// チャットボックスで返答が切れるなら全文を paste サービスに上げ、URL を付けた表示に差し替える
+fn attach_full_text_link(config: &Config, chatbox_text: String, reply: &str) -> String {
+    let truncated = chatbox_text.chars().count() > vrchat::CHATBOX_MAX_CHARS || !chatbox_text.contains(reply);
+    if !config.paste_long_replies_enabled || !truncated {
+        return chatbox_text;
+    }
+    match paste::upload(&config.paste_endpoint, reply) {
+        Ok(url) => {
+            println!("Full reply uploaded: {}", url);
+            paste::with_link(&chatbox_text, &url, vrchat::CHATBOX_MAX_CHARS)
+        }
+        Err(e) => {
+            eprintln!("Paste upload failed, sending truncated reply: {}", e);
+            chatbox_text
+        }
+    }
+}
+
+/// dialogue モードでの Agent 名 (未設定なら Agent Names の先頭)
+fn dialogue_agent_label(config: &Config) -> String {
+    if !config.dialogue_agent_label.is_empty() {
+        return config.dialogue_agent_label.clone();
+    }
+    intent::parse_agent_names(&config.agent_names)
+        .into_iter()
+        .next()
+        .unwrap_or_else(|| "Agent".to_string())
+}
+
+/// Whisper の prompt に付け足す直前の会話 (直前の発話、設定によっては Agent の返答も)
+fn whisper_context(config: &Config, client: &ElizaClient) -> String {
+    let history = client.history();
+    let mut context = Vec::new();
+    if let Some(user_idx) = history.iter().rposition(|m| m.role == "user") {
+        context.push(history[user_idx].content.as_str());
+        if config.whisper_context_include_agent {
+            if let Some(reply) = history[user_idx..].iter().find(|m| m.role == "assistant") {
+                context.push(reply.content.as_str());
+            }
+        }
+    }
+    context.join("\n")
+}
+
+/// 発話が Agent に向けたものか判定する (ローカル判定 → 必要なら LLM)
+fn classify_intent(
+    config: &Config,
+    openai_client: &OpenAIClient,
+    text: &str,
+    client: Option<&ElizaClient>,
+) -> Intent {
+    let names = intent::parse_agent_names(&config.agent_names);
+    let in_conversation = client
+        .and_then(|c| c.secs_since_last_reply())
+        .is_some_and(|secs| secs <= intent::FOLLOW_UP_SECS);
+    let local = intent::classify_local(text, &names, in_conversation);
+    if local == Intent::Noise || !config.intent_use_llm {
+        return local;
+    }
+
+    match openai_client.chat_completion(
+        &config.intent_model,
+        &intent::classifier_instruction(&names),
+        text,
+    ) {
+        Ok(label) => intent::parse_intent(&label),
+        Err(e) => {
+            eprintln!("Intent classification failed: {}", e);
+            local
+        }
+    }
+}
+
+/// Update the mood from the user's utterance, inject it into the prompt and
+/// send it to the avatar expression parameter
+/// Tell the agent to reply in the language the user spoke (keeps the last one if unknown)
+fn update_reply_language(config: &Config, language: Option<&str>, client: &mut ElizaClient) {
+    if !config.auto_reply_language_enabled {
+        client.set_context_note("language", String::new());
+        return;
+    }
+    if let Some(code) = language {
+        let name = openai::language_name(code);
+        println!("Reply language: {}", code);
+        client.set_context_note(
+            "language",
+            format!("ユーザーは{}で話しています。返答も{}で書いてください。", name, name),
+        );
+    }
+}
+
+/// VRChat のログから拾った最近のチャットを文脈として添える
+fn update_world_chat(config: &Config, client: &mut ElizaClient) {
+    let note = if config.world_chat_context_enabled {
+        let max_age = std::time::Duration::from_secs(config.world_chat_minutes * 60);
+        vrchat_log::chat_note(&vrchat_log::recent_chat(max_age))
+    } else {
+        String::new()
+    };
+    client.set_context_note("world_chat", note);
+}
+
+/// 今のインスタンスにいるプレイヤーのメモを文脈として添える
+fn update_player_notes(config: &Config, client: &mut ElizaClient) {
+    let note = if config.player_notes_enabled {
+        vrchat_log::player_notes_note(&config.player_notes, &vrchat_log::present_players())
+    } else {
+        String::new()
+    };
+    client.set_context_note("player_notes", note);
+}
+
+fn update_mood(
+    config: &Config,
+    mut mood: MoodState,
+    text: &str,
+    client: &mut ElizaClient,
+    sender: &Sender<ProcessingMessage>,
+) {
+    if !config.mood_enabled {
+        client.set_context_note("mood", String::new());
+        return;
+    }
+
+    let previous = mood.mood();
+    mood.update(text);
+    let current = mood.mood();
+    client.set_context_note("mood", current.prompt_note());
+    if current != previous {
+        println!("Mood: {:?} → {:?}", previous, current);
+        if !config.mood_osc_address.is_empty() {
+            // 返答と同じパケットで送り、表情と返答が同時に切り替わるようにする
+            vrchat::queue_parameter(&config.mood_osc_address, vec![OscType::Int(current.expression_value())]);
+        }
+    }
+    let _ = sender.send(ProcessingMessage::MoodChanged(mood));
+}
+
+/// Strip `[sfx:name]` tags from the reply and play the mapped sound effects
+fn play_sfx_tags(config: &Config, reply: String) -> String {
+    if !config.soundboard_enabled {
+        return reply;
+    }
+    let (cleaned, tags) = soundboard::extract_sfx_tags(&reply);
+    soundboard::play_tags(config, &tags);
+    cleaned
+}
+
+/// VRChat が起動していないならチャットボックスに送らない
+fn chatbox_paused(config: &Config) -> bool {
+    config.pause_when_vrchat_closed && !vrchat::is_vrchat_running()
+}
+
+/// Do-not-disturb キーワードを含む発話・返答ならその理由
+fn dnd_reason(config: &Config, transcribed: &str, reply: &str) -> Option<String> {
+    dnd::find_keyword(&config.dnd_keywords, transcribed)
+        .or_else(|| dnd::find_keyword(&config.dnd_keywords, reply))
+        .map(|keyword| format!("キーワード「{}」", keyword))
+}
+
+/// Send the agent reply to the chatbox, or buffer it while VRChat is not running
+///
+/// `reply` is the full reply; it is uploaded for a link only once the text is actually going to be sent
+fn send_reply_to_chatbox(
+    config: &Config,
+    text: &str,
+    reply: &str,
+    transcribed: &str,
+    sender: &Sender<ProcessingMessage>,
+) -> Result<(), VRChatError> {
+    if let Some(reason) = dnd_reason(config, transcribed, text) {
+        println!("Do-not-disturb ({}), reply shown in UI only", reason);
+        let _ = VRChatClient::new().finish_without_message();
+        let _ = sender.send(ProcessingMessage::ChatboxSuppressed(reason));
+        return Ok(());
+    }
+    if chatbox_paused(config) {
+        println!("VRChat is not running, reply buffered");
+        let _ = sender.send(ProcessingMessage::ChatboxBuffered(text.to_string()));
+        return Ok(());
+    }
+    if let Err(reason) = limiter::allow_reply(config) {
+        println!("Reply limited ({}), shown in UI only", reason);
+        let _ = VRChatClient::new().finish_without_message();
+        let _ = sender.send(ProcessingMessage::ChatboxSuppressed(reason));
+        return Ok(());
+    }
+    // UI にだけ表示する返答は公開の paste サービスに上げない
+    let text = attach_full_text_link(config, text.to_string(), reply);
+    let text = text.as_str();
+    let client = VRChatClient::new();
+    let result = if config.chatbox_paginate_enabled {
+        client.send_paginated(text, config.chatbox_reading_cps)
+    } else {
+        client.send_message(text)
+    };
+    if result.is_ok() && config.chatbox_clear_enabled {
+        client.clear_chatbox_after(std::time::Duration::from_secs(config.chatbox_clear_secs));
+    }
+    match result {
+        Err(e) if config.chatbox_retry_enabled => {
+            eprintln!("VRChat send failed, queued for retry: {}", e);
+            let _ = sender.send(ProcessingMessage::ChatboxFailed(text.to_string()));
+            Ok(())
+        }
+        result => result,
+    }
+}
+
+/// Regenerate the status topic every N turns
+fn update_status_topic(
+    config: &Config,
+    client: &mut ElizaClient,
+    sender: &Sender<ProcessingMessage>,
+) {
+    if !config.status_topic_enabled
+        || config.status_topic_interval_turns == 0
+        || !client
+            .turn_count()
+            .is_multiple_of(config.status_topic_interval_turns)
+    {
+        return;
+    }
+
+    match client.update_topic() {
+        Ok(topic) => {
+            if !config.status_topic_osc_address.is_empty() {
+                let vrchat = VRChatClient::new();
+                if let Err(e) = vrchat.send_osc(
+                    &config.status_topic_osc_address,
+                    vec![OscType::String(topic.clone())],
+                ) {
+                    eprintln!("✗ VRChat topic send failed: {}", e);
+                }
+            }
+            let _ = sender.send(ProcessingMessage::TopicUpdated(topic));
+        }
+        Err(e) => {
+            eprintln!("Failed to update topic: {}", e);
+        }
+    }
+}
 
 fn process_pipeline(
     audio_path: PathBuf,
-    openai_key: String,
-    whisper_model: String,
-    custom_prompt: String,
+    config: Config,
+    current_preset: String,
     eliza_client: Option<ElizaClient>,
+    mood: MoodState,
     sender: Sender<ProcessingMessage>,
 ) -> Option<ElizaClient> {
     // Step 1: Transcribe
     let _ = sender.send(ProcessingMessage::TranscriptionInProgress);
 
-    let openai_client = OpenAIClient::new(openai_key, whisper_model, custom_prompt);
-    let transcribed_text = match openai_client.transcribe_audio(&audio_path) {
-        Ok(text) => text,
+    let mut openai_client = OpenAIClient::new(
+        config.openai_api_key.clone(),
+        config.whisper_model.clone(),
+        config.custom_prompt.clone(),
+    )
+    .with_language_detection(
+        config.language_routing_enabled || config.auto_reply_language_enabled || config.language_filter_enabled,
+    )
+    .with_word_timestamps(config.karaoke_enabled)
+    .with_timeout(config.stt_timeout_secs);
+    if config.whisper_context_enabled {
+        if let Some(client) = &eliza_client {
+            openai_client = openai_client
+                .with_context(&whisper_context(&config, client), config.whisper_context_chars);
+        }
+    }
+    let stt_started = std::time::Instant::now();
+    let transcription = match stt::transcribe(&config, &openai_client, &audio_path) {
+        Ok(transcription) => transcription,
         Err(e) => {
             let _ = sender.send(ProcessingMessage::Error(
-                format!("Transcription failed: {}", e),
+                e.context("Transcription failed"),
                 eliza_client,
             ));
             return None;
         }
     };
+    let mut transcribed_text = transcription.text;
+
+    // Step 1.1: Safety word check (before anything is sent anywhere)
+    if matches_safety_word(&transcribed_text, &config.safety_word) {
+        let _ = sender.send(ProcessingMessage::SafetyStop(eliza_client));
+        return None;
+    }
+
+    // Step 1.15: Voice commands (not sent to the agent)
+    if config.voice_commands_enabled {
+        if let Some(command) = voice_command::parse_voice_command(&transcribed_text) {
+            let _ = sender.send(ProcessingMessage::VoiceCommand(command, eliza_client));
+            return None;
+        }
+    }
+
+    // Step 1.155: Discard speech in other languages (TV or music playing nearby)
+    if config.language_filter_enabled {
+        let language = transcription
+            .language
+            .clone()
+            .or_else(|| openai::detect_language_from_text(&transcribed_text));
+        if !config.accepts_language(language.as_deref()) {
+            println!("Skipping speech in {:?}: {}", language, transcribed_text);
+            let _ = sender.send(ProcessingMessage::Ignored(transcribed_text, Intent::OtherLanguage, eliza_client));
+            return None;
+        }
+    }
+
+    // Step 1.16: Redact personal info (before anything is sent to the LLM or the chatbox)
+    if config.pii_redaction_enabled {
+        let (redacted, count) = redact::redact(&transcribed_text);
+        if count > 0 {
+            println!("Redacted {} item(s) from the transcription", count);
+            transcribed_text = redacted;
+        }
+    }
+
+    // Step 1.165: Instruction-like speech from bystanders (quote it or skip it)
+    let injection = config.injection_guard != "off" && injection_guard::looks_like_injection(&transcribed_text);
+    if injection {
+        println!("Instruction-like speech detected ({}): {}", config.injection_guard, transcribed_text);
+        if config.injection_guard == "ignore" {
+            let _ = sender.send(ProcessingMessage::Ignored(transcribed_text, Intent::Injection, eliza_client));
+            return None;
+        }
+    }
+
+    // Step 1.17: Clean up the transcription with a cheap LLM (失敗したらそのまま使う)
+    if config.transcript_cleanup_enabled && !transcribed_text.trim().is_empty() {
+        match openai_client.cleanup_transcription(
+            &transcribed_text,
+            &config.transcript_cleanup_model,
+            &config.transcript_cleanup_prompt,
+        ) {
+            Ok(cleaned) => transcribed_text = cleaned,
+            Err(e) => eprintln!("Transcription cleanup failed: {}", e),
+        }
+    }
+
+    // Step 1.18: Only reply to speech directed at the agent
+    if config.intent_filter_enabled {
+        let intent = classify_intent(&config, &openai_client, &transcribed_text, eliza_client.as_ref());
+        if intent != Intent::Directed {
+            println!("Skipping {} speech: {}", intent.label(), transcribed_text);
+            let _ = sender.send(ProcessingMessage::Ignored(transcribed_text, intent, eliza_client));
+            return None;
+        }
+    }
+
+    // Step 1.2: Route to another preset by detected language
+    if config.language_routing_enabled {
+        let route = transcription
+            .language
+            .as_deref()
+            .and_then(|lang| config.preset_for_language(lang));
+        if let Some(preset) = route {
+            if preset != current_preset {
+                println!(
+                    "Language {:?} → preset {}",
+                    transcription.language, preset
+                );
+                let _ = sender.send(ProcessingMessage::RouteToPreset(
+                    preset,
+                    transcribed_text,
+                    eliza_client,
+                ));
+                return None;
+            }
+        }
+    }
 
+    let stt_meta = TurnMeta {
+        model: stt_model(&config),
+        language: transcription
+            .language
+            .clone()
+            .or_else(|| openai::detect_language_from_text(&transcribed_text)),
+        stt_ms: Some(stt_started.elapsed().as_millis() as u64),
+        ..TurnMeta::default()
+    };
     let _ = sender.send(ProcessingMessage::TranscriptionComplete(
         transcribed_text.clone(),
+        stt_meta,
     ));
 
     // Step 1.5: Send transcribed text to VRChat (as quote)
     println!("===== VRChat Sending (Transcription) =====");
-    let quoted_text = format!("> {}", transcribed_text);
-    let vrchat = VRChatClient::new();
-    match vrchat.send_message(&quoted_text) {
-        Ok(_) => {
-            println!("✓ VRChat transcription sent successfully");
-        }
-        Err(e) => {
-            eprintln!("✗ VRChat transcription send failed: {}", e);
-            // Don't return error - continue to Eliza step
+    let quoted_text = config.quote(&transcribed_text);
+    let mut karaoke_handle = None;
+    if config.effective_quote_mode() != "separate" {
+        println!("Quote mode is {}, transcription not sent separately", config.effective_quote_mode());
+    } else if dnd::find_keyword(&config.dnd_keywords, &transcribed_text).is_some() {
+        println!("Do-not-disturb keyword detected, transcription not sent");
+    } else if chatbox_paused(&config) {
+        println!("VRChat is not running, transcription not sent");
+    } else if config.karaoke_enabled && !transcription.words.is_empty() {
+        // 返答を待つ間に単語のタイミングで少しずつ表示する
+        let frames = karaoke::build_frames(&transcribed_text, &transcription.words, config.karaoke_replay_secs);
+        let quote_config = config.clone();
+        karaoke_handle = Some(karaoke::play(frames, move |text| quote_config.quote(text)));
+    } else {
+        let vrchat = VRChatClient::new();
+        match vrchat.send_message(&quoted_text) {
+            Ok(_) => {
+                println!("✓ VRChat transcription sent successfully");
+            }
+            Err(e) => {
+                eprintln!("✗ VRChat transcription send failed: {}", e);
+                // Don't return error - continue to Eliza step
+            }
         }
     }
 
@@ -458,25 +2671,43 @@ fn process_pipeline(
 
     if eliza_client.is_none() {
         let _ = sender.send(ProcessingMessage::Error(
-            "Eliza client not initialized".to_string(),
+            AppError::from("Eliza client not initialized"),
             None,
         ));
         return None;
     }
 
     let mut client = eliza_client.unwrap();
-    let (eliza_response, sleep) = match client.send_message(&transcribed_text) {
+    update_mood(&config, mood, &transcribed_text, &mut client, &sender);
+    update_reply_language(&config, transcription.language.as_deref(), &mut client);
+    update_world_chat(&config, &mut client);
+    update_player_notes(&config, &mut client);
+    let agent_text = if injection {
+        injection_guard::escape(&transcribed_text)
+    } else {
+        transcribed_text.clone()
+    };
+    let agent_started = std::time::Instant::now();
+    let tokens_in = estimate_request_tokens(&client, &agent_text);
+    let (eliza_response, sleep) = match health::measure(Backend::Chat, || {
+        ask_agent(&config, &mut client, &agent_text, &sender)
+    }) {
         Ok(result) => result,
         Err(e) => {
             let _ = sender.send(ProcessingMessage::Error(
-                format!("Eliza failed: {}", e),
+                AppError::from(e).context("Eliza failed"),
                 Some(client),
             ));
             return None;
         }
     };
 
-    let _ = sender.send(ProcessingMessage::ElizaComplete(eliza_response.clone(), sleep));
+    let eliza_response = play_sfx_tags(&config, eliza_response);
+    let meta = agent_meta(&config, agent_started, tokens_in, &eliza_response);
+    let _ = sender.send(ProcessingMessage::ElizaComplete(eliza_response.clone(), sleep, meta));
+    if dnd_reason(&config, &transcribed_text, &eliza_response).is_none() {
+        tts::speak(&config, &eliza_response);
+    }
 
     // Step 3: Send to VRChat
     println!("===== VRChat Sending =====");
@@ -484,218 +2715,496 @@ fn process_pipeline(
     let preview: String = eliza_response.chars().take(50).collect();
     println!("Response preview: {:?}...", preview);
 
-    let vrchat = VRChatClient::new();
-    match vrchat.send_message(eliza_response.as_str()) {
+    let chatbox_text = chatbox_reply_text(&config, &client, &eliza_response, &transcribed_text);
+    if let Some(handle) = karaoke_handle {
+        // カラオケ表示が終わる前に返答で上書きしない
+        let _ = handle.join();
+    }
+    match send_reply_to_chatbox(&config, &chatbox_text, &eliza_response, &transcribed_text, &sender) {
         Ok(_) => {
             println!("✓ VRChat message sent successfully");
         }
         Err(e) => {
             eprintln!("✗ VRChat send failed: {}", e);
             let _ = sender.send(ProcessingMessage::Error(
-                format!("VRChat failed: {}", e),
+                AppError::from(e).context("VRChat failed"),
                 Some(client),
             ));
             return None;
         }
     }
 
+    update_status_topic(&config, &mut client, &sender);
+
     let _ = sender.send(ProcessingMessage::Complete(Some(client)));
     None
 }
 
+/// 書き起こしに使ったモデル (フォールバックした場合も主バックエンドのもの)
+fn stt_model(config: &Config) -> String {
+    match config.stt_backend.as_str() {
+        "local" => format!("whisper.cpp {}", config.local_whisper_model_size),
+        _ => config.whisper_model.clone(),
+    }
+}
+
+/// text を送るときのリクエスト全体のトークン数の見積もり
+fn estimate_request_tokens(client: &ElizaClient, text: &str) -> usize {
+    client
+        .pending_messages(text)
+        .iter()
+        .map(|m| eliza::estimate_tokens(&m.content))
+        .sum()
+}
+
+fn agent_meta(config: &Config, started: std::time::Instant, tokens_in: usize, reply: &str) -> TurnMeta {
+    TurnMeta {
+        model: config.agent_model.clone(),
+        agent_ms: Some(started.elapsed().as_millis() as u64),
+        tokens_in: Some(tokens_in),
+        tokens_out: Some(eliza::estimate_tokens(reply)),
+        ..TurnMeta::default()
+    }
+}
+
+/// テキストを Agent に送り、返答を読み上げてチャットボックスに送る。
+/// follow_up なら text は沈黙が続いたときに話しかけさせる指示で、その回だけの system メッセージとして送り、
+/// チャットボックスにも会話履歴にも残さない
 fn text_pipeline(
     text: String,
+    config: Config,
     eliza_client: Option<ElizaClient>,
+    mood: MoodState,
     sender: Sender<ProcessingMessage>,
+    follow_up: bool,
 ) {
     let _ = sender.send(ProcessingMessage::ElizaInProgress);
 
     if eliza_client.is_none() {
         let _ = sender.send(ProcessingMessage::Error(
-            "Eliza client not initialized".to_string(),
+            AppError::from("Eliza client not initialized"),
             None,
         ));
         return;
     }
 
+    let mut client = eliza_client.unwrap();
+    let text = if follow_up {
+        client.set_transient_note(text);
+        String::new()
+    } else {
+        text
+    };
+
     // Send quoted text to VRChat
-    let quoted_text = format!("> {}", text);
-    let vrchat = VRChatClient::new();
-    if let Err(e) = vrchat.send_message(&quoted_text) {
-        eprintln!("VRChat text send failed: {}", e);
+    let quoted_text = config.quote(&text);
+    if !text.is_empty()
+        && config.effective_quote_mode() == "separate"
+        && !chatbox_paused(&config)
+        && dnd::find_keyword(&config.dnd_keywords, &text).is_none()
+    {
+        let vrchat = VRChatClient::new();
+        if let Err(e) = vrchat.send_message(&quoted_text) {
+            eprintln!("VRChat text send failed: {}", e);
+        }
     }
 
-    let mut client = eliza_client.unwrap();
-    let (eliza_response, sleep) = match client.send_message(&text) {
+    if !follow_up {
+        update_mood(&config, mood, &text, &mut client, &sender);
+        update_reply_language(&config, openai::detect_language_from_text(&text).as_deref(), &mut client);
+    }
+    update_world_chat(&config, &mut client);
+    update_player_notes(&config, &mut client);
+    let agent_started = std::time::Instant::now();
+    let tokens_in = estimate_request_tokens(&client, &text);
+    let (eliza_response, sleep) = match health::measure(Backend::Chat, || {
+        ask_agent(&config, &mut client, &text, &sender)
+    }) {
         Ok(result) => result,
         Err(e) => {
             let _ = sender.send(ProcessingMessage::Error(
-                format!("Eliza failed: {}", e),
+                AppError::from(e).context("Eliza failed"),
                 Some(client),
             ));
             return;
         }
     };
 
-    let _ = sender.send(ProcessingMessage::ElizaComplete(eliza_response.clone(), sleep));
+    let eliza_response = play_sfx_tags(&config, eliza_response);
+    let meta = agent_meta(&config, agent_started, tokens_in, &eliza_response);
+    let _ = sender.send(ProcessingMessage::ElizaComplete(eliza_response.clone(), sleep, meta));
+    if dnd_reason(&config, &text, &eliza_response).is_none() {
+        tts::speak(&config, &eliza_response);
+    }
 
-    let vrchat = VRChatClient::new();
-    match vrchat.send_message(eliza_response.as_str()) {
+    let chatbox_text = chatbox_reply_text(&config, &client, &eliza_response, &text);
+    match send_reply_to_chatbox(&config, &chatbox_text, &eliza_response, &text, &sender) {
         Ok(_) => {
             println!("VRChat message sent successfully");
         }
         Err(e) => {
             eprintln!("VRChat send failed: {}", e);
             let _ = sender.send(ProcessingMessage::Error(
-                format!("VRChat failed: {}", e),
+                AppError::from(e).context("VRChat failed"),
                 Some(client),
             ));
             return;
         }
     }
 
+    update_status_topic(&config, &mut client, &sender);
+
     let _ = sender.send(ProcessingMessage::Complete(Some(client)));
 }
 
+/// 終了時に送信と保存を待つ最長時間
+const SHUTDOWN_WAIT: std::time::Duration = std::time::Duration::from_secs(5);
+
 impl eframe::App for ElizaAgentApp {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        if ctx.input_mut(|i| i.consume_shortcut(&ui::start_button::TEXT_MODE_SHORTCUT)) {
+            self.set_text_mode(!self.config.text_mode);
+        }
+        // 録音中以外は Ctrl+Enter をテキスト入力の送信に残す
+        if self.pipeline.state() == AppState::Recording
+            && ctx.input_mut(|i| i.consume_shortcut(&ui::start_button::SEND_NOW_SHORTCUT))
+        {
+            self.send_recording_now();
+        }
+
         // Check for processing messages
         if let Some(receiver) = &self.processing_receiver {
             if let Ok(message) = receiver.try_recv() {
                 match message {
                     ProcessingMessage::TranscriptionInProgress => {
                         self.status_message = "Transcribing audio...".to_string();
+                        rpc::notify_event("transcribing", "", &self.current_preset);
                     }
-                    ProcessingMessage::TranscriptionComplete(text) => {
+                    ProcessingMessage::TranscriptionComplete(text, meta) => {
                         self.status_message = format!("Transcribed: {}", text);
+                        self.fire_hook(HookEvent::TranscriptionComplete, &text);
+                        if meta.language.is_some() {
+                            self.last_language = meta.language.clone();
+                        }
+                        let meta = TurnMeta {
+                            preset: self.current_preset.clone(),
+                            audio_path: self.audio_file_path.clone(),
+                            self_voice_ratio: self.recording_self_voice,
+                            ..meta
+                        };
+                        // 録音中ほとんど自分の声が検出されていなければ、マイクが拾った他の人の声とみなす
+                        let role = match self.recording_self_voice {
+                            Some(ratio) if ratio < MIN_SELF_VOICE_RATIO => "Others",
+                            _ => "You",
+                        };
+                        self.silence_follow_up_armed = role == "You";
+                        self.conversation_history
+                            .push(HistoryEntry::new(role, text.clone()).with_meta(meta));
+                    }
+                    ProcessingMessage::ElizaInProgress => {
+                        self.status_message = "Asking Eliza...".to_string();
+                        rpc::notify_event("thinking", "", &self.current_preset);
+                    }
+                    ProcessingMessage::ElizaPartial(partial) => {
+                        self.streaming_reply = Some(partial);
+                    }
+                    ProcessingMessage::ElizaComplete(response, sleep, meta) => {
+                        self.streaming_reply = None;
+                        if let Some(variant) = self.ab_variant.take() {
+                            let user = self
+                                .conversation_history
+                                .iter()
+                                .rev()
+                                .find(|e| e.role == "You")
+                                .map(|e| e.message.clone())
+                                .unwrap_or_default();
+                            self.ab_test.record(variant, user, response.clone());
+                            if !self.privacy_mode && !self.is_replaying() {
+                                self.ab_test.save_log();
+                            }
+                            if !self.ab_test.is_running() {
+                                self.show_ab_report = true;
+                            }
+                        }
+                        self.status_message = format!("Eliza: {}", response);
+                        self.last_reply = response.clone();
+                        // 沈黙への問いかけなど、発話のないターンでは言葉を数えない
+                        let user_text = self
+                            .conversation_history
+                            .last()
+                            .filter(|e| e.role == "You")
+                            .map(|e| e.message.as_str());
+                        if !self.privacy_mode && !self.is_replaying() {
+                            self.persona_stats.record_turn(user_text);
+                            self.save_persona_stats();
+                        }
+                        let meta = TurnMeta {
+                            preset: self.current_preset.clone(),
+                            ..meta
+                        };
                         self.conversation_history
-                            .push(("You".to_string(), text.clone()));
+                            .push(HistoryEntry::new("Agent", response.clone()).with_meta(meta));
+                        if sleep {
+                            self.pending_sleep = true;
+                        }
+                    }
+                    ProcessingMessage::CompareComplete(comparison) => {
+                        self.last_comparison = Some(comparison);
+                    }
+                    ProcessingMessage::TopicUpdated(topic) => {
+                        self.current_topic = Some(topic);
+                    }
+                    ProcessingMessage::MoodChanged(mood) => {
+                        self.mood = mood;
+                    }
+                    ProcessingMessage::ChatboxBuffered(text) => {
+                        self.chatbox_buffer.push(text);
+                    }
+                    ProcessingMessage::ChatboxFailed(text) => {
+                        self.chatbox_retry.push(text, std::time::Instant::now());
+                    }
+                    ProcessingMessage::ChatboxSuppressed(reason) => {
+                        if let Some(entry) = self
+                            .conversation_history
+                            .iter_mut()
+                            .rev()
+                            .find(|e| e.role == "Agent")
+                        {
+                            entry.suppressed = Some(reason);
+                        }
                     }
-                    ProcessingMessage::ElizaInProgress => {
-                        self.status_message = "Asking Eliza...".to_string();
+                    ProcessingMessage::SafetyStop(eliza_client) => {
+                        self.safety_stop(eliza_client);
                     }
-                    ProcessingMessage::ElizaComplete(response, sleep) => {
-                        self.status_message = format!("Eliza: {}", response);
-                        self.conversation_history
-                            .push(("Agent".to_string(), response.clone()));
-                        if sleep {
-                            self.pending_sleep = true;
+                    ProcessingMessage::VoiceCommand(command, eliza_client) => {
+                        self.processing_receiver = None;
+                        if eliza_client.is_some() {
+                            self.eliza_client = eliza_client;
+                        }
+                        self.apply_voice_command(command);
+                        // 新しい閾値でモニタリングを再開
+                        let status = self.status_message.clone();
+                        if self.dispatch(PipelineEvent::ProcessingFinished).is_some() {
+                            self.status_message = status;
+                        }
+                    }
+                    ProcessingMessage::Ignored(text, intent, eliza_client) => {
+                        self.processing_receiver = None;
+                        if eliza_client.is_some() {
+                            self.eliza_client = eliza_client;
+                        }
+                        self.conversation_history.push(HistoryEntry::new(
+                            "Ignored",
+                            format!("[{}] {}", intent.label(), text),
+                        ));
+                        if self.dispatch(PipelineEvent::ProcessingFinished).is_some() {
+                            self.status_message = format!("聞き流しました ({})", intent.label());
                         }
                     }
+                    ProcessingMessage::RouteToPreset(preset, text, eliza_client) => {
+                        self.processing_receiver = None;
+                        self.route_to_preset(&preset, text, eliza_client);
+                    }
                     ProcessingMessage::Complete(eliza_client) => {
                         self.processing_receiver = None;
                         // Restore the eliza_client for next use (regardless of state)
                         self.eliza_client = eliza_client;
+                        self.fire_hook(HookEvent::ReplySent, &self.last_reply);
+                        notify_sound::play(&self.config, SoundEvent::ReplyPosted);
                         // Check if Eliza detected sleep intent
                         if self.pending_sleep {
                             self.pending_sleep = false;
-                            self.stop_monitoring();
+                            self.dispatch(PipelineEvent::SleepRequested);
+                            self.start_session_summary();
+                            self.fire_hook(HookEvent::Sleep, &self.last_reply);
                             self.status_message = "おやすみなさい。モニタリングを停止しました。".to_string();
-                        } else if self.state == AppState::Processing {
-                            // Only restart monitoring if we're still in Processing state
-                            // (user may have manually stopped while waiting for response)
-                            self.status_message = "Sent to VRChat! Ready for next input.".to_string();
-                            self.start_monitoring();
-                        } else {
+                        } else if self.dispatch(PipelineEvent::ProcessingFinished).is_none() {
+                            // Stopped while waiting for the response (or a typed message)
                             self.status_message = "Sent to VRChat!".to_string();
                         }
                     }
                     ProcessingMessage::Error(error, eliza_client) => {
                         self.processing_receiver = None;
+                        self.streaming_reply = None;
+                        self.ab_variant = None;
                         // Restore ElizaClient to preserve conversation history (regardless of state)
                         if eliza_client.is_some() {
                             self.eliza_client = eliza_client;
                         }
+                        // 状態に関係なくトーストで通知する
+                        eprintln!("[Error] {} ({})", error, error.category());
+                        self.toasts.error(error.user_message());
+                        self.fire_hook(HookEvent::Error, &error.to_string());
+                        notify_sound::play(&self.config, SoundEvent::Error);
                         // Only restart monitoring if we're still in Processing state
-                        if self.state == AppState::Processing {
-                            self.status_message = format!("❌ Error: {}", error);
-                            self.start_monitoring();
+                        if self.dispatch(PipelineEvent::ProcessingFinished).is_some() {
+                            self.status_message = format!("❌ Error: {}", error.user_message());
                         }
                     }
+                    ProcessingMessage::Cancelled(eliza_client) => {
+                        self.processing_receiver = None;
+                        self.eliza_client = eliza_client;
+                        self.dispatch(PipelineEvent::ProcessingFinished);
+                    }
                 }
             }
         }
 
-        // VRChat mute state detection
-        if self.config.use_vrchat_mute_detection {
-            if let Some(ref rx) = self.mute_receiver {
-                // drain all pending messages, keep only the last
-                let mut last_muted = None;
-                while let Ok(is_muted) = rx.try_recv() {
-                    last_muted = Some(is_muted);
+        // MIDI controller
+        let midi_events: Vec<MidiEvent> = self
+            .midi_receiver
+            .as_ref()
+            .map(|rx| rx.try_iter().collect())
+            .unwrap_or_default();
+        for event in midi_events {
+            self.last_midi_event = Some(event);
+            self.handle_midi_event(event);
+        }
+
+        if let Some(client) = &self.eliza_client {
+            self.history_stats = Some(client.history_stats());
+        }
+
+        self.drive_replay(ctx);
+
+        // Chatbox retry queue
+        if !self.chatbox_retry.is_empty() {
+            self.retry_chatbox_messages();
+        }
+
+        // Batch re-transcription
+        if let Some(receiver) = &self.batch_receiver {
+            let mut finished = false;
+            for message in receiver.try_iter() {
+                match message {
+                    batch::BatchMessage::Progress { done, total, file } => {
+                        self.batch_status = format!("{}/{} {}", done + 1, total, file);
+                    }
+                    batch::BatchMessage::Finished(Ok(path)) => {
+                        self.batch_status = format!("✔ {} に書き出しました", path.display());
+                        finished = true;
+                    }
+                    batch::BatchMessage::Finished(Err(e)) => {
+                        self.batch_status = format!("❌ {}", e);
+                        finished = true;
+                    }
                 }
-                if let Some(is_muted) = last_muted {
-                    // MuteSelf=true → ミュート中 → start_monitoring
-                    // MuteSelf=false → ミュート解除 → stop_monitoring
-                    if is_muted && self.state == AppState::Idle {
-                        println!("VRChat muted → start monitoring");
-                        self.start_monitoring();
-                    } else if !is_muted && self.state != AppState::Idle {
-                        println!("VRChat unmuted → stop monitoring");
-                        self.stop_monitoring();
+            }
+            if finished {
+                self.batch_receiver = None;
+            }
+        }
+
+        self.poll_memory_saves();
+        self.poll_memory_load();
+        self.poll_discarded_processing();
+
+        // Session summary
+        if let Some(receiver) = &self.summary_receiver {
+            if let Ok(result) = receiver.try_recv() {
+                self.summary_receiver = None;
+                match result {
+                    Ok(summary) => {
+                        if let Err(e) = Session::save_summary(&self.summary_key(), &summary) {
+                            self.toasts.error(e);
+                        }
+                        self.last_summary = Some(summary);
                     }
+                    Err(e) => self.toasts.error(format!("Failed to summarize: {}", e)),
                 }
             }
         }
 
-        // Calibration: silence phase (2 seconds)
-        if self.state == AppState::CalibratingSilence {
-            if let Some(recorder) = &self.audio_recorder {
-                let rms = recorder.get_rms_amplitude();
-                self.calib_rms_samples.push(rms);
-
-                let elapsed = self.calib_start_time.map(|t| t.elapsed().as_secs_f32()).unwrap_or(0.0);
-                self.recording_info = format!("無音録音中: {:.1}s / 2.0s", elapsed);
-
-                if elapsed >= 2.0 {
-                    // 無音フェーズ完了: 最大 RMS を silence_threshold に
-                    let max_rms = self.calib_rms_samples.iter().cloned().fold(0.0f32, f32::max);
-                    self.config.silence_threshold = max_rms;
-                    self.settings_silence_threshold = max_rms;
-                    println!("Calib silence done: max_rms={:.6} → silence_threshold", max_rms);
-
-                    // 発話フェーズへ
-                    self.calib_rms_samples.clear();
-                    self.calib_start_time = Some(std::time::Instant::now());
-                    self.state = AppState::CalibratingVoice;
-                    self.status_message = "キャリブレーション: 話してください... (2秒以上)".to_string();
-                    self.recording_info = String::new();
+        self.handle_close_request(ctx);
+
+        // Greetings for joined players
+        self.greet_joined_players();
+        self.switch_preset_for_world();
+        self.poll_scheduled_messages();
+        if let Some(receiver) = &self.greeting_receiver {
+            if let Ok(result) = receiver.try_recv() {
+                self.greeting_receiver = None;
+                match result {
+                    Ok((name, greeting)) if !greeting.is_empty() => {
+                        println!("[Greeting] {}: {}", name, greeting);
+                        self.conversation_history.push(HistoryEntry::new("Agent", greeting));
+                    }
+                    Ok(_) => {}
+                    Err(e) => self.report_error(e.context("Failed to greet")),
                 }
             }
-            ctx.request_repaint();
         }
+        self.poll_chatbox_messages();
 
-        // Calibration: voice phase (2+ seconds, manual stop via button)
-        if self.state == AppState::CalibratingVoice {
-            if let Some(recorder) = &self.audio_recorder {
-                let rms = recorder.get_rms_amplitude();
-                self.calib_rms_samples.push(rms);
+        self.handle_afk();
 
-                let elapsed = self.calib_start_time.map(|t| t.elapsed().as_secs_f32()).unwrap_or(0.0);
-                self.recording_info = format!("発話録音中: {:.1}s (2秒以上話したら停止ボタン)", elapsed);
+        // VRChat mute state detection
+        if let Some(ref rx) = self.mute_receiver {
+            // drain all pending messages, keep only the last
+            let mut last_muted = None;
+            while let Ok(is_muted) = rx.try_recv() {
+                last_muted = Some(is_muted);
+            }
+            if let Some(is_muted) = last_muted {
+                self.vrchat_muted = is_muted;
+                // MuteSelf=true → ミュート中 → start_monitoring
+                // MuteSelf=false → ミュート解除 → stop_monitoring
+                if self.config.use_vrchat_mute_detection {
+                    if is_muted && self.pipeline.is_idle() {
+                        println!("VRChat muted → start monitoring");
+                        self.dispatch(PipelineEvent::StartPressed);
+                    } else if !is_muted && !self.pipeline.is_idle() {
+                        println!("VRChat unmuted → stop monitoring");
+                        self.dispatch(PipelineEvent::StopPressed);
+                    }
+                }
             }
-            ctx.request_repaint();
         }
 
-        // Monitor for voice detection in Monitoring state
-        // RMSベースで判定し、連続2回以上で録音開始 (単発ノイズスパイク誤検出防止)
-        if self.state == AppState::Monitoring {
-            if let Some(recorder) = &self.audio_recorder {
-                let rms = recorder.get_rms_amplitude();
-                if rms > self.config.start_threshold {
-                    self.voice_detection_count += 1;
-                    if self.voice_detection_count >= 2 {
-                        self.voice_detection_count = 0;
-                        self.start_recording();
-                    }
-                } else {
-                    self.voice_detection_count = 0;
+        // Control API: 届いたテキストは処理が空いたら順に Agent に送る
+        if let Some(ref rx) = self.control_receiver {
+            while let Ok(text) = rx.try_recv() {
+                self.pending_control_texts.push_back(text);
+            }
+        }
+        self.handle_rpc_requests();
+        if self.processing_receiver.is_none() {
+            if let Some(text) = self.pending_control_texts.pop_front() {
+                self.send_text_message(text);
+            }
+        }
+
+        self.update_calibration(ctx);
+        self.check_silence_follow_up();
+
+        // 録音開始・終了の判定は VadThread が一定間隔で行い、結果だけをここで受け取る
+        if let Some(vad) = &self.vad {
+            // 感度の音声コマンドやキャリブレーションで変わった設定を反映する
+            vad.set_params(VadParams::from_config(&self.config));
+        }
+        match self.vad.as_ref().and_then(VadThread::try_recv) {
+            Some(VadEvent::VoiceDetected(at)) => {
+                println!("Voice detected! Starting recording...");
+                self.begin_recording();
+                // 画面の更新が止まっていても、VAD が声を検出した時刻から数える
+                if self.pipeline.state() == AppState::Recording {
+                    self.recording_started_at = Some(at);
                 }
+                self.status_message = "Recording... Speak now!".to_string();
             }
+            Some(VadEvent::SilenceDetected(samples)) => {
+                self.vad_cut_samples = Some(samples);
+                self.dispatch(PipelineEvent::SilenceDetected);
+                self.vad_cut_samples = None;
+            }
+            None => {}
+        }
+        if self.config.hold_to_talk_enabled {
+            self.update_hold_to_talk(ctx);
         }
 
         // Check for silence in Recording state
-        if self.state == AppState::Recording {
+        if self.pipeline.state() == AppState::Recording {
             if let Some(recorder) = &self.audio_recorder {
                 let buffer_size = recorder.get_buffer_size();
                 let sample_rate = recorder.get_sample_rate();
@@ -710,152 +3219,347 @@ impl eframe::App for ElizaAgentApp {
                     "Recording: {:.1}s | Silence: {:.1}s/{:.1}s",
                     duration, silence_elapsed, self.config.silence_duration_secs
                 );
-
-                if recorder.is_silent(self.config.silence_duration_secs) {
-                    self.stop_recording_and_process();
-                }
             }
             ctx.request_repaint();
         }
 
+        self.toasts.show(ctx);
+
         // Settings modal
-        if self.show_settings {
-            egui::Window::new("Settings")
+        if self.settings.open {
+            self.show_settings_window(ctx);
+        }
+
+        // Persona library window
+        if self.show_persona_library {
+            let mut open = true;
+            let mut apply_target = None;
+            egui::Window::new("人格ライブラリ")
+                .open(&mut open)
                 .collapsible(false)
-                .resizable(false)
+                .resizable(true)
+                .default_width(420.0)
+                .show(ctx, |ui| {
+                    ui.horizontal_top(|ui| {
+                        ui.vertical(|ui| {
+                            ui.set_width(140.0);
+                            egui::ScrollArea::vertical()
+                                .id_salt("persona_list")
+                                .max_height(300.0)
+                                .show(ui, |ui| {
+                                    for (idx, persona) in self.persona_library.iter().enumerate() {
+                                        let label = if persona.builtin {
+                                            persona.name.clone()
+                                        } else {
+                                            format!("👤 {}", persona.name)
+                                        };
+                                        ui.selectable_value(&mut self.selected_persona_index, idx, label);
+                                    }
+                                });
+                        });
+                        ui.separator();
+                        ui.vertical(|ui| {
+                            if let Some(persona) = self.persona_library.get(self.selected_persona_index) {
+                                ui.strong(&persona.name);
+                                if !persona.description.is_empty() {
+                                    ui.label(&persona.description);
+                                }
+                                ui.separator();
+                                egui::ScrollArea::vertical()
+                                    .id_salt("persona_preview")
+                                    .max_height(220.0)
+                                    .show(ui, |ui| {
+                                        ui.label(&persona.prompt);
+                                    });
+                                ui.add_space(5.0);
+                                if ui.button("✔ 現在のプリセットに適用").clicked() {
+                                    apply_target = Some(persona.clone());
+                                }
+                            }
+                        });
+                    });
+                    ui.separator();
+                    if let Ok(dir) = persona::user_personas_dir() {
+                        ui.small(format!("自作の人格: {} に JSON を置く", dir.display()));
+                    }
+                });
+            if let Some(persona) = apply_target {
+                self.apply_persona(persona);
+                open = false;
+            }
+            self.show_persona_library = open;
+        }
+
+        // Batch re-transcription window
+        if self.show_batch {
+            let mut open = true;
+            egui::Window::new("録音の再書き起こし")
+                .open(&mut open)
+                .collapsible(false)
+                .default_width(420.0)
                 .show(ctx, |ui| {
-                    egui::ScrollArea::vertical().max_height(400.0).show(ui, |ui| {
-                        ui.label("OpenAI API Key:");
-                        ui.text_edit_singleline(&mut self.settings_openai_key);
-                        ui.add_space(5.0);
-
-                        ui.label("Agent Server URL:");
-                        ui.text_edit_singleline(&mut self.settings_agent_server_url);
-                        ui.add_space(10.0);
-
-                        ui.label("Start Threshold:");
-                        ui.add(egui::Slider::new(&mut self.settings_start_threshold, 0.001..=0.3).logarithmic(true));
-                        ui.add_space(5.0);
-
-                        ui.label("Silence Threshold:");
-                        ui.add(egui::Slider::new(&mut self.settings_silence_threshold, 0.001..=0.3).logarithmic(true));
-                        ui.add_space(5.0);
-
-                        ui.label("Silence Duration (seconds):");
-                        ui.add(egui::Slider::new(&mut self.settings_silence_duration, 0.5..=10.0));
-                        ui.add_space(10.0);
-
-                        ui.label("Whisper Model:");
-                        ui.text_edit_singleline(&mut self.settings_whisper_model);
-                        ui.add_space(5.0);
-
-                        ui.label("Custom Prompt:");
-                        ui.add(egui::TextEdit::multiline(&mut self.settings_custom_prompt).desired_rows(2));
-                        ui.add_space(10.0);
-
-                        ui.label("Agent Model:");
-                        ui.text_edit_singleline(&mut self.settings_agent_model);
-                        ui.add_space(5.0);
-
-                        ui.label("Max Conversation History:");
-                        ui.add(egui::Slider::new(&mut self.settings_max_history, 1..=50));
-                        ui.add_space(10.0);
-
-                        ui.checkbox(&mut self.settings_use_vrchat_mute_detection, "VRChat のミュート状態を使う");
-                        ui.label("  ミュート解除で録音開始、ミュートで録音停止 (OSC 9001ポート)");
-                        ui.add_space(10.0);
-
-                        ui.label("Input Device:");
-                        egui::ComboBox::from_id_salt("input_device_combo")
-                            .selected_text(
-                                self.available_devices
-                                    .get(self.selected_device_index)
-                                    .unwrap_or(&"Default".to_string()),
-                            )
+                    ui.label("録音フォルダ (.wav):");
+                    ui.text_edit_singleline(&mut self.batch_dir);
+                    let count = batch::list_recordings(std::path::Path::new(&self.batch_dir)).len();
+                    ui.weak(format!("{} 件の録音", count));
+                    ui.horizontal(|ui| {
+                        ui.label("Backend:");
+                        egui::ComboBox::from_id_salt("batch_backend_combo")
+                            .selected_text(stt::backend_label(&self.batch_backend))
                             .show_ui(ui, |ui| {
-                                for (idx, device_name) in
-                                    self.available_devices.iter().enumerate()
-                                {
-                                    ui.selectable_value(
-                                        &mut self.selected_device_index,
-                                        idx,
-                                        device_name,
-                                    );
+                                for (name, label) in &stt::BACKENDS[..2] {
+                                    ui.selectable_value(&mut self.batch_backend, name.to_string(), *label);
                                 }
                             });
                     });
+                    let running = self.batch_receiver.is_some();
+                    if ui
+                        .add_enabled(!running && count > 0, egui::Button::new("▶ 書き起こし開始"))
+                        .clicked()
+                    {
+                        let (sender, receiver) = channel();
+                        self.batch_receiver = Some(receiver);
+                        self.batch_status = "開始しています...".to_string();
+                        batch::start(
+                            PathBuf::from(&self.batch_dir),
+                            self.batch_backend.clone(),
+                            self.config.clone(),
+                            sender,
+                        );
+                    }
+                    if running {
+                        ui.spinner();
+                    }
+                    if !self.batch_status.is_empty() {
+                        ui.label(&self.batch_status);
+                    }
+                    ui.separator();
+                    ui.small("Settings の Audio タブで「録音を保存する」を有効にすると、録音がこのフォルダに残ります");
+                });
+            self.show_batch = open;
+        }
 
-                    ui.add_space(10.0);
+        // OSC debug console
+        if self.show_osc_console {
+            let mut open = true;
+            egui::Window::new("OSC コンソール")
+                .open(&mut open)
+                .default_width(520.0)
+                .show(ctx, |ui| {
+                    let console = &mut self.osc_console;
                     ui.horizontal(|ui| {
-                        if ui.button("Save").clicked() {
-                            self.config.openai_api_key = self.settings_openai_key.clone();
-                            self.config.agent_server_url = self.settings_agent_server_url.clone();
-                            self.config.start_threshold = self.settings_start_threshold;
-                            self.config.silence_threshold = self.settings_silence_threshold;
-                            self.config.silence_duration_secs = self.settings_silence_duration;
-                            self.config.whisper_model = self.settings_whisper_model.clone();
-                            self.config.custom_prompt = self.settings_custom_prompt.clone();
-                            self.config.agent_model = self.settings_agent_model.clone();
-                            self.config.max_length_of_conversation_history = self.settings_max_history;
-
-                            // Apply mute detection setting (restart listener if changed)
-                            let mute_changed = self.config.use_vrchat_mute_detection != self.settings_use_vrchat_mute_detection;
-                            self.config.use_vrchat_mute_detection = self.settings_use_vrchat_mute_detection;
-                            if mute_changed {
-                                if self.config.use_vrchat_mute_detection {
-                                    let (tx, rx) = channel::<bool>();
-                                    start_mute_listener(tx);
-                                    self.mute_receiver = Some(rx);
-                                } else {
-                                    self.mute_receiver = None;
+                        ui.label("Address:");
+                        ui.add(egui::TextEdit::singleline(&mut console.filter).desired_width(140.0).hint_text("/chatbox"));
+                        ui.checkbox(&mut console.show_sent, "送信");
+                        ui.checkbox(&mut console.show_received, "受信");
+                        ui.checkbox(&mut console.errors_only, "失敗のみ");
+                        if ui.button("クリア").clicked() {
+                            osc_log::clear();
+                        }
+                    });
+                    ui.weak(format!(
+                        "送信先 {} / 受信ポート {}{}",
+                        VRChatClient::new().target_addr,
+                        self.config.osc_listen_port,
+                        if self.mute_receiver.is_some() { "" } else { " (ミュート連動がオフのため受信していません)" }
+                    ));
+                    ui.separator();
+                    egui::ScrollArea::vertical()
+                        .max_height(300.0)
+                        .auto_shrink([false, false])
+                        .stick_to_bottom(true)
+                        .show(ui, |ui| {
+                            for entry in osc_log::entries().iter().filter(|e| console.matches(e)) {
+                                let arrow = match entry.direction {
+                                    osc_log::Direction::Sent => "→",
+                                    osc_log::Direction::Received => "←",
+                                };
+                                let line = format!(
+                                    "{} {} {} {}",
+                                    entry.time.format("%H:%M:%S%.3f"),
+                                    arrow,
+                                    entry.addr,
+                                    entry.args
+                                );
+                                match &entry.error {
+                                    Some(error) => {
+                                        ui.colored_label(egui::Color32::RED, format!("{} ✗ {}", line, error));
+                                    }
+                                    None => {
+                                        ui.monospace(line).on_hover_text(&entry.peer);
+                                    }
                                 }
                             }
-
-                            // Save selected input device
-                            self.config.input_device_name = self
-                                .available_devices
-                                .get(self.selected_device_index)
-                                .cloned();
-
-                            // Save to current preset
-                            match self.config.save_preset(&self.current_preset) {
-                                Ok(_) => self.status_message = format!(
-                                    "Settings saved to {}!",
-                                    Config::preset_display_name(&self.current_preset)
-                                ),
-                                Err(e) => self.status_message = format!("Failed to save: {}", e),
+                        });
+                    ui.separator();
+                    ui.label("手動送信:");
+                    ui.horizontal(|ui| {
+                        ui.add(egui::TextEdit::singleline(&mut console.send_addr).desired_width(180.0));
+                        egui::ComboBox::from_id_salt("osc_send_type_combo")
+                            .selected_text(console.send_type.as_str())
+                            .show_ui(ui, |ui| {
+                                for arg_type in osc_log::ARG_TYPES {
+                                    ui.selectable_value(&mut console.send_type, arg_type.to_string(), arg_type);
+                                }
+                            });
+                        ui.add(egui::TextEdit::singleline(&mut console.send_value).desired_width(120.0));
+                        if ui.button("送信").clicked() {
+                            let vrchat = VRChatClient::new();
+                            let result = if console.send_addr == "/chatbox/input" && console.send_type == "String" {
+                                // チャットボックスは即時表示・通知音の引数も要る
+                                vrchat.send_message(&console.send_value).map_err(|e| e.to_string())
+                            } else {
+                                osc_log::parse_arg(&console.send_type, &console.send_value).and_then(|arg| {
+                                    vrchat.send_osc(&console.send_addr, vec![arg]).map_err(|e| e.to_string())
+                                })
+                            };
+                            if let Err(e) = result {
+                                self.toasts.error(e);
                             }
-                            self.show_settings = false;
                         }
+                    });
+                });
+            self.show_osc_console = open;
+        }
 
-                        if ui.button("Cancel").clicked() {
-                            // Revert settings changes
-                            self.settings_openai_key = self.config.openai_api_key.clone();
-                            self.settings_agent_server_url = self.config.agent_server_url.clone();
-                            self.settings_start_threshold = self.config.start_threshold;
-                            self.settings_silence_threshold = self.config.silence_threshold;
-                            self.settings_silence_duration = self.config.silence_duration_secs;
-                            self.settings_whisper_model = self.config.whisper_model.clone();
-                            self.settings_custom_prompt = self.config.custom_prompt.clone();
-                            self.settings_agent_model = self.config.agent_model.clone();
-                            self.settings_max_history = self.config.max_length_of_conversation_history;
-                            self.settings_use_vrchat_mute_detection = self.config.use_vrchat_mute_detection;
-
-                            // Restore device index
-                            self.selected_device_index =
-                                if let Some(ref device_name) = self.config.input_device_name {
-                                    self.available_devices
-                                        .iter()
-                                        .position(|d| d == device_name)
-                                        .unwrap_or(0)
-                                } else {
-                                    0
-                                };
+        if self.show_scheduler {
+            self.show_scheduler_window(ctx);
+        }
+
+        if self.show_context_preview {
+            self.show_context_preview_window(ctx);
+        }
+
+        if self.mic_check.open {
+            self.show_mic_check_window(ctx);
+        }
+
+        if self.replay.open {
+            self.show_replay_window(ctx);
+        }
+
+        if self.show_diagnostics {
+            self.show_diagnostics_window(ctx);
+            ctx.request_repaint_after(std::time::Duration::from_secs(1));
+        }
+
+        // A/B test report window
+        if self.show_ab_report {
+            let mut open = true;
+            egui::Window::new("A/B テスト結果")
+                .open(&mut open)
+                .collapsible(false)
+                .default_width(420.0)
+                .show(ctx, |ui| {
+                    egui::Grid::new("ab_summary").striped(true).show(ui, |ui| {
+                        ui.strong("Prompt");
+                        ui.strong("返答");
+                        ui.strong("👍");
+                        ui.strong("👎");
+                        ui.end_row();
+                        for summary in self.ab_test.summary() {
+                            ui.label(summary.variant.label());
+                            ui.label(summary.replies.to_string());
+                            ui.label(summary.up.to_string());
+                            ui.label(summary.down.to_string());
+                            ui.end_row();
+                        }
+                    });
+                    ui.separator();
+                    ui.weak("評価するまでどちらのプロンプトの返答かは表示しません");
+                    let mut rating = None;
+                    egui::ScrollArea::vertical().max_height(300.0).show(ui, |ui| {
+                        for (i, record) in self.ab_test.records.iter().enumerate() {
+                            ui.horizontal(|ui| {
+                                if ui.selectable_label(record.rating == Some(1), "👍").clicked() {
+                                    rating = Some((i, 1));
+                                }
+                                if ui.selectable_label(record.rating == Some(-1), "👎").clicked() {
+                                    rating = Some((i, -1));
+                                }
+                                if record.rating.is_some() {
+                                    ui.strong(record.variant.label());
+                                }
+                                ui.weak(&record.user);
+                            });
+                            ui.label(&record.reply);
+                            ui.add_space(5.0);
+                        }
+                    });
+                    if let Some((i, value)) = rating {
+                        self.ab_test.rate(i, value);
+                        if !self.privacy_mode {
+                            self.ab_test.save_log();
+                        }
+                    }
+                });
+            self.show_ab_report = open;
+        }
 
-                            self.show_settings = false;
+        // Session save/load window
+        if self.show_sessions {
+            let mut open = true;
+            let mut load_target = None;
+            egui::Window::new("Sessions")
+                .open(&mut open)
+                .collapsible(false)
+                .resizable(false)
+                .show(ctx, |ui| {
+                    ui.label("セッション名:");
+                    ui.horizontal(|ui| {
+                        ui.text_edit_singleline(&mut self.session_name_input);
+                        let save_enabled = !self.session_name_input.trim().is_empty();
+                        if ui.add_enabled(save_enabled, egui::Button::new("Save session")).clicked() {
+                            self.save_session();
+                        }
+                    });
+                    ui.add_space(10.0);
+                    ui.label("保存済みセッション:");
+                    egui::ScrollArea::vertical().max_height(200.0).show(ui, |ui| {
+                        if self.saved_sessions.is_empty() {
+                            ui.label("(なし)");
+                        }
+                        for name in &self.saved_sessions {
+                            ui.horizontal(|ui| {
+                                ui.label(name);
+                                if ui.button("Load").clicked() {
+                                    load_target = Some(name.clone());
+                                }
+                            });
+                        }
+                    });
+                    ui.add_space(10.0);
+                    ui.separator();
+                    ui.label("会話の書き出し / 読み込み (JSON):");
+                    ui.text_edit_singleline(&mut self.history_file_input);
+                    ui.horizontal(|ui| {
+                        let has_path = !self.history_file_input.trim().is_empty();
+                        if ui.add_enabled(has_path, egui::Button::new("📤 書き出し")).clicked() {
+                            self.export_history();
+                        }
+                        if ui
+                            .add_enabled(has_path, egui::Button::new("📥 読み込み"))
+                            .on_hover_text("今の会話を置き換えて、読み込んだ会話の続きから話す")
+                            .clicked()
+                        {
+                            self.import_history();
                         }
                     });
                 });
+            if let Some(name) = load_target {
+                self.load_session(&name);
+                open = false;
+            }
+            self.show_sessions = open;
+        }
+
+        // Persona prompt editor (side panel)
+        if self.persona_editor.open {
+            self.show_persona_editor(ctx);
+        }
+
+        if self.show_persona_stats {
+            self.show_persona_stats_window(ctx);
         }
 
         // Main UI
@@ -866,13 +3570,109 @@ impl eframe::App for ElizaAgentApp {
                 // Header with settings button
                 ui.horizontal(|ui| {
                     ui.heading("Eliza Agent");
+                    if mock::is_enabled() {
+                        ui.colored_label(egui::Color32::from_rgb(200, 100, 0), "MOCK")
+                            .on_hover_text("--mock: 書き起こし・返答は台本、VRChat への送信はログのみ");
+                    }
+                    if self.privacy_mode {
+                        ui.colored_label(egui::Color32::from_rgb(150, 100, 200), "🕶 PRIVATE")
+                            .on_hover_text("プライベートモード: 録音・記憶・要約・評価・セッションを保存しません");
+                    }
                     ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
                         if ui.button("⚙ Settings").clicked() {
-                            self.show_settings = true;
+                            self.load_settings_from_config();
+                            self.settings.midi_ports = midi::list_input_ports();
+                            self.settings.output_devices = playback::get_output_devices();
+                            self.settings.open = true;
+                        }
+                        if ui
+                            .selectable_label(self.privacy_mode, "🕶")
+                            .on_hover_text("プライベートモード (このセッションの間、録音・記憶・要約・評価・セッションを保存しない)")
+                            .clicked()
+                        {
+                            self.privacy_mode = !self.privacy_mode;
+                            self.sync_scheduler();
+                            self.status_message = if self.privacy_mode {
+                                "🕶 プライベートモードをオンにしました".to_string()
+                            } else {
+                                "プライベートモードをオフにしました".to_string()
+                            };
+                        }
+                        if self.toasts.history_len() > 0
+                            && ui
+                                .button(format!("🔔 {}", self.toasts.history_len()))
+                                .on_hover_text("エラー履歴")
+                                .clicked()
+                        {
+                            self.toasts.open_history();
                         }
                     });
                 });
 
+                // VRChat が起動していない / 保留中の返答がある
+                if chatbox_paused(&self.config) {
+                    ui.colored_label(
+                        egui::Color32::from_rgb(200, 100, 0),
+                        "⚠ VRChat が起動していません。チャットボックス送信を一時停止中 (返答は保留されます)",
+                    );
+                }
+                if !self.chatbox_buffer.is_empty() {
+                    ui.horizontal(|ui| {
+                        ui.label(format!("保留中の返答: {} 件", self.chatbox_buffer.len()))
+                            .on_hover_text(self.chatbox_buffer.join("\n"));
+                        let can_send = !chatbox_paused(&self.config);
+                        // 送るのは最新の 1 件だけ。残りは保留したままにして、続けて送るか破棄するかを選べるようにする
+                        if ui
+                            .add_enabled(can_send, egui::Button::new("最新を送信"))
+                            .on_hover_text("最新の 1 件を送り、それより前の返答は保留したままにします")
+                            .clicked()
+                        {
+                            if let Some(text) = self.chatbox_buffer.pop() {
+                                match VRChatClient::new().send_message(&text) {
+                                    Ok(_) if !self.chatbox_buffer.is_empty() => {
+                                        self.status_message =
+                                            format!("最新の返答を送りました (保留中の返答が {} 件残っています)", self.chatbox_buffer.len());
+                                    }
+                                    Ok(_) => {}
+                                    Err(e) => {
+                                        self.report_error(AppError::from(e).context("VRChat failed"));
+                                        self.chatbox_buffer.push(text);
+                                    }
+                                }
+                            }
+                        }
+                        if ui.button("破棄").clicked() {
+                            self.chatbox_buffer.clear();
+                        }
+                    });
+                }
+                if self.config.agent_account_mode {
+                    let mut line = "🤖 Agent アカウントモード".to_string();
+                    if self.control_server.is_some() {
+                        line.push_str(&format!(" / 操作用 API :{}", self.config.control_api_port));
+                    }
+                    if !self.pending_control_texts.is_empty() {
+                        line.push_str(&format!(" / 順番待ち {} 件", self.pending_control_texts.len()));
+                    }
+                    ui.weak(line);
+                }
+                if !self.chatbox_retry.is_empty() {
+                    ui.horizontal(|ui| {
+                        let pending: Vec<&str> = self.chatbox_retry.texts().collect();
+                        ui.colored_label(
+                            egui::Color32::from_rgb(200, 100, 0),
+                            format!("📨 再送待ち: {} 件", self.chatbox_retry.len()),
+                        )
+                        .on_hover_text(pending.join("\n"));
+                        if ui.button("今すぐ再送").clicked() {
+                            self.chatbox_retry.retry_now(std::time::Instant::now());
+                        }
+                        if ui.button("破棄").clicked() {
+                            self.chatbox_retry.clear();
+                        }
+                    });
+                }
+
                 ui.add_space(5.0);
 
                 // Preset selector
@@ -884,226 +3684,122 @@ impl eframe::App for ElizaAgentApp {
                         .show_ui(ui, |ui| {
                             for preset in Config::list_presets() {
                                 let display_name = Config::preset_display_name(&preset);
-                                if ui.selectable_value(&mut self.current_preset, preset.clone(), &display_name).clicked() {
+                                // 切り替える前の会話を元のプリセットで保存するので、current_preset は switch_preset に任せる
+                                let selected = self.current_preset == preset;
+                                if ui.selectable_label(selected, &display_name).clicked() && !selected {
                                     self.switch_preset(&preset);
                                 }
                             }
                         });
-                });
-
-                ui.add_space(10.0);
-
-                // Status
-                let status_color = match self.state {
-                    AppState::Idle => egui::Color32::GRAY,
-                    AppState::Monitoring => egui::Color32::from_rgb(0, 128, 0),
-                    AppState::Recording => egui::Color32::RED,
-                    AppState::Processing => egui::Color32::from_rgb(200, 100, 0),
-                    AppState::CalibratingSilence => egui::Color32::from_rgb(100, 100, 200),
-                    AppState::CalibratingVoice => egui::Color32::from_rgb(200, 100, 200),
-                };
-                ui.colored_label(status_color, &self.status_message);
-
-                if !self.recording_info.is_empty() {
-                    ui.label(&self.recording_info);
-                }
-
-                ui.add_space(20.0);
-
-                // Start/Stop button (always show either Start or Stop)
-                let (button_text, is_stop_button) = match self.state {
-                    AppState::Idle => ("▶ Start Monitoring", false),
-                    AppState::Monitoring => ("⏹ Stop", true),
-                    AppState::Recording => ("⏹ Stop", true),
-                    AppState::Processing => ("⏹ Stop", true),
-                    AppState::CalibratingSilence => ("⏹ Stop", true),
-                    AppState::CalibratingVoice => ("⏹ 停止して閾値を確定", true),
-                };
-
-                // Calculate silence progress for Recording state
-                let silence_progress = if self.state == AppState::Recording {
-                    if let Some(recorder) = &self.audio_recorder {
-                        let silence_elapsed = recorder.get_silence_duration().as_secs_f32();
-                        (silence_elapsed / self.config.silence_duration_secs).min(1.0)
-                    } else {
-                        0.0
+                    if ui.button("📚 人格ライブラリ").clicked() {
+                        self.persona_library = persona::load_library();
+                        self.selected_persona_index = 0;
+                        self.show_persona_library = true;
                     }
-                } else {
-                    0.0
-                };
-
-                let button_size = egui::vec2(300.0, 60.0);
-                let (rect, response) = ui.allocate_exact_size(button_size, egui::Sense::click());
-
-                // Draw button background
-                let visuals = ui.style().interact(&response);
-                ui.painter().rect_filled(rect, visuals.rounding, visuals.bg_fill);
-
-                // Draw silence progress bar (start full, drain as silence progresses) when recording
-                if self.state == AppState::Recording {
-                    let fill_height = rect.height() * (1.0 - silence_progress);
-                    if fill_height > 0.0 {
-                        let progress_rect = egui::Rect::from_min_size(
-                            egui::pos2(rect.min.x, rect.max.y - fill_height),
-                            egui::vec2(rect.width(), fill_height),
-                        );
-                        ui.painter().rect_filled(
-                            progress_rect,
-                            visuals.rounding,
-                            egui::Color32::from_rgb(100, 200, 255),
-                        );
-                    }
-                }
-
-                // Draw button border
-                ui.painter().rect_stroke(rect, visuals.rounding, visuals.bg_stroke);
-
-                // Draw button text
-                ui.painter().text(
-                    rect.center(),
-                    egui::Align2::CENTER_CENTER,
-                    button_text,
-                    egui::FontId::proportional(18.0),
-                    visuals.text_color(),
-                );
-
-                if response.clicked() {
-                    if is_stop_button {
-                        if self.state == AppState::CalibratingVoice {
-                            // 発話フェーズ完了: 平均 RMS を start_threshold に
-                            if !self.calib_rms_samples.is_empty() {
-                                let avg_rms = self.calib_rms_samples.iter().sum::<f32>()
-                                    / self.calib_rms_samples.len() as f32;
-                                self.config.start_threshold = avg_rms;
-                                self.settings_start_threshold = avg_rms;
-                                println!("Calib voice done: avg_rms={:.6} → start_threshold", avg_rms);
-                                self.status_message = format!(
-                                    "✓ キャリブレーション完了! silence={:.4}, start={:.4}",
-                                    self.config.silence_threshold,
-                                    self.config.start_threshold
-                                );
-                            }
-                            if let Some(mut recorder) = self.audio_recorder.take() {
-                                recorder.stop_recording();
-                            }
-                            self.state = AppState::Idle;
-                            self.recording_info.clear();
-                            self.calib_rms_samples.clear();
-                            self.calib_start_time = None;
+                    if ui
+                        .selectable_label(self.persona_editor.open, "✏ 人格を編集")
+                        .on_hover_text("会話履歴を消さずにプロンプトを直す")
+                        .clicked()
+                    {
+                        if self.persona_editor.open {
+                            self.persona_editor.open = false;
                         } else {
-                            self.stop_monitoring();
+                            self.persona_editor.open_with(&self.config.persona_prompt);
                         }
-                    } else {
-                        self.start_monitoring();
                     }
-                }
-
-                ui.add_space(5.0);
-
-                // Calibration button (Idle 時のみ表示)
-                if self.state == AppState::Idle {
                     if ui
-                        .add(egui::Button::new("⚙ 音量閾値を自動設定").min_size(egui::vec2(300.0, 30.0)))
+                        .button("📊 記録")
+                        .on_hover_text("この人格と話した回数とよく出る話題")
                         .clicked()
                     {
-                        self.start_calibration();
+                        self.show_persona_stats = true;
                     }
-                }
-
-                ui.add_space(20.0);
+                });
 
-                // Conversation history
+                // Named conversation selector
+                let mut open_target = None;
                 ui.horizontal(|ui| {
-                    ui.heading("Conversation");
-                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
-                        if ui.button("🗑 Clear History").clicked() {
-                            self.conversation_history.clear();
-                            if let Some(ref mut eliza_client) = self.eliza_client {
-                                // Save memory before clearing
-                                if let Err(e) = eliza_client.save_memory() {
-                                    eprintln!("Failed to save memory on clear: {}", e);
+                    ui.label("会話:");
+                    egui::ComboBox::from_id_salt("session_selector")
+                        .selected_text(self.active_session.as_deref().unwrap_or("(名前なし)"))
+                        .show_ui(ui, |ui| {
+                            for name in &self.saved_sessions {
+                                if ui
+                                    .selectable_label(self.active_session.as_deref() == Some(name.as_str()), name)
+                                    .clicked()
+                                {
+                                    open_target = Some(name.clone());
                                 }
-                                eliza_client.clear_history();
-                                println!("Conversation history cleared");
-                                self.status_message = "Conversation history cleared".to_string();
                             }
-                        }
-                    });
-                });
-                ui.separator();
-
-                egui::ScrollArea::vertical()
-                    .max_height(300.0)
-                    .auto_shrink([false, false])
-                    .stick_to_bottom(true)
-                    .show(ui, |ui| {
-                        for (role, message) in &self.conversation_history {
-                            ui.horizontal(|ui| {
-                                let color = if role == "You" {
-                                    egui::Color32::from_rgb(30, 80, 180)
-                                } else {
-                                    egui::Color32::from_rgb(0, 128, 0) // Dark green
-                                };
-                                ui.colored_label(color, format!("{}:", role));
-                            });
-                            ui.label(message);
-                            ui.add_space(10.0);
-                        }
-                    });
-
-                // Text input area
-                ui.add_space(10.0);
-                ui.separator();
-                ui.label("テキスト送信 (Ctrl+Enter で送信 / Shift+Enter で改行):");
-
-                let text_edit = egui::TextEdit::multiline(&mut self.text_input)
-                    .desired_rows(3)
-                    .desired_width(f32::INFINITY)
-                    .hint_text("ここにテキストを入力...");
-
-                let response = ui.add(text_edit);
-
-                // Handle Ctrl+Enter to send
-                if response.has_focus() {
-                    let ctrl = ctx.input(|i| i.modifiers.ctrl);
-                    let shift = ctx.input(|i| i.modifiers.shift);
-                    let enter_pressed = ctx.input(|i| i.key_pressed(egui::Key::Enter));
-
-                    if enter_pressed && ctrl && !shift {
-                        // Ctrl+Enter: send
-                        let text = self.text_input.trim().to_string();
-                        if !text.is_empty() && self.processing_receiver.is_none() {
-                            self.text_input.clear();
-                            self.send_text_message(text);
-                        }
-                    } else if enter_pressed && !shift && !ctrl {
-                        // Plain Enter: do nothing (remove the newline that was just added)
-                        // Remove trailing newline if added by egui
-                        if self.text_input.ends_with('\n') {
-                            self.text_input.pop();
-                        }
+                        })
+                        .response
+                        .on_hover_text("過去の会話を開いて続きから話す (モニタリングを止めるたびに保存されます)");
+                    ui.add(
+                        egui::TextEdit::singleline(&mut self.new_session_name)
+                            .desired_width(140.0)
+                            .hint_text("新しい会話の名前"),
+                    );
+                    let name = self.new_session_name.trim().to_string();
+                    if ui.add_enabled(!name.is_empty(), egui::Button::new("＋ 新規")).clicked() {
+                        open_target = Some(name);
+                        self.new_session_name.clear();
                     }
-                    // Shift+Enter: allow newline (default egui behavior)
+                });
+                if let Some(name) = open_target {
+                    self.start_named_session(&name);
                 }
 
-                ui.add_space(5.0);
+                // Audio profile selector (independent of the persona preset)
+                let mut profile_target = None;
                 ui.horizontal(|ui| {
-                    let send_enabled = !self.text_input.trim().is_empty()
-                        && self.processing_receiver.is_none();
+                    ui.label("音声:");
+                    egui::ComboBox::from_id_salt("audio_profile_selector")
+                        .selected_text(self.audio_profile.as_deref().unwrap_or("(プリセットごと)"))
+                        .show_ui(ui, |ui| {
+                            if ui.selectable_label(self.audio_profile.is_none(), "(プリセットごと)").clicked() {
+                                profile_target = Some(None);
+                            }
+                            for name in &self.audio_profiles {
+                                if ui
+                                    .selectable_label(self.audio_profile.as_deref() == Some(name.as_str()), name)
+                                    .clicked()
+                                {
+                                    profile_target = Some(Some(name.clone()));
+                                }
+                            }
+                        })
+                        .response
+                        .on_hover_text("マイク・閾値・出力デバイスをプリセットと別に選ぶ (プリセットを切り替えても変わりません)");
+                    ui.add(
+                        egui::TextEdit::singleline(&mut self.new_audio_profile_name)
+                            .desired_width(140.0)
+                            .hint_text("新しいプロファイル名"),
+                    );
+                    let name = self.new_audio_profile_name.trim().to_string();
                     if ui
-                        .add_enabled(send_enabled, egui::Button::new("送信 (Ctrl+Enter)"))
+                        .add_enabled(!name.is_empty(), egui::Button::new("＋ 今の音声設定で作成"))
                         .clicked()
                     {
-                        let text = self.text_input.trim().to_string();
-                        if !text.is_empty() {
-                            self.text_input.clear();
-                            self.send_text_message(text);
-                        }
+                        self.create_audio_profile(&name);
+                        self.new_audio_profile_name.clear();
                     }
                 });
+                if let Some(target) = profile_target {
+                    self.select_audio_profile(target);
+                }
+
+                ui.add_space(10.0);
+
+                self.show_status(ui);
+                self.show_start_button(ui);
+
+                ui.add_space(20.0);
+
+                self.show_conversation(ui);
+                self.show_text_input(ui);
 
                 // Warning if keys not set
-                if self.config.openai_api_key.is_empty() || self.config.agent_server_url.is_empty() {
+                if !mock::is_enabled() && (self.config.openai_api_key.is_empty() || self.config.agent_server_url.is_empty()) {
                     ui.add_space(10.0);
                     ui.colored_label(
                         egui::Color32::from_rgb(255, 165, 0),
@@ -1118,10 +3814,30 @@ impl eframe::App for ElizaAgentApp {
     }
 
     fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
-        println!("App exiting, saving memory...");
-        if let Some(ref eliza_client) = self.eliza_client {
-            if let Err(e) = eliza_client.save_memory() {
-                eprintln!("Failed to save memory on exit: {}", e);
+        // 閉じるボタン以外で終了したときも、できるところまで終了処理をする
+        if self.shutdown.is_none() {
+            self.begin_shutdown();
+        }
+        let deadline = self
+            .shutdown
+            .as_ref()
+            .map_or_else(std::time::Instant::now, |s| s.started_at)
+            + SHUTDOWN_WAIT;
+        if let Some(receiver) = self.shutdown.as_mut().and_then(|s| s.chatbox_flush.take()) {
+            let _ = receiver.recv_timeout(deadline.saturating_duration_since(std::time::Instant::now()));
+        }
+        if self.shutdown.as_ref().is_some_and(|s| !s.saved) {
+            // 処理中のリクエストは待たずに、手元の履歴だけで保存する
+            self.processing_receiver = None;
+            self.drive_shutdown();
+        }
+        // 保存中のものも含めて、決まった時間だけ待って終了する
+        for receiver in &self.memory_save_receivers {
+            let remaining = deadline.saturating_duration_since(std::time::Instant::now());
+            match receiver.recv_timeout(remaining) {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => eprintln!("Failed to save memory on exit: {}", e),
+                Err(_) => eprintln!("Gave up waiting for memory save on exit"),
             }
         }
     }
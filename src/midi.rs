@@ -0,0 +1,141 @@
+use crate::error::AppError;
+use midir::{MidiInput, MidiInputConnection};
+use serde::{Deserialize, Serialize};
+use std::sync::mpsc::Sender;
+
+/// MIDI コントローラから受け取ったイベント
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MidiEvent {
+    /// パッド / 鍵盤 (note 番号)
+    Note(u8),
+    /// パッド / 鍵盤を離した (hold_to_talk でだけ使う)
+    NoteOff(u8),
+    /// ノブ / フェーダー (CC 番号, 値 0-127)
+    Control(u8, u8),
+}
+
+impl MidiEvent {
+    /// バインドの照合に使うキー ("note:36", "cc:1")
+    pub fn key(&self) -> String {
+        match self {
+            MidiEvent::Note(note) | MidiEvent::NoteOff(note) => format!("note:{}", note),
+            MidiEvent::Control(cc, _) => format!("cc:{}", cc),
+        }
+    }
+}
+
+/// MIDI の生メッセージを解釈する (Note On / Off と Control Change のみ)
+pub fn parse_message(message: &[u8]) -> Option<MidiEvent> {
+    let status = *message.first()? & 0xF0;
+    match (status, message.get(1), message.get(2)) {
+        (0x90, Some(&note), Some(&velocity)) if velocity > 0 => Some(MidiEvent::Note(note)),
+        // velocity 0 の Note On も Note Off として送ってくる機器が多い
+        (0x80, Some(&note), _) | (0x90, Some(&note), Some(0)) => Some(MidiEvent::NoteOff(note)),
+        (0xB0, Some(&cc), Some(&value)) => Some(MidiEvent::Control(cc, value)),
+        _ => None,
+    }
+}
+
+/// バインドで選べるアクション
+pub const ACTIONS: [(&str, &str); 8] = [
+    ("toggle", "開始 / 停止"),
+    ("next_preset", "次のプリセット"),
+    ("prev_preset", "前のプリセット"),
+    ("sensitivity_up", "感度を上げる"),
+    ("sensitivity_down", "感度を下げる"),
+    ("threshold_knob", "開始閾値 (ノブ)"),
+    ("send_text", "定型文を送信"),
+    ("hold_to_talk", "押している間だけ録音"),
+];
+
+pub fn action_label(action: &str) -> &str {
+    ACTIONS
+        .iter()
+        .find(|(name, _)| *name == action)
+        .map(|(_, label)| *label)
+        .unwrap_or(action)
+}
+
+/// MIDI イベント → アクションの割り当て
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MidiBinding {
+    /// MidiEvent::key() の形式 ("note:36", "cc:1")
+    pub trigger: String,
+    /// ACTIONS の名前
+    pub action: String,
+    /// send_text で送る文
+    #[serde(default)]
+    pub text: String,
+}
+
+impl Default for MidiBinding {
+    fn default() -> Self {
+        Self {
+            trigger: "note:36".to_string(),
+            action: "toggle".to_string(),
+            text: String::new(),
+        }
+    }
+}
+
+/// ノブの値 (0-127) を閾値 (0.001〜0.3, 対数) に変換する
+pub fn knob_to_threshold(value: u8) -> f32 {
+    let t = value.min(127) as f32 / 127.0;
+    0.001 * (0.3f32 / 0.001).powf(t)
+}
+
+/// Get list of MIDI input port names
+pub fn list_input_ports() -> Vec<String> {
+    let Ok(midi_in) = MidiInput::new("eliza-agent") else {
+        return Vec::new();
+    };
+    midi_in
+        .ports()
+        .iter()
+        .filter_map(|port| midi_in.port_name(port).ok())
+        .collect()
+}
+
+/// MIDI 入力ポートに接続する。接続はハンドルを drop すると閉じる
+/// (port_name が空なら最初のポート)
+pub fn connect(port_name: &str, sender: Sender<MidiEvent>) -> Result<MidiInputConnection<()>, AppError> {
+    let midi_in = MidiInput::new("eliza-agent")
+        .map_err(|e| AppError::Config(format!("Failed to open MIDI input: {}", e)))?;
+    let ports = midi_in.ports();
+    let port = ports
+        .iter()
+        .find(|p| port_name.is_empty() || midi_in.port_name(p).map(|n| n == port_name).unwrap_or(false))
+        .ok_or_else(|| AppError::Config(format!("MIDI port '{}' not found", port_name)))?;
+    let name = midi_in.port_name(port).unwrap_or_default();
+
+    let connection = midi_in
+        .connect(
+            port,
+            "eliza-agent-input",
+            move |_, message, _| {
+                if let Some(event) = parse_message(message) {
+                    let _ = sender.send(event);
+                }
+            },
+            (),
+        )
+        .map_err(|e| AppError::Config(format!("Failed to connect MIDI port: {}", e)))?;
+    println!("[MIDI] Connected to {}", name);
+    Ok(connection)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_message() {
+        assert_eq!(parse_message(&[0x90, 36, 100]), Some(MidiEvent::Note(36)));
+        assert_eq!(parse_message(&[0x91, 36, 0]), Some(MidiEvent::NoteOff(36)));
+        assert_eq!(parse_message(&[0x80, 36, 64]), Some(MidiEvent::NoteOff(36)));
+        assert_eq!(MidiEvent::NoteOff(36).key(), MidiEvent::Note(36).key());
+        assert_eq!(parse_message(&[0xB0, 1, 64]), Some(MidiEvent::Control(1, 64)));
+        assert_eq!(MidiEvent::Control(1, 64).key(), "cc:1");
+        assert!((knob_to_threshold(127) - 0.3).abs() < 1e-4);
+    }
+}
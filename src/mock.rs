@@ -0,0 +1,57 @@
+//! `--mock` で起動したときの偽の書き起こし・返答 (キーやサーバー、VRChat なしで UI を動かす)
+
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static TRANSCRIPTION_INDEX: AtomicUsize = AtomicUsize::new(0);
+static REPLY_INDEX: AtomicUsize = AtomicUsize::new(0);
+
+/// 録音のたびに順に返す書き起こし
+const TRANSCRIPTIONS: [&str; 5] = [
+    "こんにちは、聞こえてる？",
+    "今日はどんなワールドに行こうかな",
+    "おすすめの映画を教えて",
+    "ちょっと眠くなってきた",
+    "ありがとう、またね",
+];
+
+/// 返答の台本 ({} に発話が入る)
+const REPLIES: [&str; 4] = [
+    "「{}」ですね。モックモードなので、台本どおりにお返事しています。",
+    "なるほど、「{}」。もう少し詳しく聞かせてください。",
+    "それは面白いですね！ (モック返答)",
+    "[sfx:laugh] 「{}」と言いましたね。",
+];
+
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+    println!("[Mock] Mock mode enabled: no API keys, servers or VRChat are used");
+}
+
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// 次の書き起こし
+pub fn next_transcription() -> String {
+    let i = TRANSCRIPTION_INDEX.fetch_add(1, Ordering::Relaxed);
+    TRANSCRIPTIONS[i % TRANSCRIPTIONS.len()].to_string()
+}
+
+/// 最後の発話に対する台本の返答
+pub fn reply(user_message: &str) -> String {
+    let i = REPLY_INDEX.fetch_add(1, Ordering::Relaxed);
+    REPLIES[i % REPLIES.len()].replace("{}", user_message.trim())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reply_cycles_through_script() {
+        let replies: Vec<String> = (0..REPLIES.len() + 1).map(|_| reply("やあ")).collect();
+        assert_eq!(replies[0], replies[REPLIES.len()]);
+        assert!(replies.iter().any(|r| r.contains("やあ")));
+    }
+}
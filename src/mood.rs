@@ -0,0 +1,110 @@
+/// キャラクターの気分
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Mood {
+    Neutral,
+    Happy,
+    Annoyed,
+    Energetic,
+}
+
+impl Mood {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Mood::Neutral => "ふつう",
+            Mood::Happy => "上機嫌",
+            Mood::Annoyed => "不機嫌",
+            Mood::Energetic => "ハイテンション",
+        }
+    }
+
+    pub fn emoji(&self) -> &'static str {
+        match self {
+            Mood::Neutral => "😐",
+            Mood::Happy => "😊",
+            Mood::Annoyed => "😠",
+            Mood::Energetic => "🤩",
+        }
+    }
+
+    /// アバターの表情パラメータに送る値
+    pub fn expression_value(&self) -> i32 {
+        match self {
+            Mood::Neutral => 0,
+            Mood::Happy => 1,
+            Mood::Annoyed => 2,
+            Mood::Energetic => 3,
+        }
+    }
+
+    /// system プロンプトに添える一文
+    pub fn prompt_note(&self) -> String {
+        format!(
+            "あなたの現在の気分は「{}」です。返答の口調にさりげなく反映してください。",
+            self.label()
+        )
+    }
+}
+
+const POSITIVE_WORDS: [&str; 10] = [
+    "ありがとう", "嬉しい", "うれしい", "楽しい", "好き", "すごい", "かわいい", "可愛い", "いいね", "thank",
+];
+const NEGATIVE_WORDS: [&str; 9] = [
+    "うるさい", "嫌い", "きらい", "バカ", "ばか", "つまらない", "黙れ", "ムカつく", "最悪",
+];
+const EXCITED_WORDS: [&str; 6] = ["!", "！", "やった", "最高", "テンション", "わーい"];
+
+/// 会話の感情から更新される気分 (valence = 快/不快, arousal = 興奮度)
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MoodState {
+    valence: f32,
+    arousal: f32,
+}
+
+impl MoodState {
+    /// 発話 1 つ分の感情を反映する (古い感情は少しずつ薄れる)
+    pub fn update(&mut self, text: &str) {
+        let lower = text.to_lowercase();
+        let count = |words: &[&str]| words.iter().filter(|w| lower.contains(*w)).count() as f32;
+
+        self.valence = self.valence * 0.7 + 0.4 * (count(&POSITIVE_WORDS) - count(&NEGATIVE_WORDS));
+        self.arousal = self.arousal * 0.7 + 0.3 * count(&EXCITED_WORDS);
+        self.valence = self.valence.clamp(-1.0, 1.0);
+        self.arousal = self.arousal.clamp(0.0, 1.0);
+    }
+
+    pub fn mood(&self) -> Mood {
+        if self.valence <= -0.3 {
+            Mood::Annoyed
+        } else if self.arousal >= 0.5 {
+            Mood::Energetic
+        } else if self.valence >= 0.3 {
+            Mood::Happy
+        } else {
+            Mood::Neutral
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mood_transitions() {
+        let mut state = MoodState::default();
+        assert_eq!(state.mood(), Mood::Neutral);
+
+        state.update("ありがとう、楽しいね");
+        assert_eq!(state.mood(), Mood::Happy);
+
+        state.update("うるさい、バカ");
+        state.update("つまらない");
+        assert_eq!(state.mood(), Mood::Annoyed);
+
+        // 何も感情のない発話が続くと元に戻る
+        for _ in 0..5 {
+            state.update("今日は雨だね");
+        }
+        assert_eq!(state.mood(), Mood::Neutral);
+    }
+}
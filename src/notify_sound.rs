@@ -0,0 +1,99 @@
+//! 状態が変わったことを知らせる通知音
+
+use crate::config::Config;
+use crate::playback;
+use std::path::PathBuf;
+use std::time::Duration;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SoundEvent {
+    RecordingStarted,
+    ReplyPosted,
+    Error,
+}
+
+/// 鳴らすもの
+#[derive(Debug, Clone, PartialEq)]
+enum Sound {
+    File(PathBuf),
+    /// 内蔵のビープ音 (周波数 Hz, 長さ)
+    Tone(f32, Duration),
+}
+
+impl SoundEvent {
+    pub const ALL: [SoundEvent; 3] = [SoundEvent::RecordingStarted, SoundEvent::ReplyPosted, SoundEvent::Error];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            SoundEvent::RecordingStarted => "録音開始",
+            SoundEvent::ReplyPosted => "返答送信",
+            SoundEvent::Error => "エラー",
+        }
+    }
+
+    /// 音声ファイルが設定されていないときのビープ音 (高い音 = 開始、低い音 = エラー)
+    fn default_tone(&self) -> Sound {
+        match self {
+            SoundEvent::RecordingStarted => Sound::Tone(880.0, Duration::from_millis(120)),
+            SoundEvent::ReplyPosted => Sound::Tone(660.0, Duration::from_millis(150)),
+            SoundEvent::Error => Sound::Tone(220.0, Duration::from_millis(400)),
+        }
+    }
+}
+
+/// 有効なら鳴らす音 (無効なら None)
+fn sound_for(config: &Config, event: SoundEvent) -> Option<Sound> {
+    let (enabled, path) = match event {
+        SoundEvent::RecordingStarted => (config.notify_recording_enabled, &config.notify_recording_sound),
+        SoundEvent::ReplyPosted => (config.notify_reply_enabled, &config.notify_reply_sound),
+        SoundEvent::Error => (config.notify_error_enabled, &config.notify_error_sound),
+    };
+    if !enabled {
+        return None;
+    }
+    let path = path.trim();
+    if path.is_empty() {
+        Some(event.default_tone())
+    } else {
+        Some(Sound::File(PathBuf::from(path)))
+    }
+}
+
+/// バックグラウンドで通知音を鳴らす (エラーはログのみ)
+pub fn play(config: &Config, event: SoundEvent) {
+    let Some(sound) = sound_for(config, event) else {
+        return;
+    };
+    let device = config.notify_output_device.clone();
+    let volume = config.notify_volume;
+    std::thread::spawn(move || {
+        let result = match sound {
+            Sound::File(path) => playback::play_file(&path, &device, volume),
+            Sound::Tone(frequency, duration) => playback::play_tone(frequency, duration, &device, volume),
+        };
+        if let Err(e) = result {
+            eprintln!("[Notify] {:?}: {}", event, e);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sound_for() {
+        let mut config = Config::default();
+        assert_eq!(sound_for(&config, SoundEvent::Error), None);
+
+        config.notify_error_enabled = true;
+        assert_eq!(sound_for(&config, SoundEvent::Error), Some(SoundEvent::Error.default_tone()));
+
+        config.notify_error_sound = " C:\\sounds\\error.wav ".to_string();
+        assert_eq!(
+            sound_for(&config, SoundEvent::Error),
+            Some(Sound::File(PathBuf::from("C:\\sounds\\error.wav")))
+        );
+        assert_eq!(sound_for(&config, SoundEvent::ReplyPosted), None);
+    }
+}
@@ -2,37 +2,54 @@ use serde::{Deserialize, Serialize};
 use std::path::Path;
 
 const OPENAI_API_URL: &str = "https://api.openai.com/v1/audio/transcriptions";
+const OPENAI_CHAT_URL: &str = "https://api.openai.com/v1/chat/completions";
+const OPENAI_SPEECH_URL: &str = "https://api.openai.com/v1/audio/speech";
+const TTS_MODEL: &str = "gpt-4o-mini-tts";
+
+/// 読み上げに使える声
+pub const TTS_VOICES: [&str; 10] = [
+    "alloy", "ash", "ballad", "coral", "echo", "fable", "nova", "onyx", "sage", "shimmer",
+];
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct TranscriptionResponse {
     pub text: String,
+    /// verbose_json の場合のみ返る ("japanese" など)。ISO 639-1 に正規化して使う
+    #[serde(default)]
+    pub language: Option<String>,
+    /// timestamp_granularities[]=word を要求した場合のみ返る
+    #[serde(default)]
+    pub words: Vec<WordTimestamp>,
+}
+
+/// 単語ごとの発話時刻 (秒, 録音の先頭から)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WordTimestamp {
+    pub word: String,
+    pub start: f32,
+    pub end: f32,
 }
 
-#[derive(Debug)]
+#[derive(Debug, thiserror::Error)]
 pub enum OpenAIError {
+    #[error("Network error: {0}")]
     NetworkError(String),
-    ApiError(String),
+    /// サーバーがエラーの HTTP ステータスを返した (ステータスコード, メッセージ)
+    #[error("API error: {1}")]
+    HttpStatus(u16, String),
+    #[error("File error: {0}")]
     FileError(String),
+    #[error("Parse error: {0}")]
     ParseError(String),
 }
 
-impl std::fmt::Display for OpenAIError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            OpenAIError::NetworkError(msg) => write!(f, "Network error: {}", msg),
-            OpenAIError::ApiError(msg) => write!(f, "API error: {}", msg),
-            OpenAIError::FileError(msg) => write!(f, "File error: {}", msg),
-            OpenAIError::ParseError(msg) => write!(f, "Parse error: {}", msg),
-        }
-    }
-}
-
-impl std::error::Error for OpenAIError {}
-
 pub struct OpenAIClient {
     api_key: String,
     model: String,
     prompt: Option<String>,
+    detect_language: bool,
+    word_timestamps: bool,
+    timeout: Option<std::time::Duration>,
 }
 
 impl OpenAIClient {
@@ -47,10 +64,45 @@ impl OpenAIClient {
             api_key,
             model,
             prompt: prompt_option,
+            detect_language: false,
+            word_timestamps: false,
+            timeout: None,
         }
     }
 
-    pub fn transcribe_audio(&self, audio_file_path: &Path) -> Result<String, OpenAIError> {
+    /// Append the previous utterances to the prompt so proper nouns stay consistent
+    pub fn with_context(mut self, context: &str, max_chars: usize) -> Self {
+        let prompt = build_prompt_with_context(
+            self.prompt.as_deref().unwrap_or_default(),
+            context,
+            max_chars,
+        );
+        self.prompt = if prompt.is_empty() { None } else { Some(prompt) };
+        self
+    }
+
+    /// Detect the spoken language (verbose_json for whisper-1, text heuristic otherwise)
+    pub fn with_language_detection(mut self, enabled: bool) -> Self {
+        self.detect_language = enabled;
+        self
+    }
+
+    /// Give up the transcription request after the given number of seconds (0 = no limit)
+    pub fn with_timeout(mut self, secs: u64) -> Self {
+        self.timeout = (secs > 0).then(|| std::time::Duration::from_secs(secs));
+        self
+    }
+
+    /// Request word-level timestamps (whisper models only)
+    pub fn with_word_timestamps(mut self, enabled: bool) -> Self {
+        self.word_timestamps = enabled;
+        self
+    }
+
+    pub fn transcribe_audio(
+        &self,
+        audio_file_path: &Path,
+    ) -> Result<TranscriptionResponse, OpenAIError> {
         // Check if file exists
         if !audio_file_path.exists() {
             return Err(OpenAIError::FileError(format!(
@@ -93,8 +145,23 @@ impl OpenAIClient {
             println!("No prompt specified");
         }
 
+        // gpt-4o 系は verbose_json 非対応なので whisper 系のみ language / words を要求する
+        let is_whisper = self.model.starts_with("whisper");
+        if (self.detect_language || self.word_timestamps) && is_whisper {
+            form = form.text("response_format", "verbose_json");
+        }
+        if self.word_timestamps && is_whisper {
+            form = form.text("timestamp_granularities[]", "word");
+        }
+
         // Send request
-        let client = reqwest::blocking::Client::new();
+        let mut builder = reqwest::blocking::Client::builder();
+        if let Some(timeout) = self.timeout {
+            builder = builder.timeout(timeout);
+        }
+        let client = builder
+            .build()
+            .map_err(|e| OpenAIError::NetworkError(format!("Failed to build client: {}", e)))?;
         let response = client
             .post(OPENAI_API_URL)
             .header("Authorization", format!("Bearer {}", self.api_key))
@@ -108,14 +175,14 @@ impl OpenAIClient {
             .map_err(|e| OpenAIError::NetworkError(format!("Failed to read response: {}", e)))?;
 
         if !status.is_success() {
-            return Err(OpenAIError::ApiError(format!(
-                "API returned status {}: {}",
-                status, response_text
-            )));
+            return Err(OpenAIError::HttpStatus(
+                status.as_u16(),
+                format!("API returned status {}: {}", status, response_text),
+            ));
         }
 
         // Parse response
-        let transcription: TranscriptionResponse =
+        let mut transcription: TranscriptionResponse =
             serde_json::from_str(&response_text).map_err(|e| {
                 OpenAIError::ParseError(format!(
                     "Failed to parse response: {}. Response was: {}",
@@ -123,11 +190,205 @@ impl OpenAIClient {
                 ))
             })?;
 
+        if self.detect_language {
+            transcription.language = transcription
+                .language
+                .as_deref()
+                .map(normalize_language)
+                .or_else(|| detect_language_from_text(&transcription.text));
+            println!("Detected language: {:?}", transcription.language);
+        } else {
+            transcription.language = None;
+        }
+
         println!("Transcription result: {}", transcription.text);
-        Ok(transcription.text)
+        Ok(transcription)
+    }
+
+    /// 安価な LLM で書き起こしを整える (句読点・フィラー・明らかな誤認識の修正)
+    pub fn cleanup_transcription(
+        &self,
+        text: &str,
+        model: &str,
+        instruction: &str,
+    ) -> Result<String, OpenAIError> {
+        println!("Cleaning up transcription with {}", model);
+        let cleaned = self.chat_completion(model, instruction, text)?;
+        println!("Cleaned transcription: {}", cleaned);
+        Ok(cleaned)
+    }
+
+    /// テキストを読み上げた音声 (WAV) を返す
+    pub fn synthesize_speech(&self, text: &str, voice: &str) -> Result<Vec<u8>, OpenAIError> {
+        let request = serde_json::json!({
+            "model": TTS_MODEL,
+            "voice": voice,
+            "input": text,
+            "response_format": "wav",
+        });
+
+        let client = reqwest::blocking::Client::new();
+        let response = client
+            .post(OPENAI_SPEECH_URL)
+            .header("Authorization", format!("Bearer {}", self.api_key))
+            .json(&request)
+            .send()
+            .map_err(|e| OpenAIError::NetworkError(format!("Failed to send request: {}", e)))?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let response_text = response.text().unwrap_or_default();
+            return Err(OpenAIError::HttpStatus(
+                status.as_u16(),
+                format!("API returned status {}: {}", status, response_text),
+            ));
+        }
+        response
+            .bytes()
+            .map(|b| b.to_vec())
+            .map_err(|e| OpenAIError::NetworkError(format!("Failed to read response: {}", e)))
+    }
+
+    /// Single-turn Chat Completions request (system instruction + user text)
+    pub fn chat_completion(
+        &self,
+        model: &str,
+        instruction: &str,
+        text: &str,
+    ) -> Result<String, OpenAIError> {
+        if crate::mock::is_enabled() {
+            return Ok(text.to_string());
+        }
+        let request = serde_json::json!({
+            "model": model,
+            "temperature": 0.0,
+            "messages": [
+                { "role": "system", "content": instruction },
+                { "role": "user", "content": text },
+            ],
+        });
+
+        let client = reqwest::blocking::Client::new();
+        let response = client
+            .post(OPENAI_CHAT_URL)
+            .header("Authorization", format!("Bearer {}", self.api_key))
+            .json(&request)
+            .send()
+            .map_err(|e| OpenAIError::NetworkError(format!("Failed to send request: {}", e)))?;
+
+        let status = response.status();
+        let response_text = response
+            .text()
+            .map_err(|e| OpenAIError::NetworkError(format!("Failed to read response: {}", e)))?;
+
+        if !status.is_success() {
+            return Err(OpenAIError::HttpStatus(
+                status.as_u16(),
+                format!("API returned status {}: {}", status, response_text),
+            ));
+        }
+
+        let value: serde_json::Value = serde_json::from_str(&response_text).map_err(|e| {
+            OpenAIError::ParseError(format!(
+                "Failed to parse response: {}. Response was: {}",
+                e, response_text
+            ))
+        })?;
+        value["choices"][0]["message"]["content"]
+            .as_str()
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+            .ok_or_else(|| {
+                OpenAIError::ParseError(format!("No content in response: {}", response_text))
+            })
     }
 }
 
+/// prompt の後ろに直前の会話を付け足す。
+/// Whisper は prompt の末尾ほど重視するので、context は末尾 max_chars 文字だけ残す
+pub fn build_prompt_with_context(prompt: &str, context: &str, max_chars: usize) -> String {
+    let context = context.trim();
+    if context.is_empty() || max_chars == 0 {
+        return prompt.to_string();
+    }
+    let char_count = context.chars().count();
+    let tail: String = context
+        .chars()
+        .skip(char_count.saturating_sub(max_chars))
+        .collect();
+    if prompt.is_empty() {
+        tail
+    } else {
+        format!("{}\n{}", prompt, tail)
+    }
+}
+
+/// Whisper の言語名 ("japanese") を ISO 639-1 ("ja") に正規化する
+pub fn normalize_language(language: &str) -> String {
+    let lower = language.trim().to_lowercase();
+    let code = match lower.as_str() {
+        "japanese" => "ja",
+        "english" => "en",
+        "chinese" => "zh",
+        "korean" => "ko",
+        "french" => "fr",
+        "german" => "de",
+        "spanish" => "es",
+        "russian" => "ru",
+        "portuguese" => "pt",
+        "italian" => "it",
+        _ => return lower,
+    };
+    code.to_string()
+}
+
+/// ISO 639-1 の言語コードを返答の指示に使う言語名にする
+pub fn language_name(code: &str) -> &str {
+    match code {
+        "ja" => "日本語",
+        "en" => "English",
+        "zh" => "中文",
+        "ko" => "한국어",
+        "fr" => "Français",
+        "de" => "Deutsch",
+        "es" => "Español",
+        "ru" => "Русский",
+        "pt" => "Português",
+        "it" => "Italiano",
+        _ => code,
+    }
+}
+
+/// 文字種から言語をざっくり推定する (verbose_json が使えないモデル用)
+pub fn detect_language_from_text(text: &str) -> Option<String> {
+    let mut kana = 0usize;
+    let mut han = 0usize;
+    let mut hangul = 0usize;
+    let mut latin = 0usize;
+    for c in text.chars() {
+        match c {
+            '\u{3040}'..='\u{30FF}' => kana += 1,
+            '\u{4E00}'..='\u{9FFF}' => han += 1,
+            '\u{AC00}'..='\u{D7AF}' => hangul += 1,
+            'a'..='z' | 'A'..='Z' => latin += 1,
+            _ => {}
+        }
+    }
+
+    let code = if kana > 0 {
+        "ja"
+    } else if hangul > 0 && hangul >= latin {
+        "ko"
+    } else if han > 0 && han >= latin {
+        "zh"
+    } else if latin > 0 {
+        "en"
+    } else {
+        return None;
+    };
+    Some(code.to_string())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -153,4 +414,22 @@ mod tests {
         );
         assert_eq!(client.prompt, None);
     }
+
+    #[test]
+    fn test_detect_language_from_text() {
+        assert_eq!(detect_language_from_text("こんにちは、元気？"), Some("ja".to_string()));
+        assert_eq!(detect_language_from_text("Hello, how are you?"), Some("en".to_string()));
+        assert_eq!(detect_language_from_text("你好世界"), Some("zh".to_string()));
+        assert_eq!(detect_language_from_text("...!?"), None);
+        assert_eq!(normalize_language("Japanese"), "ja");
+        assert_eq!(language_name("en"), "English");
+        assert_eq!(language_name("xx"), "xx");
+    }
+
+    #[test]
+    fn test_build_prompt_with_context() {
+        assert_eq!(build_prompt_with_context("base", "", 10), "base");
+        assert_eq!(build_prompt_with_context("", "あいうえお", 3), "うえお");
+        assert_eq!(build_prompt_with_context("base", "abcdef", 4), "base\ncdef");
+    }
 }
@@ -0,0 +1,140 @@
+use chrono::{DateTime, Local};
+use rosc::OscType;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+/// 覚えておくメッセージの件数
+const MAX_ENTRIES: usize = 500;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Direction {
+    Sent,
+    Received,
+}
+
+/// 送受信した OSC メッセージ 1 件 (デバッグ用)
+#[derive(Debug, Clone)]
+pub struct OscLogEntry {
+    pub time: DateTime<Local>,
+    pub direction: Direction,
+    /// 送信先 / 送信元
+    pub peer: String,
+    pub addr: String,
+    pub args: String,
+    /// 送信に失敗した理由
+    pub error: Option<String>,
+}
+
+static LOG: Mutex<VecDeque<OscLogEntry>> = Mutex::new(VecDeque::new());
+
+pub fn record(direction: Direction, peer: &str, addr: &str, args: &[OscType], error: Option<String>) {
+    let mut log = LOG.lock().unwrap_or_else(|e| e.into_inner());
+    log.push_back(OscLogEntry {
+        time: Local::now(),
+        direction,
+        peer: peer.to_string(),
+        addr: addr.to_string(),
+        args: format_args(args),
+        error,
+    });
+    while log.len() > MAX_ENTRIES {
+        log.pop_front();
+    }
+}
+
+pub fn entries() -> Vec<OscLogEntry> {
+    LOG.lock().unwrap_or_else(|e| e.into_inner()).iter().cloned().collect()
+}
+
+pub fn clear() {
+    LOG.lock().unwrap_or_else(|e| e.into_inner()).clear();
+}
+
+fn format_args(args: &[OscType]) -> String {
+    args.iter()
+        .map(|arg| match arg {
+            OscType::String(s) => format!("{:?}", s),
+            OscType::Int(i) => i.to_string(),
+            OscType::Float(f) => format!("{}f", f),
+            OscType::Bool(b) => b.to_string(),
+            other => format!("{:?}", other),
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// 手動送信で選べる引数の型
+pub const ARG_TYPES: [&str; 4] = ["String", "Int", "Float", "Bool"];
+
+/// 手動送信フォームの値を OSC の引数にする
+pub fn parse_arg(arg_type: &str, value: &str) -> Result<OscType, String> {
+    let value = value.trim();
+    match arg_type {
+        "String" => Ok(OscType::String(value.to_string())),
+        "Int" => value
+            .parse()
+            .map(OscType::Int)
+            .map_err(|_| format!("Not an integer: {}", value)),
+        "Float" => value
+            .parse()
+            .map(OscType::Float)
+            .map_err(|_| format!("Not a number: {}", value)),
+        "Bool" => match value.to_lowercase().as_str() {
+            "true" | "1" => Ok(OscType::Bool(true)),
+            "false" | "0" => Ok(OscType::Bool(false)),
+            _ => Err(format!("Not a bool: {}", value)),
+        },
+        _ => Err(format!("Unknown type: {}", arg_type)),
+    }
+}
+
+/// OSC コンソールの表示条件と手動送信フォーム
+pub struct ConsoleState {
+    pub filter: String,
+    pub show_sent: bool,
+    pub show_received: bool,
+    pub errors_only: bool,
+    pub send_addr: String,
+    pub send_type: String,
+    pub send_value: String,
+}
+
+impl Default for ConsoleState {
+    fn default() -> Self {
+        Self {
+            filter: String::new(),
+            show_sent: true,
+            show_received: true,
+            errors_only: false,
+            send_addr: "/chatbox/input".to_string(),
+            send_type: "String".to_string(),
+            send_value: String::new(),
+        }
+    }
+}
+
+impl ConsoleState {
+    pub fn matches(&self, entry: &OscLogEntry) -> bool {
+        let direction = match entry.direction {
+            Direction::Sent => self.show_sent,
+            Direction::Received => self.show_received,
+        };
+        direction
+            && (!self.errors_only || entry.error.is_some())
+            && (self.filter.is_empty() || entry.addr.contains(self.filter.trim()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_arg() {
+        assert_eq!(parse_arg("Int", " 3 "), Ok(OscType::Int(3)));
+        assert_eq!(parse_arg("Float", "0.5"), Ok(OscType::Float(0.5)));
+        assert_eq!(parse_arg("Bool", "TRUE"), Ok(OscType::Bool(true)));
+        assert_eq!(parse_arg("String", "hi"), Ok(OscType::String("hi".to_string())));
+        assert!(parse_arg("Int", "x").is_err());
+    }
+}
@@ -0,0 +1,59 @@
+//! チャットボックスに収まらない返答の全文を paste サービスに上げ、短い URL を添える
+
+use crate::error::AppError;
+use crate::mock;
+use std::time::Duration;
+
+pub const DEFAULT_PASTE_ENDPOINT: &str = "https://paste.rs/";
+
+/// text を endpoint に POST し、応答本文の URL を返す (paste.rs のように本文で URL を返すサービス向け)
+pub fn upload(endpoint: &str, text: &str) -> Result<String, AppError> {
+    if mock::is_enabled() {
+        println!("[Mock] Paste upload skipped ({} chars)", text.chars().count());
+        return Ok("https://paste.invalid/mock".to_string());
+    }
+    let client = reqwest::blocking::Client::builder()
+        .timeout(Duration::from_secs(10))
+        .build()
+        .map_err(|e| AppError::Other(format!("Failed to build HTTP client: {}", e)))?;
+    let response = client
+        .post(endpoint)
+        .header("Content-Type", "text/plain; charset=utf-8")
+        .body(text.to_string())
+        .send()
+        .map_err(|e| AppError::Network(format!("Failed to upload: {}", e)))?;
+    let status = response.status();
+    let body = response
+        .text()
+        .map_err(|e| AppError::Network(format!("Failed to read paste response: {}", e)))?;
+    if !status.is_success() {
+        return Err(AppError::http(status.as_u16(), format!("Paste endpoint returned status {}: {}", status, body)));
+    }
+    let url = body.trim();
+    if !url.starts_with("http") {
+        return Err(AppError::Config(format!("Paste endpoint did not return a URL: {}", url)));
+    }
+    Ok(url.to_string())
+}
+
+/// text を切り詰めて末尾に URL を付け、max_chars に収める
+pub fn with_link(text: &str, url: &str, max_chars: usize) -> String {
+    let suffix = format!("… {}", url);
+    let budget = max_chars.saturating_sub(suffix.chars().count());
+    let head: String = text.chars().take(budget).collect();
+    format!("{}{}", head.trim_end(), suffix)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_with_link() {
+        let text = "あ".repeat(200);
+        let result = with_link(&text, "https://paste.rs/abc", 144);
+        assert_eq!(result.chars().count(), 144);
+        assert!(result.ends_with("… https://paste.rs/abc"));
+        assert!(result.starts_with("ああ"));
+    }
+}
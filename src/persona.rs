@@ -0,0 +1,94 @@
+use crate::config::Config;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+
+/// アプリに同梱している人格プロンプト
+const BUILTIN_PERSONAS: &[&str] = &[
+    include_str!("../personas/lisa.json"),
+    include_str!("../personas/tsundere.json"),
+    include_str!("../personas/listener.json"),
+    include_str!("../personas/english_partner.json"),
+];
+
+/// 人格プロンプトのライブラリ項目
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PersonaTemplate {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    pub prompt: String,
+    /// 同梱 (true) かユーザーフォルダ (false) か
+    #[serde(skip)]
+    pub builtin: bool,
+}
+
+/// Get the user persona folder (~/.config/eliza-agent/personas)
+pub fn user_personas_dir() -> Result<PathBuf, String> {
+    let dir = Config::config_dir()?.join("personas");
+    if !dir.exists() {
+        fs::create_dir_all(&dir)
+            .map_err(|e| format!("Failed to create personas directory: {}", e))?;
+    }
+    Ok(dir)
+}
+
+/// Load built-in personas followed by the user's personas (*.json)
+pub fn load_library() -> Vec<PersonaTemplate> {
+    let mut library: Vec<PersonaTemplate> = BUILTIN_PERSONAS
+        .iter()
+        .filter_map(|json| match serde_json::from_str::<PersonaTemplate>(json) {
+            Ok(mut persona) => {
+                persona.builtin = true;
+                Some(persona)
+            }
+            Err(e) => {
+                eprintln!("Failed to parse built-in persona: {}", e);
+                None
+            }
+        })
+        .collect();
+
+    let dir = match user_personas_dir() {
+        Ok(dir) => dir,
+        Err(e) => {
+            eprintln!("{}", e);
+            return library;
+        }
+    };
+    let mut paths: Vec<PathBuf> = fs::read_dir(&dir)
+        .map(|rd| {
+            rd.filter_map(|e| e.ok())
+                .map(|e| e.path())
+                .filter(|p| p.extension().and_then(|x| x.to_str()) == Some("json"))
+                .collect()
+        })
+        .unwrap_or_default();
+    paths.sort();
+
+    for path in paths {
+        match fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|c| serde_json::from_str::<PersonaTemplate>(&c).map_err(|e| e.to_string()))
+        {
+            Ok(persona) => library.push(persona),
+            Err(e) => eprintln!("Failed to load persona {:?}: {}", path, e),
+        }
+    }
+
+    library
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin_personas_parse() {
+        for json in BUILTIN_PERSONAS {
+            let persona: PersonaTemplate = serde_json::from_str(json).unwrap();
+            assert!(!persona.name.is_empty());
+            assert!(!persona.prompt.is_empty());
+        }
+    }
+}
@@ -0,0 +1,227 @@
+//! モニタリング → 録音 → 処理 の状態遷移 (egui に依存しない)
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AppState {
+    Idle,
+    Monitoring,
+    Recording,
+    Processing,
+    CalibratingSilence, // キャリブレーション: 無音フェーズ (2秒)
+    CalibratingVoice,   // キャリブレーション: 発話フェーズ (2秒以上)
+}
+
+/// 状態を動かすきっかけ
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PipelineEvent {
+    /// Start ボタン / MIDI / VRChat のミュート
+    StartPressed,
+    /// Stop ボタン / MIDI / ミュート解除 / Safety word / プリセット切り替え
+    StopPressed,
+    /// 開始閾値を超える音が続いた
+    VoiceDetected,
+    /// 録音中に無音が続いた
+    SilenceDetected,
+    /// 録音中に「今すぐ送る」が押された (無音を待たずに送る)
+    SendNowPressed,
+    /// 録音が空だった / 保存に失敗した
+    RecordingDiscarded,
+    /// バックグラウンド処理が終わった (成功・失敗・聞き流しを問わない)
+    ProcessingFinished,
+    /// Agent がおやすみを検出した
+    SleepRequested,
+    /// マイクを開けなかった
+    MicrophoneFailed,
+    CalibrationRequested,
+    /// キャリブレーションの無音フェーズが終わった
+    CalibrationSilenceDone,
+}
+
+/// 遷移に伴ってアプリ側が行うこと
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Effect {
+    OpenMicrophone,
+    CloseMicrophone,
+    /// 録音を止めて WAV に保存し、パイプラインに回す
+    ProcessRecording,
+    OpenCalibrationMicrophone,
+    /// 発話フェーズの平均 RMS を開始閾値にしてマイクを閉じる
+    FinishCalibration,
+}
+
+/// 録音パイプラインの状態機械
+///
+/// 処理中に Stop された場合は Idle になり、処理が終わってもモニタリングは再開しない
+#[derive(Debug)]
+pub struct PipelineController {
+    state: AppState,
+}
+
+impl Default for PipelineController {
+    fn default() -> Self {
+        Self {
+            state: AppState::Idle,
+        }
+    }
+}
+
+impl PipelineController {
+    pub fn state(&self) -> AppState {
+        self.state
+    }
+
+    pub fn is_idle(&self) -> bool {
+        self.state == AppState::Idle
+    }
+
+    /// イベントを受けて状態を進め、アプリ側でやるべきことを返す
+    ///
+    /// その状態で意味のないイベントは無視する (状態は変わらない)
+    pub fn handle(&mut self, event: PipelineEvent) -> Option<Effect> {
+        use AppState::*;
+        use PipelineEvent::*;
+
+        let (next, effect) = match (self.state, event) {
+            (Idle, StartPressed) => (Monitoring, Some(Effect::OpenMicrophone)),
+            (Idle, CalibrationRequested) => (CalibratingSilence, Some(Effect::OpenCalibrationMicrophone)),
+            (Idle, StopPressed) => (Idle, None),
+            (CalibratingVoice, StopPressed) => (Idle, Some(Effect::FinishCalibration)),
+            (_, StopPressed) | (_, SleepRequested) => (Idle, Some(Effect::CloseMicrophone)),
+            (_, MicrophoneFailed) => (Idle, None),
+            (Monitoring, VoiceDetected) => (Recording, None),
+            (Recording, SilenceDetected) | (Recording, SendNowPressed) => {
+                (Processing, Some(Effect::ProcessRecording))
+            }
+            (Processing, RecordingDiscarded) | (Processing, ProcessingFinished) => {
+                (Monitoring, Some(Effect::OpenMicrophone))
+            }
+            (CalibratingSilence, CalibrationSilenceDone) => (CalibratingVoice, None),
+            (state, _) => (state, None),
+        };
+        if next != self.state {
+            println!("[Pipeline] {:?} --{:?}--> {:?}", self.state, event, next);
+        }
+        self.state = next;
+        effect
+    }
+}
+
+/// Hold-to-talk で、キーを押しているかどうかから起こすイベント
+/// (押したら録音を始め、離したら無音を待たずに送る)
+pub fn hold_to_talk_event(state: AppState, held: bool) -> Option<PipelineEvent> {
+    match (state, held) {
+        (AppState::Monitoring, true) => Some(PipelineEvent::VoiceDetected),
+        (AppState::Recording, false) => Some(PipelineEvent::SilenceDetected),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn controller_in(events: &[PipelineEvent]) -> PipelineController {
+        let mut controller = PipelineController::default();
+        for event in events {
+            controller.handle(*event);
+        }
+        controller
+    }
+
+    #[test]
+    fn test_voice_turn_returns_to_monitoring() {
+        let mut controller = PipelineController::default();
+        assert_eq!(controller.handle(PipelineEvent::StartPressed), Some(Effect::OpenMicrophone));
+        assert_eq!(controller.handle(PipelineEvent::VoiceDetected), None);
+        assert_eq!(controller.state(), AppState::Recording);
+        assert_eq!(
+            controller.handle(PipelineEvent::SilenceDetected),
+            Some(Effect::ProcessRecording)
+        );
+        assert_eq!(
+            controller.handle(PipelineEvent::ProcessingFinished),
+            Some(Effect::OpenMicrophone)
+        );
+        assert_eq!(controller.state(), AppState::Monitoring);
+    }
+
+    #[test]
+    fn test_send_now_only_while_recording() {
+        let mut controller = controller_in(&[PipelineEvent::StartPressed]);
+        assert_eq!(controller.handle(PipelineEvent::SendNowPressed), None);
+        assert_eq!(controller.state(), AppState::Monitoring);
+        controller.handle(PipelineEvent::VoiceDetected);
+        assert_eq!(
+            controller.handle(PipelineEvent::SendNowPressed),
+            Some(Effect::ProcessRecording)
+        );
+        assert_eq!(controller.state(), AppState::Processing);
+    }
+
+    #[test]
+    fn test_hold_to_talk_event() {
+        assert_eq!(hold_to_talk_event(AppState::Monitoring, true), Some(PipelineEvent::VoiceDetected));
+        assert_eq!(hold_to_talk_event(AppState::Monitoring, false), None);
+        assert_eq!(hold_to_talk_event(AppState::Recording, true), None);
+        assert_eq!(hold_to_talk_event(AppState::Recording, false), Some(PipelineEvent::SilenceDetected));
+        assert_eq!(hold_to_talk_event(AppState::Idle, true), None);
+        assert_eq!(hold_to_talk_event(AppState::Processing, true), None);
+    }
+
+    #[test]
+    fn test_stop_during_processing_does_not_restart() {
+        let mut controller = controller_in(&[
+            PipelineEvent::StartPressed,
+            PipelineEvent::VoiceDetected,
+            PipelineEvent::SilenceDetected,
+        ]);
+        assert_eq!(controller.handle(PipelineEvent::StopPressed), Some(Effect::CloseMicrophone));
+        assert_eq!(controller.handle(PipelineEvent::ProcessingFinished), None);
+        assert!(controller.is_idle());
+    }
+
+    #[test]
+    fn test_ignored_events_keep_state() {
+        let mut controller = controller_in(&[PipelineEvent::StartPressed]);
+        assert_eq!(controller.handle(PipelineEvent::StartPressed), None);
+        assert_eq!(controller.handle(PipelineEvent::SilenceDetected), None);
+        assert_eq!(controller.handle(PipelineEvent::ProcessingFinished), None);
+        assert_eq!(controller.state(), AppState::Monitoring);
+
+        let mut idle = PipelineController::default();
+        assert_eq!(idle.handle(PipelineEvent::StopPressed), None);
+        assert_eq!(idle.handle(PipelineEvent::VoiceDetected), None);
+        assert!(idle.is_idle());
+    }
+
+    #[test]
+    fn test_sleep_and_microphone_failure_go_idle() {
+        let mut controller = controller_in(&[
+            PipelineEvent::StartPressed,
+            PipelineEvent::VoiceDetected,
+            PipelineEvent::SilenceDetected,
+        ]);
+        assert_eq!(controller.handle(PipelineEvent::SleepRequested), Some(Effect::CloseMicrophone));
+        assert!(controller.is_idle());
+
+        let mut controller = controller_in(&[PipelineEvent::StartPressed]);
+        assert_eq!(controller.handle(PipelineEvent::MicrophoneFailed), None);
+        assert!(controller.is_idle());
+    }
+
+    #[test]
+    fn test_calibration() {
+        let mut controller = PipelineController::default();
+        assert_eq!(
+            controller.handle(PipelineEvent::CalibrationRequested),
+            Some(Effect::OpenCalibrationMicrophone)
+        );
+        assert_eq!(controller.handle(PipelineEvent::VoiceDetected), None);
+        controller.handle(PipelineEvent::CalibrationSilenceDone);
+        assert_eq!(controller.state(), AppState::CalibratingVoice);
+        assert_eq!(
+            controller.handle(PipelineEvent::StopPressed),
+            Some(Effect::FinishCalibration)
+        );
+        assert!(controller.is_idle());
+    }
+}
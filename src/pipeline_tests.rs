@@ -0,0 +1,166 @@
+//! 偽サーバーを使ったパイプラインの結合テスト (失敗時に ElizaClient が戻ってくるか、など)
+
+use crate::config::Config;
+use crate::eliza::ElizaClient;
+use crate::error::AppError;
+use crate::mood::MoodState;
+use crate::test_support::{FakeAgentServer, FakeResponse, OscSink};
+use crate::vrchat;
+use crate::{silence_follow_up_due, text_pipeline, ProcessingMessage};
+use std::sync::mpsc::channel;
+use std::time::Duration;
+
+struct Outcome {
+    messages: Vec<ProcessingMessage>,
+    chatbox: Vec<String>,
+}
+
+impl Outcome {
+    fn error(&self) -> Option<(&AppError, bool)> {
+        self.messages.iter().find_map(|m| match m {
+            ProcessingMessage::Error(e, client) => Some((e, client.is_some())),
+            _ => None,
+        })
+    }
+
+    fn completed(&self) -> bool {
+        self.messages
+            .iter()
+            .any(|m| matches!(m, ProcessingMessage::Complete(Some(_))))
+    }
+
+    fn history_roles(&self) -> Option<Vec<String>> {
+        self.messages.iter().find_map(|m| match m {
+            ProcessingMessage::Complete(Some(client)) => {
+                Some(client.history().into_iter().map(|m| m.role).collect())
+            }
+            _ => None,
+        })
+    }
+
+    fn reply(&self) -> Option<&str> {
+        self.messages.iter().find_map(|m| match m {
+            ProcessingMessage::ElizaComplete(reply, _, _) => Some(reply.as_str()),
+            _ => None,
+        })
+    }
+}
+
+/// `follow_up` なら沈黙時の話しかけとして流す。`configure` でテストごとに設定を上書きする
+fn run_text_pipeline(
+    responses: Vec<FakeResponse>,
+    timeout_secs: u64,
+    follow_up: bool,
+    configure: impl FnOnce(&mut Config),
+) -> Outcome {
+    let server = FakeAgentServer::start(responses);
+    let sink = OscSink::bind();
+    vrchat::TEST_SEND_PORT.with(|p| p.set(Some(sink.port())));
+
+    let mut config = Config {
+        agent_server_url: server.url.clone(),
+        quote_mode: "off".to_string(),
+        pause_when_vrchat_closed: false,
+        chatbox_retry_enabled: false,
+        ..Config::default()
+    };
+    configure(&mut config);
+    let text = if follow_up {
+        config.silence_follow_up_prompt.clone()
+    } else {
+        "こんにちは".to_string()
+    };
+    let client = ElizaClient::new(server.url, "test".to_string(), 20).with_timeout(timeout_secs);
+    let (sender, receiver) = channel();
+    text_pipeline(
+        text,
+        config,
+        Some(client),
+        MoodState::default(),
+        sender,
+        follow_up,
+    );
+
+    vrchat::TEST_SEND_PORT.with(|p| p.set(None));
+    Outcome {
+        messages: receiver.try_iter().collect(),
+        chatbox: sink.chatbox_texts(),
+    }
+}
+
+#[test]
+fn test_text_pipeline_sends_reply_to_chatbox() {
+    let outcome = run_text_pipeline(vec![FakeResponse::reply("やあ")], 5, false, |_| {});
+    assert_eq!(outcome.reply(), Some("やあ"));
+    assert!(outcome.completed());
+    assert_eq!(outcome.chatbox, vec!["やあ".to_string()]);
+}
+
+#[test]
+fn test_text_pipeline_server_error_returns_client() {
+    let outcome = run_text_pipeline(vec![FakeResponse::json(500, "boom")], 5, false, |_| {});
+    let (error, has_client) = outcome.error().expect("error expected");
+    assert!(error.to_string().contains("500"), "{}", error);
+    assert!(has_client);
+    assert!(outcome.chatbox.is_empty());
+}
+
+#[test]
+fn test_text_pipeline_malformed_json_returns_client() {
+    let outcome = run_text_pipeline(vec![FakeResponse::json(200, "{not json")], 5, false, |_| {});
+    let (error, has_client) = outcome.error().expect("error expected");
+    assert!(error.to_string().contains("Parse error"), "{}", error);
+    assert!(has_client);
+}
+
+#[test]
+fn test_text_pipeline_timeout_returns_client() {
+    let slow = FakeResponse::reply("遅すぎた").delayed(Duration::from_secs(3));
+    let outcome = run_text_pipeline(vec![slow], 1, false, |_| {});
+    let (error, has_client) = outcome.error().expect("error expected");
+    assert!(error.to_string().contains("Network error"), "{}", error);
+    assert_eq!(error.category(), "network");
+    assert!(has_client);
+    assert!(outcome.chatbox.is_empty());
+}
+
+#[test]
+fn test_suppressed_reply_is_not_uploaded() {
+    let long_reply = "秘密の話".repeat(60);
+    let paste = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    paste.set_nonblocking(true).unwrap();
+    let paste_endpoint = format!("http://{}", paste.local_addr().unwrap());
+    let outcome = run_text_pipeline(vec![FakeResponse::reply(&long_reply)], 5, false, |config| {
+        config.paste_long_replies_enabled = true;
+        config.paste_endpoint = paste_endpoint;
+        config.dnd_keywords = "秘密".to_string();
+    });
+    assert!(outcome
+        .messages
+        .iter()
+        .any(|m| matches!(m, ProcessingMessage::ChatboxSuppressed(_))));
+    assert!(outcome.chatbox.is_empty());
+    assert!(paste.accept().is_err(), "suppressed reply was uploaded");
+}
+
+#[test]
+fn test_follow_up_sends_only_reply() {
+    let reply = "そういえば、さっきのゲームはどうだった？";
+    let outcome = run_text_pipeline(vec![FakeResponse::reply(reply)], 5, true, |config| {
+        config.quote_mode = "separate".to_string();
+    });
+    assert!(outcome.completed());
+    assert_eq!(outcome.chatbox, vec![reply.to_string()]);
+    // 指示文はユーザーの発話として履歴に残さない
+    assert_eq!(outcome.history_roles(), Some(vec!["assistant".to_string()]));
+}
+
+#[test]
+fn test_silence_follow_up_due() {
+    let secs = Duration::from_secs;
+    assert!(silence_follow_up_due(10, secs(12), Some(15)));
+    assert!(!silence_follow_up_due(0, secs(12), Some(15)));
+    assert!(!silence_follow_up_due(10, secs(5), Some(15)));
+    assert!(!silence_follow_up_due(10, secs(12), Some(3)));
+    assert!(!silence_follow_up_due(10, secs(12), None));
+}
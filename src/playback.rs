@@ -0,0 +1,300 @@
+use crate::error::AppError;
+use rodio::cpal::traits::{DeviceTrait, HostTrait};
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// 再生中の出力レベル (直近のピーク、音量適用後。f32 のビット列で持つ)
+static OUTPUT_LEVEL: AtomicU32 = AtomicU32::new(0);
+/// レベルを更新する間隔 (サンプル数)
+const METER_WINDOW: usize = 1024;
+
+/// 再生中の出力レベル (0.0〜1.0、何も鳴っていなければ 0)
+pub fn output_level() -> f32 {
+    f32::from_bits(OUTPUT_LEVEL.load(Ordering::Relaxed))
+}
+
+/// 止めるときに音量を下げていく時間
+const FADE_OUT: Duration = Duration::from_millis(150);
+
+/// 再生を途中で止めるためのハンドル (clone して別スレッドから stop する)
+#[derive(Clone, Default)]
+pub struct StopHandle(Arc<AtomicBool>);
+
+impl StopHandle {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn stop(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    /// 同じ再生を指すハンドルか
+    pub fn is_same(&self, other: &StopHandle) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+/// 出力先のデバイス名 (空なら既定のデバイス) と音量
+pub type Output = (String, f32);
+
+/// 出力先ごとの再生 (stream は再生が終わるまで持っておく)
+struct OutputSink {
+    _stream: rodio::OutputStream,
+    sink: rodio::Sink,
+    volume: f32,
+}
+
+/// outputs のデバイスをすべて開く。開けなかったものはログに出して飛ばし、1 つも開けなければエラー
+fn open_sinks(outputs: &[Output]) -> Result<Vec<OutputSink>, AppError> {
+    let mut sinks = Vec::new();
+    let mut last_error = None;
+    for (device_name, volume) in outputs {
+        match open_stream(device_name) {
+            Ok(stream) => {
+                let sink = rodio::Sink::connect_new(stream.mixer());
+                sink.set_volume(*volume);
+                sinks.push(OutputSink {
+                    _stream: stream,
+                    sink,
+                    volume: *volume,
+                });
+            }
+            Err(e) => {
+                eprintln!("[Playback] {}", e);
+                last_error = Some(e);
+            }
+        }
+    }
+    match last_error {
+        Some(e) if sinks.is_empty() => Err(e),
+        _ => Ok(sinks),
+    }
+}
+
+/// すべての再生が終わるか stop されるまで待つ (stop されたらフェードアウトしてから止める)
+fn wait_until_end(sinks: &[OutputSink], stop: &StopHandle) {
+    const POLL: Duration = Duration::from_millis(20);
+    while sinks.iter().any(|s| !s.sink.empty()) {
+        if stop.is_stopped() {
+            let steps = (FADE_OUT.as_millis() / POLL.as_millis()).max(1) as usize;
+            for i in (0..steps).rev() {
+                for s in sinks {
+                    s.sink.set_volume(s.volume * i as f32 / steps as f32);
+                }
+                std::thread::sleep(POLL);
+            }
+            for s in sinks {
+                s.sink.stop();
+            }
+            return;
+        }
+        std::thread::sleep(POLL);
+    }
+}
+
+/// 再生されたサンプルのピークを OUTPUT_LEVEL に書き込む
+struct Metered<S> {
+    inner: S,
+    volume: f32,
+    peak: f32,
+    count: usize,
+}
+
+impl<S> Metered<S> {
+    fn new(inner: S, volume: f32) -> Self {
+        Self {
+            inner,
+            volume,
+            peak: 0.0,
+            count: 0,
+        }
+    }
+}
+
+impl<S: rodio::Source> Iterator for Metered<S> {
+    type Item = rodio::Sample;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let sample = self.inner.next()?;
+        self.peak = self.peak.max((sample * self.volume).abs());
+        self.count += 1;
+        if self.count >= METER_WINDOW {
+            OUTPUT_LEVEL.store(self.peak.min(1.0).to_bits(), Ordering::Relaxed);
+            self.peak = 0.0;
+            self.count = 0;
+        }
+        Some(sample)
+    }
+}
+
+impl<S: rodio::Source> rodio::Source for Metered<S> {
+    fn current_span_len(&self) -> Option<usize> {
+        self.inner.current_span_len()
+    }
+
+    fn channels(&self) -> rodio::ChannelCount {
+        self.inner.channels()
+    }
+
+    fn sample_rate(&self) -> rodio::SampleRate {
+        self.inner.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.inner.total_duration()
+    }
+}
+
+impl<S> Drop for Metered<S> {
+    fn drop(&mut self) {
+        OUTPUT_LEVEL.store(0, Ordering::Relaxed);
+    }
+}
+
+/// Get list of available output devices
+pub fn get_output_devices() -> Vec<String> {
+    let host = rodio::cpal::default_host();
+    match host.output_devices() {
+        Ok(devices) => devices.filter_map(|d| d.name().ok()).collect(),
+        Err(e) => {
+            eprintln!("Failed to get output devices: {}", e);
+            Vec::new()
+        }
+    }
+}
+
+fn open_stream(device_name: &str) -> Result<rodio::OutputStream, AppError> {
+    let builder = if device_name.is_empty() {
+        rodio::OutputStreamBuilder::from_default_device()
+    } else {
+        let device = rodio::cpal::default_host()
+            .output_devices()
+            .map_err(|e| AppError::Audio(format!("Failed to get output devices: {}", e)))?
+            .find(|d| d.name().map(|n| n == device_name).unwrap_or(false))
+            .ok_or_else(|| AppError::Config(format!("Output device '{}' not found", device_name)))?;
+        rodio::OutputStreamBuilder::from_device(device)
+    }
+    .map_err(|e| AppError::Audio(format!("Failed to open output device: {}", e)))?;
+
+    let mut stream = builder
+        .open_stream_or_fallback()
+        .map_err(|e| AppError::Audio(format!("Failed to open output stream: {}", e)))?;
+    stream.log_on_drop(false);
+    Ok(stream)
+}
+
+/// 音声ファイルを再生し、終わるまで待つ (device_name が空なら既定のデバイス)
+pub fn play_file(path: &Path, device_name: &str, volume: f32) -> Result<(), AppError> {
+    play_file_until(path, &[(device_name.to_string(), volume)], &StopHandle::new())
+}
+
+/// 同じ音声ファイルを outputs のデバイスで同時に再生し、終わるか stop されるまで待つ
+pub fn play_file_until(path: &Path, outputs: &[Output], stop: &StopHandle) -> Result<(), AppError> {
+    if stop.is_stopped() {
+        return Ok(());
+    }
+    let decode = || {
+        let file = File::open(path).map_err(|e| AppError::Audio(format!("Failed to open {:?}: {}", path, e)))?;
+        rodio::Decoder::try_from(file).map_err(|e| AppError::Audio(format!("Failed to decode {:?}: {}", path, e)))
+    };
+    let sinks = open_sinks(outputs)?;
+    // レベルメーターは 1 つ目の出力先で測る
+    for (i, s) in sinks.iter().enumerate() {
+        if i == 0 {
+            s.sink.append(Metered::new(decode()?, s.volume));
+        } else {
+            s.sink.append(decode()?);
+        }
+    }
+    wait_until_end(&sinks, stop);
+    Ok(())
+}
+
+/// 短いビープ音を鳴らし、終わるまで待つ
+pub fn play_tone(frequency: f32, duration: Duration, device_name: &str, volume: f32) -> Result<(), AppError> {
+    use rodio::Source;
+
+    let stream = open_stream(device_name)?;
+    let sink = rodio::Sink::connect_new(stream.mixer());
+    sink.set_volume(volume);
+    sink.append(Metered::new(
+        rodio::source::SineWave::new(frequency)
+            .take_duration(duration)
+            .fade_in(Duration::from_millis(5)),
+        volume,
+    ));
+    sink.sleep_until_end();
+    Ok(())
+}
+
+/// 16bit リトルエンディアンのモノラル PCM を読みながら outputs のデバイスで再生し、終わるか stop されるまで待つ
+pub fn play_pcm_stream(
+    mut reader: impl Read,
+    sample_rate: u32,
+    outputs: &[Output],
+    stop: &StopHandle,
+) -> Result<(), AppError> {
+    let sinks = open_sinks(outputs)?;
+
+    let mut buf = [0u8; 8192];
+    // 2 バイトに満たず次の読み込みに回す分
+    let mut pending: Vec<u8> = Vec::new();
+    while !stop.is_stopped() {
+        let n = reader
+            .read(&mut buf)
+            .map_err(|e| AppError::Network(format!("Failed to read audio stream: {}", e)))?;
+        if n == 0 {
+            break;
+        }
+        pending.extend_from_slice(&buf[..n]);
+        let complete = pending.len() / 2 * 2;
+        let samples: Vec<f32> = pending[..complete]
+            .chunks_exact(2)
+            .map(|b| i16::from_le_bytes([b[0], b[1]]) as f32 / i16::MAX as f32)
+            .collect();
+        pending.drain(..complete);
+        if samples.is_empty() {
+            continue;
+        }
+        for (i, s) in sinks.iter().enumerate() {
+            let buffer = rodio::buffer::SamplesBuffer::new(1, sample_rate, samples.clone());
+            if i == 0 {
+                s.sink.append(Metered::new(buffer, s.volume));
+            } else {
+                s.sink.append(buffer);
+            }
+        }
+    }
+    wait_until_end(&sinks, stop);
+    Ok(())
+}
+
+/// バックグラウンドで再生する (エラーはログのみ)
+pub fn play_file_async(path: &Path, device_name: &str, volume: f32) {
+    let path = path.to_path_buf();
+    let device_name = device_name.to_string();
+    std::thread::spawn(move || {
+        if let Err(e) = play_file(&path, &device_name, volume) {
+            eprintln!("[Playback] {}", e);
+        }
+    });
+}
+
+/// 出力デバイスの確認用にテストトーンをバックグラウンドで鳴らす (エラーはログのみ)
+pub fn play_test_tone_async(device_name: &str, volume: f32) {
+    let device_name = device_name.to_string();
+    std::thread::spawn(move || {
+        if let Err(e) = play_tone(440.0, Duration::from_secs(1), &device_name, volume) {
+            eprintln!("[Playback] {}", e);
+        }
+    });
+}
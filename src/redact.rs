@@ -0,0 +1,94 @@
+use regex::{Captures, Regex};
+use std::sync::OnceLock;
+
+/// 伏せ字
+pub const MASK: &str = "███";
+
+/// 電話番号・メールアドレス・住所らしい部分のパターン
+///
+/// regex には look-around がないので、長い数字の一部を伏せないよう直前の 1 文字を prefix で受けて残し、
+/// 直後に数字が続くもの (tail が空でないもの) は伏せない
+fn patterns() -> &'static [Regex] {
+    static PATTERNS: OnceLock<Vec<Regex>> = OnceLock::new();
+    PATTERNS.get_or_init(|| {
+        [
+            // メールアドレス
+            r"[\w.+-]+(?:@|＠)[\w-]+(?:\.[\w-]+)+",
+            // 電話番号 (国内 0 始まり / +国番号)
+            r"(?:\+|＋)[0-9０-９][0-9０-９ \-－]{8,}[0-9０-９]",
+            r"(?P<prefix>^|[^0-9０-９])[0０][0-9０-９]{1,4}[-－ー ]?[0-9０-９]{1,4}[-－ー ]?[0-9０-９]{3,4}(?P<tail>[0-9０-９]?)",
+            // 郵便番号 (電話番号の後に見る)
+            r"(?:〒\s*)?[0-9０-９]{3}[-－ー][0-9０-９]{4}\b",
+            // 住所 (都道府県 + 市区町村 + 番地)
+            r"(?:東京都|北海道|(?:京都|大阪)府|\p{Han}{2,3}県)?\p{Han}{1,6}[市区町村郡]\p{Han}*(?:[0-9０-９一二三四五六七八九十]+(?:丁目|番地?|号|[-－ー]))+[0-9０-９]*",
+            // 住所 (英語の番地 + 通り名)
+            r"(?i)\b\d+\s+(?:\w+\s+){1,3}(?:street|st|avenue|ave|road|rd|boulevard|blvd|lane|ln|drive|dr)\b\.?",
+        ]
+        .iter()
+        .map(|pattern| Regex::new(pattern).expect("invalid redaction pattern"))
+        .collect()
+    })
+}
+
+/// 個人情報らしい部分を ███ に置き換える。戻り値は (置換後のテキスト, 伏せた数)
+pub fn redact(text: &str) -> (String, usize) {
+    let mut result = text.to_string();
+    let mut count = 0;
+    for pattern in patterns() {
+        result = pattern
+            .replace_all(&result, |caps: &Captures| {
+                if caps
+                    .name("tail")
+                    .is_some_and(|tail| !tail.as_str().is_empty())
+                {
+                    return caps[0].to_string();
+                }
+                count += 1;
+                format!(
+                    "{}{}",
+                    caps.name("prefix").map_or("", |prefix| prefix.as_str()),
+                    MASK
+                )
+            })
+            .into_owned();
+    }
+    (result, count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redact() {
+        assert_eq!(
+            redact("連絡は foo.bar@example.com か 090-1234-5678 まで"),
+            (format!("連絡は {} か {} まで", MASK, MASK), 2)
+        );
+        assert_eq!(
+            redact("住所は新宿区西新宿2丁目8番1号です"),
+            (format!("住所は{}です", MASK), 1)
+        );
+        assert_eq!(
+            redact("I live at 221 Baker Street"),
+            (format!("I live at {}", MASK), 1)
+        );
+        assert_eq!(
+            redact("今日は 3 人で 20 分話した"),
+            ("今日は 3 人で 20 分話した".to_string(), 0)
+        );
+        assert_eq!(
+            redact("電話は0312345678です"),
+            (format!("電話は{}です", MASK), 1)
+        );
+        // 長い数字の途中は伏せない
+        for text in [
+            "予算は1000000円だった",
+            "2010000 views",
+            "注文番号 0123456789012345",
+            "１０００００００円",
+        ] {
+            assert_eq!(redact(text), (text.to_string(), 0));
+        }
+    }
+}
@@ -0,0 +1,270 @@
+//! 過去の会話をもう一度パイプラインに流す (プロンプトや VAD の設定を変えたときの確かめ用)
+//!
+//! 書き出した会話・セッションファイルなら自分の発話を、録音フォルダなら .wav を順に流す。
+//! 発話どうしの間隔は元の時刻から求め、speed 倍に縮める。
+//! 録音はマイクから録ったときと同じ VAD の判定で発話ごとに区切ってから書き起こす
+
+use crate::audio::{AudioRecorder, VoiceOnsetDetector};
+use crate::batch;
+use crate::config::Config;
+use crate::error::AppError;
+use crate::history::{self, HistoryEntry};
+use crate::vad::VadParams;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// これより長い間は詰める (休憩や放置を待たない)
+const MAX_GAP: Duration = Duration::from_secs(30);
+/// 録音の音量を見る単位 (マイクのバッファ 1 つ分くらい)
+const VAD_CHUNK: Duration = Duration::from_millis(10);
+/// 録音を始めてからしばらくは無音とみなさない (AudioProbe::is_silent と同じ)
+const SILENCE_GRACE: Duration = Duration::from_secs(3);
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReplayInput {
+    Text(String),
+    Audio(PathBuf),
+}
+
+/// 流す 1 件と、前の 1 件を流し始めてから待つ時間
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplayStep {
+    pub input: ReplayInput,
+    pub delay: Duration,
+}
+
+/// フォルダなら録音、ファイルなら書き出した会話として読む
+pub fn load(path: &Path, use_audio: bool, speed: f32) -> Result<Vec<ReplayStep>, String> {
+    let steps = if path.is_dir() {
+        from_recordings(&batch::list_recordings(path), speed)
+    } else {
+        from_history(&history::import(path)?, use_audio, speed)
+    };
+    if steps.is_empty() {
+        return Err(format!("No utterances to replay in {}", path.display()));
+    }
+    Ok(steps)
+}
+
+/// 自分の発話を順に (use_audio なら録音が残っているものは録音から書き起こし直す)
+pub fn from_history(entries: &[HistoryEntry], use_audio: bool, speed: f32) -> Vec<ReplayStep> {
+    let mut previous = None;
+    entries
+        .iter()
+        .filter(|entry| entry.role == "You")
+        .map(|entry| {
+            let audio = entry
+                .meta
+                .as_ref()
+                .and_then(|meta| meta.audio_path.clone())
+                .filter(|path| use_audio && path.exists());
+            let input = match audio {
+                Some(path) => ReplayInput::Audio(path),
+                None => ReplayInput::Text(entry.message.clone()),
+            };
+            let time = (entry.timestamp > 0).then_some(entry.timestamp * 1000);
+            let delay = gap(previous, time, speed);
+            previous = time;
+            ReplayStep { input, delay }
+        })
+        .collect()
+}
+
+/// アーカイブした録音を順に (間隔はファイル名の日時から)
+pub fn from_recordings(files: &[PathBuf], speed: f32) -> Vec<ReplayStep> {
+    let mut previous = None;
+    files
+        .iter()
+        .map(|file| {
+            let time = recorded_at_ms(file);
+            let delay = gap(previous, time, speed);
+            previous = time;
+            ReplayStep {
+                input: ReplayInput::Audio(file.clone()),
+                delay,
+            }
+        })
+        .collect()
+}
+
+/// archive_recording が付けたファイル名 (20240501_210315.123.wav) の日時 (ミリ秒)
+fn recorded_at_ms(path: &Path) -> Option<u64> {
+    let stem = path.file_stem()?.to_str()?;
+    let time = chrono::NaiveDateTime::parse_from_str(stem, "%Y%m%d_%H%M%S%.3f").ok()?;
+    u64::try_from(time.and_utc().timestamp_millis()).ok()
+}
+
+/// マイクから録ったときと同じ判定 (RMS の平滑化・開始の確定・無音の長さ) で録音を発話ごとに区切る。
+/// 各区間は前の発話の終わり (モニタリングを再開したところ) から、無音を検出したところまで
+pub fn vad_segments(samples: &[f32], sample_rate: u32, params: &VadParams, silence_threshold: f32) -> Vec<Range<usize>> {
+    let chunk_len = ((sample_rate as f32 * VAD_CHUNK.as_secs_f32()) as usize).max(1);
+    let origin = Instant::now();
+    let mut onset = VoiceOnsetDetector::default();
+    let mut rms = 0.0f32;
+    let mut segment_start = 0;
+    let mut recording_since = None;
+    let mut last_sound = Duration::ZERO;
+    let mut next_check = Duration::ZERO;
+    let mut segments = Vec::new();
+    for (i, chunk) in samples.chunks(chunk_len).enumerate() {
+        let now = VAD_CHUNK * (i as u32 + 1);
+        let end = i * chunk_len + chunk.len();
+        let chunk_rms = (chunk.iter().map(|s| s * s).sum::<f32>() / chunk.len() as f32).sqrt();
+        rms = if chunk_rms > rms { rms * 0.6 + chunk_rms * 0.4 } else { rms * 0.9 };
+        if chunk.iter().any(|s| s.abs() > silence_threshold) {
+            last_sound = now;
+        }
+        // VAD のスレッドと同じく window ごとに判定する
+        if now < next_check {
+            continue;
+        }
+        next_check = now + params.window;
+        match recording_since {
+            None => {
+                let loud = rms > params.start_threshold;
+                if onset.update(origin + now, loud, params.confirm_frames, params.window) {
+                    recording_since = Some(now);
+                    last_sound = now;
+                }
+            }
+            Some(since) => {
+                let silent = now.saturating_sub(last_sound) >= Duration::from_secs_f32(params.silence_duration_secs);
+                if now.saturating_sub(since) >= SILENCE_GRACE && silent {
+                    segments.push(segment_start..end);
+                    segment_start = end;
+                    recording_since = None;
+                    // モニタリングを再開すると新しいレコーダーで測り直す
+                    rms = 0.0;
+                    onset.reset();
+                }
+            }
+        }
+    }
+    if recording_since.is_some() {
+        segments.push(segment_start..samples.len());
+    }
+    segments
+}
+
+/// 録音を vad_segments で区切り、発話ごとに work_dir の WAV に書く (前後の無音はマイクから録ったときと同じく詰める)
+pub fn split_recording(path: &Path, config: &Config, work_dir: &Path) -> Result<Vec<PathBuf>, AppError> {
+    let (samples, sample_rate) = read_mono(path)?;
+    let recorder = AudioRecorder::new(config.silence_threshold)?;
+    let params = VadParams::from_config(config);
+    let mut files = Vec::new();
+    for range in vad_segments(&samples, sample_rate, &params, config.silence_threshold) {
+        match recorder.save_audio_to_wav(&samples[range], sample_rate, config.preroll_secs, work_dir) {
+            Ok(file) => files.push(file),
+            // 無音だけの区間は送らない
+            Err(e) => eprintln!("[Replay] {}", e),
+        }
+    }
+    Ok(files)
+}
+
+/// WAV をモノラルの f32 で読む
+fn read_mono(path: &Path) -> Result<(Vec<f32>, u32), AppError> {
+    let reader = hound::WavReader::open(path)
+        .map_err(|e| AppError::Audio(format!("Failed to open {}: {}", path.display(), e)))?;
+    let spec = reader.spec();
+    let samples: Vec<f32> = match spec.sample_format {
+        hound::SampleFormat::Float => reader.into_samples::<f32>().collect::<Result<_, _>>(),
+        hound::SampleFormat::Int => {
+            let scale = (1i64 << (spec.bits_per_sample.max(1) - 1)) as f32;
+            reader
+                .into_samples::<i32>()
+                .map(|sample| sample.map(|s| s as f32 / scale))
+                .collect::<Result<_, _>>()
+        }
+    }
+    .map_err(|e| AppError::Audio(format!("Failed to read {}: {}", path.display(), e)))?;
+    let channels = usize::from(spec.channels.max(1));
+    let mono = samples
+        .chunks(channels)
+        .map(|frame| frame.iter().sum::<f32>() / frame.len() as f32)
+        .collect();
+    Ok((mono, spec.sample_rate))
+}
+
+fn gap(previous_ms: Option<u64>, ms: Option<u64>, speed: f32) -> Duration {
+    match (previous_ms, ms) {
+        (Some(previous), Some(ms)) => Duration::from_millis(ms.saturating_sub(previous))
+            .div_f32(speed.max(0.1))
+            .min(MAX_GAP),
+        _ => Duration::ZERO,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::TurnMeta;
+
+    #[test]
+    fn test_from_history() {
+        let dir = tempfile::tempdir().unwrap();
+        let audio = dir.path().join("a.wav");
+        std::fs::write(&audio, b"").unwrap();
+        let entry = |role: &str, message: &str, timestamp: u64| HistoryEntry {
+            timestamp,
+            ..HistoryEntry::new(role, message.to_string())
+        };
+        let entries = vec![
+            entry("You", "こんにちは", 1000),
+            entry("Agent", "やあ", 1002),
+            entry("You", "元気?", 1010).with_meta(TurnMeta {
+                audio_path: Some(audio.clone()),
+                ..TurnMeta::default()
+            }),
+            entry("Ignored", "[noise] ...", 1012),
+            entry("You", "またね", 2000),
+        ];
+
+        let steps = from_history(&entries, true, 2.0);
+        assert_eq!(
+            steps,
+            vec![
+                ReplayStep { input: ReplayInput::Text("こんにちは".to_string()), delay: Duration::ZERO },
+                ReplayStep { input: ReplayInput::Audio(audio), delay: Duration::from_secs(5) },
+                ReplayStep { input: ReplayInput::Text("またね".to_string()), delay: MAX_GAP },
+            ]
+        );
+        assert_eq!(from_history(&entries, false, 1.0)[1].input, ReplayInput::Text("元気?".to_string()));
+    }
+
+    #[test]
+    fn test_from_recordings() {
+        let files = vec![
+            PathBuf::from("20240501_210315.000.wav"),
+            PathBuf::from("20240501_210318.500.wav"),
+            PathBuf::from("other.wav"),
+        ];
+        let delays: Vec<Duration> = from_recordings(&files, 1.0).into_iter().map(|s| s.delay).collect();
+        assert_eq!(delays, vec![Duration::ZERO, Duration::from_millis(3500), Duration::ZERO]);
+    }
+
+    #[test]
+    fn test_vad_segments() {
+        let rate = 16000;
+        let quiet = |secs: usize| vec![0.0f32; rate * secs];
+        let voice = |secs: usize| (0..rate * secs).map(|i| if i % 2 == 0 { 0.3 } else { -0.3 }).collect::<Vec<f32>>();
+        let samples = [quiet(1), voice(4), quiet(2), voice(4), quiet(2)].concat();
+        let params = VadParams {
+            start_threshold: 0.05,
+            confirm_frames: 3,
+            window: Duration::from_millis(100),
+            silence_duration_secs: 1.0,
+        };
+
+        let segments = vad_segments(&samples, rate as u32, &params, 0.02);
+        assert_eq!(segments.len(), 2);
+        assert_eq!(segments[0].start, 0);
+        // 1 秒の無音で区切る (5 秒で声が止む)
+        assert!((rate * 6..rate * 6 + rate / 5).contains(&segments[0].end), "{:?}", segments);
+        assert_eq!(segments[1].start, segments[0].end);
+        assert!((rate * 12..rate * 12 + rate / 5).contains(&segments[1].end), "{:?}", segments);
+
+        assert!(vad_segments(&quiet(5), rate as u32, &params, 0.02).is_empty());
+    }
+}
@@ -0,0 +1,130 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// 再送の間隔の上限
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+/// 続けて送るときの間隔 (VRChat のスパム制限に掛からないように)
+pub const SEND_INTERVAL: Duration = Duration::from_millis(1500);
+
+/// 失敗した回数に応じた次の再送までの待ち時間 (1, 2, 4, ... 秒, 最大 30 秒)
+pub fn backoff(attempts: u32) -> Duration {
+    Duration::from_secs(1u64 << attempts.min(5)).min(MAX_BACKOFF)
+}
+
+struct PendingMessage {
+    text: String,
+    failed_at: Instant,
+    next_retry: Instant,
+    attempts: u32,
+}
+
+/// 送信に失敗したチャットボックスメッセージの再送待ち行列 (古いものから順に送る)
+#[derive(Default)]
+pub struct RetryQueue {
+    pending: VecDeque<PendingMessage>,
+}
+
+impl RetryQueue {
+    pub fn push(&mut self, text: String, now: Instant) {
+        self.pending.push_back(PendingMessage {
+            text,
+            failed_at: now,
+            next_retry: now + backoff(0),
+            attempts: 1,
+        });
+    }
+
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    pub fn texts(&self) -> impl Iterator<Item = &str> {
+        self.pending.iter().map(|m| m.text.as_str())
+    }
+
+    pub fn clear(&mut self) {
+        self.pending.clear();
+    }
+
+    /// 待っているメッセージを古い順にすべて取り出す
+    pub fn drain(&mut self) -> Vec<String> {
+        self.pending.drain(..).map(|m| m.text).collect()
+    }
+
+    /// 次の確認で全件すぐに再送する
+    pub fn retry_now(&mut self, now: Instant) {
+        for message in &mut self.pending {
+            message.next_retry = now;
+        }
+    }
+
+    /// 最初に失敗してから max_age を過ぎたメッセージを取り除いて返す
+    pub fn expire(&mut self, now: Instant, max_age: Duration) -> Vec<String> {
+        let (expired, kept): (Vec<_>, Vec<_>) = self
+            .pending
+            .drain(..)
+            .partition(|m| now.duration_since(m.failed_at) >= max_age);
+        self.pending = kept.into();
+        expired.into_iter().map(|m| m.text).collect()
+    }
+
+    /// 再送の時刻になった先頭のメッセージ
+    pub fn due(&self, now: Instant) -> Option<&str> {
+        self.pending
+            .front()
+            .filter(|m| m.next_retry <= now)
+            .map(|m| m.text.as_str())
+    }
+
+    /// 先頭の再送に成功した
+    pub fn mark_sent(&mut self, now: Instant) {
+        self.pending.pop_front();
+        if let Some(next) = self.pending.front_mut() {
+            next.next_retry = next.next_retry.max(now + SEND_INTERVAL);
+        }
+    }
+
+    /// 先頭の再送に失敗した
+    pub fn mark_failed(&mut self, now: Instant) {
+        if let Some(message) = self.pending.front_mut() {
+            message.next_retry = now + backoff(message.attempts);
+            message.attempts += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retry_queue() {
+        assert_eq!(backoff(0), Duration::from_secs(1));
+        assert_eq!(backoff(3), Duration::from_secs(8));
+        assert_eq!(backoff(10), MAX_BACKOFF);
+
+        let start = Instant::now();
+        let mut queue = RetryQueue::default();
+        queue.push("first".to_string(), start);
+        queue.push("second".to_string(), start);
+        assert_eq!(queue.due(start), None);
+
+        let t = start + Duration::from_secs(1);
+        assert_eq!(queue.due(t), Some("first"));
+        queue.mark_failed(t);
+        assert_eq!(queue.due(t + Duration::from_secs(1)), None);
+        assert_eq!(queue.due(t + Duration::from_secs(2)), Some("first"));
+
+        let t = t + Duration::from_secs(2);
+        queue.mark_sent(t);
+        assert_eq!(queue.due(t), None);
+        assert_eq!(queue.due(t + SEND_INTERVAL), Some("second"));
+
+        assert_eq!(queue.expire(start + Duration::from_secs(60), Duration::from_secs(60)), vec!["second"]);
+        assert!(queue.is_empty());
+    }
+}
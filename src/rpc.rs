@@ -0,0 +1,146 @@
+//! `--rpc` で起動したときの標準入出力での JSON-RPC 2.0 (他のプログラムに組み込んで操作するためのもの)
+//!
+//! 1 行に 1 つの JSON。標準入力でリクエストを受け、標準出力に応答とパイプラインの各段階のイベント
+//! (`method: "event"` の通知) を書く。標準出力にはこれ以外を書かない (ログは main.rs の println! で標準エラー出力へ)
+
+use serde_json::{json, Value};
+use std::collections::VecDeque;
+use std::io::{BufRead, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+pub const PARSE_ERROR: i64 = -32700;
+pub const INVALID_REQUEST: i64 = -32600;
+pub const METHOD_NOT_FOUND: i64 = -32601;
+pub const INVALID_PARAMS: i64 = -32602;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static REQUESTS: Mutex<VecDeque<Request>> = Mutex::new(VecDeque::new());
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Request {
+    /// None なら通知 (応答しない)
+    pub id: Option<Value>,
+    pub method: String,
+    pub params: Value,
+}
+
+impl Request {
+    /// params の文字列 (なければ INVALID_PARAMS)
+    pub fn str_param(&self, name: &str) -> Result<String, (i64, String)> {
+        self.params
+            .get(name)
+            .and_then(Value::as_str)
+            .map(str::to_string)
+            .ok_or_else(|| (INVALID_PARAMS, format!("params.{} (string) is required", name)))
+    }
+}
+
+/// 標準入力を読むスレッドを起動する
+pub fn enable() {
+    if ENABLED.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    println!("[RPC] JSON-RPC on stdin/stdout enabled");
+    std::thread::spawn(|| {
+        for line in std::io::stdin().lock().lines() {
+            let Ok(line) = line else {
+                break;
+            };
+            if line.trim().is_empty() {
+                continue;
+            }
+            match parse_line(&line) {
+                Ok(request) => REQUESTS.lock().unwrap_or_else(|e| e.into_inner()).push_back(request),
+                Err(response) => write_line(&response),
+            }
+        }
+        println!("[RPC] stdin closed");
+    });
+}
+
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// 届いたリクエストを取り出す
+pub fn take_requests() -> Vec<Request> {
+    REQUESTS.lock().unwrap_or_else(|e| e.into_inner()).drain(..).collect()
+}
+
+/// リクエストに応答する (通知には何も返さない)
+pub fn respond(request: &Request, result: Result<Value, (i64, String)>) {
+    let Some(id) = &request.id else {
+        return;
+    };
+    let response = match result {
+        Ok(result) => json!({"jsonrpc": "2.0", "id": id, "result": result}),
+        Err((code, message)) => error_response(id.clone(), code, &message),
+    };
+    write_line(&response);
+}
+
+/// パイプラインの段階を通知する (`--rpc` でなければ何もしない)
+pub fn notify_event(stage: &str, text: &str, preset: &str) {
+    if !is_enabled() {
+        return;
+    }
+    write_line(&json!({
+        "jsonrpc": "2.0",
+        "method": "event",
+        "params": {"stage": stage, "text": text, "preset": preset},
+    }));
+}
+
+/// 1 行をリクエストとして読む。読めなければ返すべきエラー応答
+fn parse_line(line: &str) -> Result<Request, Value> {
+    let value: Value = serde_json::from_str(line)
+        .map_err(|e| error_response(Value::Null, PARSE_ERROR, &format!("Parse error: {}", e)))?;
+    let id = value.get("id").cloned();
+    let method = value
+        .get("method")
+        .and_then(Value::as_str)
+        .filter(|_| value.get("jsonrpc").and_then(Value::as_str) == Some("2.0"))
+        .ok_or_else(|| error_response(id.clone().unwrap_or(Value::Null), INVALID_REQUEST, "Invalid request"))?;
+    Ok(Request {
+        id,
+        method: method.to_string(),
+        params: value.get("params").cloned().unwrap_or(Value::Null),
+    })
+}
+
+fn error_response(id: Value, code: i64, message: &str) -> Value {
+    json!({"jsonrpc": "2.0", "id": id, "error": {"code": code, "message": message}})
+}
+
+fn write_line(value: &Value) {
+    let mut stdout = std::io::stdout().lock();
+    if writeln!(stdout, "{}", value).and_then(|_| stdout.flush()).is_err() {
+        eprintln!("[RPC] Failed to write to stdout");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_line() {
+        let request = parse_line(r#"{"jsonrpc":"2.0","id":1,"method":"say","params":{"text":"こんにちは"}}"#).unwrap();
+        assert_eq!(request.id, Some(json!(1)));
+        assert_eq!(request.method, "say");
+        assert_eq!(request.str_param("text"), Ok("こんにちは".to_string()));
+        assert_eq!(request.str_param("name").unwrap_err().0, INVALID_PARAMS);
+
+        let notification = parse_line(r#"{"jsonrpc":"2.0","method":"stop"}"#).unwrap();
+        assert_eq!(notification.id, None);
+        assert_eq!(notification.params, Value::Null);
+
+        let error = parse_line("{oops").unwrap_err();
+        assert_eq!(error["error"]["code"], PARSE_ERROR);
+        assert_eq!(error["id"], Value::Null);
+        let error = parse_line(r#"{"id":"a","method":"say"}"#).unwrap_err();
+        assert_eq!(error["error"]["code"], INVALID_REQUEST);
+        assert_eq!(error["id"], "a");
+    }
+}
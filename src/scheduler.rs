@@ -0,0 +1,135 @@
+//! 決まった時刻にチャットボックスへ送る予約メッセージ (「配信終了まであと10分」など)
+//!
+//! 常駐スレッドが 1 秒ごとに時刻を見て送るので、会話のパイプラインが止まっていても動く。
+//! 送るのは返答と同じ経路 (VRChat が閉じていれば保留、DnD・送信数の制限、ページ送り) で、アプリ側から渡す
+
+use crate::config::Config;
+use chrono::{Local, NaiveDateTime, NaiveTime};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScheduledMessage {
+    pub text: String,
+    /// 最初に送る時刻 (HH:MM, ローカル時刻)
+    pub at: String,
+    /// 0 なら 1 回だけ、それ以外はこの分数ごとにその日のうちは繰り返す
+    #[serde(default)]
+    pub repeat_minutes: u32,
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+impl ScheduledMessage {
+    pub fn new(text: &str, at: &str) -> Self {
+        Self {
+            text: text.to_string(),
+            at: at.to_string(),
+            repeat_minutes: 0,
+            enabled: true,
+        }
+    }
+
+    /// from より後、to までに送る時刻が来たか
+    pub fn is_due(&self, from: NaiveDateTime, to: NaiveDateTime) -> bool {
+        if !self.enabled || self.text.trim().is_empty() {
+            return false;
+        }
+        let Ok(at) = NaiveTime::parse_from_str(self.at.trim(), "%H:%M") else {
+            return false;
+        };
+        let first = to.date().and_time(at);
+        if to < first {
+            return false;
+        }
+        let latest = if self.repeat_minutes == 0 {
+            first
+        } else {
+            let period = i64::from(self.repeat_minutes) * 60;
+            first + chrono::Duration::seconds((to - first).num_seconds() / period * period)
+        };
+        from < latest
+    }
+}
+
+static SCHEDULER_STARTED: AtomicBool = AtomicBool::new(false);
+/// 予約 (scheduled_messages) とチャットボックスへの送り方の設定
+static CONFIG: Mutex<Option<Config>> = Mutex::new(None);
+/// 送った予約 (1 回だけのものを無効にするため、アプリ側が取り出す)
+static FIRED: Mutex<VecDeque<ScheduledMessage>> = Mutex::new(VecDeque::new());
+
+/// 送る予約と設定を差し替える
+pub fn set_config(config: &Config) {
+    *CONFIG.lock().unwrap_or_else(|e| e.into_inner()) = Some(config.clone());
+}
+
+/// 送った予約を取り出す
+pub fn take_fired() -> Vec<ScheduledMessage> {
+    FIRED.lock().unwrap_or_else(|e| e.into_inner()).drain(..).collect()
+}
+
+/// 常駐スレッドを起動する。send はその時点の設定で 1 件をチャットボックスに送る (2 回目以降は何もしない)
+pub fn start(send: impl Fn(&Config, &str) + Send + 'static) {
+    if SCHEDULER_STARTED.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    std::thread::spawn(move || {
+        let mut last = Local::now().naive_local();
+        loop {
+            std::thread::sleep(Duration::from_secs(1));
+            let now = Local::now().naive_local();
+            let Some(config) = CONFIG.lock().unwrap_or_else(|e| e.into_inner()).clone() else {
+                last = now;
+                continue;
+            };
+            let due: Vec<&ScheduledMessage> =
+                config.scheduled_messages.iter().filter(|m| m.is_due(last, now)).collect();
+            last = now;
+            for message in due {
+                println!("[Scheduler] {}", message.text);
+                send(&config, &message.text);
+                FIRED.lock().unwrap_or_else(|e| e.into_inner()).push_back(message.clone());
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn time(h: u32, m: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2024, 5, 1).unwrap().and_hms_opt(h, m, 0).unwrap()
+    }
+
+    #[test]
+    fn test_is_due() {
+        let once = ScheduledMessage::new("配信終了まであと10分", "21:50");
+        assert!(once.is_due(time(21, 49), time(21, 50)));
+        assert!(!once.is_due(time(21, 50), time(21, 51)));
+        assert!(!once.is_due(time(21, 48), time(21, 49)));
+
+        let recurring = ScheduledMessage {
+            repeat_minutes: 30,
+            ..ScheduledMessage::new("水分補給しよう", "20:00")
+        };
+        assert!(recurring.is_due(time(20, 29), time(20, 30)));
+        assert!(!recurring.is_due(time(20, 30), time(20, 31)));
+        assert!(!recurring.is_due(time(19, 29), time(19, 30)));
+
+        let disabled = ScheduledMessage {
+            enabled: false,
+            ..ScheduledMessage::new("x", "21:50")
+        };
+        assert!(!disabled.is_due(time(21, 49), time(21, 50)));
+        assert!(!ScheduledMessage::new("x", "25:00").is_due(time(0, 0), time(23, 59)));
+    }
+}
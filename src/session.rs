@@ -0,0 +1,153 @@
+use crate::config::Config;
+use crate::eliza::Message;
+use crate::history::HistoryEntry;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// セッションの統計情報
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SessionStats {
+    pub turn_count: usize,
+    #[serde(default)]
+    pub topic: Option<String>,
+    /// セッション開始時刻 (UNIX 秒)
+    #[serde(default)]
+    pub started_at: u64,
+}
+
+/// 会話を中断・再開するための保存ファイル
+/// (会話履歴 + ペルソナ/プリセットのスナップショット + 統計)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Session {
+    pub name: String,
+    pub saved_at: u64,
+    pub preset: String,
+    /// 秘密の値を外したスナップショット (Config::without_secrets)
+    pub config: Config,
+    /// UI に表示している会話 (タイムスタンプ付き)
+    pub ui_history: Vec<HistoryEntry>,
+    /// Agent に送っている会話履歴
+    pub agent_history: Vec<Message>,
+    pub stats: SessionStats,
+}
+
+pub fn now_unix_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+impl Session {
+    /// Get the sessions directory
+    pub fn sessions_dir() -> Result<PathBuf, String> {
+        let dir = Config::config_dir()?.join("sessions");
+        if !dir.exists() {
+            fs::create_dir_all(&dir)
+                .map_err(|e| format!("Failed to create sessions directory: {}", e))?;
+        }
+        Ok(dir)
+    }
+
+    fn path_for(name: &str) -> Result<PathBuf, String> {
+        let file_name = sanitize_name(name);
+        if file_name.is_empty() {
+            return Err("Session name is empty".to_string());
+        }
+        Ok(Self::sessions_dir()?.join(format!("{}.json", file_name)))
+    }
+
+    fn summary_path(preset: &str) -> Result<PathBuf, String> {
+        Ok(Self::sessions_dir()?.join(format!("summary_{}.txt", sanitize_name(preset))))
+    }
+
+    /// 会話終了時の要約をプリセットごとに保存する (次回の会話の冒頭で使う)
+    pub fn save_summary(preset: &str, summary: &str) -> Result<(), String> {
+        fs::write(Self::summary_path(preset)?, summary)
+            .map_err(|e| format!("Failed to write summary: {}", e))
+    }
+
+    /// 前回の会話の要約
+    pub fn load_summary(preset: &str) -> Option<String> {
+        let content = fs::read_to_string(Self::summary_path(preset).ok()?).ok()?;
+        let content = content.trim();
+        if content.is_empty() {
+            None
+        } else {
+            Some(content.to_string())
+        }
+    }
+
+    /// Get list of saved session names (newest first)
+    pub fn list() -> Vec<String> {
+        let dir = match Self::sessions_dir() {
+            Ok(dir) => dir,
+            Err(e) => {
+                eprintln!("Failed to get sessions directory: {}", e);
+                return vec![];
+            }
+        };
+        let mut entries: Vec<(SystemTime, String)> = fs::read_dir(&dir)
+            .map(|rd| {
+                rd.filter_map(|e| e.ok())
+                    .filter_map(|e| {
+                        let path = e.path();
+                        if path.extension().and_then(|x| x.to_str()) != Some("json") {
+                            return None;
+                        }
+                        let name = path.file_stem()?.to_str()?.to_string();
+                        let modified = e.metadata().and_then(|m| m.modified()).ok()?;
+                        Some((modified, name))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        entries.sort_by_key(|e| std::cmp::Reverse(e.0));
+        entries.into_iter().map(|(_, name)| name).collect()
+    }
+
+    /// Save session to sessions/<name>.json
+    pub fn save(&self) -> Result<PathBuf, String> {
+        let path = Self::path_for(&self.name)?;
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize session: {}", e))?;
+        fs::write(&path, json).map_err(|e| format!("Failed to write session file: {}", e))?;
+        println!("Session saved to: {:?}", path);
+        Ok(path)
+    }
+
+    /// Load session from sessions/<name>.json
+    pub fn load(name: &str) -> Result<Self, String> {
+        let path = Self::path_for(name)?;
+        let content = fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read session file: {}", e))?;
+        let session = serde_json::from_str(&content)
+            .map_err(|e| format!("Failed to parse session file: {}", e))?;
+        println!("Session loaded from: {:?}", path);
+        Ok(session)
+    }
+}
+
+/// ファイル名に使えない文字を置き換える
+pub fn sanitize_name(name: &str) -> String {
+    name.trim()
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c => c,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sanitize_name() {
+        assert_eq!(sanitize_name(" 金曜の夜/part:1 "), "金曜の夜_part_1");
+        assert_eq!(sanitize_name("   "), "");
+    }
+}
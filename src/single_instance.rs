@@ -0,0 +1,107 @@
+//! 二重起動の防止 (2 つ目は既にあるウィンドウを前に出して終わる)
+//!
+//! 最初のインスタンスが 127.0.0.1 の空きポートで待ち受け、そのポートをロックファイルに書く。
+//! 2 つ目はそこへ接続して "focus" を送り、返事があれば終了する。
+//! 返事がなければ前のインスタンスは落ちたとみなしてロックを引き継ぐ
+
+use eframe::egui;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream};
+use std::path::Path;
+use std::time::Duration;
+
+const FOCUS_REQUEST: &str = "focus";
+const FOCUS_ACK: &str = "ok";
+
+/// このインスタンスが持っているロック (listen するまでは前に出す依頼を受け付けない)
+pub struct InstanceLock {
+    listener: TcpListener,
+}
+
+/// ロックを取る。既に動いているインスタンスがあればそれを前に出して None を返す
+pub fn acquire(lock_path: &Path) -> Result<Option<InstanceLock>, String> {
+    if let Some(port) = std::fs::read_to_string(lock_path)
+        .ok()
+        .and_then(|s| s.trim().parse::<u16>().ok())
+    {
+        if request_focus(port) {
+            return Ok(None);
+        }
+        println!("[Instance] Taking over stale lock {}", lock_path.display());
+    }
+
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+        .map_err(|e| format!("Failed to bind instance lock: {}", e))?;
+    let port = listener
+        .local_addr()
+        .map_err(|e| format!("Failed to read instance lock port: {}", e))?
+        .port();
+    std::fs::write(lock_path, port.to_string())
+        .map_err(|e| format!("Failed to write {}: {}", lock_path.display(), e))?;
+    Ok(Some(InstanceLock { listener }))
+}
+
+/// 既存のインスタンスに前に出るよう頼む。返事があれば true
+fn request_focus(port: u16) -> bool {
+    let addr = SocketAddr::from((Ipv4Addr::LOCALHOST, port));
+    let Ok(mut stream) = TcpStream::connect_timeout(&addr, Duration::from_millis(500)) else {
+        return false;
+    };
+    let _ = stream.set_read_timeout(Some(Duration::from_secs(2)));
+    if writeln!(stream, "{}", FOCUS_REQUEST).is_err() {
+        return false;
+    }
+    let mut reply = String::new();
+    BufReader::new(stream).read_line(&mut reply).is_ok() && reply.trim() == FOCUS_ACK
+}
+
+impl InstanceLock {
+    /// 2 つ目の起動が来たらウィンドウを元に戻して前に出す
+    pub fn listen(self, ctx: egui::Context) {
+        std::thread::spawn(move || {
+            for stream in self.listener.incoming() {
+                let Ok(mut stream) = stream else {
+                    continue;
+                };
+                let _ = stream.set_read_timeout(Some(Duration::from_secs(2)));
+                let mut request = String::new();
+                let Ok(reader) = stream.try_clone() else {
+                    continue;
+                };
+                if BufReader::new(reader).read_line(&mut request).is_err()
+                    || request.trim() != FOCUS_REQUEST
+                {
+                    continue;
+                }
+                println!("[Instance] Another launch was requested; focusing the window");
+                let _ = writeln!(stream, "{}", FOCUS_ACK);
+                ctx.send_viewport_cmd(egui::ViewportCommand::Minimized(false));
+                ctx.send_viewport_cmd(egui::ViewportCommand::Focus);
+                ctx.request_repaint();
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_acquire() {
+        let dir = tempfile::tempdir().unwrap();
+        let lock_path = dir.path().join("instance.lock");
+
+        // 落ちたインスタンスのロックは引き継ぐ
+        let closed_port = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        std::fs::write(&lock_path, closed_port.to_string()).unwrap();
+        let lock = acquire(&lock_path).unwrap().expect("stale lock should be taken over");
+        lock.listen(egui::Context::default());
+
+        assert!(acquire(&lock_path).unwrap().is_none());
+    }
+}
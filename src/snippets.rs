@@ -0,0 +1,75 @@
+//! システムプロンプトの `{{include:rules.md}}` を snippets フォルダのファイルの中身に置き換える
+//!
+//! 安全のルールや返答の長さなど、どの人格にも共通する指示を 1 か所で編集するためのもの
+
+use crate::config::Config;
+use regex::Regex;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+/// スニペットの中の include をたどる深さの上限 (循環していても止まるように)
+const MAX_DEPTH: usize = 3;
+
+fn include_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r"\{\{\s*include:\s*([^}]+?)\s*\}\}").expect("invalid include pattern"))
+}
+
+pub fn snippets_dir() -> Result<PathBuf, String> {
+    Ok(Config::config_dir()?.join("snippets"))
+}
+
+/// snippets フォルダのファイルを読む (フォルダの外を指す名前は読まない)
+fn read_snippet(name: &str) -> Option<String> {
+    if name.contains(['/', '\\']) || name.starts_with('.') {
+        eprintln!("[Snippets] Invalid snippet name: {}", name);
+        return None;
+    }
+    let path = snippets_dir().ok()?.join(name);
+    match std::fs::read_to_string(&path) {
+        Ok(content) => Some(content.trim_end().to_string()),
+        Err(e) => {
+            eprintln!("[Snippets] Failed to read {}: {}", path.display(), e);
+            None
+        }
+    }
+}
+
+/// プロンプトの include を展開する。読めなかったものはそのまま残す
+pub fn expand(prompt: &str) -> String {
+    expand_with(prompt, &read_snippet, 0)
+}
+
+fn expand_with(prompt: &str, read: &dyn Fn(&str) -> Option<String>, depth: usize) -> String {
+    if depth >= MAX_DEPTH {
+        return prompt.to_string();
+    }
+    include_pattern()
+        .replace_all(prompt, |caps: &regex::Captures| match read(&caps[1]) {
+            Some(content) => expand_with(&content, read, depth + 1),
+            None => caps[0].to_string(),
+        })
+        .into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expand_with() {
+        let read = |name: &str| match name {
+            "rules.md" => Some("- 3 文以内で答える\n{{include:safety.md}}".to_string()),
+            "safety.md" => Some("- 個人情報は聞かない".to_string()),
+            "loop.md" => Some("{{include:loop.md}}".to_string()),
+            _ => None,
+        };
+        assert_eq!(
+            expand_with("あなたは Lisa です。\n{{ include: rules.md }}", &read, 0),
+            "あなたは Lisa です。\n- 3 文以内で答える\n- 個人情報は聞かない"
+        );
+        assert_eq!(expand_with("{{include:missing.md}}", &read, 0), "{{include:missing.md}}");
+        assert_eq!(expand_with("{{include:loop.md}}", &read, 0), "{{include:loop.md}}");
+        assert_eq!(expand_with("include なし", &read, 0), "include なし");
+    }
+}
@@ -0,0 +1,58 @@
+use crate::config::Config;
+use crate::playback;
+use std::path::Path;
+
+/// 返答から `[sfx:name]` タグを取り除き、(本文, タグ名のリスト) を返す
+pub fn extract_sfx_tags(text: &str) -> (String, Vec<String>) {
+    let mut cleaned = String::with_capacity(text.len());
+    let mut tags = Vec::new();
+    let mut rest = text;
+
+    while let Some(start) = rest.find("[sfx:") {
+        let Some(len) = rest[start..].find(']') else {
+            break;
+        };
+        cleaned.push_str(&rest[..start]);
+        let name = rest[start + "[sfx:".len()..start + len].trim();
+        if !name.is_empty() {
+            tags.push(name.to_string());
+        }
+        rest = &rest[start + len + 1..];
+    }
+    cleaned.push_str(rest);
+
+    // タグを抜いた跡の余分な空白を詰める (改行は残す)
+    while cleaned.contains("  ") {
+        cleaned = cleaned.replace("  ", " ");
+    }
+    (cleaned.trim().to_string(), tags)
+}
+
+/// タグに対応する効果音を再生する (未登録のタグは無視)
+pub fn play_tags(config: &Config, tags: &[String]) {
+    for tag in tags {
+        match config.sfx_map.get(tag) {
+            Some(path) => {
+                println!("[Soundboard] {} → {}", tag, path);
+                playback::play_file_async(Path::new(path), &config.sfx_output_device, config.sfx_volume);
+            }
+            None => eprintln!("[Soundboard] Unknown sfx tag: {}", tag),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract_sfx_tags() {
+        let (text, tags) = extract_sfx_tags("あはは [sfx:laugh] それは面白いね[sfx: clap ]");
+        assert_eq!(text, "あはは それは面白いね");
+        assert_eq!(tags, vec!["laugh".to_string(), "clap".to_string()]);
+
+        let (text, tags) = extract_sfx_tags("タグなし [sfx:閉じてない");
+        assert_eq!(text, "タグなし [sfx:閉じてない");
+        assert!(tags.is_empty());
+    }
+}
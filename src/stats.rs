@@ -0,0 +1,197 @@
+//! 人格 (プリセット) ごとの通算の記録 (stats/<preset>.json)
+//!
+//! 話した回数・会話の回数と、ユーザーの発話によく出てくる言葉を数える。
+//! 形態素解析はせず、漢字・カタカナ・英数字の並びを 1 語とみなす
+
+use crate::config::Config;
+use crate::session;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+/// 覚えておく言葉の数 (超えたら少ないもの、同じ回数なら長く出てきていないものから捨てる)
+const MAX_WORDS: usize = 500;
+
+/// 数えない英単語
+const STOPWORDS: &[&str] = &[
+    "the", "and", "you", "are", "for", "that", "this", "with", "have", "was", "what", "not", "but", "can",
+];
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PersonaStats {
+    /// 返答した回数
+    #[serde(default)]
+    pub turns: u64,
+    /// モニタリングを始めた (会話を始めた) 回数
+    #[serde(default)]
+    pub sessions: u64,
+    /// 最初に話した日時 (Unix 秒)
+    #[serde(default)]
+    pub since: Option<u64>,
+    /// 言葉 → 出てきた回数
+    #[serde(default)]
+    pub words: BTreeMap<String, u64>,
+    /// 言葉 → 最後に出てきたときの turns
+    #[serde(default)]
+    pub last_seen: BTreeMap<String, u64>,
+}
+
+fn stats_path(preset: &str) -> Result<PathBuf, String> {
+    let dir = Config::config_dir()?.join("stats");
+    if !dir.exists() {
+        fs::create_dir_all(&dir).map_err(|e| format!("Failed to create stats directory: {}", e))?;
+    }
+    Ok(dir.join(format!("{}.json", preset)))
+}
+
+impl PersonaStats {
+    /// なければ空の記録
+    pub fn load(preset: &str) -> Self {
+        stats_path(preset)
+            .ok()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, preset: &str) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(self).map_err(|e| format!("Failed to serialize stats: {}", e))?;
+        fs::write(stats_path(preset)?, json).map_err(|e| format!("Failed to write stats: {}", e))
+    }
+
+    pub fn record_session(&mut self) {
+        self.sessions += 1;
+    }
+
+    /// 1 往復を数える (user_text は返答のもとになった発話。なければ回数だけ)
+    pub fn record_turn(&mut self, user_text: Option<&str>) {
+        self.turns += 1;
+        self.since.get_or_insert_with(session::now_unix_secs);
+        for word in user_text.map(extract_words).unwrap_or_default() {
+            *self.words.entry(word.clone()).or_insert(0) += 1;
+            self.last_seen.insert(word, self.turns);
+        }
+        self.prune_words();
+    }
+
+    /// MAX_WORDS を超えた分を捨てる。このターンに出てきた言葉は 1 回きりでも残す
+    fn prune_words(&mut self) {
+        let excess = self.words.len().saturating_sub(MAX_WORDS);
+        if excess == 0 {
+            return;
+        }
+        let mut candidates: Vec<(u64, u64, String)> = self
+            .words
+            .iter()
+            .map(|(word, count)| (*count, self.last_seen.get(word).copied().unwrap_or(0), word.clone()))
+            .filter(|(_, seen, _)| *seen != self.turns)
+            .collect();
+        candidates.sort();
+        for (_, _, word) in candidates.into_iter().take(excess) {
+            self.words.remove(&word);
+            self.last_seen.remove(&word);
+        }
+    }
+
+    /// よく出てくる言葉 (多い順、同じ回数なら辞書順)
+    pub fn top_words(&self, count: usize) -> Vec<(&str, u64)> {
+        let mut words: Vec<(&str, u64)> = self.words.iter().map(|(w, c)| (w.as_str(), *c)).collect();
+        words.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        words.truncate(count);
+        words
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum CharKind {
+    Kanji,
+    Katakana,
+    Latin,
+}
+
+fn char_kind(c: char) -> Option<CharKind> {
+    match c {
+        '\u{4E00}'..='\u{9FFF}' | '々' => Some(CharKind::Kanji),
+        '\u{30A1}'..='\u{30FA}' | 'ー' => Some(CharKind::Katakana),
+        c if c.is_ascii_alphanumeric() => Some(CharKind::Latin),
+        _ => None,
+    }
+}
+
+/// 名詞らしい言葉を取り出す (2 文字以上の漢字・カタカナの並びと、3 文字以上の英単語)
+pub fn extract_words(text: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut current = String::new();
+    let mut kind = None;
+    for c in text.chars().chain(std::iter::once(' ')) {
+        let next = char_kind(c);
+        if next != kind {
+            if let Some(kind) = kind {
+                if let Some(word) = accept_word(kind, &current) {
+                    words.push(word);
+                }
+            }
+            current.clear();
+            kind = next;
+        }
+        if next.is_some() {
+            current.push(c);
+        }
+    }
+    words
+}
+
+fn accept_word(kind: CharKind, word: &str) -> Option<String> {
+    match kind {
+        CharKind::Kanji | CharKind::Katakana => {
+            (word.chars().count() >= 2 && word.chars().any(|c| c != 'ー')).then(|| word.to_string())
+        }
+        CharKind::Latin => {
+            let word = word.to_ascii_lowercase();
+            (word.len() >= 3 && !word.chars().all(|c| c.is_ascii_digit()) && !STOPWORDS.contains(&word.as_str()))
+                .then_some(word)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract_words() {
+        assert_eq!(
+            extract_words("今日は東京でラーメンを食べた。Rust and the Bevy 2024"),
+            vec!["今日", "東京", "ラーメン", "rust", "bevy"]
+        );
+        assert!(extract_words("うん、そうだね").is_empty());
+    }
+
+    #[test]
+    fn test_record_turn() {
+        let mut stats = PersonaStats::default();
+        stats.record_turn(Some("ラーメンが好き"));
+        stats.record_turn(Some("ラーメンと寿司"));
+        stats.record_turn(None);
+        assert_eq!(stats.turns, 3);
+        assert!(stats.since.is_some());
+        assert_eq!(stats.top_words(2), vec![("ラーメン", 2), ("寿司", 1)]);
+    }
+
+    #[test]
+    fn test_record_turn_keeps_new_words() {
+        let mut stats = PersonaStats::default();
+        for i in 0..MAX_WORDS {
+            stats.record_turn(Some(&format!("word{:03}", i)));
+        }
+        stats.record_turn(Some("word000"));
+        // 辞書順で後ろになる漢字の新しい言葉も、いちばん古い 1 回きりの言葉の代わりに残る
+        stats.record_turn(Some("寿司"));
+        assert_eq!(stats.words.len(), MAX_WORDS);
+        assert_eq!(stats.words.get("寿司"), Some(&1));
+        assert_eq!(stats.words.get("word000"), Some(&2));
+        assert!(!stats.words.contains_key("word001"));
+        assert_eq!(stats.last_seen.len(), MAX_WORDS);
+    }
+}
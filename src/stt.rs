@@ -0,0 +1,294 @@
+use crate::config::Config;
+use crate::error::AppError;
+use crate::health::{self, Backend};
+use crate::local_whisper::{self, LocalWhisper};
+use crate::mock;
+use crate::openai::{self, OpenAIClient, TranscriptionResponse, WordTimestamp};
+use std::path::Path;
+use tempfile::NamedTempFile;
+
+/// OpenAI の書き起こし API が受け付けるファイルサイズの上限
+const MAX_UPLOAD_BYTES: u64 = 25 * 1024 * 1024;
+/// 区切るときに前の区間と重ねる長さ (区切り目で単語が切れないように)
+const CHUNK_OVERLAP_SECS: f32 = 1.0;
+
+/// 選べる書き起こしバックエンド
+pub const BACKENDS: [(&str, &str); 3] = [
+    ("openai", "OpenAI API"),
+    ("local", "ローカル whisper (whisper.cpp)"),
+    ("none", "なし"),
+];
+
+pub fn backend_label(backend: &str) -> &str {
+    BACKENDS
+        .iter()
+        .find(|(name, _)| *name == backend)
+        .map(|(_, label)| *label)
+        .unwrap_or(backend)
+}
+
+/// 指定したバックエンドで書き起こす
+pub fn transcribe_with(
+    backend: &str,
+    config: &Config,
+    openai_client: &OpenAIClient,
+    audio_path: &Path,
+) -> Result<TranscriptionResponse, AppError> {
+    if mock::is_enabled() {
+        let text = mock::next_transcription();
+        return Ok(TranscriptionResponse {
+            language: openai::detect_language_from_text(&text),
+            text,
+            words: Vec::new(),
+        });
+    }
+    match backend {
+        "openai" => openai_client
+            .transcribe_audio(audio_path)
+            .map_err(AppError::from),
+        "local" => {
+            let local = LocalWhisper {
+                exe_path: config.local_whisper_path.clone(),
+                model_path: local_whisper::resolve_model_path(
+                    &config.local_whisper_model,
+                    &config.local_whisper_model_size,
+                ),
+                language: config.local_whisper_language.clone(),
+                timeout_secs: config.stt_timeout_secs,
+                threads: config.local_whisper_threads,
+                max_queue: config.local_whisper_max_queue,
+            };
+            let text = local.transcribe(audio_path)?;
+            // ローカルでは言語が返らないので、必要なら文字種から推定する
+            let language = if config.language_routing_enabled
+                || config.auto_reply_language_enabled
+                || config.language_filter_enabled
+            {
+                openai::detect_language_from_text(&text)
+            } else {
+                None
+            };
+            Ok(TranscriptionResponse {
+                text,
+                language,
+                words: Vec::new(),
+            })
+        }
+        _ => Err(AppError::Config(format!("Unknown STT backend: {}", backend))),
+    }
+}
+
+/// 長い録音は区切って書き起こし、つなげる
+pub fn transcribe(
+    config: &Config,
+    openai_client: &OpenAIClient,
+    audio_path: &Path,
+) -> Result<TranscriptionResponse, AppError> {
+    let chunks = match split_long_recording(config, audio_path) {
+        Ok(Some(chunks)) => chunks,
+        Ok(None) => return transcribe_once(config, openai_client, audio_path),
+        Err(e) => {
+            eprintln!("[STT] Failed to split the recording, sending it as is: {}", e);
+            return transcribe_once(config, openai_client, audio_path);
+        }
+    };
+    println!("[STT] Long recording: transcribing in {} chunks", chunks.len());
+    let mut result = TranscriptionResponse {
+        text: String::new(),
+        language: None,
+        words: Vec::new(),
+    };
+    for (i, chunk) in chunks.iter().enumerate() {
+        let part = transcribe_once(config, openai_client, chunk.file.path())
+            .map_err(|e| e.context(&format!("chunk {}/{}", i + 1, chunks.len())))?;
+        result.text = stitch(&result.text, &part.text);
+        result.language = result.language.or(part.language);
+        // 重ねた部分の単語は前の区間で数えてある
+        let skip_until = if i == 0 { 0.0 } else { CHUNK_OVERLAP_SECS };
+        result.words.extend(part.words.into_iter().filter(|w| w.start >= skip_until).map(|w| WordTimestamp {
+            start: w.start + chunk.start_secs,
+            end: w.end + chunk.start_secs,
+            ..w
+        }));
+    }
+    Ok(result)
+}
+
+/// メインのバックエンドで失敗・タイムアウトしたら予備のバックエンドで書き起こす
+fn transcribe_once(
+    config: &Config,
+    openai_client: &OpenAIClient,
+    audio_path: &Path,
+) -> Result<TranscriptionResponse, AppError> {
+    let primary_error = match health::measure(Backend::Stt, || {
+        transcribe_with(&config.stt_backend, config, openai_client, audio_path)
+    }) {
+        Ok(transcription) => return Ok(transcription),
+        Err(e) => e,
+    };
+
+    let fallback = config.stt_fallback_backend.as_str();
+    if fallback == "none" || fallback == config.stt_backend {
+        return Err(primary_error);
+    }
+    eprintln!(
+        "[STT] {} failed ({}), falling back to {}",
+        config.stt_backend, primary_error, fallback
+    );
+    health::measure(Backend::Stt, || transcribe_with(fallback, config, openai_client, audio_path))
+        .map_err(|e| e.context(&format!("{} / fallback {}", primary_error, fallback)))
+}
+
+/// 区切った録音の一時ファイル (drop で消える)
+struct Chunk {
+    file: NamedTempFile,
+    /// 元の録音の先頭からの位置 (秒)
+    start_secs: f32,
+}
+
+/// 25MB か stt_chunk_secs を超える録音を、少しずつ重ねた区間の WAV に分ける。超えなければ None
+fn split_long_recording(config: &Config, audio_path: &Path) -> Result<Option<Vec<Chunk>>, String> {
+    let size = std::fs::metadata(audio_path)
+        .map_err(|e| format!("Failed to read {}: {}", audio_path.display(), e))?
+        .len();
+    let reader = hound::WavReader::open(audio_path).map_err(|e| format!("Failed to open WAV: {}", e))?;
+    let spec = reader.spec();
+    if spec.sample_format != hound::SampleFormat::Int {
+        return Err("Only integer PCM WAV can be split".to_string());
+    }
+    let rate = spec.sample_rate as usize;
+    let channels = spec.channels as usize;
+    let bytes_per_frame = (channels * usize::from(spec.bits_per_sample).div_ceil(8)).max(1);
+    // ヘッダーの分だけ余裕を持たせる
+    let max_by_size = (MAX_UPLOAD_BYTES as usize - 4096) / bytes_per_frame;
+    let max_by_duration = match config.stt_chunk_secs {
+        0 => usize::MAX,
+        secs => secs as usize * rate,
+    };
+    let chunk_frames = max_by_size.min(max_by_duration);
+    let total_frames = reader.duration() as usize;
+    if size <= MAX_UPLOAD_BYTES && total_frames <= chunk_frames {
+        return Ok(None);
+    }
+
+    let samples: Vec<i32> = reader
+        .into_samples::<i32>()
+        .collect::<Result<_, _>>()
+        .map_err(|e| format!("Failed to read WAV samples: {}", e))?;
+    let overlap_frames = (CHUNK_OVERLAP_SECS * rate as f32) as usize;
+    let dir = audio_path.parent().unwrap_or(Path::new("."));
+    chunk_ranges(total_frames, chunk_frames, overlap_frames)
+        .into_iter()
+        .map(|(start, end)| {
+            let file = tempfile::Builder::new()
+                .prefix(crate::audio::WORK_FILE_PREFIX)
+                .suffix(".wav")
+                .tempfile_in(dir)
+                .map_err(|e| format!("Failed to create chunk file: {}", e))?;
+            let mut writer =
+                hound::WavWriter::create(file.path(), spec).map_err(|e| format!("Failed to write chunk: {}", e))?;
+            for &sample in &samples[start * channels..end * channels] {
+                writer
+                    .write_sample(sample)
+                    .map_err(|e| format!("Failed to write chunk: {}", e))?;
+            }
+            writer.finalize().map_err(|e| format!("Failed to write chunk: {}", e))?;
+            Ok(Chunk {
+                file,
+                start_secs: start as f32 / rate as f32,
+            })
+        })
+        .collect::<Result<Vec<_>, String>>()
+        .map(Some)
+}
+
+/// total フレームを chunk フレームずつ、overlap フレーム重ねて区切る
+fn chunk_ranges(total: usize, chunk: usize, overlap: usize) -> Vec<(usize, usize)> {
+    let chunk = chunk.max(1);
+    let step = chunk.saturating_sub(overlap).max(1);
+    let mut ranges = Vec::new();
+    let mut start = 0;
+    loop {
+        let end = (start + chunk).min(total);
+        ranges.push((start, end));
+        if end == total {
+            return ranges;
+        }
+        start += step;
+    }
+}
+
+/// 区間ごとの書き起こしをつなげる。重ねた部分で同じ文字列が繰り返されていれば 1 回にする
+fn stitch(previous: &str, next: &str) -> String {
+    let previous = previous.trim_end();
+    let next = next.trim_start();
+    if previous.is_empty() {
+        return next.to_string();
+    }
+    let next_chars: Vec<char> = next.chars().collect();
+    let longest = next_chars.len().min(previous.chars().count()).min(40);
+    let repeated = (2..=longest)
+        .rev()
+        .find(|&n| previous.ends_with(&next_chars[..n].iter().collect::<String>()))
+        .unwrap_or(0);
+    let rest: String = next_chars[repeated..].iter().collect();
+    let rest = rest.trim_start();
+    let needs_space = previous.chars().last().is_some_and(|c| c.is_ascii_alphanumeric() || c.is_ascii_punctuation())
+        && rest.chars().next().is_some_and(|c| c.is_ascii_alphanumeric());
+    if needs_space {
+        format!("{} {}", previous, rest)
+    } else {
+        format!("{}{}", previous, rest)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chunk_ranges() {
+        assert_eq!(chunk_ranges(250, 100, 10), vec![(0, 100), (90, 190), (180, 250)]);
+        assert_eq!(chunk_ranges(100, 100, 10), vec![(0, 100)]);
+    }
+
+    #[test]
+    fn test_stitch() {
+        assert_eq!(stitch("", "こんにちは"), "こんにちは");
+        assert_eq!(stitch("今日はいい天気ですね", "天気ですね、散歩に行こう"), "今日はいい天気ですね、散歩に行こう");
+        assert_eq!(stitch("it was a long day", "day at work"), "it was a long day at work");
+        assert_eq!(stitch("Hello.", "How are you"), "Hello. How are you");
+    }
+
+    #[test]
+    fn test_split_long_recording() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("long.wav");
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate: 16000,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = hound::WavWriter::create(&path, spec).unwrap();
+        for i in 0..16000 * 5 {
+            writer.write_sample((i % 100) as i16).unwrap();
+        }
+        writer.finalize().unwrap();
+
+        let config = Config {
+            stt_chunk_secs: 2,
+            ..Config::default()
+        };
+        let chunks = split_long_recording(&config, &path).unwrap().unwrap();
+        let starts: Vec<f32> = chunks.iter().map(|c| c.start_secs).collect();
+        assert_eq!(starts, vec![0.0, 1.0, 2.0, 3.0]);
+        assert_eq!(hound::WavReader::open(chunks[0].file.path()).unwrap().duration(), 32000);
+
+        let short = Config {
+            stt_chunk_secs: 10,
+            ..Config::default()
+        };
+        assert!(split_long_recording(&short, &path).unwrap().is_none());
+    }
+}
@@ -0,0 +1,133 @@
+//! テスト用の偽 Agent サーバーと OSC の受け口
+
+use rosc::{decoder, OscPacket, OscType};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, UdpSocket};
+use std::time::Duration;
+
+/// 偽サーバーが返す応答
+#[derive(Clone)]
+pub struct FakeResponse {
+    pub status: u16,
+    pub body: String,
+    /// 応答を返すまでの待ち時間 (タイムアウトの確認用)
+    pub delay: Duration,
+}
+
+impl FakeResponse {
+    pub fn reply(content: &str) -> Self {
+        Self::json(200, serde_json::json!({ "message": { "role": "assistant", "content": content } }).to_string())
+    }
+
+    pub fn json(status: u16, body: impl Into<String>) -> Self {
+        Self {
+            status,
+            body: body.into(),
+            delay: Duration::ZERO,
+        }
+    }
+
+    pub fn delayed(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+}
+
+/// 決まった応答を順に返す偽の Agent (Eliza) サーバー
+pub struct FakeAgentServer {
+    pub url: String,
+}
+
+impl FakeAgentServer {
+    /// responses を 1 リクエストに 1 つずつ返す (使い切ったら最後のものを繰り返す)
+    pub fn start(responses: Vec<FakeResponse>) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            for (i, stream) in listener.incoming().enumerate() {
+                let Ok(mut stream) = stream else {
+                    break;
+                };
+                let response = responses[i.min(responses.len() - 1)].clone();
+                std::thread::spawn(move || {
+                    read_request(&mut stream);
+                    std::thread::sleep(response.delay);
+                    let _ = write!(
+                        stream,
+                        "HTTP/1.1 {} X\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                        response.status,
+                        response.body.len(),
+                        response.body
+                    );
+                });
+            }
+        });
+        Self { url }
+    }
+}
+
+fn read_request(stream: &mut std::net::TcpStream) {
+    let mut reader = BufReader::new(stream);
+    let mut content_length = 0;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).unwrap_or(0) == 0 || line.trim_end().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse().unwrap_or(0);
+            }
+        }
+    }
+    let mut body = vec![0; content_length];
+    let _ = reader.read_exact(&mut body);
+}
+
+/// VRChat の代わりに OSC を受け取る UDP ソケット
+pub struct OscSink {
+    socket: UdpSocket,
+}
+
+impl OscSink {
+    pub fn bind() -> Self {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket.set_read_timeout(Some(Duration::from_millis(200))).unwrap();
+        Self { socket }
+    }
+
+    pub fn port(&self) -> u16 {
+        self.socket.local_addr().unwrap().port()
+    }
+
+    /// 届いたメッセージ (address, args) をすべて取り出す
+    pub fn received(&self) -> Vec<(String, Vec<OscType>)> {
+        let mut messages = Vec::new();
+        let mut buf = [0u8; 65535];
+        while let Ok(size) = self.socket.recv(&mut buf) {
+            if let Ok((_, packet)) = decoder::decode_udp(&buf[..size]) {
+                flatten(packet, &mut messages);
+            }
+        }
+        messages
+    }
+
+    /// /chatbox/input に送られたテキスト
+    pub fn chatbox_texts(&self) -> Vec<String> {
+        self.received()
+            .into_iter()
+            .filter(|(addr, _)| addr == "/chatbox/input")
+            .filter_map(|(_, args)| match args.first() {
+                Some(OscType::String(text)) => Some(text.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+fn flatten(packet: OscPacket, messages: &mut Vec<(String, Vec<OscType>)>) {
+    match packet {
+        OscPacket::Message(msg) => messages.push((msg.addr, msg.args)),
+        OscPacket::Bundle(bundle) => bundle.content.into_iter().for_each(|p| flatten(p, messages)),
+    }
+}
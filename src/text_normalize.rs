@@ -0,0 +1,226 @@
+//! 読み上げ前のテキスト正規化 (数字・日付・単位を読みやすい形にする)
+//!
+//! "2024/05/01" や "5km" を音声合成がそのまま読むと不自然になるので、言語ごとに書き換えてから渡す
+
+use regex::{Captures, Regex};
+use std::sync::OnceLock;
+
+/// 正規化の言語 (設定値, 表示名)
+pub const NORMALIZE_LOCALES: [(&str, &str); 2] = [("ja", "日本語"), ("en", "English")];
+
+struct Patterns {
+    date: Regex,
+    time: Regex,
+    yen: Regex,
+    unit: Regex,
+    symbol_unit: Regex,
+    number: Regex,
+}
+
+fn patterns() -> &'static Patterns {
+    static PATTERNS: OnceLock<Patterns> = OnceLock::new();
+    PATTERNS.get_or_init(|| Patterns {
+        date: Regex::new(r"(\d{4})[/\-.](\d{1,2})[/\-.](\d{1,2})").unwrap(),
+        time: Regex::new(r"\b(\d{1,2}):(\d{2})\b").unwrap(),
+        yen: Regex::new(r"[¥￥](\d[\d,]*)").unwrap(),
+        // "5min" の m などを拾わないよう、後ろに英字が続くものは除く
+        unit: Regex::new(r"(\d)\s*(km|kg|cm|mm|m|g)([^A-Za-z]|$)").unwrap(),
+        symbol_unit: Regex::new(r"(\d)\s*(%|％|℃|°C)").unwrap(),
+        number: Regex::new(r"\d{1,3}(?:,\d{3})+(?:\.\d+)?|\d+(?:\.\d+)?").unwrap(),
+    })
+}
+
+/// locale に合わせて正規化する (未対応の locale ならそのまま)
+pub fn normalize(text: &str, locale: &str) -> String {
+    match locale {
+        "ja" => normalize_ja(&to_ascii_digits(text)),
+        "en" => normalize_en(&to_ascii_digits(text)),
+        _ => text.to_string(),
+    }
+}
+
+/// 全角数字を半角にする
+fn to_ascii_digits(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            '０'..='９' => char::from_u32(c as u32 - '０' as u32 + '0' as u32).unwrap_or(c),
+            _ => c,
+        })
+        .collect()
+}
+
+/// 月と日として読める値なら数値で返す
+fn month_day(month: &str, day: &str) -> Option<(u32, u32)> {
+    let month: u32 = month.parse().ok()?;
+    let day: u32 = day.parse().ok()?;
+    ((1..=12).contains(&month) && (1..=31).contains(&day)).then_some((month, day))
+}
+
+fn normalize_ja(text: &str) -> String {
+    let p = patterns();
+    let text = p.date.replace_all(text, |caps: &Captures| match month_day(&caps[2], &caps[3]) {
+        Some((month, day)) => format!("{}年{}月{}日", &caps[1], month, day),
+        None => caps[0].to_string(),
+    });
+    let text = p.time.replace_all(&text, |caps: &Captures| {
+        let hour: u32 = caps[1].parse().unwrap_or(0);
+        let minute: u32 = caps[2].parse().unwrap_or(0);
+        if hour > 24 || minute > 59 {
+            caps[0].to_string()
+        } else if minute == 0 {
+            format!("{}時", hour)
+        } else {
+            format!("{}時{}分", hour, minute)
+        }
+    });
+    let text = p.yen.replace_all(&text, "${1}円");
+    let text = p.unit.replace_all(&text, |caps: &Captures| {
+        let unit = match &caps[2] {
+            "km" => "キロメートル",
+            "kg" => "キログラム",
+            "cm" => "センチメートル",
+            "mm" => "ミリメートル",
+            "m" => "メートル",
+            _ => "グラム",
+        };
+        format!("{}{}{}", &caps[1], unit, &caps[3])
+    });
+    let text = p.symbol_unit.replace_all(&text, |caps: &Captures| {
+        let unit = match &caps[2] {
+            "%" | "％" => "パーセント",
+            _ => "度",
+        };
+        format!("{}{}", &caps[1], unit)
+    });
+    p.number
+        .replace_all(&text, |caps: &Captures| read_number_ja(&caps[0]))
+        .into_owned()
+}
+
+/// "1,234.5" → "千二百三十四点五" (0 始まりの数字列は 1 桁ずつ読む)
+fn read_number_ja(number: &str) -> String {
+    let number = number.replace(',', "");
+    let (integer, fraction) = match number.split_once('.') {
+        Some((integer, fraction)) => (integer, Some(fraction)),
+        None => (number.as_str(), None),
+    };
+    let mut result = match integer.parse::<u64>() {
+        Ok(n) if !(integer.len() > 1 && integer.starts_with('0')) => kanji_number(n),
+        _ => integer.chars().map(kanji_digit).collect(),
+    };
+    if let Some(fraction) = fraction {
+        result.push('点');
+        result.extend(fraction.chars().map(kanji_digit));
+    }
+    result
+}
+
+fn kanji_digit(c: char) -> char {
+    match c {
+        '1' => '一',
+        '2' => '二',
+        '3' => '三',
+        '4' => '四',
+        '5' => '五',
+        '6' => '六',
+        '7' => '七',
+        '8' => '八',
+        '9' => '九',
+        _ => '〇',
+    }
+}
+
+/// 漢数字にする (2024 → 二千二十四)
+fn kanji_number(n: u64) -> String {
+    if n == 0 {
+        return "零".to_string();
+    }
+    const GROUPS: [&str; 5] = ["", "万", "億", "兆", "京"];
+    let mut result = String::new();
+    let mut rest = n;
+    let mut groups = Vec::new();
+    while rest > 0 {
+        groups.push(rest % 10_000);
+        rest /= 10_000;
+    }
+    for (i, group) in groups.iter().enumerate().rev() {
+        if *group == 0 {
+            continue;
+        }
+        result.push_str(&kanji_group(*group));
+        result.push_str(GROUPS.get(i).copied().unwrap_or(""));
+    }
+    result
+}
+
+/// 4 桁以下の数 (十・百・千の前の一は読まない)
+fn kanji_group(n: u64) -> String {
+    let mut result = String::new();
+    for (unit, value) in [("千", 1000), ("百", 100), ("十", 10)] {
+        let digit = n / value % 10;
+        if digit > 1 {
+            result.push(kanji_digit(char::from_digit(digit as u32, 10).unwrap_or('0')));
+        }
+        if digit > 0 {
+            result.push_str(unit);
+        }
+    }
+    if !n.is_multiple_of(10) {
+        result.push(kanji_digit(char::from_digit((n % 10) as u32, 10).unwrap_or('0')));
+    }
+    result
+}
+
+fn normalize_en(text: &str) -> String {
+    const MONTHS: [&str; 12] = [
+        "January", "February", "March", "April", "May", "June", "July", "August", "September", "October",
+        "November", "December",
+    ];
+    let p = patterns();
+    let text = p.date.replace_all(text, |caps: &Captures| match month_day(&caps[2], &caps[3]) {
+        Some((month, day)) => format!("{} {}, {}", MONTHS[month as usize - 1], day, &caps[1]),
+        None => caps[0].to_string(),
+    });
+    let text = p.yen.replace_all(&text, "${1} yen");
+    let text = p.unit.replace_all(&text, |caps: &Captures| {
+        let unit = match &caps[2] {
+            "km" => "kilometers",
+            "kg" => "kilograms",
+            "cm" => "centimeters",
+            "mm" => "millimeters",
+            "m" => "meters",
+            _ => "grams",
+        };
+        format!("{} {}{}", &caps[1], unit, &caps[3])
+    });
+    p.symbol_unit
+        .replace_all(&text, |caps: &Captures| {
+            let unit = match &caps[2] {
+                "%" | "％" => "percent",
+                _ => "degrees Celsius",
+            };
+            format!("{} {}", &caps[1], unit)
+        })
+        .into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_ja() {
+        assert_eq!(normalize("2024/05/01 に会おう", "ja"), "二千二十四年五月一日 に会おう");
+        assert_eq!(normalize("21:00 から 5km 走った", "ja"), "二十一時 から 五キロメートル 走った");
+        assert_eq!(normalize("¥1,500 で 30% 引き", "ja"), "千五百円 で 三十パーセント 引き");
+        assert_eq!(normalize("３.５kg と 12345678", "ja"), "三点五キログラム と 千二百三十四万五千六百七十八");
+        assert_eq!(normalize("5min と 007", "ja"), "五min と 〇〇七");
+    }
+
+    #[test]
+    fn test_normalize_en() {
+        assert_eq!(normalize("See you on 2024/05/01", "en"), "See you on May 1, 2024");
+        assert_eq!(normalize("I ran 5km at 30°C", "en"), "I ran 5 kilometers at 30 degrees Celsius");
+        assert_eq!(normalize("2024/05/01", "fr"), "2024/05/01");
+    }
+}
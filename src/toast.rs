@@ -0,0 +1,134 @@
+use eframe::egui;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// トーストを表示し続ける時間
+const TOAST_DURATION: Duration = Duration::from_secs(8);
+/// 履歴に残す件数
+const MAX_HISTORY: usize = 50;
+
+struct Toast {
+    message: String,
+    created_at: Instant,
+    /// false ならお知らせ (履歴には残さない)
+    is_error: bool,
+}
+
+/// 画面右下に出すエラーとお知らせ (status_message が上書きされても見逃さないように)
+#[derive(Default)]
+pub struct Toasts {
+    active: Vec<Toast>,
+    /// (時刻 "HH:MM:SS", メッセージ) 新しいものが先頭
+    history: VecDeque<(String, String)>,
+    show_history: bool,
+}
+
+impl Toasts {
+    pub fn error(&mut self, message: impl Into<String>) {
+        let message = message.into();
+        eprintln!("[Toast] {}", message);
+        let time = chrono::Local::now().format("%H:%M:%S").to_string();
+        self.history.push_front((time, message.clone()));
+        self.history.truncate(MAX_HISTORY);
+        self.active.push(Toast {
+            message,
+            created_at: Instant::now(),
+            is_error: true,
+        });
+    }
+
+    /// エラーではないお知らせ (自動でプリセットを切り替えたときなど)
+    pub fn info(&mut self, message: impl Into<String>) {
+        let message = message.into();
+        println!("[Toast] {}", message);
+        self.active.push(Toast {
+            message,
+            created_at: Instant::now(),
+            is_error: false,
+        });
+    }
+
+    pub fn history_len(&self) -> usize {
+        self.history.len()
+    }
+
+    pub fn open_history(&mut self) {
+        self.show_history = true;
+    }
+
+    /// Draw active toasts and the history window
+    pub fn show(&mut self, ctx: &egui::Context) {
+        self.active.retain(|t| t.created_at.elapsed() < TOAST_DURATION);
+
+        if !self.active.is_empty() {
+            let mut dismissed = None;
+            egui::Area::new(egui::Id::new("error_toasts"))
+                .anchor(egui::Align2::RIGHT_BOTTOM, egui::vec2(-10.0, -10.0))
+                .order(egui::Order::Foreground)
+                .show(ctx, |ui| {
+                    ui.set_max_width(320.0);
+                    for (i, toast) in self.active.iter().enumerate() {
+                        let (fill, icon) = if toast.is_error {
+                            (egui::Color32::from_rgb(120, 30, 30), "❌")
+                        } else {
+                            (egui::Color32::from_rgb(30, 80, 120), "ℹ")
+                        };
+                        let response = egui::Frame::popup(ui.style())
+                            .fill(fill)
+                            .show(ui, |ui| {
+                                ui.colored_label(egui::Color32::WHITE, format!("{} {}", icon, toast.message));
+                            })
+                            .response
+                            .interact(egui::Sense::click())
+                            .on_hover_text("クリックで閉じる");
+                        if response.clicked() {
+                            dismissed = Some(i);
+                        }
+                        ui.add_space(4.0);
+                    }
+                });
+            if let Some(i) = dismissed {
+                self.active.remove(i);
+            }
+        }
+
+        if self.show_history {
+            let mut open = true;
+            egui::Window::new("エラー履歴")
+                .open(&mut open)
+                .collapsible(false)
+                .default_width(400.0)
+                .show(ctx, |ui| {
+                    if self.history.is_empty() {
+                        ui.label("エラーはありません");
+                    } else if ui.button("Clear").clicked() {
+                        self.history.clear();
+                    }
+                    egui::ScrollArea::vertical().max_height(300.0).show(ui, |ui| {
+                        for (time, message) in &self.history {
+                            ui.horizontal_wrapped(|ui| {
+                                ui.weak(time);
+                                ui.label(message);
+                            });
+                        }
+                    });
+                });
+            self.show_history = open;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_history_is_capped() {
+        let mut toasts = Toasts::default();
+        for i in 0..(MAX_HISTORY + 5) {
+            toasts.error(format!("error {}", i));
+        }
+        assert_eq!(toasts.history_len(), MAX_HISTORY);
+        assert_eq!(toasts.history[0].1, format!("error {}", MAX_HISTORY + 4));
+    }
+}
@@ -0,0 +1,209 @@
+use crate::config::Config;
+use crate::elevenlabs::{self, ElevenLabsClient};
+use crate::error::AppError;
+use crate::openai::OpenAIClient;
+use crate::playback::{self, Output, StopHandle};
+use crate::text_normalize;
+use crate::voicevox::VoicevoxClient;
+use std::io::Write;
+use std::sync::Mutex;
+
+/// 読み上げの方式 (設定値, 表示名)
+pub const TTS_BACKENDS: [(&str, &str); 3] = [
+    ("openai", "OpenAI TTS"),
+    ("voicevox", "VOICEVOX"),
+    ("elevenlabs", "ElevenLabs"),
+];
+
+/// Preview ボタンで読み上げる文
+const PREVIEW_LINE: &str = "こんにちは。今日は 2024/05/01、いい天気ですね。";
+
+/// 返答を読み上げる (バックグラウンド、エラーはログのみ)
+///
+/// 出力デバイスを仮想オーディオケーブルにすれば、別アカウントの VRChat のマイクとして使える。
+/// 自分用の読み上げが有効なら、同じ声をオペレーターのヘッドホンにも鳴らす (音声の生成は 1 回だけ)
+pub fn speak(config: &Config, text: &str) {
+    if text.trim().is_empty() {
+        return;
+    }
+    let outputs = outputs(config);
+    if !outputs.is_empty() {
+        speak_in_background(config, text, outputs);
+    }
+}
+
+/// 読み上げを鳴らすデバイスと音量
+fn outputs(config: &Config) -> Vec<Output> {
+    let mut outputs = Vec::new();
+    if config.tts_enabled {
+        outputs.push((config.tts_output_device.clone(), config.tts_volume));
+    }
+    if config.monitor_tts_enabled {
+        outputs.push((config.monitor_tts_output_device.clone(), config.monitor_tts_volume));
+    }
+    outputs
+}
+
+/// 設定中の声でサンプルの文を読み上げる (読み上げが無効でも鳴らす)
+pub fn preview(config: &Config) {
+    speak_in_background(config, PREVIEW_LINE, vec![(config.tts_output_device.clone(), config.tts_volume)]);
+}
+
+fn speak_in_background(config: &Config, text: &str, outputs: Vec<Output>) {
+    if crate::mock::is_enabled() {
+        println!("[TTS] (mock) {}", text);
+        return;
+    }
+    let text = if config.tts_normalize_enabled {
+        text_normalize::normalize(text, &config.tts_normalize_locale)
+    } else {
+        text.to_string()
+    };
+    let backend = backend(config);
+    let stop = begin_speaking();
+    std::thread::spawn(move || {
+        if let Err(e) = backend.speak(&text, &outputs, &stop) {
+            eprintln!("[TTS] {}", e);
+        }
+        finish_speaking(&stop);
+    });
+}
+
+/// 再生中の読み上げ (barge-in で止めるため)
+static SPEAKING: Mutex<Vec<StopHandle>> = Mutex::new(Vec::new());
+
+fn begin_speaking() -> StopHandle {
+    let stop = StopHandle::new();
+    SPEAKING.lock().unwrap_or_else(|e| e.into_inner()).push(stop.clone());
+    stop
+}
+
+fn finish_speaking(stop: &StopHandle) {
+    SPEAKING
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .retain(|s| !s.is_same(stop));
+}
+
+/// 再生中の読み上げをフェードアウトして止める (止めたものがあれば true)
+pub fn stop_speaking() -> bool {
+    let speaking = std::mem::take(&mut *SPEAKING.lock().unwrap_or_else(|e| e.into_inner()));
+    for stop in &speaking {
+        stop.stop();
+    }
+    !speaking.is_empty()
+}
+
+/// 読み上げの方式ごとの実装
+pub trait TtsBackend: Send {
+    /// text の音声を 1 回だけ作って outputs のデバイスすべてで鳴らし、再生が終わるまで待つ
+    fn speak(&self, text: &str, outputs: &[Output], stop: &StopHandle) -> Result<(), AppError>;
+}
+
+struct OpenAiTts {
+    client: OpenAIClient,
+    voice: String,
+}
+
+impl TtsBackend for OpenAiTts {
+    fn speak(&self, text: &str, outputs: &[Output], stop: &StopHandle) -> Result<(), AppError> {
+        let audio = self
+            .client
+            .synthesize_speech(text, &self.voice)?;
+        play_wav(&audio, outputs, stop)
+    }
+}
+
+struct VoicevoxTts {
+    client: VoicevoxClient,
+    style_id: u32,
+    speed: f32,
+    pitch: f32,
+}
+
+impl TtsBackend for VoicevoxTts {
+    fn speak(&self, text: &str, outputs: &[Output], stop: &StopHandle) -> Result<(), AppError> {
+        let audio = self.client.synthesize(text, self.style_id, self.speed, self.pitch)?;
+        play_wav(&audio, outputs, stop)
+    }
+}
+
+/// 生成された分から順に再生する (長い返答でも話し始めが早い)
+struct ElevenLabsTts {
+    client: ElevenLabsClient,
+    voice_id: String,
+}
+
+impl TtsBackend for ElevenLabsTts {
+    fn speak(&self, text: &str, outputs: &[Output], stop: &StopHandle) -> Result<(), AppError> {
+        let stream = self.client.stream(text, &self.voice_id)?;
+        playback::play_pcm_stream(stream, elevenlabs::STREAM_SAMPLE_RATE, outputs, stop)
+    }
+}
+
+/// 設定に合わせた読み上げの実装
+pub fn backend(config: &Config) -> Box<dyn TtsBackend> {
+    match config.tts_backend.as_str() {
+        "voicevox" => Box::new(VoicevoxTts {
+            client: VoicevoxClient::new(&config.voicevox_url),
+            style_id: config.voicevox_style_id,
+            speed: config.voicevox_speed,
+            pitch: config.voicevox_pitch,
+        }),
+        "elevenlabs" => Box::new(ElevenLabsTts {
+            client: ElevenLabsClient::new(&config.elevenlabs_api_key),
+            voice_id: config.elevenlabs_voice_id.clone(),
+        }),
+        _ => Box::new(OpenAiTts {
+            client: OpenAIClient::new(config.openai_api_key.clone(), String::new(), String::new()),
+            voice: config.tts_voice.clone(),
+        }),
+    }
+}
+
+fn play_wav(audio: &[u8], outputs: &[Output], stop: &StopHandle) -> Result<(), AppError> {
+    let mut file = tempfile::Builder::new()
+        .prefix("eliza_tts_")
+        .suffix(".wav")
+        .tempfile()
+        .map_err(|e| AppError::Audio(format!("Failed to create temp file: {}", e)))?;
+    file.write_all(audio)
+        .map_err(|e| AppError::Audio(format!("Failed to write speech audio: {}", e)))?;
+    playback::play_file_until(file.path(), outputs, stop)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stop_speaking() {
+        let first = begin_speaking();
+        let second = begin_speaking();
+        finish_speaking(&first);
+        assert!(stop_speaking());
+        assert!(!first.is_stopped());
+        assert!(second.is_stopped());
+        assert!(!stop_speaking());
+    }
+
+    #[test]
+    fn test_outputs() {
+        let config = Config {
+            tts_enabled: true,
+            tts_output_device: "CABLE Input".to_string(),
+            tts_volume: 1.0,
+            monitor_tts_enabled: true,
+            monitor_tts_output_device: String::new(),
+            monitor_tts_volume: 0.5,
+            ..Config::default()
+        };
+        // 1 回の読み上げで両方のデバイスに鳴らす
+        assert_eq!(outputs(&config), vec![("CABLE Input".to_string(), 1.0), (String::new(), 0.5)]);
+        let config = Config {
+            tts_enabled: false,
+            ..config
+        };
+        assert_eq!(outputs(&config), vec![(String::new(), 0.5)]);
+    }
+}
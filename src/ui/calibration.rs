@@ -0,0 +1,164 @@
+//! 音量閾値の自動設定 (無音 2 秒 → 発話 2 秒以上)
+
+use crate::audio::AudioRecorder;
+use crate::pipeline_controller::{AppState, PipelineEvent};
+use crate::ElizaAgentApp;
+use eframe::egui;
+use std::time::Instant;
+
+/// 無音フェーズの長さ (秒)
+const SILENCE_PHASE_SECS: f32 = 2.0;
+
+/// キャリブレーション中に集めた RMS
+#[derive(Debug, Default)]
+pub struct CalibrationState {
+    started_at: Option<Instant>,
+    rms_samples: Vec<f32>,
+}
+
+impl CalibrationState {
+    /// フェーズの開始 (集めた値は捨てる)
+    pub fn begin(&mut self, now: Instant) {
+        self.started_at = Some(now);
+        self.rms_samples.clear();
+    }
+
+    pub fn reset(&mut self) {
+        self.started_at = None;
+        self.rms_samples.clear();
+    }
+
+    pub fn record(&mut self, rms: f32) {
+        self.rms_samples.push(rms);
+    }
+
+    pub fn elapsed_secs(&self, now: Instant) -> f32 {
+        self.started_at
+            .map(|t| now.saturating_duration_since(t).as_secs_f32())
+            .unwrap_or(0.0)
+    }
+
+    /// 無音フェーズの結果 (silence_threshold に使う)
+    pub fn max_rms(&self) -> f32 {
+        self.rms_samples.iter().cloned().fold(0.0f32, f32::max)
+    }
+
+    /// 発話フェーズの結果 (start_threshold に使う)
+    pub fn average_rms(&self) -> Option<f32> {
+        if self.rms_samples.is_empty() {
+            return None;
+        }
+        Some(self.rms_samples.iter().sum::<f32>() / self.rms_samples.len() as f32)
+    }
+}
+
+impl ElizaAgentApp {
+    pub(crate) fn start_calibration(&mut self) {
+        println!("Starting calibration: silence phase");
+        // マイクを起動（silence_threshold=0 で全サンプル拾う）
+        match AudioRecorder::new(0.0) {
+            Ok(mut recorder) => {
+                recorder.set_gain(self.config.input_gain());
+                let device_name = self
+                    .config
+                    .input_device_name
+                    .as_ref()
+                    .filter(|name| name.as_str() != "Windows既定")
+                    .map(|s| s.as_str());
+                match recorder.start_recording_with_device(device_name) {
+                    Ok(_) => {
+                        self.audio_recorder = Some(recorder);
+                        self.calibration.begin(Instant::now());
+                        self.status_message = "キャリブレーション: 静かにしてください... (2秒)".to_string();
+                    }
+                    Err(e) => {
+                        self.report_error(e.context("Error"));
+                        self.dispatch(PipelineEvent::MicrophoneFailed);
+                    }
+                }
+            }
+            Err(e) => {
+                self.report_error(e.context("Error"));
+                self.dispatch(PipelineEvent::MicrophoneFailed);
+            }
+        }
+    }
+
+    /// 発話フェーズ完了: 平均 RMS を start_threshold に
+    pub(crate) fn finish_calibration(&mut self) {
+        if let Some(avg_rms) = self.calibration.average_rms() {
+            self.config.start_threshold = avg_rms;
+            self.settings.draft.start_threshold = avg_rms;
+            println!("Calib voice done: avg_rms={:.6} → start_threshold", avg_rms);
+            self.status_message = format!(
+                "✓ キャリブレーション完了! silence={:.4}, start={:.4}",
+                self.config.silence_threshold, self.config.start_threshold
+            );
+        }
+        if let Some(mut recorder) = self.audio_recorder.take() {
+            recorder.stop_recording();
+        }
+        self.recording_info.clear();
+        self.calibration.reset();
+    }
+
+    /// 毎フレーム: マイクの RMS を集め、無音フェーズが終わったら発話フェーズへ進める
+    pub(crate) fn update_calibration(&mut self, ctx: &egui::Context) {
+        let state = self.pipeline.state();
+        if state != AppState::CalibratingSilence && state != AppState::CalibratingVoice {
+            return;
+        }
+        ctx.request_repaint();
+        let Some(recorder) = &self.audio_recorder else {
+            return;
+        };
+        self.calibration.record(recorder.get_rms_amplitude());
+        let now = Instant::now();
+        let elapsed = self.calibration.elapsed_secs(now);
+
+        if state == AppState::CalibratingVoice {
+            self.recording_info = format!("発話録音中: {:.1}s (2秒以上話したら停止ボタン)", elapsed);
+            return;
+        }
+
+        self.recording_info = format!("無音録音中: {:.1}s / {:.1}s", elapsed, SILENCE_PHASE_SECS);
+        if elapsed >= SILENCE_PHASE_SECS {
+            // 無音フェーズ完了: 最大 RMS を silence_threshold に
+            let max_rms = self.calibration.max_rms();
+            self.config.silence_threshold = max_rms;
+            self.settings.draft.silence_threshold = max_rms;
+            println!("Calib silence done: max_rms={:.6} → silence_threshold", max_rms);
+
+            // 発話フェーズへ
+            self.calibration.begin(now);
+            self.dispatch(PipelineEvent::CalibrationSilenceDone);
+            self.status_message = "キャリブレーション: 話してください... (2秒以上)".to_string();
+            self.recording_info = String::new();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_calibration_state() {
+        let start = Instant::now();
+        let mut state = CalibrationState::default();
+        assert_eq!(state.average_rms(), None);
+        assert_eq!(state.elapsed_secs(start), 0.0);
+
+        state.begin(start);
+        for rms in [0.01, 0.03, 0.02] {
+            state.record(rms);
+        }
+        assert_eq!(state.max_rms(), 0.03);
+        assert!((state.average_rms().unwrap() - 0.02).abs() < 1e-6);
+        assert_eq!(state.elapsed_secs(start + Duration::from_millis(1500)), 1.5);
+
+        state.begin(start);
+        assert_eq!(state.average_rms(), None);
+    }
+}
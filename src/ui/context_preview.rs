@@ -0,0 +1,59 @@
+//! 次のリクエストで Agent に送るメッセージの一覧 (人格が何を覚えているかの確認用)
+
+use crate::eliza;
+use crate::ElizaAgentApp;
+use eframe::egui;
+
+/// 次の発話の代わりに表示する文
+const NEXT_UTTERANCE: &str = "(次の発話)";
+
+impl ElizaAgentApp {
+    pub(crate) fn show_context_preview_window(&mut self, ctx: &egui::Context) {
+        let mut open = true;
+        egui::Window::new("送信されるコンテキスト")
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(true)
+            .default_width(480.0)
+            .show(ctx, |ui| {
+                let Some(client) = &self.eliza_client else {
+                    ui.label("処理中か、まだ会話が始まっていません");
+                    return;
+                };
+                let messages = client.next_request_preview(NEXT_UTTERANCE);
+                let stats = client.history_stats();
+                let tokens: usize = messages.iter().map(|m| eliza::estimate_tokens(&m.content)).sum();
+                ui.label(format!(
+                    "{} 件 / 約 {} トークン (履歴 {}/{})",
+                    messages.len(),
+                    tokens,
+                    stats.length,
+                    stats.max_length
+                ));
+                if stats.compacts_next {
+                    ui.colored_label(
+                        egui::Color32::from_rgb(200, 100, 0),
+                        "次のやり取りで履歴が上限を超えるので、古いものから削られます",
+                    );
+                }
+                ui.weak("気分・言語・ワールドの会話などの補足は、送る直前に更新されます");
+                ui.separator();
+                egui::ScrollArea::vertical().max_height(420.0).show(ui, |ui| {
+                    for message in &messages {
+                        let color = match message.role.as_str() {
+                            "system" => egui::Color32::GRAY,
+                            "user" => egui::Color32::from_rgb(30, 80, 180),
+                            _ => egui::Color32::from_rgb(0, 128, 0),
+                        };
+                        ui.horizontal(|ui| {
+                            ui.colored_label(color, egui::RichText::new(&message.role).strong());
+                            ui.weak(format!("~{} tok", eliza::estimate_tokens(&message.content)));
+                        });
+                        ui.label(&message.content);
+                        ui.add_space(4.0);
+                    }
+                });
+            });
+        self.show_context_preview = open;
+    }
+}
@@ -0,0 +1,303 @@
+//! 会話履歴の表示とテキスト送信欄
+
+use crate::history;
+use crate::mood::MoodState;
+use crate::session::{self, Session};
+use crate::ElizaAgentApp;
+use eframe::egui;
+
+impl ElizaAgentApp {
+    /// 会話履歴と、その操作ボタン・話題・気分などの状態表示
+    pub(crate) fn show_conversation(&mut self, ui: &mut egui::Ui) {
+        // Conversation history
+        ui.horizontal(|ui| {
+            ui.heading("Conversation");
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                if ui.button("🗑 Clear History").clicked() {
+                    self.conversation_history.clear();
+                    self.current_topic = None;
+                    self.session_started_at = session::now_unix_secs();
+                    self.summarized_turns = 0;
+                    self.mood = MoodState::default();
+                    // Save memory before clearing (in the background, from a snapshot)
+                    self.save_memory_in_background();
+                    if let Some(ref mut eliza_client) = self.eliza_client {
+                        eliza_client.clear_history();
+                        println!("Conversation history cleared");
+                        self.status_message = "Conversation history cleared".to_string();
+                    }
+                }
+            });
+        });
+        ui.horizontal(|ui| {
+            if ui.button("💾 Session").clicked() {
+                self.saved_sessions = Session::list();
+                self.show_sessions = true;
+            }
+            if ui
+                .button("🗂 再書き起こし")
+                .on_hover_text("保存した録音をまとめて書き起こします")
+                .clicked()
+            {
+                self.show_batch = true;
+            }
+            if ui
+                .button("⏯ リプレイ")
+                .on_hover_text("書き出した会話や保存した録音をパイプラインに流し直します")
+                .clicked()
+            {
+                self.replay.open = true;
+            }
+            if ui
+                .button("🛠 OSC")
+                .on_hover_text("送受信した OSC メッセージを確認します")
+                .clicked()
+            {
+                self.show_osc_console = true;
+            }
+            if ui
+                .button("🩺 診断")
+                .on_hover_text("書き起こし・Agent・OSC の応答時間とエラー率")
+                .clicked()
+            {
+                self.show_diagnostics = true;
+            }
+            if ui
+                .button("⏰ 予約")
+                .on_hover_text("決まった時刻にチャットボックスへ送るメッセージ")
+                .clicked()
+            {
+                self.show_scheduler = true;
+            }
+            if ui
+                .button("🔍 コンテキスト")
+                .on_hover_text("次の返答で Agent に送るプロンプトと履歴を表示します")
+                .clicked()
+            {
+                self.show_context_preview = true;
+            }
+            let has_client = self.eliza_client.is_some();
+            if ui
+                .add_enabled(has_client, egui::Button::new("🧠 記憶を保存"))
+                .on_hover_text("今の会話履歴をサーバーの記憶に保存します")
+                .clicked()
+            {
+                self.save_memory_in_background();
+            }
+            let can_load = self.agent_configured() && self.memory_load_receiver.is_none();
+            if ui
+                .add_enabled(can_load, egui::Button::new("📥 記憶を読み込む"))
+                .on_hover_text("サーバーの記憶を取得して、次の返答から参照させます")
+                .clicked()
+            {
+                self.load_memory();
+            }
+        });
+        if let Some(topic) = &self.current_topic {
+            ui.label(format!("📌 話題: {}", topic));
+        }
+        if self.config.mood_enabled {
+            let mood = self.mood.mood();
+            ui.label(format!("{} 気分: {}", mood.emoji(), mood.label()));
+        }
+        if let Some(stats) = self.history_stats {
+            ui.weak(format!(
+                "📚 履歴 {}/{} 件 · 約 {} tokens",
+                stats.length, stats.max_length, stats.estimated_tokens
+            ))
+            .on_hover_text("上限を超えると記憶をサーバーに保存し、直近の数件を残して履歴を消します");
+            if stats.compacts_next {
+                ui.colored_label(
+                    egui::Color32::from_rgb(200, 100, 0),
+                    "⚠ 次の発言で記憶を保存して履歴を圧縮します (古いやりとりは忘れます)",
+                );
+            }
+        }
+        if !self.config.ab_prompt_b.trim().is_empty() || !self.ab_test.records.is_empty() {
+            ui.horizontal(|ui| {
+                if self.ab_test.is_running() {
+                    ui.label(format!("🧪 A/B テスト中 (残り {} ターン)", self.ab_test.remaining_turns()));
+                    if ui.button("停止").clicked() {
+                        self.ab_test.stop();
+                    }
+                } else if ui
+                    .add_enabled(
+                        !self.config.ab_prompt_b.trim().is_empty(),
+                        egui::Button::new("🧪 A/B テスト開始"),
+                    )
+                    .on_hover_text("Persona Prompt (A) と A/B Test Prompt (B) を返答ごとにランダムに切り替えます")
+                    .clicked()
+                {
+                    self.ab_test.start(self.config.ab_test_turns);
+                }
+                if !self.ab_test.records.is_empty() && ui.button("📊 結果").clicked() {
+                    self.show_ab_report = true;
+                }
+            });
+        }
+        if self.config.session_summary_enabled {
+            if self.summary_receiver.is_some() {
+                ui.weak("📝 会話を要約しています...");
+            } else if let Some(summary) = &self.last_summary {
+                ui.label(format!("📝 前回の会話: {}", summary));
+            }
+        }
+        ui.separator();
+
+        egui::ScrollArea::vertical()
+            .max_height(300.0)
+            .auto_shrink([false, false])
+            .stick_to_bottom(true)
+            .show(ui, |ui| {
+                let now = session::now_unix_secs();
+                let mut last_date = None;
+                let mut rating = None;
+                for (i, entry) in self.conversation_history.iter().enumerate() {
+                    // 日付が変わったら区切りを入れる
+                    if let Some(date) = entry.local_date() {
+                        if last_date != Some(date) {
+                            ui.vertical_centered(|ui| {
+                                ui.weak(history::format_day_separator(date));
+                            });
+                            ui.separator();
+                            last_date = Some(date);
+                        }
+                    }
+                    ui.horizontal(|ui| {
+                        let color = match entry.role.as_str() {
+                            "You" => egui::Color32::from_rgb(30, 80, 180),
+                            "Others" => egui::Color32::from_rgb(160, 90, 0),
+                            "Ignored" => egui::Color32::GRAY,
+                            _ => egui::Color32::from_rgb(0, 128, 0), // Dark green
+                        };
+                        ui.colored_label(color, format!("{}:", entry.role));
+                        ui.weak(entry.relative_time(now))
+                            .on_hover_text(entry.absolute_time());
+                        if entry.role == "Agent" {
+                            if ui.selectable_label(entry.rating == Some(1), "👍").clicked() {
+                                rating = Some((i, 1));
+                            }
+                            if ui.selectable_label(entry.rating == Some(-1), "👎").clicked() {
+                                rating = Some((i, -1));
+                            }
+                        }
+                        if let Some(reason) = &entry.suppressed {
+                            ui.weak("🔇 UI のみ").on_hover_text(reason);
+                        }
+                        if let Some(meta) = &entry.meta {
+                            ui.weak("ℹ").on_hover_text(meta.describe());
+                        }
+                    });
+                    ui.label(&entry.message);
+                    ui.add_space(10.0);
+                }
+                if let Some((i, value)) = rating {
+                    self.rate_history_entry(i, value);
+                }
+                if let Some(partial) = &self.streaming_reply {
+                    ui.colored_label(egui::Color32::from_rgb(0, 128, 0), "Agent:");
+                    ui.weak(format!("{}▌", partial));
+                }
+            });
+
+        // Compare mode: 直近の 2 つの返答を並べて表示
+        if self.config.compare_mode_enabled {
+            ui.add_space(10.0);
+            ui.separator();
+            ui.horizontal(|ui| {
+                ui.label("比較モード: VRChat に送る返答");
+                ui.radio_value(&mut self.config.compare_use_secondary, false, "A");
+                ui.radio_value(&mut self.config.compare_use_secondary, true, "B");
+            });
+            if let Some(comparison) = &self.last_comparison {
+                ui.columns(2, |columns| {
+                    let sides = [
+                        ("A", &comparison.model_a, &comparison.reply_a, !comparison.sent_b),
+                        ("B", &comparison.model_b, &comparison.reply_b, comparison.sent_b),
+                    ];
+                    for (column, (side, model, reply, sent)) in columns.iter_mut().zip(sides) {
+                        let title = if sent {
+                            format!("{}: {} ✔ VRChat", side, model)
+                        } else {
+                            format!("{}: {}", side, model)
+                        };
+                        column.colored_label(egui::Color32::from_rgb(0, 128, 0), title);
+                        column.label(reply.as_str());
+                    }
+                });
+            }
+        }
+
+        // Quick replies (パイプライン処理中でも直接送れる)
+        if !self.config.quick_replies.is_empty() {
+            ui.add_space(10.0);
+            let mut clicked = None;
+            ui.horizontal_wrapped(|ui| {
+                ui.label("⚡");
+                for phrase in &self.config.quick_replies {
+                    let enabled = self.config.quick_replies_bypass_agent
+                        || self.processing_receiver.is_none();
+                    if ui.add_enabled(enabled, egui::Button::new(phrase)).clicked() {
+                        clicked = Some(phrase.clone());
+                    }
+                }
+            });
+            if let Some(phrase) = clicked {
+                self.send_quick_reply(phrase);
+            }
+        }
+    }
+
+    pub(crate) fn show_text_input(&mut self, ui: &mut egui::Ui) {
+        // Text input area
+        ui.add_space(10.0);
+        ui.separator();
+        ui.label("テキスト送信 (Ctrl+Enter で送信 / Shift+Enter で改行):");
+
+        let text_edit = egui::TextEdit::multiline(&mut self.text_input)
+            .desired_rows(3)
+            .desired_width(f32::INFINITY)
+            .hint_text("ここにテキストを入力...");
+
+        let response = ui.add(text_edit);
+
+        // Handle Ctrl+Enter to send
+        if response.has_focus() {
+            let ctrl = ui.input(|i| i.modifiers.ctrl);
+            let shift = ui.input(|i| i.modifiers.shift);
+            let enter_pressed = ui.input(|i| i.key_pressed(egui::Key::Enter));
+
+            if enter_pressed && ctrl && !shift {
+                // Ctrl+Enter: send
+                let text = self.text_input.trim().to_string();
+                if !text.is_empty() && self.processing_receiver.is_none() {
+                    self.text_input.clear();
+                    self.send_text_message(text);
+                }
+            } else if enter_pressed && !shift && !ctrl {
+                // Plain Enter: do nothing (remove the newline that was just added)
+                // Remove trailing newline if added by egui
+                if self.text_input.ends_with('\n') {
+                    self.text_input.pop();
+                }
+            }
+            // Shift+Enter: allow newline (default egui behavior)
+        }
+
+        ui.add_space(5.0);
+        ui.horizontal(|ui| {
+            let send_enabled = !self.text_input.trim().is_empty()
+                && self.processing_receiver.is_none();
+            if ui
+                .add_enabled(send_enabled, egui::Button::new("送信 (Ctrl+Enter)"))
+                .clicked()
+            {
+                let text = self.text_input.trim().to_string();
+                if !text.is_empty() {
+                    self.text_input.clear();
+                    self.send_text_message(text);
+                }
+            }
+        });
+    }
+}
@@ -0,0 +1,74 @@
+//! バックエンドごとの応答時間とエラー率の一覧 (遅いのがどこかを見分ける)
+
+use super::widgets::sparkline;
+use crate::health::{self, Backend};
+use crate::ElizaAgentApp;
+use eframe::egui;
+use std::time::Instant;
+
+fn format_ms(ms: Option<u64>) -> String {
+    match ms {
+        Some(ms) if ms >= 1000 => format!("{:.1}s", ms as f64 / 1000.0),
+        Some(ms) => format!("{}ms", ms),
+        None => "-".to_string(),
+    }
+}
+
+impl ElizaAgentApp {
+    pub(crate) fn show_diagnostics_window(&mut self, ctx: &egui::Context) {
+        let mut open = true;
+        egui::Window::new("診断")
+            .open(&mut open)
+            .collapsible(false)
+            .default_width(460.0)
+            .show(ctx, |ui| {
+                ui.weak(format!("直近の呼び出し (バックエンドごとに最大 {} 件)", health::MAX_SAMPLES));
+                egui::Grid::new("diagnostics_grid")
+                    .num_columns(5)
+                    .spacing([12.0, 8.0])
+                    .show(ui, |ui| {
+                        ui.strong("");
+                        ui.strong("平均");
+                        ui.strong("p90");
+                        ui.strong("エラー率");
+                        ui.strong("応答時間");
+                        ui.end_row();
+                        let now = Instant::now();
+                        for backend in Backend::ALL {
+                            let samples = health::samples(backend);
+                            ui.label(backend.label());
+                            match health::summarize(&samples) {
+                                Some(summary) => {
+                                    ui.label(format_ms(summary.avg_ms));
+                                    ui.label(format_ms(summary.p90_ms));
+                                    let rate = format!("{:.0}% ({} 件)", summary.error_rate * 100.0, summary.count);
+                                    if summary.error_rate > 0.0 {
+                                        ui.colored_label(egui::Color32::from_rgb(200, 100, 0), rate);
+                                    } else {
+                                        ui.label(rate);
+                                    }
+                                }
+                                None => {
+                                    ui.weak("-");
+                                    ui.weak("-");
+                                    ui.weak("まだ記録なし");
+                                }
+                            }
+                            let values: Vec<(f32, bool)> = samples
+                                .iter()
+                                .map(|s| (s.latency.as_secs_f32(), s.ok))
+                                .collect();
+                            let last = samples
+                                .last()
+                                .map(|s| format!("最後: {}秒前", now.duration_since(s.at).as_secs()))
+                                .unwrap_or_default();
+                            sparkline(ui, egui::vec2(160.0, 28.0), &values).on_hover_text(last);
+                            ui.end_row();
+                        }
+                    });
+                ui.separator();
+                ui.small("STT と Agent が遅ければネットワークや中継サーバー、OSC の失敗が多ければ VRChat 側を確認してください");
+            });
+        self.show_diagnostics = open;
+    }
+}
@@ -0,0 +1,298 @@
+//! マイクの動作確認 (3 秒録音 → 再生 → 音量の表示 → 試しに書き起こし)
+
+use crate::audio::{self, AudioRecorder};
+use crate::config::Config;
+use crate::error::AppError;
+use crate::openai::OpenAIClient;
+use crate::{playback, stt, ElizaAgentApp};
+use eframe::egui;
+use std::sync::mpsc::{channel, Receiver};
+use std::time::Instant;
+
+/// 録音する長さ (秒)
+const RECORD_SECS: f32 = 3.0;
+/// これより小さいピークは音が入っていないとみなす
+const SILENT_PEAK: f32 = 0.01;
+/// これ以上のピークは音割れとみなす
+const CLIPPING_PEAK: f32 = 0.99;
+/// 話し声・雑音の RMS を測る区間 (秒)
+const WINDOW_SECS: f32 = 0.1;
+
+/// 録音の音量
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Levels {
+    pub peak: f32,
+    /// 録音全体の RMS
+    pub rms: f32,
+    /// 一番大きい区間の RMS (話し声。Start Threshold と比べる)
+    pub loudest_rms: f32,
+    /// 一番静かな区間の RMS (周りの雑音。Silence Threshold と比べる)
+    pub quietest_rms: f32,
+}
+
+fn rms(samples: &[f32]) -> f32 {
+    if samples.is_empty() {
+        return 0.0;
+    }
+    (samples.iter().map(|s| s * s).sum::<f32>() / samples.len() as f32).sqrt()
+}
+
+pub fn measure(samples: &[f32], sample_rate: u32) -> Levels {
+    let window = ((sample_rate as f32 * WINDOW_SECS) as usize).max(1);
+    let windows: Vec<f32> = samples.chunks(window).map(rms).collect();
+    Levels {
+        peak: samples.iter().fold(0.0f32, |max, s| max.max(s.abs())),
+        rms: rms(samples),
+        loudest_rms: windows.iter().cloned().fold(0.0f32, f32::max),
+        quietest_rms: windows.iter().cloned().reduce(f32::min).unwrap_or(0.0),
+    }
+}
+
+/// 結果から分かる、直したほうがいいこと (なければ空)
+pub fn suggestions(levels: Levels, config: &Config, transcription: &Result<String, AppError>) -> Vec<String> {
+    let mut tips = Vec::new();
+    if levels.peak < SILENT_PEAK {
+        tips.push("ほとんど音が入っていません。入力デバイスの選択と、マイクや Windows のミュートを確認してください".to_string());
+        return tips;
+    }
+    if levels.peak >= CLIPPING_PEAK {
+        tips.push("音が割れています。入力ゲインかマイクの音量を下げてください".to_string());
+    }
+    if levels.loudest_rms < config.start_threshold {
+        tips.push(format!(
+            "話し声 ({:.4}) が Start Threshold ({:.4}) に届かず、録音が始まりません。マイクを近づけるか「音量閾値を自動設定」を使ってください",
+            levels.loudest_rms, config.start_threshold
+        ));
+    }
+    if levels.quietest_rms > config.silence_threshold {
+        tips.push(format!(
+            "周りの音 ({:.4}) が Silence Threshold ({:.4}) より大きく、録音が終わりません。静かな場所で「音量閾値を自動設定」を使ってください",
+            levels.quietest_rms, config.silence_threshold
+        ));
+    }
+    match transcription {
+        Ok(text) if text.trim().is_empty() => {
+            tips.push("書き起こしが空でした。録音中にはっきり話してみてください".to_string())
+        }
+        Ok(_) => {}
+        Err(e) => tips.push(e.remediation().unwrap_or("書き起こしの設定を確認してください").to_string()),
+    }
+    tips
+}
+
+#[derive(Default)]
+enum Step {
+    #[default]
+    Idle,
+    Recording {
+        recorder: AudioRecorder,
+        started_at: Instant,
+    },
+    /// 再生と書き起こしをバックグラウンドで行っている
+    Testing {
+        levels: Levels,
+        receiver: Receiver<Result<String, AppError>>,
+    },
+    Done {
+        levels: Levels,
+        transcription: Result<String, AppError>,
+    },
+    Failed(String),
+}
+
+#[derive(Default)]
+pub struct MicCheckState {
+    pub open: bool,
+    step: Step,
+}
+
+impl ElizaAgentApp {
+    fn start_mic_check(&mut self) {
+        let device_name = self
+            .config
+            .input_device_name
+            .clone()
+            .filter(|name| name.as_str() != "Windows既定");
+        let started = AudioRecorder::new(0.0).and_then(|mut recorder| {
+            recorder.set_gain(self.config.input_gain());
+            recorder.start_recording_with_device(device_name.as_deref())?;
+            Ok(recorder)
+        });
+        self.mic_check.step = match started {
+            Ok(recorder) => Step::Recording {
+                recorder,
+                started_at: Instant::now(),
+            },
+            Err(e) => Step::Failed(e.user_message()),
+        };
+    }
+
+    /// 録音を止めて、再生と書き起こしを始める
+    fn finish_mic_check_recording(&mut self, mut recorder: AudioRecorder) {
+        let samples = recorder.stop_recording();
+        let sample_rate = recorder.get_sample_rate();
+        let levels = measure(&samples, sample_rate);
+        let work_dir = audio::work_dir(&self.config.audio_work_dir);
+        let path = match recorder.save_audio_to_wav(&samples, sample_rate, RECORD_SECS, &work_dir) {
+            Ok(path) => path,
+            Err(e) => {
+                self.mic_check.step = Step::Done {
+                    levels,
+                    transcription: Err(e),
+                };
+                return;
+            }
+        };
+
+        let config = self.config.clone();
+        let (sender, receiver) = channel();
+        std::thread::spawn(move || {
+            if let Err(e) = playback::play_file(&path, "", 1.0) {
+                eprintln!("[Mic Check] Failed to play back: {}", e);
+            }
+            let openai_client = OpenAIClient::new(
+                config.openai_api_key.clone(),
+                config.whisper_model.clone(),
+                config.custom_prompt.clone(),
+            )
+            .with_timeout(config.stt_timeout_secs);
+            let result = stt::transcribe_with(&config.stt_backend, &config, &openai_client, &path)
+                .map(|transcription| transcription.text);
+            let _ = std::fs::remove_file(&path);
+            let _ = sender.send(result);
+        });
+        self.mic_check.step = Step::Testing { levels, receiver };
+    }
+
+    pub(crate) fn show_mic_check_window(&mut self, ctx: &egui::Context) {
+        // 録音と書き起こしの進み具合を見る
+        match std::mem::take(&mut self.mic_check.step) {
+            Step::Recording { recorder, started_at } if started_at.elapsed().as_secs_f32() >= RECORD_SECS => {
+                self.finish_mic_check_recording(recorder);
+            }
+            Step::Testing { levels, receiver } => {
+                self.mic_check.step = match receiver.try_recv() {
+                    Ok(transcription) => Step::Done { levels, transcription },
+                    Err(_) => Step::Testing { levels, receiver },
+                };
+            }
+            step => self.mic_check.step = step,
+        }
+        if matches!(self.mic_check.step, Step::Recording { .. } | Step::Testing { .. }) {
+            ctx.request_repaint_after(std::time::Duration::from_millis(100));
+        }
+
+        let mut open = true;
+        let mut start = false;
+        egui::Window::new("マイクチェック")
+            .open(&mut open)
+            .collapsible(false)
+            .default_width(420.0)
+            .show(ctx, |ui| {
+                match &self.mic_check.step {
+                    Step::Idle => {
+                        ui.label(format!(
+                            "{} 秒録音して再生し、音量と書き起こしを確認します。録音中はふだんの声で話してください",
+                            RECORD_SECS
+                        ));
+                    }
+                    Step::Recording { recorder, started_at } => {
+                        ui.label(format!(
+                            "🔴 録音中... 話してください ({:.1}s / {:.1}s)",
+                            started_at.elapsed().as_secs_f32(),
+                            RECORD_SECS
+                        ));
+                        ui.add(egui::ProgressBar::new((recorder.get_max_amplitude() * 2.0).min(1.0)));
+                    }
+                    Step::Testing { .. } => {
+                        ui.horizontal(|ui| {
+                            ui.spinner();
+                            ui.label("再生して書き起こしています...");
+                        });
+                    }
+                    Step::Done { levels, transcription } => {
+                        egui::Grid::new("mic_check_grid").num_columns(2).spacing([12.0, 4.0]).show(ui, |ui| {
+                            ui.label("ピーク");
+                            ui.label(format!("{:.3}", levels.peak));
+                            ui.end_row();
+                            ui.label("RMS (全体)");
+                            ui.label(format!("{:.4}", levels.rms));
+                            ui.end_row();
+                            ui.label("話し声の RMS");
+                            ui.label(format!("{:.4} (Start Threshold {:.4})", levels.loudest_rms, self.config.start_threshold));
+                            ui.end_row();
+                            ui.label("周りの音の RMS");
+                            ui.label(format!("{:.4} (Silence Threshold {:.4})", levels.quietest_rms, self.config.silence_threshold));
+                            ui.end_row();
+                            ui.label("書き起こし");
+                            match transcription {
+                                Ok(text) => ui.label(text.trim()),
+                                Err(e) => ui.colored_label(egui::Color32::from_rgb(200, 50, 50), e.to_string()),
+                            };
+                            ui.end_row();
+                        });
+                        ui.separator();
+                        let tips = suggestions(*levels, &self.config, transcription);
+                        if tips.is_empty() {
+                            ui.colored_label(egui::Color32::from_rgb(0, 128, 0), "✓ 問題は見つかりませんでした");
+                        }
+                        for tip in tips {
+                            ui.label(format!("💡 {}", tip));
+                        }
+                    }
+                    Step::Failed(message) => {
+                        ui.colored_label(egui::Color32::from_rgb(200, 50, 50), message);
+                    }
+                }
+                ui.add_space(6.0);
+                let busy = matches!(self.mic_check.step, Step::Recording { .. } | Step::Testing { .. });
+                let label = if matches!(self.mic_check.step, Step::Idle) { "🎤 開始" } else { "🔁 もう一度" };
+                if ui.add_enabled(!busy, egui::Button::new(label)).clicked() {
+                    start = true;
+                }
+            });
+        if start {
+            self.start_mic_check();
+        }
+        if !open {
+            // 録音中に閉じたらマイクも閉じる
+            self.mic_check.step = Step::Idle;
+        }
+        self.mic_check.open = open;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_measure_and_suggestions() {
+        // 静かな 0.1 秒と大きな 0.1 秒 (1kHz)
+        let mut samples = vec![0.001f32; 100];
+        samples.extend((0..100).map(|i| if i % 2 == 0 { 0.5 } else { -0.5 }));
+        let levels = measure(&samples, 1000);
+        assert_eq!(levels.peak, 0.5);
+        assert!((levels.loudest_rms - 0.5).abs() < 1e-6);
+        assert!((levels.quietest_rms - 0.001).abs() < 1e-6);
+
+        let config = Config {
+            start_threshold: 0.05,
+            silence_threshold: 0.01,
+            ..Config::default()
+        };
+        assert!(suggestions(levels, &config, &Ok("こんにちは".to_string())).is_empty());
+        assert_eq!(suggestions(levels, &config, &Ok(" ".to_string())).len(), 1);
+        let auth = Err(AppError::Auth("401".to_string()));
+        assert_eq!(suggestions(levels, &config, &auth), vec!["Settings で API キーを確認してください"]);
+
+        let quiet = measure(&[0.001; 200], 1000);
+        assert_eq!(suggestions(quiet, &config, &Ok(String::new())).len(), 1);
+        let loud_room = Config {
+            start_threshold: 0.6,
+            silence_threshold: 0.0001,
+            ..config
+        };
+        assert_eq!(suggestions(levels, &loud_room, &Ok("x".to_string())).len(), 2);
+    }
+}
@@ -0,0 +1,16 @@
+//! 画面の部品 (ElizaAgentApp::update から呼ぶ)
+//!
+//! 各モジュールは自分の状態を構造体に持ち、描画は `impl ElizaAgentApp` の `show_*` で行う
+
+pub mod calibration;
+pub mod context_preview;
+pub mod conversation;
+pub mod diagnostics;
+pub mod mic_check;
+pub mod persona_editor;
+pub mod persona_stats;
+pub mod replay;
+pub mod scheduler;
+pub mod settings;
+pub mod start_button;
+pub mod widgets;
@@ -0,0 +1,78 @@
+//! 今の人格のプロンプトを横のパネルで直す (会話履歴は消さずにそのまま次の返答から効く)
+
+use crate::config::Config;
+use crate::ElizaAgentApp;
+use eframe::egui;
+
+#[derive(Debug, Default)]
+pub struct PersonaEditorState {
+    pub open: bool,
+    /// 編集中のプロンプト (適用するまで設定には書かない)
+    draft: String,
+}
+
+impl PersonaEditorState {
+    /// 今の設定のプロンプトを読み込んで開く
+    pub fn open_with(&mut self, prompt: &str) {
+        self.draft = prompt.to_string();
+        self.open = true;
+    }
+}
+
+impl ElizaAgentApp {
+    pub(crate) fn show_persona_editor(&mut self, ctx: &egui::Context) {
+        let mut apply = false;
+        egui::SidePanel::right("persona_editor")
+            .resizable(true)
+            .default_width(320.0)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.strong(format!(
+                        "✏ 人格の編集 ({})",
+                        Config::preset_display_name(&self.current_preset)
+                    ));
+                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                        if ui.small_button("✖").clicked() {
+                            self.persona_editor.open = false;
+                        }
+                    });
+                });
+                ui.weak("適用すると次の返答から効きます (会話履歴はそのまま)");
+                ui.separator();
+                let changed = self.persona_editor.draft != self.config.persona_prompt;
+                ui.horizontal(|ui| {
+                    if ui.add_enabled(changed, egui::Button::new("✔ 適用")).clicked() {
+                        apply = true;
+                    }
+                    if ui.add_enabled(changed, egui::Button::new("↺ 元に戻す")).clicked() {
+                        self.persona_editor.draft = self.config.persona_prompt.clone();
+                    }
+                });
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    ui.add(
+                        egui::TextEdit::multiline(&mut self.persona_editor.draft)
+                            .desired_width(f32::INFINITY)
+                            .desired_rows(20)
+                            .hint_text("空ならサーバー側の設定のみ"),
+                    );
+                });
+            });
+        if apply {
+            self.apply_persona_prompt(self.persona_editor.draft.clone());
+        }
+    }
+
+    /// 今のクライアントのプロンプトだけを差し替えて保存する (履歴は消さない)
+    fn apply_persona_prompt(&mut self, prompt: String) {
+        self.config.persona_prompt = prompt.clone();
+        self.settings.draft.persona_prompt = prompt.clone();
+        // 処理中でクライアントが手元にないときは、次のターンの前に設定から適用される
+        if let Some(client) = &mut self.eliza_client {
+            client.set_system_prompt(prompt);
+        }
+        match self.config.save_preset(&self.current_preset) {
+            Ok(_) => self.status_message = "人格のプロンプトを更新しました".to_string(),
+            Err(e) => self.report_error(format!("Failed to save: {}", e)),
+        }
+    }
+}
@@ -0,0 +1,54 @@
+//! 今の人格の通算の記録 (話した回数とよく出る話題)
+
+use crate::config::Config;
+use crate::ElizaAgentApp;
+use eframe::egui;
+
+/// 表示する話題の数
+const TOP_WORDS: usize = 20;
+
+impl ElizaAgentApp {
+    pub(crate) fn show_persona_stats_window(&mut self, ctx: &egui::Context) {
+        let mut open = true;
+        egui::Window::new(format!(
+            "📊 人格の記録 ({})",
+            Config::preset_display_name(&self.current_preset)
+        ))
+        .id(egui::Id::new("persona_stats"))
+        .open(&mut open)
+        .collapsible(false)
+        .default_width(320.0)
+        .show(ctx, |ui| {
+            let stats = &self.persona_stats;
+            egui::Grid::new("persona_stats_grid").num_columns(2).spacing([12.0, 4.0]).show(ui, |ui| {
+                ui.label("話した回数");
+                ui.label(stats.turns.to_string());
+                ui.end_row();
+                ui.label("会話した回数");
+                ui.label(stats.sessions.to_string());
+                ui.end_row();
+                ui.label("はじめて話した日");
+                ui.label(
+                    stats
+                        .since
+                        .and_then(|secs| chrono::DateTime::from_timestamp(secs as i64, 0))
+                        .map(|t| t.with_timezone(&chrono::Local).format("%Y-%m-%d").to_string())
+                        .unwrap_or_else(|| "-".to_string()),
+                );
+                ui.end_row();
+            });
+            ui.separator();
+            ui.strong("よく出る話題");
+            let words = stats.top_words(TOP_WORDS);
+            if words.is_empty() {
+                ui.weak("まだありません");
+            }
+            ui.horizontal_wrapped(|ui| {
+                for (word, count) in words {
+                    ui.label(format!("{} ({})", word, count));
+                }
+            });
+        });
+        self.show_persona_stats = open;
+    }
+}
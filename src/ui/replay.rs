@@ -0,0 +1,241 @@
+//! 過去の会話を流し直すウィンドウ (開発者向け)
+//!
+//! 流している間は使い捨ての ElizaClient と会話履歴に差し替え、記憶・統計・読み上げには残さない。
+//! 止めて処理が終わったら元の会話に戻す
+
+use crate::eliza::ElizaClient;
+use crate::history::HistoryEntry;
+use crate::pipeline_controller::AppState;
+use crate::replay::{self, ReplayInput, ReplayStep};
+use crate::{audio, vrchat, ElizaAgentApp};
+use eframe::egui;
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// 流している途中の状態
+struct Run {
+    steps: VecDeque<ReplayStep>,
+    total: usize,
+    /// 録音を VAD で区切った残りの発話 (次の 1 件より先に流す)
+    segments: VecDeque<PathBuf>,
+    /// 直前の 1 件を流し始めた時刻
+    last_started: Instant,
+}
+
+/// リプレイの間よけておく元の会話
+struct LiveConversation {
+    client: Option<ElizaClient>,
+    history: Vec<HistoryEntry>,
+}
+
+pub struct ReplayState {
+    pub open: bool,
+    /// 書き出した会話 (.json) か録音フォルダ
+    path: String,
+    speed: f32,
+    use_audio: bool,
+    send_to_vrchat: bool,
+    run: Option<Run>,
+    live: Option<LiveConversation>,
+    status: String,
+}
+
+impl Default for ReplayState {
+    fn default() -> Self {
+        Self {
+            open: false,
+            path: String::new(),
+            speed: 4.0,
+            use_audio: true,
+            send_to_vrchat: false,
+            run: None,
+            live: None,
+            status: String::new(),
+        }
+    }
+}
+
+impl ElizaAgentApp {
+    /// 使い捨ての会話で流している間 (止めた後、最後の処理が終わるまでを含む)
+    pub(crate) fn is_replaying(&self) -> bool {
+        self.replay.live.is_some()
+    }
+
+    fn start_replay(&mut self) {
+        if self.processing_receiver.is_some() {
+            self.replay.status = "処理中の発話が終わってから開始してください".to_string();
+            return;
+        }
+        let path = self.replay.path.trim().to_string();
+        match replay::load(Path::new(&path), self.replay.use_audio, self.replay.speed) {
+            Ok(steps) => {
+                vrchat::set_sends_disabled(!self.replay.send_to_vrchat);
+                self.replay.status = format!("0/{}", steps.len());
+                self.replay.run = Some(Run {
+                    total: steps.len(),
+                    steps: steps.into(),
+                    segments: VecDeque::new(),
+                    last_started: Instant::now(),
+                });
+                if self.replay.live.is_none() {
+                    self.replay.live = Some(LiveConversation {
+                        client: self.eliza_client.take(),
+                        history: std::mem::take(&mut self.conversation_history),
+                    });
+                }
+                self.eliza_client = self.agent_configured().then(|| self.new_eliza_client());
+            }
+            Err(e) => self.replay.status = format!("❌ {}", e),
+        }
+    }
+
+    fn stop_replay(&mut self, status: &str) {
+        self.replay.run = None;
+        vrchat::set_sends_disabled(false);
+        self.replay.status = status.to_string();
+        self.restore_live_conversation();
+    }
+
+    /// 使い捨ての会話を捨てて元の会話に戻す (処理中なら終わるまで待つ)
+    fn restore_live_conversation(&mut self) {
+        if self.processing_receiver.is_some() {
+            return;
+        }
+        if let Some(live) = self.replay.live.take() {
+            self.eliza_client = live.client;
+            self.conversation_history = live.history;
+            self.streaming_reply = None;
+        }
+    }
+
+    /// 前の 1 件の処理が終わり、間隔も空いたら次を流す
+    pub(crate) fn drive_replay(&mut self, ctx: &egui::Context) {
+        let Some(run) = &self.replay.run else {
+            self.restore_live_conversation();
+            return;
+        };
+        ctx.request_repaint_after(Duration::from_millis(100));
+        if self.pipeline.state() != AppState::Idle {
+            self.stop_replay("モニタリングが始まったので止めました");
+            return;
+        }
+        if self.processing_receiver.is_some() {
+            return;
+        }
+        if !run.segments.is_empty() {
+            if let Some(segment) = self.replay.run.as_mut().and_then(|run| run.segments.pop_front()) {
+                self.process_replay_segment(segment);
+            }
+            return;
+        }
+        let Some(next) = run.steps.front() else {
+            self.stop_replay("✔ 終わりました");
+            return;
+        };
+        if run.last_started.elapsed() < next.delay {
+            return;
+        }
+
+        let Some(run) = &mut self.replay.run else {
+            return;
+        };
+        let Some(step) = run.steps.pop_front() else {
+            return;
+        };
+        run.last_started = Instant::now();
+        self.replay.status = format!("{}/{}", run.total - run.steps.len(), run.total);
+        match step.input {
+            ReplayInput::Text(text) => self.send_text_message(text),
+            ReplayInput::Audio(path) => self.replay_recording(&path),
+        }
+    }
+
+    /// 録音をマイクと同じ VAD の判定で発話に区切り、作業フォルダに書いてから順に流す (元のアーカイブは消さない)
+    fn replay_recording(&mut self, path: &Path) {
+        let work_dir = audio::work_dir(&self.config.audio_work_dir);
+        let segments = match replay::split_recording(path, &self.config, &work_dir) {
+            Ok(segments) => segments,
+            Err(e) => {
+                self.report_error(e);
+                return;
+            }
+        };
+        if segments.is_empty() {
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            self.conversation_history
+                .push(HistoryEntry::new("Ignored", format!("[VAD] {}: 発話を検出できませんでした", name)));
+            return;
+        }
+        self.audio_file_path = Some(path.to_path_buf());
+        if let Some(run) = &mut self.replay.run {
+            run.segments.extend(segments);
+        }
+        if let Some(segment) = self.replay.run.as_mut().and_then(|run| run.segments.pop_front()) {
+            self.process_replay_segment(segment);
+        }
+    }
+
+    fn process_replay_segment(&mut self, segment: PathBuf) {
+        if self.eliza_client.is_none() && self.agent_configured() {
+            self.eliza_client = Some(self.new_eliza_client());
+        }
+        self.recording_self_voice = None;
+        self.status_message = "Processing audio...".to_string();
+        self.start_background_processing(segment);
+    }
+
+    pub(crate) fn show_replay_window(&mut self, ctx: &egui::Context) {
+        let mut open = true;
+        let mut start = false;
+        let mut stop = false;
+        egui::Window::new("リプレイ")
+            .open(&mut open)
+            .collapsible(false)
+            .default_width(420.0)
+            .show(ctx, |ui| {
+                let running = self.replay.run.is_some();
+                ui.label("書き出した会話 (.json) または録音フォルダ:");
+                ui.add_enabled(
+                    !running,
+                    egui::TextEdit::singleline(&mut self.replay.path).hint_text("C:\\...\\history.json"),
+                );
+                ui.add_enabled_ui(!running, |ui| {
+                    ui.checkbox(&mut self.replay.use_audio, "録音が残っている発話は録音から書き起こし直す");
+                    ui.checkbox(&mut self.replay.send_to_vrchat, "VRChat にも送る");
+                    ui.horizontal(|ui| {
+                        ui.label("速さ:");
+                        ui.add(egui::Slider::new(&mut self.replay.speed, 1.0..=20.0).suffix("x"));
+                    });
+                });
+                ui.horizontal(|ui| {
+                    let idle = self.pipeline.state() == AppState::Idle;
+                    if ui
+                        .add_enabled(!running && idle, egui::Button::new("▶ 開始"))
+                        .on_disabled_hover_text("モニタリングを止めてから開始してください")
+                        .clicked()
+                    {
+                        start = true;
+                    }
+                    if running {
+                        if ui.button("⏹ 停止").clicked() {
+                            stop = true;
+                        }
+                        ui.spinner();
+                    }
+                });
+                if !self.replay.status.is_empty() {
+                    ui.label(&self.replay.status);
+                }
+                ui.separator();
+                ui.small("使い捨ての会話で流します (記憶・統計・セッションに残さず、読み上げもしません)。止めると元の会話に戻ります");
+            });
+        if start {
+            self.start_replay();
+        }
+        if stop {
+            self.stop_replay("止めました");
+        }
+        self.replay.open = open;
+    }
+}
@@ -0,0 +1,95 @@
+//! 予約メッセージの編集ウィンドウ
+
+use crate::scheduler::{self, ScheduledMessage};
+use crate::ElizaAgentApp;
+use eframe::egui;
+
+impl ElizaAgentApp {
+    pub(crate) fn show_scheduler_window(&mut self, ctx: &egui::Context) {
+        let mut open = true;
+        let mut save = false;
+        egui::Window::new("予約メッセージ")
+            .open(&mut open)
+            .collapsible(false)
+            .default_width(480.0)
+            .show(ctx, |ui| {
+                ui.weak("時刻 (HH:MM) になったらチャットボックスに送ります。繰り返しが 0 なら 1 回だけ");
+                ui.separator();
+                let mut remove = None;
+                egui::Grid::new("scheduler_grid")
+                    .num_columns(5)
+                    .spacing([8.0, 6.0])
+                    .show(ui, |ui| {
+                        ui.strong("");
+                        ui.strong("時刻");
+                        ui.strong("繰り返し (分)");
+                        ui.strong("メッセージ");
+                        ui.end_row();
+                        for (i, message) in self.config.scheduled_messages.iter_mut().enumerate() {
+                            ui.checkbox(&mut message.enabled, "");
+                            ui.add(egui::TextEdit::singleline(&mut message.at).desired_width(50.0).hint_text("21:50"));
+                            ui.add(egui::DragValue::new(&mut message.repeat_minutes).range(0..=1440));
+                            ui.add(egui::TextEdit::singleline(&mut message.text).desired_width(240.0));
+                            if ui.small_button("🗑").clicked() {
+                                remove = Some(i);
+                            }
+                            ui.end_row();
+                        }
+                    });
+                if let Some(i) = remove {
+                    self.config.scheduled_messages.remove(i);
+                }
+                ui.horizontal(|ui| {
+                    if ui.button("＋ 追加").clicked() {
+                        let at = chrono::Local::now().format("%H:%M").to_string();
+                        self.config.scheduled_messages.push(ScheduledMessage::new("", &at));
+                    }
+                    if ui.button("💾 保存").clicked() {
+                        save = true;
+                    }
+                });
+            });
+        if save {
+            self.save_schedule("予約メッセージを保存しました");
+        }
+        self.show_scheduler = open;
+    }
+
+    /// 予約とチャットボックスの送り方 (DnD・制限など) を常駐スレッドに渡す
+    pub(crate) fn sync_scheduler(&self) {
+        scheduler::set_config(&self.pipeline_config());
+    }
+
+    /// 送った予約を反映する (1 回だけのものは無効にして保存)
+    pub(crate) fn poll_scheduled_messages(&mut self) {
+        let fired = scheduler::take_fired();
+        for message in &fired {
+            self.status_message = format!("⏰ 予約メッセージを送りました: {}", message.text);
+        }
+        if fired.is_empty() {
+            return;
+        }
+        let mut disabled = false;
+        for message in &fired {
+            if message.repeat_minutes == 0 {
+                if let Some(entry) = self.config.scheduled_messages.iter_mut().find(|m| *m == message) {
+                    entry.enabled = false;
+                    disabled = true;
+                }
+            }
+        }
+        if disabled {
+            let status = self.status_message.clone();
+            self.save_schedule(&status);
+        }
+    }
+
+    fn save_schedule(&mut self, status: &str) {
+        self.sync_scheduler();
+        self.settings.draft.scheduled_messages = self.config.scheduled_messages.clone();
+        match self.config.save_preset(&self.current_preset) {
+            Ok(_) => self.status_message = status.to_string(),
+            Err(e) => self.report_error(format!("Failed to save: {}", e)),
+        }
+    }
+}
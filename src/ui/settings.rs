@@ -0,0 +1,1257 @@
+//! Settings ウィンドウ
+
+use super::widgets::{dirty_marker, output_test_row, setting_checkbox, setting_header};
+use crate::config::{self, Config};
+use crate::hooks::{self, HookEvent};
+use crate::notify_sound::{self, SoundEvent};
+use crate::{autostart, config_crypt, control, elevenlabs, eliza, injection_guard, local_whisper, midi, openai, snippets, stt, text_normalize, tts, voicevox, ElizaAgentApp};
+use eframe::egui;
+
+/// Settings ウィンドウのタブ
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SettingsTab {
+    Audio,
+    Transcription,
+    Agent,
+    VRChat,
+    Ui,
+    Hooks,
+    Midi,
+}
+
+impl SettingsTab {
+    const ALL: [SettingsTab; 7] = [
+        SettingsTab::Audio,
+        SettingsTab::Transcription,
+        SettingsTab::Agent,
+        SettingsTab::VRChat,
+        SettingsTab::Ui,
+        SettingsTab::Hooks,
+        SettingsTab::Midi,
+    ];
+
+    fn label(&self) -> &'static str {
+        match self {
+            SettingsTab::Audio => "Audio",
+            SettingsTab::Transcription => "Transcription",
+            SettingsTab::Agent => "Agent",
+            SettingsTab::VRChat => "VRChat",
+            SettingsTab::Ui => "UI",
+            SettingsTab::Hooks => "Hooks",
+            SettingsTab::Midi => "MIDI",
+        }
+    }
+}
+
+/// Settings ウィンドウの状態 (編集中の値は draft に持ち、Save で config に反映する)
+pub struct SettingsState {
+    pub open: bool,
+    pub tab: SettingsTab,
+    pub draft: Config,
+    /// 1 行に 1 つの形式で編集するもの
+    pub language_routes: String,
+    pub quick_replies: String,
+    pub sfx_map: String,
+    pub player_notes: String,
+    pub world_presets: String,
+    pub custom_prompts: String,
+    /// Windows の起動時に自動で起動するか (プリセットには持たず、Run キーの登録を読み書きする)
+    pub autostart: bool,
+    pub autostart_registered: bool,
+    /// ウィンドウを開いたときに取り直すデバイス一覧
+    pub output_devices: Vec<String>,
+    pub midi_ports: Vec<String>,
+    /// 「話者一覧を取得」で取ってきた VOICEVOX の話者
+    pub voicevox_speakers: Vec<voicevox::Speaker>,
+    /// 「声の一覧を取得」で取ってきた ElevenLabs の声
+    pub elevenlabs_voices: Vec<elevenlabs::Voice>,
+}
+
+impl SettingsState {
+    pub fn new(config: &Config) -> Self {
+        let mut state = Self {
+            open: false,
+            tab: SettingsTab::Audio,
+            draft: config.clone(),
+            language_routes: String::new(),
+            quick_replies: String::new(),
+            sfx_map: String::new(),
+            player_notes: String::new(),
+            world_presets: String::new(),
+            custom_prompts: String::new(),
+            autostart: false,
+            autostart_registered: false,
+            output_devices: Vec::new(),
+            midi_ports: Vec::new(),
+            voicevox_speakers: Vec::new(),
+            elevenlabs_voices: Vec::new(),
+        };
+        state.load(config);
+        state
+    }
+
+    /// Copy current config values into the settings UI fields
+    pub fn load(&mut self, config: &Config) {
+        self.draft = config.clone();
+        self.language_routes = config::format_mapping_lines(&config.language_routes);
+        self.quick_replies = config.quick_replies.join("\n");
+        self.sfx_map = config::format_mapping_lines(&config.sfx_map);
+        self.player_notes = config::format_mapping_lines(&config.player_notes);
+        self.world_presets = config::format_mapping_lines(&config.world_presets);
+        self.custom_prompts = config::format_mapping_lines(&config.custom_prompts);
+        self.autostart_registered = autostart::is_enabled();
+        self.autostart = self.autostart_registered;
+    }
+}
+
+impl ElizaAgentApp {
+    fn saved_device_index(&self) -> usize {
+        self.config
+            .input_device_name
+            .as_ref()
+            .and_then(|name| self.available_devices.iter().position(|d| d == name))
+            .unwrap_or(0)
+    }
+
+    /// タブ内に未保存の変更があるか
+    fn settings_tab_is_dirty(&self, tab: SettingsTab) -> bool {
+        let d = &self.settings.draft;
+        let c = &self.config;
+        match tab {
+            SettingsTab::Audio => {
+                self.selected_device_index != self.saved_device_index()
+                    || d.input_gains != c.input_gains
+                    || d.start_threshold != c.start_threshold
+                    || d.voice_confirm_frames != c.voice_confirm_frames
+                    || d.voice_confirm_window_ms != c.voice_confirm_window_ms
+                    || d.hold_to_talk_enabled != c.hold_to_talk_enabled
+                    || d.hold_to_talk_key != c.hold_to_talk_key
+                    || d.preroll_secs != c.preroll_secs
+                    || d.silence_threshold != c.silence_threshold
+                    || d.silence_duration_secs != c.silence_duration_secs
+                    || d.voice_commands_enabled != c.voice_commands_enabled
+                    || d.sensitivity_step != c.sensitivity_step
+                    || d.archive_recordings != c.archive_recordings
+                    || d.audio_work_dir != c.audio_work_dir
+                    || d.audio_cleanup_minutes != c.audio_cleanup_minutes
+                    || d.soundboard_enabled != c.soundboard_enabled
+                    || self.settings.sfx_map != config::format_mapping_lines(&c.sfx_map)
+                    || d.sfx_output_device != c.sfx_output_device
+                    || d.sfx_volume != c.sfx_volume
+                    || d.tts_enabled != c.tts_enabled
+                    || d.tts_backend != c.tts_backend
+                    || d.tts_voice != c.tts_voice
+                    || d.voicevox_url != c.voicevox_url
+                    || d.voicevox_style_id != c.voicevox_style_id
+                    || d.voicevox_speed != c.voicevox_speed
+                    || d.voicevox_pitch != c.voicevox_pitch
+                    || d.elevenlabs_api_key != c.elevenlabs_api_key
+                    || d.elevenlabs_voice_id != c.elevenlabs_voice_id
+                    || d.tts_output_device != c.tts_output_device
+                    || d.tts_volume != c.tts_volume
+                    || d.monitor_tts_enabled != c.monitor_tts_enabled
+                    || d.monitor_tts_output_device != c.monitor_tts_output_device
+                    || d.monitor_tts_volume != c.monitor_tts_volume
+                    || d.barge_in_enabled != c.barge_in_enabled
+                    || d.tts_normalize_enabled != c.tts_normalize_enabled
+                    || d.tts_normalize_locale != c.tts_normalize_locale
+                    || d.notify_recording_enabled != c.notify_recording_enabled
+                    || d.notify_recording_sound != c.notify_recording_sound
+                    || d.notify_reply_enabled != c.notify_reply_enabled
+                    || d.notify_reply_sound != c.notify_reply_sound
+                    || d.notify_error_enabled != c.notify_error_enabled
+                    || d.notify_error_sound != c.notify_error_sound
+                    || d.notify_output_device != c.notify_output_device
+                    || d.notify_volume != c.notify_volume
+            }
+            SettingsTab::Transcription => {
+                d.openai_api_key != c.openai_api_key
+                    || d.whisper_model != c.whisper_model
+                    || d.stt_backend != c.stt_backend
+                    || d.stt_fallback_backend != c.stt_fallback_backend
+                    || d.stt_timeout_secs != c.stt_timeout_secs
+                    || d.stt_chunk_secs != c.stt_chunk_secs
+                    || d.local_whisper_path != c.local_whisper_path
+                    || d.local_whisper_model != c.local_whisper_model
+                    || d.local_whisper_language != c.local_whisper_language
+                    || d.local_whisper_model_size != c.local_whisper_model_size
+                    || d.local_whisper_threads != c.local_whisper_threads
+                    || d.local_whisper_max_queue != c.local_whisper_max_queue
+                    || d.custom_prompt != c.custom_prompt
+                    || self.settings.custom_prompts != config::format_mapping_lines(&c.custom_prompts)
+                    || d.custom_prompt_language != c.custom_prompt_language
+                    || d.whisper_context_enabled != c.whisper_context_enabled
+                    || d.whisper_context_chars != c.whisper_context_chars
+                    || d.whisper_context_include_agent != c.whisper_context_include_agent
+                    || d.transcript_cleanup_enabled != c.transcript_cleanup_enabled
+                    || d.transcript_cleanup_model != c.transcript_cleanup_model
+                    || d.transcript_cleanup_prompt != c.transcript_cleanup_prompt
+                    || d.pii_redaction_enabled != c.pii_redaction_enabled
+                    || d.injection_guard != c.injection_guard
+                    || d.language_routing_enabled != c.language_routing_enabled
+                    || d.auto_reply_language_enabled != c.auto_reply_language_enabled
+                    || d.language_filter_enabled != c.language_filter_enabled
+                    || d.accepted_languages != c.accepted_languages
+                    || self.settings.language_routes != config::format_mapping_lines(&c.language_routes)
+            }
+            SettingsTab::Agent => {
+                d.agent_server_url != c.agent_server_url
+                    || d.session_summary_enabled != c.session_summary_enabled
+                    || d.mood_enabled != c.mood_enabled
+                    || d.mood_osc_address != c.mood_osc_address
+                    || d.silence_follow_up_secs != c.silence_follow_up_secs
+                    || d.silence_follow_up_prompt != c.silence_follow_up_prompt
+                    || d.agent_model != c.agent_model
+                    || d.max_length_of_conversation_history != c.max_length_of_conversation_history
+                    || d.history_compaction != c.history_compaction
+                    || d.history_compact_size != c.history_compact_size
+                    || d.agent_timeout_secs != c.agent_timeout_secs
+                    || d.agent_streaming_enabled != c.agent_streaming_enabled
+                    || d.persona_prompt != c.persona_prompt
+                    || d.agent_temperature != c.agent_temperature
+                    || d.agent_spontaneity != c.agent_spontaneity
+                    || d.agent_max_tokens != c.agent_max_tokens
+                    || d.feedback_note_enabled != c.feedback_note_enabled
+                    || d.feedback_note_count != c.feedback_note_count
+                    || d.ab_prompt_b != c.ab_prompt_b
+                    || d.ab_test_turns != c.ab_test_turns
+            }
+            SettingsTab::VRChat => {
+                d.use_vrchat_mute_detection != c.use_vrchat_mute_detection
+                    || d.afk_pause_enabled != c.afk_pause_enabled
+                    || d.osc_send_port != c.osc_send_port
+                    || d.osc_listen_port != c.osc_listen_port
+                    || d.speaker_attribution_enabled != c.speaker_attribution_enabled
+                    || d.listening_osc_address != c.listening_osc_address
+                    || d.agent_account_mode != c.agent_account_mode
+                    || d.control_api_enabled != c.control_api_enabled
+                    || d.control_api_port != c.control_api_port
+                    || d.control_api_token != c.control_api_token
+                    || d.world_chat_context_enabled != c.world_chat_context_enabled
+                    || d.world_chat_pattern != c.world_chat_pattern
+                    || d.world_chat_minutes != c.world_chat_minutes
+                    || d.greet_joins_enabled != c.greet_joins_enabled
+                    || d.greet_per_minute != c.greet_per_minute
+                    || d.greet_blocklist != c.greet_blocklist
+                    || d.player_notes_enabled != c.player_notes_enabled
+                    || self.settings.player_notes != config::format_mapping_lines(&c.player_notes)
+                    || d.world_presets_enabled != c.world_presets_enabled
+                    || self.settings.world_presets != config::format_mapping_lines(&c.world_presets)
+                    || d.pause_when_vrchat_closed != c.pause_when_vrchat_closed
+                    || d.chatbox_retry_enabled != c.chatbox_retry_enabled
+                    || d.chatbox_retry_secs != c.chatbox_retry_secs
+                    || d.chatbox_paginate_enabled != c.chatbox_paginate_enabled
+                    || d.chatbox_reading_cps != c.chatbox_reading_cps
+                    || d.chatbox_clear_enabled != c.chatbox_clear_enabled
+                    || d.chatbox_clear_secs != c.chatbox_clear_secs
+                    || d.paste_long_replies_enabled != c.paste_long_replies_enabled
+                    || d.paste_endpoint != c.paste_endpoint
+                    || d.reply_limit_enabled != c.reply_limit_enabled
+                    || d.reply_limit_per_minute != c.reply_limit_per_minute
+                    || d.reply_cooldown_secs != c.reply_cooldown_secs
+                    || d.quote_mode != c.quote_mode
+                    || d.quote_prefix != c.quote_prefix
+                    || d.quote_suffix != c.quote_suffix
+                    || d.dialogue_user_label != c.dialogue_user_label
+                    || d.dialogue_agent_label != c.dialogue_agent_label
+                    || d.karaoke_enabled != c.karaoke_enabled
+                    || d.karaoke_replay_secs != c.karaoke_replay_secs
+                    || d.intent_filter_enabled != c.intent_filter_enabled
+                    || d.agent_names != c.agent_names
+                    || d.intent_use_llm != c.intent_use_llm
+                    || d.intent_model != c.intent_model
+                    || d.status_topic_enabled != c.status_topic_enabled
+                    || d.status_topic_interval_turns != c.status_topic_interval_turns
+                    || d.status_topic_osc_address != c.status_topic_osc_address
+                    || d.safety_word != c.safety_word
+                    || d.safety_word_wipe_history != c.safety_word_wipe_history
+                    || d.dnd_keywords != c.dnd_keywords
+            }
+            SettingsTab::Ui => {
+                d.compare_mode_enabled != c.compare_mode_enabled
+                    || d.compare_server_url != c.compare_server_url
+                    || d.compare_model != c.compare_model
+                    || self.settings.quick_replies != c.quick_replies.join("\n")
+                    || d.quick_replies_bypass_agent != c.quick_replies_bypass_agent
+                    || self.settings.autostart != self.settings.autostart_registered
+                    || d.config_encryption != c.config_encryption
+            }
+            SettingsTab::Hooks => {
+                d.hooks != c.hooks
+                    || d.activity_osc_enabled != c.activity_osc_enabled
+                    || d.activity_osc_target != c.activity_osc_target
+            }
+            SettingsTab::Midi => {
+                d.midi_enabled != c.midi_enabled
+                    || d.midi_port_name != c.midi_port_name
+                    || d.midi_bindings != c.midi_bindings
+            }
+        }
+    }
+
+    /// Apply the settings draft to config and save it to the current preset
+    fn save_settings(&mut self) {
+        let mut new_config = self.settings.draft.clone();
+        new_config.language_routes = config::parse_mapping_lines(&self.settings.language_routes);
+        let invalid_routes: Vec<String> = new_config
+            .language_routes
+            .iter()
+            .filter(|(_, preset)| !config::is_valid_preset_name(preset))
+            .map(|(language, preset)| format!("{}={}", language, preset))
+            .collect();
+        new_config.language_routes.retain(|_, preset| config::is_valid_preset_name(preset));
+        new_config.sfx_map = config::parse_mapping_lines(&self.settings.sfx_map);
+        new_config.player_notes = config::parse_mapping_lines(&self.settings.player_notes);
+        new_config.world_presets = config::parse_mapping_lines(&self.settings.world_presets);
+        new_config.custom_prompts = config::parse_mapping_lines(&self.settings.custom_prompts);
+        new_config.quick_replies = self
+            .settings.quick_replies
+            .lines()
+            .map(|l| l.trim().to_string())
+            .filter(|l| !l.is_empty())
+            .collect();
+        new_config.safety_word = new_config.safety_word.trim().to_string();
+        new_config.compare_server_url = new_config.compare_server_url.trim().to_string();
+        new_config.compare_model = new_config.compare_model.trim().to_string();
+        new_config.input_device_name = self
+            .available_devices
+            .get(self.selected_device_index)
+            .cloned();
+        // 設定画面の外で切り替える値は現在の値を維持する
+        new_config.compare_use_secondary = self.config.compare_use_secondary;
+        new_config.text_mode = self.config.text_mode;
+
+        let mute_changed =
+            self.config.use_vrchat_mute_detection != new_config.use_vrchat_mute_detection
+                || self.config.afk_pause_enabled != new_config.afk_pause_enabled
+                || self.config.osc_send_port != new_config.osc_send_port
+                || self.config.osc_listen_port != new_config.osc_listen_port
+                || self.config.speaker_attribution_enabled != new_config.speaker_attribution_enabled;
+        let hold_to_talk_changed = self.config.hold_to_talk_enabled != new_config.hold_to_talk_enabled;
+        let midi_changed = self.config.midi_enabled != new_config.midi_enabled
+            || self.config.midi_port_name != new_config.midi_port_name;
+        if self.config.config_encryption != new_config.config_encryption {
+            match Config::reencrypt_presets(&new_config.config_encryption) {
+                Ok(count) => println!("Re-saved {} presets with {} encryption", count, new_config.config_encryption),
+                Err(e) => {
+                    self.report_error(format!("Failed to change config encryption: {}", e));
+                    new_config.config_encryption = self.config.config_encryption.clone();
+                }
+            }
+        }
+        if self.settings.autostart != self.settings.autostart_registered {
+            if let Err(e) = autostart::set_enabled(self.settings.autostart) {
+                self.report_error(e);
+            }
+        }
+        self.config = new_config;
+        self.save_audio_profile();
+
+        // Apply mute detection setting (restart listener if changed)
+        if mute_changed {
+            self.restart_mute_listener();
+        }
+        if midi_changed {
+            self.restart_midi();
+        }
+        if hold_to_talk_changed {
+            self.restart_vad();
+        }
+        self.restart_control_api();
+        self.start_world_chat_watcher();
+        let feedback_note = self.feedback_note();
+        if let Some(ref mut eliza_client) = self.eliza_client {
+            // 会話履歴を保ったまま、今のクライアントに新しい設定を当てる
+            eliza_client.set_server(self.config.agent_server_url.clone(), self.config.agent_model.clone());
+            eliza_client.set_max_history_length(self.config.max_length_of_conversation_history);
+            eliza_client.set_compaction(
+                eliza::CompactionStrategy::from_name(&self.config.history_compaction),
+                self.config.history_compact_size,
+            );
+            eliza_client.set_timeout(self.config.agent_timeout_secs);
+            eliza_client.set_temperature(self.config.agent_temperature, self.config.agent_spontaneity);
+            eliza_client.set_max_tokens(self.config.agent_max_tokens);
+            eliza_client.set_system_prompt(self.config.persona_prompt.clone());
+            eliza_client.set_context_note("feedback", feedback_note);
+        }
+        if let Some(recorder) = &self.audio_recorder {
+            recorder.set_gain(self.config.input_gain());
+        }
+
+        if let Err(e) = self.config.save_world_presets() {
+            self.report_error(e);
+        }
+
+        // Save to current preset
+        match self.config.save_preset(&self.current_preset) {
+            Ok(_) => {
+                self.status_message = format!(
+                    "Settings saved to {}!",
+                    Config::preset_display_name(&self.current_preset)
+                )
+            }
+            Err(e) => self.report_error(format!("Failed to save: {}", e)),
+        }
+        if !invalid_routes.is_empty() {
+            self.report_error(format!(
+                "Language Routes のプリセット名にパスは使えないので無視しました: {}",
+                invalid_routes.join(", ")
+            ));
+        }
+        self.load_settings_from_config();
+    }
+
+    pub(crate) fn show_settings_window(&mut self, ctx: &egui::Context) {
+        let defaults = Config::default();
+        let mut save_clicked = false;
+        let mut cancel_clicked = false;
+
+        egui::Window::new("Settings")
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                // Tabs (未保存の変更があるタブには * を付ける)
+                ui.horizontal(|ui| {
+                    for tab in SettingsTab::ALL {
+                        let title = if self.settings_tab_is_dirty(tab) {
+                            format!("{} *", tab.label())
+                        } else {
+                            tab.label().to_string()
+                        };
+                        ui.selectable_value(&mut self.settings.tab, tab, title);
+                    }
+                });
+                ui.separator();
+
+                egui::ScrollArea::vertical().max_height(400.0).show(ui, |ui| {
+                    ui.set_min_width(380.0);
+                    let d = &mut self.settings.draft;
+                    let c = &self.config;
+                    match self.settings.tab {
+                        SettingsTab::Audio => {
+                            let saved_device_index = c
+                                .input_device_name
+                                .as_ref()
+                                .and_then(|name| self.available_devices.iter().position(|d| d == name))
+                                .unwrap_or(0);
+                            setting_header(ui, "Input Device:", &mut self.selected_device_index, &saved_device_index, &0);
+                            egui::ComboBox::from_id_salt("input_device_combo")
+                                .selected_text(
+                                    self.available_devices
+                                        .get(self.selected_device_index)
+                                        .unwrap_or(&"Default".to_string()),
+                                )
+                                .show_ui(ui, |ui| {
+                                    for (idx, device_name) in self.available_devices.iter().enumerate() {
+                                        ui.selectable_value(&mut self.selected_device_index, idx, device_name);
+                                    }
+                                });
+                            ui.add_space(5.0);
+
+                            // ゲインはデバイスごとに保存する (1.0 のデバイスは登録しない)
+                            let device_key = self
+                                .available_devices
+                                .get(self.selected_device_index)
+                                .cloned()
+                                .unwrap_or_default();
+                            let saved_gain = c.input_gains.get(&device_key).copied().unwrap_or(1.0);
+                            let mut gain = d.input_gains.get(&device_key).copied().unwrap_or(1.0);
+                            setting_header(ui, "Input Gain (このデバイス):", &mut gain, &saved_gain, &1.0);
+                            ui.horizontal(|ui| {
+                                ui.add(egui::Slider::new(&mut gain, 0.5..=8.0).logarithmic(true).suffix("x"));
+                                ui.label(format!("{:+.1} dB", 20.0 * gain.log10()));
+                            });
+                            if gain == 1.0 {
+                                d.input_gains.remove(&device_key);
+                            } else {
+                                d.input_gains.insert(device_key, gain);
+                            }
+                            if let Some(recorder) = &self.audio_recorder {
+                                ui.horizontal(|ui| {
+                                    ui.add(
+                                        egui::ProgressBar::new(recorder.get_max_amplitude())
+                                            .desired_width(200.0),
+                                    );
+                                    if recorder.is_clipping() {
+                                        ui.colored_label(egui::Color32::RED, "⚠ クリップ");
+                                    }
+                                });
+                            }
+                            ui.add_space(10.0);
+
+                            setting_checkbox(ui, "Hold-to-talk (音量で判定せず、キーを押している間だけ録音する)", &mut d.hold_to_talk_enabled, &c.hold_to_talk_enabled, &defaults.hold_to_talk_enabled);
+                            setting_header(ui, "Hold-to-talk Key (F9, Space など。MIDI は「押している間だけ録音」を割り当て):", &mut d.hold_to_talk_key, &c.hold_to_talk_key, &defaults.hold_to_talk_key);
+                            ui.add_enabled(d.hold_to_talk_enabled, egui::TextEdit::singleline(&mut d.hold_to_talk_key));
+                            if d.hold_to_talk_enabled && egui::Key::from_name(&d.hold_to_talk_key).is_none() {
+                                ui.colored_label(egui::Color32::RED, "⚠ キーの名前が分かりません");
+                            }
+                            if d.hold_to_talk_enabled {
+                                ui.colored_label(
+                                    egui::Color32::YELLOW,
+                                    "⚠ キーはこのアプリのウィンドウが前面にあるときだけ効きます。VRChat を操作しながら使うなら MIDI パッドに「押している間だけ録音」を割り当ててください",
+                                );
+                            }
+                            ui.add_space(10.0);
+
+                            setting_header(ui, "Start Threshold:", &mut d.start_threshold, &c.start_threshold, &defaults.start_threshold);
+                            ui.add(egui::Slider::new(&mut d.start_threshold, 0.001..=0.3).logarithmic(true));
+                            setting_header(ui, "Voice Confirm Frames (閾値超えが何フレーム続いたら録音を始めるか):", &mut d.voice_confirm_frames, &c.voice_confirm_frames, &defaults.voice_confirm_frames);
+                            ui.add(egui::Slider::new(&mut d.voice_confirm_frames, 1..=10));
+                            setting_header(ui, "Voice Confirm Window (ms, 1 フレームの長さ):", &mut d.voice_confirm_window_ms, &c.voice_confirm_window_ms, &defaults.voice_confirm_window_ms);
+                            ui.add(egui::Slider::new(&mut d.voice_confirm_window_ms, 10..=500));
+                            ui.label(format!(
+                                "  声が約 {} ms 続いたら録音開始",
+                                u64::from(d.voice_confirm_frames.saturating_sub(1)) * d.voice_confirm_window_ms
+                            ));
+                            ui.add_space(5.0);
+
+                            setting_header(ui, "Silence Threshold:", &mut d.silence_threshold, &c.silence_threshold, &defaults.silence_threshold);
+                            ui.add(egui::Slider::new(&mut d.silence_threshold, 0.001..=0.3).logarithmic(true));
+                            ui.add_space(5.0);
+
+                            setting_header(ui, "Silence Duration (seconds):", &mut d.silence_duration_secs, &c.silence_duration_secs, &defaults.silence_duration_secs);
+                            ui.add(egui::Slider::new(&mut d.silence_duration_secs, 0.5..=10.0));
+                            ui.add_space(5.0);
+
+                            setting_header(ui, "Pre-roll (seconds):", &mut d.preroll_secs, &c.preroll_secs, &defaults.preroll_secs);
+                            ui.add(egui::Slider::new(&mut d.preroll_secs, 0.0..=1.0));
+                            ui.add_space(10.0);
+
+                            setting_checkbox(ui, "音声コマンドを使う (「感度を上げて/下げて」)", &mut d.voice_commands_enabled, &c.voice_commands_enabled, &defaults.voice_commands_enabled);
+                            setting_header(ui, "Sensitivity Step:", &mut d.sensitivity_step, &c.sensitivity_step, &defaults.sensitivity_step);
+                            ui.add(egui::Slider::new(&mut d.sensitivity_step, 0.05..=0.5));
+                            ui.add_space(10.0);
+
+                            setting_checkbox(ui, "録音を保存する (あとで再書き起こしできるように)", &mut d.archive_recordings, &c.archive_recordings, &defaults.archive_recordings);
+                            setting_header(ui, "Work Directory (録音の一時ファイル, 空ならシステムの一時フォルダ):", &mut d.audio_work_dir, &c.audio_work_dir, &defaults.audio_work_dir);
+                            ui.add(egui::TextEdit::singleline(&mut d.audio_work_dir).hint_text("R:\\eliza"));
+                            setting_header(ui, "一時ファイルを消すまでの分数 (0 で消さない):", &mut d.audio_cleanup_minutes, &c.audio_cleanup_minutes, &defaults.audio_cleanup_minutes);
+                            ui.add(egui::Slider::new(&mut d.audio_cleanup_minutes, 0..=1440));
+                            ui.add_space(10.0);
+
+                            setting_checkbox(ui, "Soundboard (返答中の [sfx:名前] タグで効果音を鳴らす)", &mut d.soundboard_enabled, &c.soundboard_enabled, &defaults.soundboard_enabled);
+                            let saved_sfx_map = config::format_mapping_lines(&c.sfx_map);
+                            setting_header(ui, "SFX Map (タグ名=音声ファイルのパス, 1行に1つ):", &mut self.settings.sfx_map, &saved_sfx_map, &String::new());
+                            ui.add(
+                                egui::TextEdit::multiline(&mut self.settings.sfx_map)
+                                    .desired_rows(2)
+                                    .hint_text("laugh=C:\\sfx\\laugh.wav"),
+                            );
+                            setting_header(ui, "SFX Output Device:", &mut d.sfx_output_device, &c.sfx_output_device, &defaults.sfx_output_device);
+                            egui::ComboBox::from_id_salt("sfx_output_combo")
+                                .selected_text(if d.sfx_output_device.is_empty() { "既定" } else { d.sfx_output_device.as_str() })
+                                .show_ui(ui, |ui| {
+                                    ui.selectable_value(&mut d.sfx_output_device, String::new(), "既定");
+                                    for device in &self.settings.output_devices {
+                                        ui.selectable_value(&mut d.sfx_output_device, device.clone(), device);
+                                    }
+                                });
+                            setting_header(ui, "SFX Volume:", &mut d.sfx_volume, &c.sfx_volume, &defaults.sfx_volume);
+                            ui.add(egui::Slider::new(&mut d.sfx_volume, 0.0..=1.0));
+                            output_test_row(ui, &d.sfx_output_device, d.sfx_volume);
+                            ui.add_space(10.0);
+
+                            setting_checkbox(ui, "返答を読み上げる", &mut d.tts_enabled, &c.tts_enabled, &defaults.tts_enabled);
+                            setting_header(ui, "TTS Backend:", &mut d.tts_backend, &c.tts_backend, &defaults.tts_backend);
+                            egui::ComboBox::from_id_salt("tts_backend_combo")
+                                .selected_text(
+                                    tts::TTS_BACKENDS
+                                        .iter()
+                                        .find(|(name, _)| *name == d.tts_backend)
+                                        .map(|(_, label)| *label)
+                                        .unwrap_or(d.tts_backend.as_str()),
+                                )
+                                .show_ui(ui, |ui| {
+                                    for (name, label) in tts::TTS_BACKENDS {
+                                        ui.selectable_value(&mut d.tts_backend, name.to_string(), label);
+                                    }
+                                });
+                            if d.tts_backend == "voicevox" {
+                                setting_header(ui, "VOICEVOX URL:", &mut d.voicevox_url, &c.voicevox_url, &defaults.voicevox_url);
+                                ui.horizontal(|ui| {
+                                    ui.text_edit_singleline(&mut d.voicevox_url);
+                                    if ui.button("🔄 話者一覧を取得").clicked() {
+                                        match voicevox::VoicevoxClient::new(&d.voicevox_url).speakers() {
+                                            Ok(speakers) => self.settings.voicevox_speakers = speakers,
+                                            Err(e) => self.toasts.error(e.user_message()),
+                                        }
+                                    }
+                                });
+                                setting_header(ui, "Speaker / Style:", &mut d.voicevox_style_id, &c.voicevox_style_id, &defaults.voicevox_style_id);
+                                egui::ComboBox::from_id_salt("voicevox_style_combo")
+                                    .selected_text(voicevox::style_label(&self.settings.voicevox_speakers, d.voicevox_style_id))
+                                    .show_ui(ui, |ui| {
+                                        for speaker in &self.settings.voicevox_speakers {
+                                            for style in &speaker.styles {
+                                                ui.selectable_value(
+                                                    &mut d.voicevox_style_id,
+                                                    style.id,
+                                                    format!("{} ({})", speaker.name, style.name),
+                                                );
+                                            }
+                                        }
+                                    });
+                                setting_header(ui, "Speed:", &mut d.voicevox_speed, &c.voicevox_speed, &defaults.voicevox_speed);
+                                ui.add(egui::Slider::new(&mut d.voicevox_speed, 0.5..=2.0));
+                                setting_header(ui, "Pitch:", &mut d.voicevox_pitch, &c.voicevox_pitch, &defaults.voicevox_pitch);
+                                ui.add(egui::Slider::new(&mut d.voicevox_pitch, -0.15..=0.15));
+                            } else if d.tts_backend == "elevenlabs" {
+                                setting_header(ui, "ElevenLabs API Key:", &mut d.elevenlabs_api_key, &c.elevenlabs_api_key, &defaults.elevenlabs_api_key);
+                                ui.add(egui::TextEdit::singleline(&mut d.elevenlabs_api_key).password(true));
+                                setting_header(ui, "Voice:", &mut d.elevenlabs_voice_id, &c.elevenlabs_voice_id, &defaults.elevenlabs_voice_id);
+                                ui.horizontal(|ui| {
+                                    let selected = self
+                                        .settings
+                                        .elevenlabs_voices
+                                        .iter()
+                                        .find(|v| v.voice_id == d.elevenlabs_voice_id)
+                                        .map(|v| v.name.clone())
+                                        .unwrap_or_else(|| d.elevenlabs_voice_id.clone());
+                                    egui::ComboBox::from_id_salt("elevenlabs_voice_combo")
+                                        .selected_text(selected)
+                                        .show_ui(ui, |ui| {
+                                            for voice in &self.settings.elevenlabs_voices {
+                                                ui.selectable_value(&mut d.elevenlabs_voice_id, voice.voice_id.clone(), &voice.name);
+                                            }
+                                        });
+                                    if ui.button("🔄 声の一覧を取得").clicked() {
+                                        match elevenlabs::ElevenLabsClient::new(&d.elevenlabs_api_key).voices() {
+                                            Ok(voices) => self.settings.elevenlabs_voices = voices,
+                                            Err(e) => self.toasts.error(e.user_message()),
+                                        }
+                                    }
+                                });
+                                ui.add(egui::TextEdit::singleline(&mut d.elevenlabs_voice_id).hint_text("Voice ID"));
+                            } else {
+                                setting_header(ui, "TTS Voice:", &mut d.tts_voice, &c.tts_voice, &defaults.tts_voice);
+                                egui::ComboBox::from_id_salt("tts_voice_combo")
+                                    .selected_text(d.tts_voice.as_str())
+                                    .show_ui(ui, |ui| {
+                                        for voice in openai::TTS_VOICES {
+                                            ui.selectable_value(&mut d.tts_voice, voice.to_string(), voice);
+                                        }
+                                    });
+                            }
+                            if ui.button("▶ Preview").clicked() {
+                                tts::preview(d);
+                            }
+                            setting_header(ui, "TTS Output Device (仮想ケーブルで Agent アカウントのマイクに):", &mut d.tts_output_device, &c.tts_output_device, &defaults.tts_output_device);
+                            egui::ComboBox::from_id_salt("tts_output_combo")
+                                .selected_text(if d.tts_output_device.is_empty() { "既定" } else { d.tts_output_device.as_str() })
+                                .show_ui(ui, |ui| {
+                                    ui.selectable_value(&mut d.tts_output_device, String::new(), "既定");
+                                    for device in &self.settings.output_devices {
+                                        ui.selectable_value(&mut d.tts_output_device, device.clone(), device);
+                                    }
+                                });
+                            setting_header(ui, "TTS Volume:", &mut d.tts_volume, &c.tts_volume, &defaults.tts_volume);
+                            ui.add(egui::Slider::new(&mut d.tts_volume, 0.0..=1.0));
+                            output_test_row(ui, &d.tts_output_device, d.tts_volume);
+                            ui.add_space(5.0);
+                            setting_checkbox(ui, "返答を自分のヘッドホンでも読み上げる (上の読み上げがオフでも鳴らす)", &mut d.monitor_tts_enabled, &c.monitor_tts_enabled, &defaults.monitor_tts_enabled);
+                            ui.add_enabled_ui(d.monitor_tts_enabled, |ui| {
+                                setting_header(ui, "Monitor Output Device:", &mut d.monitor_tts_output_device, &c.monitor_tts_output_device, &defaults.monitor_tts_output_device);
+                                egui::ComboBox::from_id_salt("monitor_tts_output_combo")
+                                    .selected_text(if d.monitor_tts_output_device.is_empty() { "既定" } else { d.monitor_tts_output_device.as_str() })
+                                    .show_ui(ui, |ui| {
+                                        ui.selectable_value(&mut d.monitor_tts_output_device, String::new(), "既定");
+                                        for device in &self.settings.output_devices {
+                                            ui.selectable_value(&mut d.monitor_tts_output_device, device.clone(), device);
+                                        }
+                                    });
+                                setting_header(ui, "Monitor Volume:", &mut d.monitor_tts_volume, &c.monitor_tts_volume, &defaults.monitor_tts_volume);
+                                ui.add(egui::Slider::new(&mut d.monitor_tts_volume, 0.0..=1.0));
+                                output_test_row(ui, &d.monitor_tts_output_device, d.monitor_tts_volume);
+                            });
+                            ui.add_space(5.0);
+                            setting_checkbox(ui, "読み上げ中に話し始めたら読み上げを止める (スピーカーで鳴らす場合は自分の声で止まることがあります)", &mut d.barge_in_enabled, &c.barge_in_enabled, &defaults.barge_in_enabled);
+                            setting_checkbox(ui, "読み上げ前に数字・日付・単位を読みやすくする", &mut d.tts_normalize_enabled, &c.tts_normalize_enabled, &defaults.tts_normalize_enabled);
+                            setting_header(ui, "Normalize Locale:", &mut d.tts_normalize_locale, &c.tts_normalize_locale, &defaults.tts_normalize_locale);
+                            ui.add_enabled_ui(d.tts_normalize_enabled, |ui| {
+                                egui::ComboBox::from_id_salt("tts_normalize_locale_combo")
+                                    .selected_text(
+                                        text_normalize::NORMALIZE_LOCALES
+                                            .iter()
+                                            .find(|(name, _)| *name == d.tts_normalize_locale)
+                                            .map(|(_, label)| *label)
+                                            .unwrap_or(d.tts_normalize_locale.as_str()),
+                                    )
+                                    .show_ui(ui, |ui| {
+                                        for (name, label) in text_normalize::NORMALIZE_LOCALES {
+                                            ui.selectable_value(&mut d.tts_normalize_locale, name.to_string(), label);
+                                        }
+                                    });
+                            });
+                            ui.add_space(10.0);
+
+                            ui.label("通知音 (ファイルが空なら内蔵のビープ音):");
+                            setting_checkbox(ui, "録音開始", &mut d.notify_recording_enabled, &c.notify_recording_enabled, &defaults.notify_recording_enabled);
+                            ui.add(egui::TextEdit::singleline(&mut d.notify_recording_sound).hint_text("C:\\sounds\\start.wav"));
+                            setting_checkbox(ui, "返答送信", &mut d.notify_reply_enabled, &c.notify_reply_enabled, &defaults.notify_reply_enabled);
+                            ui.add(egui::TextEdit::singleline(&mut d.notify_reply_sound).hint_text("C:\\sounds\\reply.wav"));
+                            setting_checkbox(ui, "エラー", &mut d.notify_error_enabled, &c.notify_error_enabled, &defaults.notify_error_enabled);
+                            ui.add(egui::TextEdit::singleline(&mut d.notify_error_sound).hint_text("C:\\sounds\\error.wav"));
+                            setting_header(ui, "Notification Output Device:", &mut d.notify_output_device, &c.notify_output_device, &defaults.notify_output_device);
+                            egui::ComboBox::from_id_salt("notify_output_combo")
+                                .selected_text(if d.notify_output_device.is_empty() { "既定" } else { d.notify_output_device.as_str() })
+                                .show_ui(ui, |ui| {
+                                    ui.selectable_value(&mut d.notify_output_device, String::new(), "既定");
+                                    for device in &self.settings.output_devices {
+                                        ui.selectable_value(&mut d.notify_output_device, device.clone(), device);
+                                    }
+                                });
+                            setting_header(ui, "Notification Volume:", &mut d.notify_volume, &c.notify_volume, &defaults.notify_volume);
+                            ui.add(egui::Slider::new(&mut d.notify_volume, 0.0..=1.0));
+                            output_test_row(ui, &d.notify_output_device, d.notify_volume);
+                            ui.horizontal(|ui| {
+                                ui.label("試し聞き:");
+                                for event in SoundEvent::ALL {
+                                    if ui.button(event.label()).clicked() {
+                                        notify_sound::play(d, event);
+                                    }
+                                }
+                            });
+                        }
+                        SettingsTab::Transcription => {
+                            setting_header(ui, "OpenAI API Key:", &mut d.openai_api_key, &c.openai_api_key, &defaults.openai_api_key);
+                            ui.text_edit_singleline(&mut d.openai_api_key);
+                            ui.add_space(5.0);
+
+                            setting_header(ui, "Whisper Model:", &mut d.whisper_model, &c.whisper_model, &defaults.whisper_model);
+                            ui.text_edit_singleline(&mut d.whisper_model);
+                            ui.add_space(5.0);
+
+                            setting_header(ui, "STT Backend:", &mut d.stt_backend, &c.stt_backend, &defaults.stt_backend);
+                            egui::ComboBox::from_id_salt("stt_backend_combo")
+                                .selected_text(stt::backend_label(&d.stt_backend))
+                                .show_ui(ui, |ui| {
+                                    for (name, label) in &stt::BACKENDS[..2] {
+                                        ui.selectable_value(&mut d.stt_backend, name.to_string(), *label);
+                                    }
+                                });
+                            setting_header(ui, "Fallback Backend (失敗・タイムアウト時):", &mut d.stt_fallback_backend, &c.stt_fallback_backend, &defaults.stt_fallback_backend);
+                            egui::ComboBox::from_id_salt("stt_fallback_combo")
+                                .selected_text(stt::backend_label(&d.stt_fallback_backend))
+                                .show_ui(ui, |ui| {
+                                    for (name, label) in stt::BACKENDS {
+                                        ui.selectable_value(&mut d.stt_fallback_backend, name.to_string(), label);
+                                    }
+                                });
+                            setting_header(ui, "STT Timeout (sec, 0 で無制限):", &mut d.stt_timeout_secs, &c.stt_timeout_secs, &defaults.stt_timeout_secs);
+                            ui.add(egui::Slider::new(&mut d.stt_timeout_secs, 0..=120));
+                            setting_header(ui, "STT Chunk (sec, 長い録音を区切って書き起こす。0 = 25MB を超えるときだけ):", &mut d.stt_chunk_secs, &c.stt_chunk_secs, &defaults.stt_chunk_secs);
+                            ui.add(egui::Slider::new(&mut d.stt_chunk_secs, 0..=600));
+                            setting_header(ui, "whisper.cpp Executable:", &mut d.local_whisper_path, &c.local_whisper_path, &defaults.local_whisper_path);
+                            ui.text_edit_singleline(&mut d.local_whisper_path);
+                            setting_header(ui, "whisper.cpp Model (ggml-*.bin またはモデルのフォルダ):", &mut d.local_whisper_model, &c.local_whisper_model, &defaults.local_whisper_model);
+                            ui.text_edit_singleline(&mut d.local_whisper_model);
+                            setting_header(ui, "Model Size (フォルダ指定時):", &mut d.local_whisper_model_size, &c.local_whisper_model_size, &defaults.local_whisper_model_size);
+                            egui::ComboBox::from_id_salt("local_whisper_size_combo")
+                                .selected_text(d.local_whisper_model_size.as_str())
+                                .show_ui(ui, |ui| {
+                                    for size in local_whisper::MODEL_SIZES {
+                                        ui.selectable_value(&mut d.local_whisper_model_size, size.to_string(), size);
+                                    }
+                                });
+                            setting_header(ui, "Threads (0 で既定):", &mut d.local_whisper_threads, &c.local_whisper_threads, &defaults.local_whisper_threads);
+                            ui.add(egui::Slider::new(&mut d.local_whisper_threads, 0..=16));
+                            setting_header(ui, "Max Queue (推論中に待たせる発話の数, 超えたら捨てる):", &mut d.local_whisper_max_queue, &c.local_whisper_max_queue, &defaults.local_whisper_max_queue);
+                            ui.add(egui::Slider::new(&mut d.local_whisper_max_queue, 0..=5));
+                            setting_header(ui, "Local Language (auto で自動判定):", &mut d.local_whisper_language, &c.local_whisper_language, &defaults.local_whisper_language);
+                            ui.text_edit_singleline(&mut d.local_whisper_language);
+                            ui.add_space(5.0);
+
+                            setting_header(ui, "Custom Prompt:", &mut d.custom_prompt, &c.custom_prompt, &defaults.custom_prompt);
+                            ui.add(egui::TextEdit::multiline(&mut d.custom_prompt).desired_rows(2));
+                            let saved_custom_prompts = config::format_mapping_lines(&c.custom_prompts);
+                            let default_custom_prompts = config::format_mapping_lines(&defaults.custom_prompts);
+                            setting_header(ui, "Language Prompts (言語コード=プロンプト, 1行に1つ。なければ Custom Prompt。自動判定では、Custom Prompt を書き換えていれば初期値のプロンプトより優先):", &mut self.settings.custom_prompts, &saved_custom_prompts, &default_custom_prompts);
+                            ui.add(
+                                egui::TextEdit::multiline(&mut self.settings.custom_prompts)
+                                    .desired_rows(3)
+                                    .hint_text("en=An English conversation."),
+                            );
+                            setting_header(ui, "Prompt Language (auto で直前の発話の言語):", &mut d.custom_prompt_language, &c.custom_prompt_language, &defaults.custom_prompt_language);
+                            ui.text_edit_singleline(&mut d.custom_prompt_language);
+                            ui.add_space(5.0);
+
+                            setting_checkbox(ui, "直前の発話を prompt に含める (固有名詞の表記を揃える)", &mut d.whisper_context_enabled, &c.whisper_context_enabled, &defaults.whisper_context_enabled);
+                            setting_checkbox(ui, "直前の Agent の返答も含める", &mut d.whisper_context_include_agent, &c.whisper_context_include_agent, &defaults.whisper_context_include_agent);
+                            setting_header(ui, "Context Length (chars):", &mut d.whisper_context_chars, &c.whisper_context_chars, &defaults.whisper_context_chars);
+                            ui.add(egui::Slider::new(&mut d.whisper_context_chars, 20..=500));
+                            ui.add_space(10.0);
+
+                            setting_checkbox(ui, "書き起こしを LLM で整える (句読点・フィラー・誤認識)", &mut d.transcript_cleanup_enabled, &c.transcript_cleanup_enabled, &defaults.transcript_cleanup_enabled);
+                            setting_header(ui, "Cleanup Model:", &mut d.transcript_cleanup_model, &c.transcript_cleanup_model, &defaults.transcript_cleanup_model);
+                            ui.text_edit_singleline(&mut d.transcript_cleanup_model);
+                            setting_header(ui, "Cleanup Prompt:", &mut d.transcript_cleanup_prompt, &c.transcript_cleanup_prompt, &defaults.transcript_cleanup_prompt);
+                            ui.add(egui::TextEdit::multiline(&mut d.transcript_cleanup_prompt).desired_rows(3));
+                            ui.add_space(10.0);
+
+                            setting_checkbox(ui, "電話番号・メールアドレス・住所を伏せ字 (███) にしてから送る", &mut d.pii_redaction_enabled, &c.pii_redaction_enabled, &defaults.pii_redaction_enabled);
+                            setting_header(ui, "指示のような発話 (「前の指示を無視して」など):", &mut d.injection_guard, &c.injection_guard, &defaults.injection_guard);
+                            egui::ComboBox::from_id_salt("injection_guard_combo")
+                                .selected_text(
+                                    injection_guard::INJECTION_GUARD_MODES
+                                        .iter()
+                                        .find(|(name, _)| *name == d.injection_guard)
+                                        .map(|(_, label)| *label)
+                                        .unwrap_or(d.injection_guard.as_str()),
+                                )
+                                .show_ui(ui, |ui| {
+                                    for (name, label) in injection_guard::INJECTION_GUARD_MODES {
+                                        ui.selectable_value(&mut d.injection_guard, name.to_string(), label);
+                                    }
+                                });
+                            ui.add_space(10.0);
+
+                            setting_checkbox(ui, "決めた言語以外の発話は聞き流す (近くのテレビや音楽に反応しない)", &mut d.language_filter_enabled, &c.language_filter_enabled, &defaults.language_filter_enabled);
+                            if d.language_filter_enabled {
+                                setting_header(ui, "Accepted Languages (言語コード, カンマ区切り):", &mut d.accepted_languages, &c.accepted_languages, &defaults.accepted_languages);
+                                ui.add(egui::TextEdit::singleline(&mut d.accepted_languages).hint_text("ja,en"));
+                            }
+                            setting_checkbox(ui, "話しかけられた言語で返答する", &mut d.auto_reply_language_enabled, &c.auto_reply_language_enabled, &defaults.auto_reply_language_enabled);
+                            setting_checkbox(ui, "話した言語でプリセットを切り替える", &mut d.language_routing_enabled, &c.language_routing_enabled, &defaults.language_routing_enabled);
+                            let saved_routes = config::format_mapping_lines(&c.language_routes);
+                            setting_header(ui, "Language Routes (言語コード=プリセット名, 1行に1つ):", &mut self.settings.language_routes, &saved_routes, &String::new());
+                            ui.add(
+                                egui::TextEdit::multiline(&mut self.settings.language_routes)
+                                    .desired_rows(2)
+                                    .hint_text("ja=default\nen=setting1"),
+                            );
+                        }
+                        SettingsTab::Agent => {
+                            setting_header(ui, "Agent Server URL:", &mut d.agent_server_url, &c.agent_server_url, &defaults.agent_server_url);
+                            ui.text_edit_singleline(&mut d.agent_server_url);
+                            ui.add_space(5.0);
+
+                            setting_header(ui, "Agent Model:", &mut d.agent_model, &c.agent_model, &defaults.agent_model);
+                            ui.text_edit_singleline(&mut d.agent_model);
+                            ui.add_space(5.0);
+
+                            setting_header(ui, "Max Conversation History:", &mut d.max_length_of_conversation_history, &c.max_length_of_conversation_history, &defaults.max_length_of_conversation_history);
+                            ui.add(egui::Slider::new(&mut d.max_length_of_conversation_history, 1..=50));
+                            setting_header(ui, "History Compaction (上限を超えたとき):", &mut d.history_compaction, &c.history_compaction, &defaults.history_compaction);
+                            egui::ComboBox::from_id_salt("history_compaction_combo")
+                                .selected_text(
+                                    eliza::COMPACTION_STRATEGIES
+                                        .iter()
+                                        .find(|(name, _)| *name == d.history_compaction)
+                                        .map(|(_, label)| *label)
+                                        .unwrap_or(d.history_compaction.as_str()),
+                                )
+                                .show_ui(ui, |ui| {
+                                    for (name, label) in eliza::COMPACTION_STRATEGIES {
+                                        ui.selectable_value(&mut d.history_compaction, name.to_string(), label);
+                                    }
+                                });
+                            if d.history_compaction != "drop_oldest_pairs" {
+                                setting_header(ui, "Keep After Compaction:", &mut d.history_compact_size, &c.history_compact_size, &defaults.history_compact_size);
+                                ui.add(egui::Slider::new(&mut d.history_compact_size, 1..=50));
+                            }
+                            setting_header(ui, "Agent Timeout (sec, 0 = 無制限):", &mut d.agent_timeout_secs, &c.agent_timeout_secs, &defaults.agent_timeout_secs);
+                            ui.add(egui::Slider::new(&mut d.agent_timeout_secs, 0..=300));
+                            ui.add_space(5.0);
+
+                            setting_checkbox(ui, "返答をストリーミングで受け取る (SSE, 比較モードでは無効)", &mut d.agent_streaming_enabled, &c.agent_streaming_enabled, &defaults.agent_streaming_enabled);
+                            ui.add_space(5.0);
+
+                            setting_header(ui, "Persona Prompt (空ならサーバー側の設定のみ):", &mut d.persona_prompt, &c.persona_prompt, &defaults.persona_prompt);
+                            ui.add(egui::TextEdit::multiline(&mut d.persona_prompt).desired_rows(3));
+                            if let Ok(dir) = snippets::snippets_dir() {
+                                ui.weak(format!("{{{{include:rules.md}}}} で {} のファイルを差し込めます", dir.display()));
+                            }
+                            ui.add_space(5.0);
+
+                            setting_header(ui, "Temperature:", &mut d.agent_temperature, &c.agent_temperature, &defaults.agent_temperature);
+                            ui.add(egui::Slider::new(&mut d.agent_temperature, 0.0..=2.0));
+                            setting_header(ui, "Spontaneity (毎ターン Temperature を ± この幅で揺らす。0 = 揺らさない):", &mut d.agent_spontaneity, &c.agent_spontaneity, &defaults.agent_spontaneity);
+                            ui.add(egui::Slider::new(&mut d.agent_spontaneity, 0.0..=1.0));
+                            setting_header(ui, "Max Tokens (返答の長さの上限。0 = 制限しない):", &mut d.agent_max_tokens, &c.agent_max_tokens, &defaults.agent_max_tokens);
+                            ui.add(egui::DragValue::new(&mut d.agent_max_tokens).range(0..=4096).speed(10));
+                            ui.add_space(5.0);
+
+                            setting_checkbox(ui, "最近の 👍/👎 の評価をプロンプトに添える", &mut d.feedback_note_enabled, &c.feedback_note_enabled, &defaults.feedback_note_enabled);
+                            setting_header(ui, "Feedback Count:", &mut d.feedback_note_count, &c.feedback_note_count, &defaults.feedback_note_count);
+                            ui.add(egui::Slider::new(&mut d.feedback_note_count, 1..=20));
+                            ui.add_space(5.0);
+
+                            setting_header(ui, "A/B Test Prompt (Persona Prompt と比べるプロンプト B):", &mut d.ab_prompt_b, &c.ab_prompt_b, &defaults.ab_prompt_b);
+                            ui.add(egui::TextEdit::multiline(&mut d.ab_prompt_b).desired_rows(3));
+                            setting_header(ui, "A/B Test Turns:", &mut d.ab_test_turns, &c.ab_test_turns, &defaults.ab_test_turns);
+                            ui.add(egui::Slider::new(&mut d.ab_test_turns, 2..=100));
+                            ui.add_space(10.0);
+
+                            setting_checkbox(ui, "停止・おやすみ時に会話を要約し、次回の会話の冒頭に添える", &mut d.session_summary_enabled, &c.session_summary_enabled, &defaults.session_summary_enabled);
+                            ui.add_space(10.0);
+
+                            setting_checkbox(ui, "気分 (会話の感情で変化し、口調に反映される)", &mut d.mood_enabled, &c.mood_enabled, &defaults.mood_enabled);
+                            setting_header(ui, "Mood OSC Address (Int: 0=ふつう 1=上機嫌 2=不機嫌 3=ハイテンション):", &mut d.mood_osc_address, &c.mood_osc_address, &defaults.mood_osc_address);
+                            ui.add(
+                                egui::TextEdit::singleline(&mut d.mood_osc_address)
+                                    .hint_text("/avatar/parameters/Mood"),
+                            );
+                            ui.add_space(10.0);
+
+                            setting_header(ui, "Silence Follow-up (sec, 会話中に沈黙が続いたら Agent から質問する。0 = しない):", &mut d.silence_follow_up_secs, &c.silence_follow_up_secs, &defaults.silence_follow_up_secs);
+                            ui.add(egui::Slider::new(&mut d.silence_follow_up_secs, 0..=120));
+                            if d.silence_follow_up_secs > 0 {
+                                setting_header(ui, "Follow-up Prompt (Agent への指示。チャットボックスには出ない):", &mut d.silence_follow_up_prompt, &c.silence_follow_up_prompt, &defaults.silence_follow_up_prompt);
+                                ui.add(egui::TextEdit::multiline(&mut d.silence_follow_up_prompt).desired_rows(2));
+                            }
+                        }
+                        SettingsTab::VRChat => {
+                            setting_checkbox(ui, "VRChat のミュート状態を使う", &mut d.use_vrchat_mute_detection, &c.use_vrchat_mute_detection, &defaults.use_vrchat_mute_detection);
+                            ui.label(format!("  ミュート解除で録音開始、ミュートで録音停止 (OSC {}ポート)", d.osc_listen_port));
+                            setting_checkbox(ui, "AFK の間 (ヘッドセットを外したときなど) はモニタリングを止め、戻ったら再開する", &mut d.afk_pause_enabled, &c.afk_pause_enabled, &defaults.afk_pause_enabled);
+                            setting_header(ui, "OSC Send Port (VRChat の受信ポート):", &mut d.osc_send_port, &c.osc_send_port, &defaults.osc_send_port);
+                            ui.add(egui::DragValue::new(&mut d.osc_send_port).range(1024..=65535));
+                            setting_header(ui, "OSC Listen Port (VRChat の送信ポート):", &mut d.osc_listen_port, &c.osc_listen_port, &defaults.osc_listen_port);
+                            ui.add(egui::DragValue::new(&mut d.osc_listen_port).range(1024..=65535));
+                            ui.label("  2 つ目の VRChat は起動オプション --osc=受信:127.0.0.1:送信 でポートを変える");
+                            setting_checkbox(ui, "話者の判定 (VRChat が自分の声を検出していない発話は Others として記録。ミュート中は判定しない)", &mut d.speaker_attribution_enabled, &c.speaker_attribution_enabled, &defaults.speaker_attribution_enabled);
+                            setting_header(ui, "Listening OSC Address (録音中だけ true にする Bool パラメータ):", &mut d.listening_osc_address, &c.listening_osc_address, &defaults.listening_osc_address);
+                            ui.add(
+                                egui::TextEdit::singleline(&mut d.listening_osc_address)
+                                    .hint_text("/avatar/parameters/AgentListening"),
+                            );
+                            ui.add_space(10.0);
+
+                            setting_checkbox(ui, "Agent アカウントモード (入力は引用せず、返答だけをこのアカウントで話す)", &mut d.agent_account_mode, &c.agent_account_mode, &defaults.agent_account_mode);
+                            setting_checkbox(ui, "操作用 API (POST http://127.0.0.1:<port>/say でテキストを送る)", &mut d.control_api_enabled, &c.control_api_enabled, &defaults.control_api_enabled);
+                            setting_header(ui, "Control API Port:", &mut d.control_api_port, &c.control_api_port, &defaults.control_api_port);
+                            ui.add(egui::DragValue::new(&mut d.control_api_port).range(1024..=65535));
+                            setting_header(ui, "Control API Token (Authorization: Bearer <token> で送る):", &mut d.control_api_token, &c.control_api_token, &defaults.control_api_token);
+                            ui.horizontal(|ui| {
+                                if d.control_api_token.is_empty() {
+                                    ui.weak("(API を有効にして保存すると作ります)");
+                                } else {
+                                    ui.monospace(format!("{}…", &d.control_api_token[..8.min(d.control_api_token.len())]));
+                                    if ui.small_button("📋 コピー").clicked() {
+                                        ui.ctx().copy_text(d.control_api_token.clone());
+                                    }
+                                }
+                                if ui.small_button("🔄 作り直す").on_hover_text("保存すると前のトークンは使えなくなります").clicked() {
+                                    match control::generate_token() {
+                                        Ok(token) => d.control_api_token = token,
+                                        Err(e) => eprintln!("{}", e),
+                                    }
+                                }
+                            });
+                            ui.add_space(10.0);
+
+                            setting_checkbox(ui, "他のプレイヤーのチャットを VRChat のログから拾って返答の参考にする", &mut d.world_chat_context_enabled, &c.world_chat_context_enabled, &defaults.world_chat_context_enabled);
+                            ui.label("  ログに書き込みが出るワールドでのみ使えます。拾った内容は LLM に送られます");
+                            setting_header(ui, "Chat Log Pattern (正規表現, name / text グループ):", &mut d.world_chat_pattern, &c.world_chat_pattern, &defaults.world_chat_pattern);
+                            ui.text_edit_singleline(&mut d.world_chat_pattern);
+                            setting_header(ui, "Chat Context Window (min):", &mut d.world_chat_minutes, &c.world_chat_minutes, &defaults.world_chat_minutes);
+                            ui.add(egui::Slider::new(&mut d.world_chat_minutes, 1..=30));
+                            ui.add_space(10.0);
+
+                            setting_checkbox(ui, "インスタンスに入ってきたプレイヤーにあいさつする", &mut d.greet_joins_enabled, &c.greet_joins_enabled, &defaults.greet_joins_enabled);
+                            setting_header(ui, "Max Greetings per Minute:", &mut d.greet_per_minute, &c.greet_per_minute, &defaults.greet_per_minute);
+                            ui.add(egui::Slider::new(&mut d.greet_per_minute, 1..=10));
+                            setting_header(ui, "Greeting Blocklist (あいさつしない名前, カンマ区切り):", &mut d.greet_blocklist, &c.greet_blocklist, &defaults.greet_blocklist);
+                            ui.add(egui::TextEdit::singleline(&mut d.greet_blocklist).hint_text("自分の表示名, Friend"));
+                            ui.add_space(10.0);
+
+                            setting_checkbox(ui, "インスタンスにいるプレイヤーのメモを返答の参考にする", &mut d.player_notes_enabled, &c.player_notes_enabled, &defaults.player_notes_enabled);
+                            let saved_player_notes = config::format_mapping_lines(&c.player_notes);
+                            setting_header(ui, "Player Notes (名前=メモ, 1行に1人):", &mut self.settings.player_notes, &saved_player_notes, &String::new());
+                            ui.add(
+                                egui::TextEdit::multiline(&mut self.settings.player_notes)
+                                    .desired_rows(4)
+                                    .hint_text("Alice=猫を飼っている。甘いものが好き"),
+                            );
+                            setting_checkbox(ui, "入ったワールドに合わせてプリセットを切り替える (全プリセット共通)", &mut d.world_presets_enabled, &c.world_presets_enabled, &defaults.world_presets_enabled);
+                            let saved_world_presets = config::format_mapping_lines(&c.world_presets);
+                            setting_header(ui, "World Presets (ワールド名=プリセット, 1行に1つ。* はその他のワールド):", &mut self.settings.world_presets, &saved_world_presets, &String::new());
+                            ui.add(
+                                egui::TextEdit::multiline(&mut self.settings.world_presets)
+                                    .desired_rows(3)
+                                    .hint_text("Study Room=設定1\nParty Hall=設定2\n*=default"),
+                            );
+                            setting_checkbox(ui, "VRChat が起動していない間は送信を止めて返答を保留する", &mut d.pause_when_vrchat_closed, &c.pause_when_vrchat_closed, &defaults.pause_when_vrchat_closed);
+                            setting_checkbox(ui, "送信に失敗したら再送する", &mut d.chatbox_retry_enabled, &c.chatbox_retry_enabled, &defaults.chatbox_retry_enabled);
+                            setting_header(ui, "Retry Period (sec, これを過ぎたら諦める):", &mut d.chatbox_retry_secs, &c.chatbox_retry_secs, &defaults.chatbox_retry_secs);
+                            ui.add(egui::Slider::new(&mut d.chatbox_retry_secs, 10..=600));
+                            ui.add_space(10.0);
+
+                            setting_checkbox(ui, "収まらない返答はページに分けて順に送る", &mut d.chatbox_paginate_enabled, &c.chatbox_paginate_enabled, &defaults.chatbox_paginate_enabled);
+                            setting_header(ui, "Reading Speed (文字/秒, ページを切り替えるまでの時間):", &mut d.chatbox_reading_cps, &c.chatbox_reading_cps, &defaults.chatbox_reading_cps);
+                            ui.add_enabled(d.chatbox_paginate_enabled, egui::Slider::new(&mut d.chatbox_reading_cps, 2.0..=20.0));
+                            setting_checkbox(ui, "しばらくしたらチャットボックスを空にする", &mut d.chatbox_clear_enabled, &c.chatbox_clear_enabled, &defaults.chatbox_clear_enabled);
+                            setting_header(ui, "Clear After (sec, 最後のページを送ってから):", &mut d.chatbox_clear_secs, &c.chatbox_clear_secs, &defaults.chatbox_clear_secs);
+                            ui.add_enabled(d.chatbox_clear_enabled, egui::Slider::new(&mut d.chatbox_clear_secs, 5..=300));
+                            setting_checkbox(ui, "収まらない返答は全文をアップロードして URL を添える", &mut d.paste_long_replies_enabled, &c.paste_long_replies_enabled, &defaults.paste_long_replies_enabled);
+                            setting_header(ui, "Paste Endpoint:", &mut d.paste_endpoint, &c.paste_endpoint, &defaults.paste_endpoint);
+                            ui.add_enabled(d.paste_long_replies_enabled, egui::TextEdit::singleline(&mut d.paste_endpoint));
+                            ui.label("※ 返答の全文が公開の paste サービスに送られます");
+                            ui.add_space(10.0);
+
+                            setting_checkbox(ui, "発言頻度を制限する (超えた返答は UI にだけ表示)", &mut d.reply_limit_enabled, &c.reply_limit_enabled, &defaults.reply_limit_enabled);
+                            setting_header(ui, "Max Replies per Minute (0 = 無制限):", &mut d.reply_limit_per_minute, &c.reply_limit_per_minute, &defaults.reply_limit_per_minute);
+                            ui.add(egui::Slider::new(&mut d.reply_limit_per_minute, 0..=20));
+                            setting_header(ui, "Cooldown (sec, 返答どうしの最小間隔):", &mut d.reply_cooldown_secs, &c.reply_cooldown_secs, &defaults.reply_cooldown_secs);
+                            ui.add(egui::Slider::new(&mut d.reply_cooldown_secs, 0..=120));
+                            ui.add_space(10.0);
+
+                            setting_header(ui, "Transcription Quote:", &mut d.quote_mode, &c.quote_mode, &defaults.quote_mode);
+                            egui::ComboBox::from_id_salt("quote_mode_combo")
+                                .selected_text(
+                                    config::QUOTE_MODES
+                                        .iter()
+                                        .find(|(name, _)| *name == d.quote_mode)
+                                        .map(|(_, label)| *label)
+                                        .unwrap_or(d.quote_mode.as_str()),
+                                )
+                                .show_ui(ui, |ui| {
+                                    for (name, label) in config::QUOTE_MODES {
+                                        ui.selectable_value(&mut d.quote_mode, name.to_string(), label);
+                                    }
+                                });
+                            ui.horizontal(|ui| {
+                                setting_header(ui, "Prefix:", &mut d.quote_prefix, &c.quote_prefix, &defaults.quote_prefix);
+                                ui.add(egui::TextEdit::singleline(&mut d.quote_prefix).desired_width(60.0));
+                                setting_header(ui, "Suffix:", &mut d.quote_suffix, &c.quote_suffix, &defaults.quote_suffix);
+                                ui.add(egui::TextEdit::singleline(&mut d.quote_suffix).desired_width(60.0));
+                            });
+                            if d.quote_mode == "dialogue" {
+                                ui.horizontal(|ui| {
+                                    setting_header(ui, "You:", &mut d.dialogue_user_label, &c.dialogue_user_label, &defaults.dialogue_user_label);
+                                    ui.add(egui::TextEdit::singleline(&mut d.dialogue_user_label).desired_width(80.0));
+                                    setting_header(ui, "Agent:", &mut d.dialogue_agent_label, &c.dialogue_agent_label, &defaults.dialogue_agent_label);
+                                    ui.add(
+                                        egui::TextEdit::singleline(&mut d.dialogue_agent_label)
+                                            .desired_width(80.0)
+                                            .hint_text("Agent Names の先頭"),
+                                    );
+                                });
+                                ui.label("  144 文字を超える場合は発話 → 返答の順に切り詰めます");
+                            }
+                            ui.add_space(5.0);
+
+                            setting_checkbox(ui, "カラオケ表示 (書き起こしを話した速さで少しずつ表示, whisper 系モデルのみ)", &mut d.karaoke_enabled, &c.karaoke_enabled, &defaults.karaoke_enabled);
+                            setting_header(ui, "Karaoke Max Duration (sec):", &mut d.karaoke_replay_secs, &c.karaoke_replay_secs, &defaults.karaoke_replay_secs);
+                            ui.add(egui::Slider::new(&mut d.karaoke_replay_secs, 1.0..=10.0));
+                            ui.add_space(10.0);
+
+                            setting_checkbox(ui, "自分に話しかけられた時だけ返答する (周りとの会話・雑音は聞き流す)", &mut d.intent_filter_enabled, &c.intent_filter_enabled, &defaults.intent_filter_enabled);
+                            setting_header(ui, "Agent Names (カンマ区切り, 呼ばれたら返答):", &mut d.agent_names, &c.agent_names, &defaults.agent_names);
+                            ui.add(egui::TextEdit::singleline(&mut d.agent_names).hint_text("リサ, Lisa"));
+                            setting_checkbox(ui, "LLM で判定する", &mut d.intent_use_llm, &c.intent_use_llm, &defaults.intent_use_llm);
+                            setting_header(ui, "Intent Model:", &mut d.intent_model, &c.intent_model, &defaults.intent_model);
+                            ui.text_edit_singleline(&mut d.intent_model);
+                            ui.add_space(10.0);
+
+                            setting_checkbox(ui, "会話の話題をステータス表示する", &mut d.status_topic_enabled, &c.status_topic_enabled, &defaults.status_topic_enabled);
+                            setting_header(ui, "Topic Update Interval (turns):", &mut d.status_topic_interval_turns, &c.status_topic_interval_turns, &defaults.status_topic_interval_turns);
+                            ui.add(egui::Slider::new(&mut d.status_topic_interval_turns, 1..=20));
+                            setting_header(ui, "Topic OSC Address (空ならチャットボックスの先頭行に表示):", &mut d.status_topic_osc_address, &c.status_topic_osc_address, &defaults.status_topic_osc_address);
+                            ui.add(
+                                egui::TextEdit::singleline(&mut d.status_topic_osc_address)
+                                    .hint_text("/avatar/parameters/..."),
+                            );
+                            ui.add_space(10.0);
+
+                            setting_header(ui, "Safety Word (認識したら即停止してチャットボックスを消す, 空で無効):", &mut d.safety_word, &c.safety_word, &defaults.safety_word);
+                            ui.add(
+                                egui::TextEdit::singleline(&mut d.safety_word)
+                                    .hint_text("リサ、ストップ"),
+                            );
+                            setting_checkbox(ui, "停止時に会話履歴も消去する", &mut d.safety_word_wipe_history, &c.safety_word_wipe_history, &defaults.safety_word_wipe_history);
+                            ui.add_space(10.0);
+
+                            setting_header(ui, "Do-not-disturb Keywords (含まれていたら UI にだけ表示, カンマ区切り):", &mut d.dnd_keywords, &c.dnd_keywords, &defaults.dnd_keywords);
+                            ui.add(
+                                egui::TextEdit::singleline(&mut d.dnd_keywords)
+                                    .hint_text("住所, 電話番号, 本名"),
+                            );
+                        }
+                        SettingsTab::Ui => {
+                            setting_checkbox(ui, "比較モード (2つのモデルの返答を並べて表示)", &mut d.compare_mode_enabled, &c.compare_mode_enabled, &defaults.compare_mode_enabled);
+                            setting_header(ui, "Compare Server URL (空ならメインと同じ):", &mut d.compare_server_url, &c.compare_server_url, &defaults.compare_server_url);
+                            ui.text_edit_singleline(&mut d.compare_server_url);
+                            setting_header(ui, "Compare Model:", &mut d.compare_model, &c.compare_model, &defaults.compare_model);
+                            ui.text_edit_singleline(&mut d.compare_model);
+                            ui.add_space(10.0);
+
+                            let saved_quick_replies = c.quick_replies.join("\n");
+                            let default_quick_replies = defaults.quick_replies.join("\n");
+                            setting_header(ui, "Quick Replies (1行に1つ):", &mut self.settings.quick_replies, &saved_quick_replies, &default_quick_replies);
+                            ui.add(egui::TextEdit::multiline(&mut self.settings.quick_replies).desired_rows(3));
+                            setting_checkbox(ui, "Agent を通さずそのまま送る", &mut d.quick_replies_bypass_agent, &c.quick_replies_bypass_agent, &defaults.quick_replies_bypass_agent);
+                            ui.add_space(10.0);
+
+                            setting_checkbox(ui, "Windows の起動時に自動で起動する (全プリセット共通)", &mut self.settings.autostart, &self.settings.autostart_registered, &false);
+                            ui.add_space(10.0);
+
+                            setting_header(ui, "設定ファイルの暗号化:", &mut d.config_encryption, &c.config_encryption, &defaults.config_encryption);
+                            egui::ComboBox::from_id_salt("config_encryption_combo")
+                                .selected_text(
+                                    config_crypt::ENCRYPTION_MODES
+                                        .iter()
+                                        .find(|(name, _)| *name == d.config_encryption)
+                                        .map(|(_, label)| *label)
+                                        .unwrap_or(d.config_encryption.as_str()),
+                                )
+                                .show_ui(ui, |ui| {
+                                    for (name, label) in config_crypt::ENCRYPTION_MODES {
+                                        ui.selectable_value(&mut d.config_encryption, name.to_string(), label);
+                                    }
+                                });
+                            if d.config_encryption == "passphrase" {
+                                ui.weak(format!("パスフレーズは環境変数 {} で渡します", config_crypt::PASSPHRASE_ENV));
+                            }
+                            ui.weak("保存すると、すべてのプリセットをこの方式で保存し直します");
+                        }
+                        SettingsTab::Hooks => {
+                            ui.horizontal(|ui| {
+                                ui.label("イベント時に外部コマンド / Webhook を実行します");
+                                dirty_marker(ui, d.hooks != c.hooks);
+                            });
+                            ui.weak("テンプレート: {event} {text} {preset} {time} (コマンドでは値を環境変数 ELIZA_EVENT などで渡し、{text} はその参照に置き換わります)");
+                            ui.add_space(5.0);
+
+                            let mut remove_index = None;
+                            for (i, hook) in d.hooks.iter_mut().enumerate() {
+                                ui.group(|ui| {
+                                    ui.horizontal(|ui| {
+                                        egui::ComboBox::from_id_salt(("hook_event", i))
+                                            .selected_text(hook.event.clone())
+                                            .show_ui(ui, |ui| {
+                                                for event in HookEvent::ALL {
+                                                    ui.selectable_value(&mut hook.event, event.name().to_string(), event.name());
+                                                }
+                                            });
+                                        ui.radio_value(&mut hook.webhook, false, "Command");
+                                        ui.radio_value(&mut hook.webhook, true, "Webhook");
+                                        if ui.small_button("🗑").clicked() {
+                                            remove_index = Some(i);
+                                        }
+                                    });
+                                    let hint = if hook.webhook {
+                                        "https://example.com/hook"
+                                    } else {
+                                        "play-sound.bat {event}"
+                                    };
+                                    ui.add(egui::TextEdit::singleline(&mut hook.target).hint_text(hint).desired_width(f32::INFINITY));
+                                    if hook.webhook {
+                                        ui.add(
+                                            egui::TextEdit::multiline(&mut hook.payload)
+                                                .desired_rows(2)
+                                                .hint_text("body (空なら {\"event\",\"text\",\"preset\",\"time\"} の JSON)"),
+                                        );
+                                    }
+                                });
+                            }
+                            if let Some(i) = remove_index {
+                                d.hooks.remove(i);
+                            }
+                            if ui.button("＋ フックを追加").clicked() {
+                                d.hooks.push(hooks::HookConfig::default());
+                            }
+                            ui.add_space(10.0);
+
+                            setting_checkbox(ui, "すべてのイベントを OSC でも送る (/eliza/<event> に JSON の文字列)", &mut d.activity_osc_enabled, &c.activity_osc_enabled, &defaults.activity_osc_enabled);
+                            setting_header(ui, "OSC Target (host:port):", &mut d.activity_osc_target, &c.activity_osc_target, &defaults.activity_osc_target);
+                            ui.text_edit_singleline(&mut d.activity_osc_target);
+                        }
+                        SettingsTab::Midi => {
+                            setting_checkbox(ui, "MIDI コントローラを使う", &mut d.midi_enabled, &c.midi_enabled, &defaults.midi_enabled);
+                            setting_header(ui, "MIDI Port:", &mut d.midi_port_name, &c.midi_port_name, &defaults.midi_port_name);
+                            ui.horizontal(|ui| {
+                                let selected = if d.midi_port_name.is_empty() {
+                                    "(最初のポート)".to_string()
+                                } else {
+                                    d.midi_port_name.clone()
+                                };
+                                egui::ComboBox::from_id_salt("midi_port_combo")
+                                    .selected_text(selected)
+                                    .show_ui(ui, |ui| {
+                                        ui.selectable_value(&mut d.midi_port_name, String::new(), "(最初のポート)");
+                                        for port in &self.settings.midi_ports {
+                                            ui.selectable_value(&mut d.midi_port_name, port.clone(), port);
+                                        }
+                                    });
+                                if ui.button("🔄").on_hover_text("ポート一覧を更新").clicked() {
+                                    self.settings.midi_ports = midi::list_input_ports();
+                                }
+                            });
+                            ui.horizontal(|ui| {
+                                ui.label("最後に受信:");
+                                match self.last_midi_event {
+                                    Some(event) => {
+                                        ui.monospace(event.key());
+                                        if ui.small_button("＋ これを割り当て").clicked() {
+                                            d.midi_bindings.push(midi::MidiBinding {
+                                                trigger: event.key(),
+                                                ..Default::default()
+                                            });
+                                        }
+                                    }
+                                    None => {
+                                        ui.weak("(なし — パッドやノブを操作すると表示されます)");
+                                    }
+                                }
+                            });
+                            ui.add_space(5.0);
+
+                            ui.horizontal(|ui| {
+                                ui.label("Bindings:");
+                                dirty_marker(ui, d.midi_bindings != c.midi_bindings);
+                            });
+                            let mut remove_index = None;
+                            for (i, binding) in d.midi_bindings.iter_mut().enumerate() {
+                                ui.horizontal(|ui| {
+                                    ui.add(egui::TextEdit::singleline(&mut binding.trigger).desired_width(70.0));
+                                    egui::ComboBox::from_id_salt(("midi_action", i))
+                                        .selected_text(midi::action_label(&binding.action))
+                                        .show_ui(ui, |ui| {
+                                            for (name, label) in midi::ACTIONS {
+                                                ui.selectable_value(&mut binding.action, name.to_string(), label);
+                                            }
+                                        });
+                                    if binding.action == "send_text" {
+                                        ui.add(egui::TextEdit::singleline(&mut binding.text).hint_text("AFKです").desired_width(120.0));
+                                    }
+                                    if ui.small_button("🗑").clicked() {
+                                        remove_index = Some(i);
+                                    }
+                                });
+                            }
+                            if let Some(i) = remove_index {
+                                d.midi_bindings.remove(i);
+                            }
+                            if ui.button("＋ 割り当てを追加").clicked() {
+                                d.midi_bindings.push(midi::MidiBinding::default());
+                            }
+                            ui.weak("MIDI ポートの変更は Save 後に反映されます");
+                        }
+                    }
+                });
+
+                ui.add_space(10.0);
+                ui.horizontal(|ui| {
+                    if ui.button("Save").clicked() {
+                        save_clicked = true;
+                    }
+                    if ui.button("Cancel").clicked() {
+                        cancel_clicked = true;
+                    }
+                    if SettingsTab::ALL.iter().any(|tab| self.settings_tab_is_dirty(*tab)) {
+                        ui.colored_label(egui::Color32::from_rgb(255, 165, 0), "● 未保存の変更があります");
+                    }
+                });
+            });
+
+        if save_clicked {
+            self.save_settings();
+            self.settings.open = false;
+        } else if cancel_clicked {
+            // Revert settings changes
+            self.load_settings_from_config();
+            self.settings.open = false;
+        }
+    }
+}
@@ -0,0 +1,167 @@
+//! 状態表示と大きな Start / Stop ボタン
+
+use super::widgets::gauge_button;
+use crate::pipeline_controller::{AppState, Effect, PipelineEvent};
+use crate::{latency_preset, vad_preset, ElizaAgentApp};
+use eframe::egui;
+
+/// 音声モードとテキストモードを切り替えるキー
+pub const TEXT_MODE_SHORTCUT: egui::KeyboardShortcut =
+    egui::KeyboardShortcut::new(egui::Modifiers::CTRL, egui::Key::T);
+
+/// 録音中に無音を待たずに送るキー
+pub const SEND_NOW_SHORTCUT: egui::KeyboardShortcut =
+    egui::KeyboardShortcut::new(egui::Modifiers::CTRL, egui::Key::Enter);
+
+fn status_color(state: AppState) -> egui::Color32 {
+    match state {
+        AppState::Idle => egui::Color32::GRAY,
+        AppState::Monitoring => egui::Color32::from_rgb(0, 128, 0),
+        AppState::Recording => egui::Color32::RED,
+        AppState::Processing => egui::Color32::from_rgb(200, 100, 0),
+        AppState::CalibratingSilence => egui::Color32::from_rgb(100, 100, 200),
+        AppState::CalibratingVoice => egui::Color32::from_rgb(200, 100, 200),
+    }
+}
+
+/// ボタンの表示と、押したら Stop になるか (常に Start か Stop のどちらかを出す)
+fn button_label(state: AppState) -> (&'static str, bool) {
+    match state {
+        AppState::Idle => ("▶ Start Monitoring", false),
+        AppState::Monitoring => ("⏹ Stop", true),
+        AppState::Recording => ("⏹ Stop", true),
+        AppState::Processing => ("⏹ Stop", true),
+        AppState::CalibratingSilence => ("⏹ Stop", true),
+        AppState::CalibratingVoice => ("⏹ 停止して閾値を確定", true),
+    }
+}
+
+impl ElizaAgentApp {
+    pub(crate) fn show_status(&mut self, ui: &mut egui::Ui) {
+        ui.colored_label(status_color(self.pipeline.state()), &self.status_message);
+
+        if !self.recording_info.is_empty() {
+            ui.label(&self.recording_info);
+        }
+        if self.audio_recorder.as_ref().is_some_and(|r| r.is_clipping()) {
+            ui.colored_label(
+                egui::Color32::RED,
+                "⚠ 入力がクリップしています (Settings で Input Gain を下げてください)",
+            );
+        }
+    }
+
+    pub(crate) fn show_start_button(&mut self, ui: &mut egui::Ui) {
+        ui.add_space(10.0);
+
+        let shortcut = ui.ctx().format_shortcut(&TEXT_MODE_SHORTCUT);
+        let mut text_mode = self.config.text_mode;
+        ui.horizontal(|ui| {
+            ui.selectable_value(&mut text_mode, false, "🎤 音声")
+                .on_hover_text(format!("マイクで話しかける ({})", shortcut));
+            ui.selectable_value(&mut text_mode, true, "⌨ テキスト")
+                .on_hover_text(format!("入力欄から送るだけにして、マイクは使わない ({})", shortcut));
+        });
+        if text_mode != self.config.text_mode {
+            self.set_text_mode(text_mode);
+        }
+        if self.config.text_mode {
+            // 録音まわりのボタンは出さない
+            return;
+        }
+        ui.add_space(10.0);
+
+        let (button_text, is_stop_button) = button_label(self.pipeline.state());
+
+        // 録音中は無音の進み具合をゲージで見せる
+        let silence_progress = if self.pipeline.state() == AppState::Recording && !self.config.hold_to_talk_enabled {
+            let progress = self.audio_recorder.as_ref().map(|recorder| {
+                let silence_elapsed = recorder.get_silence_duration().as_secs_f32();
+                (silence_elapsed / self.config.silence_duration_secs).min(1.0)
+            });
+            Some(progress.unwrap_or(0.0))
+        } else {
+            None
+        };
+
+        let response = gauge_button(ui, egui::vec2(300.0, 60.0), button_text, silence_progress);
+        if response.clicked() {
+            if is_stop_button {
+                if self.dispatch(PipelineEvent::StopPressed) == Some(Effect::CloseMicrophone) {
+                    self.start_session_summary();
+                }
+            } else {
+                self.dispatch(PipelineEvent::StartPressed);
+            }
+        }
+        if self.pipeline.state() == AppState::Recording {
+            let shortcut = ui.ctx().format_shortcut(&SEND_NOW_SHORTCUT);
+            if ui
+                .button("📤 今すぐ送る")
+                .on_hover_text(format!("無音を待たずに、ここまでの録音を送ります ({})", shortcut))
+                .clicked()
+            {
+                self.send_recording_now();
+            }
+        }
+
+        ui.add_space(5.0);
+
+        // VAD presets (閾値をまとめて切り替える)
+        ui.horizontal(|ui| {
+            ui.label("🎚 感度:");
+            let current = vad_preset::current(&self.config);
+            let mut clicked = None;
+            for preset in &vad_preset::PRESETS {
+                let selected = current == Some(preset);
+                if ui
+                    .selectable_label(selected, preset.label)
+                    .on_hover_text(preset.description)
+                    .clicked()
+                {
+                    clicked = Some(preset);
+                }
+            }
+            if let Some(preset) = clicked {
+                self.apply_vad_preset(preset);
+            }
+        });
+
+        // Latency presets (書き起こしモデルや補助の LLM 呼び出しをまとめて切り替える)
+        ui.horizontal(|ui| {
+            ui.label("⚡ 応答:");
+            let current = latency_preset::current(&self.config);
+            let mut clicked = None;
+            for preset in &latency_preset::PRESETS {
+                let selected = current == Some(preset);
+                if ui
+                    .selectable_label(selected, preset.label)
+                    .on_hover_text(preset.description)
+                    .clicked()
+                {
+                    clicked = Some(preset);
+                }
+            }
+            if let Some(preset) = clicked {
+                self.apply_latency_preset(preset);
+            }
+        });
+        ui.add_space(5.0);
+
+        // Calibration button (Idle 時のみ表示)
+        if self.pipeline.is_idle()
+            && ui
+                .add(egui::Button::new("⚙ 音量閾値を自動設定").min_size(egui::vec2(300.0, 30.0)))
+                .clicked()
+        {
+            self.dispatch(PipelineEvent::CalibrationRequested);
+        }
+        if self.pipeline.is_idle()
+            && ui
+                .add(egui::Button::new("🎤 マイクチェック").min_size(egui::vec2(300.0, 30.0)))
+                .clicked()
+        {
+            self.mic_check.open = true;
+        }
+    }
+}
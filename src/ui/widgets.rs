@@ -0,0 +1,129 @@
+//! 画面をまたいで使う小さな部品
+
+use crate::playback;
+use eframe::egui;
+
+/// 未保存の変更を示すマーク
+pub fn dirty_marker(ui: &mut egui::Ui, dirty: bool) {
+    if dirty {
+        ui.colored_label(egui::Color32::from_rgb(255, 165, 0), "●")
+            .on_hover_text("未保存の変更があります");
+    }
+}
+
+/// 「デフォルトに戻す」ボタン (既にデフォルトなら無効)
+pub fn reset_button<T: PartialEq + Clone>(ui: &mut egui::Ui, value: &mut T, default: &T) {
+    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+        if ui
+            .add_enabled(value != default, egui::Button::new("↺").small())
+            .on_hover_text("デフォルトに戻す")
+            .clicked()
+        {
+            *value = default.clone();
+        }
+    });
+}
+
+/// Settings の 1 項目の見出し: ラベル + 変更マーク + リセットボタン
+pub fn setting_header<T: PartialEq + Clone>(
+    ui: &mut egui::Ui,
+    label: &str,
+    value: &mut T,
+    saved: &T,
+    default: &T,
+) {
+    ui.horizontal(|ui| {
+        ui.label(label);
+        dirty_marker(ui, value != saved);
+        reset_button(ui, value, default);
+    });
+}
+
+/// チェックボックスの設定項目
+pub fn setting_checkbox(ui: &mut egui::Ui, label: &str, value: &mut bool, saved: &bool, default: &bool) {
+    ui.horizontal(|ui| {
+        ui.checkbox(value, label);
+        dirty_marker(ui, value != saved);
+        reset_button(ui, value, default);
+    });
+}
+
+/// 大きなボタン。`drain` を渡すと下から減っていくゲージを背景に描く (録音中の無音の進み具合など)
+pub fn gauge_button(ui: &mut egui::Ui, size: egui::Vec2, text: &str, drain: Option<f32>) -> egui::Response {
+    let (rect, response) = ui.allocate_exact_size(size, egui::Sense::click());
+
+    // Draw button background
+    let visuals = ui.style().interact(&response);
+    ui.painter().rect_filled(rect, visuals.rounding, visuals.bg_fill);
+
+    // Draw gauge (start full, drain as progress goes on)
+    if let Some(progress) = drain {
+        let fill_height = rect.height() * (1.0 - progress.clamp(0.0, 1.0));
+        if fill_height > 0.0 {
+            let progress_rect = egui::Rect::from_min_size(
+                egui::pos2(rect.min.x, rect.max.y - fill_height),
+                egui::vec2(rect.width(), fill_height),
+            );
+            ui.painter().rect_filled(
+                progress_rect,
+                visuals.rounding,
+                egui::Color32::from_rgb(100, 200, 255),
+            );
+        }
+    }
+
+    // Draw button border
+    ui.painter().rect_stroke(rect, visuals.rounding, visuals.bg_stroke);
+
+    // Draw button text
+    ui.painter().text(
+        rect.center(),
+        egui::Align2::CENTER_CENTER,
+        text,
+        egui::FontId::proportional(18.0),
+        visuals.text_color(),
+    );
+    response
+}
+
+/// 出力デバイスの確認用: テストトーンのボタンと再生中の出力レベル
+pub fn output_test_row(ui: &mut egui::Ui, device: &str, volume: f32) {
+    ui.horizontal(|ui| {
+        if ui.button("🔔 テストトーン").clicked() {
+            playback::play_test_tone_async(device, volume);
+        }
+        let level = playback::output_level();
+        ui.add(egui::ProgressBar::new(level).desired_width(200.0))
+            .on_hover_text("再生中の出力レベル (どのデバイスから鳴っているかに関わらず表示)");
+        if level > 0.0 {
+            ui.ctx().request_repaint();
+        }
+    });
+}
+
+/// 小さな折れ線グラフ (値は古い順。failed の点は赤で示す)
+pub fn sparkline(ui: &mut egui::Ui, size: egui::Vec2, values: &[(f32, bool)]) -> egui::Response {
+    let (rect, response) = ui.allocate_exact_size(size, egui::Sense::hover());
+    let painter = ui.painter();
+    painter.rect_filled(rect, 2.0, ui.visuals().extreme_bg_color);
+    if values.is_empty() {
+        return response;
+    }
+    let max = values.iter().map(|(v, _)| *v).fold(f32::EPSILON, f32::max);
+    let step = rect.width() / (values.len().max(2) - 1) as f32;
+    let points: Vec<egui::Pos2> = values
+        .iter()
+        .enumerate()
+        .map(|(i, (v, _))| egui::pos2(rect.min.x + step * i as f32, rect.max.y - rect.height() * (v / max)))
+        .collect();
+    painter.add(egui::Shape::line(
+        points.clone(),
+        egui::Stroke::new(1.5, egui::Color32::from_rgb(100, 200, 255)),
+    ));
+    for (point, (_, ok)) in points.iter().zip(values) {
+        if !ok {
+            painter.circle_filled(*point, 2.5, egui::Color32::RED);
+        }
+    }
+    response
+}
@@ -0,0 +1,170 @@
+//! 録音開始・終了の判定を専用スレッドで行う
+//!
+//! egui の再描画 (ウィンドウを最小化したり裏に回すと間引かれる、止まる) とは関係なく一定間隔で RMS と無音を見る。
+//! 声を検出したらそのまま無音の判定に移り、無音を検出したらその時点までの録音を切り出して UI に送るので、
+//! UI が止まっていても録音の始まりと終わりは検出した時点で決まる (UI は受け取ってから処理を始めるだけ)
+
+use crate::audio::{AudioProbe, VoiceOnsetDetector};
+use crate::config::Config;
+use crate::pipeline_controller::AppState;
+use eframe::egui;
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use std::sync::mpsc::{channel, Receiver};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum VadEvent {
+    /// 声を検出した時刻
+    VoiceDetected(Instant),
+    /// 無音を検出した時点までの録音
+    SilenceDetected(Vec<f32>),
+}
+
+/// スレッドが今行う判定
+const MODE_OFF: u8 = 0;
+const MODE_MONITORING: u8 = 1;
+const MODE_RECORDING: u8 = 2;
+/// 無音で録音を切り出し、UI が受け取るのを待っている
+const MODE_CUT: u8 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VadParams {
+    pub start_threshold: f32,
+    pub confirm_frames: u32,
+    pub window: Duration,
+    pub silence_duration_secs: f32,
+}
+
+impl VadParams {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            start_threshold: config.start_threshold,
+            confirm_frames: config.voice_confirm_frames,
+            window: Duration::from_millis(config.voice_confirm_window_ms.max(1)),
+            silence_duration_secs: config.silence_duration_secs,
+        }
+    }
+}
+
+/// drop すると止まる
+pub struct VadThread {
+    mode: Arc<AtomicU8>,
+    params: Arc<Mutex<VadParams>>,
+    stop: Arc<AtomicBool>,
+    receiver: Receiver<VadEvent>,
+}
+
+impl VadThread {
+    pub fn start(probe: AudioProbe, params: VadParams, ctx: Option<egui::Context>) -> Self {
+        let mode = Arc::new(AtomicU8::new(MODE_OFF));
+        let params = Arc::new(Mutex::new(params));
+        let stop = Arc::new(AtomicBool::new(false));
+        let (sender, receiver) = channel();
+
+        let (thread_mode, thread_params, thread_stop) = (mode.clone(), params.clone(), stop.clone());
+        std::thread::spawn(move || {
+            let mut onset = VoiceOnsetDetector::default();
+            while !thread_stop.load(Ordering::Relaxed) {
+                let params = *thread_params.lock().unwrap_or_else(|e| e.into_inner());
+                let now = Instant::now();
+                let event = match thread_mode.load(Ordering::SeqCst) {
+                    MODE_MONITORING => onset
+                        .update(now, probe.rms() > params.start_threshold, params.confirm_frames, params.window)
+                        .then(|| {
+                            // UI を待たずに無音の判定に移る
+                            thread_mode.store(MODE_RECORDING, Ordering::SeqCst);
+                            VadEvent::VoiceDetected(now)
+                        }),
+                    MODE_RECORDING => {
+                        onset.reset();
+                        probe.is_silent(params.silence_duration_secs).then(|| {
+                            // UI が受け取るまで同じ判定を繰り返さない
+                            thread_mode.store(MODE_CUT, Ordering::SeqCst);
+                            VadEvent::SilenceDetected(probe.take_samples())
+                        })
+                    }
+                    _ => {
+                        onset.reset();
+                        None
+                    }
+                };
+                if let Some(event) = event {
+                    if sender.send(event).is_err() {
+                        break;
+                    }
+                    if let Some(ctx) = &ctx {
+                        ctx.request_repaint();
+                    }
+                }
+                std::thread::sleep(params.window);
+            }
+        });
+
+        Self {
+            mode,
+            params,
+            stop,
+            receiver,
+        }
+    }
+
+    /// パイプラインの状態に合わせて判定を切り替える
+    /// (切り出した録音を UI が受け取る前に Recording になっても、録音中には戻さない)
+    pub fn set_state(&self, state: AppState) {
+        let mode = match state {
+            AppState::Monitoring => MODE_MONITORING,
+            AppState::Recording => MODE_RECORDING,
+            _ => MODE_OFF,
+        };
+        let _ = self.mode.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |current| {
+            (current != MODE_CUT || mode != MODE_RECORDING).then_some(mode)
+        });
+    }
+
+    pub fn set_params(&self, params: VadParams) {
+        *self.params.lock().unwrap_or_else(|e| e.into_inner()) = params;
+    }
+
+    pub fn try_recv(&self) -> Option<VadEvent> {
+        self.receiver.try_recv().ok()
+    }
+}
+
+impl Drop for VadThread {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::AudioRecorder;
+
+    #[test]
+    fn test_silence_is_reported_once() {
+        let recorder = AudioRecorder::new(0.01).unwrap();
+        let params = VadParams {
+            silence_duration_secs: 0.0,
+            window: Duration::from_millis(10),
+            ..VadParams::from_config(&Config::default())
+        };
+        let vad = VadThread::start(recorder.probe(), params, None);
+
+        // モニタリング中は RMS が 0 なので何も起きない
+        vad.set_state(AppState::Monitoring);
+        std::thread::sleep(Duration::from_millis(100));
+        assert_eq!(vad.try_recv(), None);
+
+        vad.set_state(AppState::Recording);
+        std::thread::sleep(Duration::from_millis(100));
+        assert_eq!(vad.try_recv(), Some(VadEvent::SilenceDetected(Vec::new())));
+        assert_eq!(vad.try_recv(), None);
+
+        // UI が遅れて Recording に進んでも、切り出した後はもう一度送らない
+        vad.set_state(AppState::Recording);
+        std::thread::sleep(Duration::from_millis(100));
+        assert_eq!(vad.try_recv(), None);
+    }
+}
@@ -0,0 +1,78 @@
+//! 録音開始・終了の感度をまとめて切り替えるプリセット (RMS の閾値を知らなくても選べるように)
+
+use crate::config::Config;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VadPreset {
+    pub label: &'static str,
+    pub description: &'static str,
+    pub start_threshold: f32,
+    pub silence_threshold: f32,
+    pub silence_duration_secs: f32,
+    pub preroll_secs: f32,
+}
+
+pub const PRESETS: [VadPreset; 3] = [
+    VadPreset {
+        label: "静かな部屋",
+        description: "小さな声でも拾い、短い間で区切ります",
+        start_threshold: 0.03,
+        silence_threshold: 0.02,
+        silence_duration_secs: 1.2,
+        preroll_secs: 0.2,
+    },
+    VadPreset {
+        label: "うるさい部屋",
+        description: "周りの音や BGM では録音を始めず、少し長めの間で区切ります",
+        start_threshold: 0.15,
+        silence_threshold: 0.1,
+        silence_duration_secs: 1.8,
+        preroll_secs: 0.3,
+    },
+    VadPreset {
+        label: "VR ヘッドセットのマイク",
+        description: "口元に近いマイク向け。息やこすれる音を拾いにくくします",
+        start_threshold: 0.09,
+        silence_threshold: 0.06,
+        silence_duration_secs: 1.5,
+        preroll_secs: 0.2,
+    },
+];
+
+impl VadPreset {
+    pub fn apply(&self, config: &mut Config) {
+        config.start_threshold = self.start_threshold;
+        config.silence_threshold = self.silence_threshold;
+        config.silence_duration_secs = self.silence_duration_secs;
+        config.preroll_secs = self.preroll_secs;
+    }
+
+    fn matches(&self, config: &Config) -> bool {
+        config.start_threshold == self.start_threshold
+            && config.silence_threshold == self.silence_threshold
+            && config.silence_duration_secs == self.silence_duration_secs
+            && config.preroll_secs == self.preroll_secs
+    }
+}
+
+/// 今の設定と一致するプリセット (手で調整していれば None)
+pub fn current(config: &Config) -> Option<&'static VadPreset> {
+    PRESETS.iter().find(|p| p.matches(config))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply_and_current() {
+        let mut config = Config::default();
+        assert_eq!(current(&config).map(|p| p.label), Some("VR ヘッドセットのマイク"));
+        PRESETS[1].apply(&mut config);
+        assert_eq!(current(&config), Some(&PRESETS[1]));
+        assert_eq!(config.silence_duration_secs, 1.8);
+
+        config.start_threshold += 0.01;
+        assert_eq!(current(&config), None);
+    }
+}
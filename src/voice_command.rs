@@ -0,0 +1,60 @@
+/// 発話で操作できるアプリ内コマンド
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VoiceCommand {
+    SensitivityUp,
+    SensitivityDown,
+}
+
+/// コマンドとみなす発話の最大文字数 (長い発話は会話として扱う)
+const MAX_COMMAND_CHARS: usize = 20;
+
+/// 書き起こしテキストからコマンドを判定する
+pub fn parse_voice_command(text: &str) -> Option<VoiceCommand> {
+    let normalized: String = text.chars().filter(|c| c.is_alphanumeric()).collect();
+    if normalized.is_empty() || normalized.chars().count() > MAX_COMMAND_CHARS {
+        return None;
+    }
+
+    if normalized.contains("感度") {
+        if normalized.contains("上げ") || normalized.contains("あげ") {
+            return Some(VoiceCommand::SensitivityUp);
+        }
+        if normalized.contains("下げ") || normalized.contains("さげ") {
+            return Some(VoiceCommand::SensitivityDown);
+        }
+    }
+    None
+}
+
+/// 感度を step の割合だけ変えた閾値を返す (感度を上げる = 閾値を下げる)
+pub fn adjust_threshold(threshold: f32, command: VoiceCommand, step: f32) -> f32 {
+    let factor = match command {
+        VoiceCommand::SensitivityUp => 1.0 - step,
+        VoiceCommand::SensitivityDown => 1.0 + step,
+    };
+    (threshold * factor).clamp(0.001, 0.3)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_voice_command() {
+        assert_eq!(parse_voice_command("感度を上げて"), Some(VoiceCommand::SensitivityUp));
+        assert_eq!(parse_voice_command("感度さげて。"), Some(VoiceCommand::SensitivityDown));
+        assert_eq!(parse_voice_command("こんにちは"), None);
+        assert_eq!(
+            parse_voice_command("昨日マイクの感度を上げてみたんだけど全然変わらなかったよ"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_adjust_threshold() {
+        let up = adjust_threshold(0.1, VoiceCommand::SensitivityUp, 0.2);
+        assert!((up - 0.08).abs() < 1e-6);
+        let down = adjust_threshold(0.1, VoiceCommand::SensitivityDown, 0.2);
+        assert!((down - 0.12).abs() < 1e-6);
+    }
+}
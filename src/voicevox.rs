@@ -0,0 +1,122 @@
+//! VOICEVOX エンジン (ローカルの HTTP API) による音声合成
+
+use crate::error::AppError;
+use serde::Deserialize;
+use std::time::Duration;
+
+pub const DEFAULT_VOICEVOX_URL: &str = "http://127.0.0.1:50021";
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Style {
+    pub name: String,
+    pub id: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Speaker {
+    pub name: String,
+    pub styles: Vec<Style>,
+}
+
+pub struct VoicevoxClient {
+    base_url: String,
+    client: reqwest::blocking::Client,
+}
+
+impl VoicevoxClient {
+    pub fn new(base_url: &str) -> Self {
+        let client = reqwest::blocking::Client::builder()
+            .timeout(Duration::from_secs(30))
+            .build()
+            .unwrap_or_default();
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            client,
+        }
+    }
+
+    /// エンジンに入っている話者とスタイルの一覧
+    pub fn speakers(&self) -> Result<Vec<Speaker>, AppError> {
+        let response = self
+            .client
+            .get(format!("{}/speakers", self.base_url))
+            .send()
+            .map_err(|e| AppError::Network(format!("Failed to connect to VOICEVOX: {}", e)))?;
+        let status = response.status();
+        if !status.is_success() {
+            return Err(AppError::http(status.as_u16(), format!("VOICEVOX returned status {}", status)));
+        }
+        response
+            .json()
+            .map_err(|e| AppError::Other(format!("Failed to parse VOICEVOX speakers: {}", e)))
+    }
+
+    /// text を style_id の声で WAV にする (speed は 1.0 が標準、pitch は 0.0 が標準)
+    pub fn synthesize(&self, text: &str, style_id: u32, speed: f32, pitch: f32) -> Result<Vec<u8>, AppError> {
+        let response = self
+            .client
+            .post(format!("{}/audio_query", self.base_url))
+            .query(&[("text", text), ("speaker", &style_id.to_string())])
+            .send()
+            .map_err(|e| AppError::Network(format!("Failed to connect to VOICEVOX: {}", e)))?;
+        let status = response.status();
+        if !status.is_success() {
+            return Err(AppError::http(status.as_u16(), format!("VOICEVOX audio_query returned status {}", status)));
+        }
+        let mut query: serde_json::Value = response
+            .json()
+            .map_err(|e| AppError::Other(format!("Failed to parse VOICEVOX audio query: {}", e)))?;
+        query["speedScale"] = serde_json::json!(speed);
+        query["pitchScale"] = serde_json::json!(pitch);
+
+        let response = self
+            .client
+            .post(format!("{}/synthesis", self.base_url))
+            .query(&[("speaker", style_id.to_string())])
+            .json(&query)
+            .send()
+            .map_err(|e| AppError::Network(format!("Failed to connect to VOICEVOX: {}", e)))?;
+        let status = response.status();
+        if !status.is_success() {
+            return Err(AppError::http(status.as_u16(), format!("VOICEVOX synthesis returned status {}", status)));
+        }
+        response
+            .bytes()
+            .map(|b| b.to_vec())
+            .map_err(|e| AppError::Other(format!("Failed to read VOICEVOX audio: {}", e)))
+    }
+}
+
+/// "話者 (スタイル)" の表示名 (一覧にない ID なら番号だけ)
+pub fn style_label(speakers: &[Speaker], style_id: u32) -> String {
+    speakers
+        .iter()
+        .find_map(|speaker| {
+            speaker
+                .styles
+                .iter()
+                .find(|style| style.id == style_id)
+                .map(|style| format!("{} ({})", speaker.name, style.name))
+        })
+        .unwrap_or_else(|| format!("Style #{}", style_id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{FakeAgentServer, FakeResponse};
+
+    #[test]
+    fn test_speakers_and_style_label() {
+        let server = FakeAgentServer::start(vec![FakeResponse::json(
+            200,
+            r#"[{"name":"四国めたん","speaker_uuid":"x","styles":[{"name":"ノーマル","id":2},{"name":"あまあま","id":0}],"version":"0.14.0"},
+                {"name":"ずんだもん","speaker_uuid":"y","styles":[{"name":"ノーマル","id":3}],"version":"0.14.0"}]"#,
+        )]);
+        let speakers = VoicevoxClient::new(&format!("{}/", server.url)).speakers().unwrap();
+        assert_eq!(speakers.len(), 2);
+        assert_eq!(speakers[0].styles[1], Style { name: "あまあま".to_string(), id: 0 });
+        assert_eq!(style_label(&speakers, 3), "ずんだもん (ノーマル)");
+        assert_eq!(style_label(&speakers, 99), "Style #99");
+    }
+}
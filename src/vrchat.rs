@@ -1,25 +1,78 @@
+use crate::health::{self, Backend};
+use crate::osc_log::{self, Direction};
 use rosc::decoder;
 use rosc::encoder;
-use rosc::{OscMessage, OscPacket, OscType};
+use rosc::{OscBundle, OscMessage, OscPacket, OscTime, OscType};
 use std::net::UdpSocket;
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, AtomicU16, AtomicU64, Ordering};
+use std::collections::VecDeque;
 use std::sync::mpsc::Sender;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
 
-#[derive(Debug)]
-pub enum VRChatError {
-    SocketError(String),
-    SendError(String),
+/// VRChat プロセスが起動しているか (start_process_watcher が更新する)
+static VRCHAT_RUNNING: AtomicBool = AtomicBool::new(true);
+/// 送信を止めてログだけ残す (リプレイで VRChat に送らないとき)
+static SENDS_DISABLED: AtomicBool = AtomicBool::new(false);
+/// 分割送信の世代 (新しい返答を送り始めたら、前の返答の残りのページは送らない)
+static PAGE_GENERATION: AtomicU64 = AtomicU64::new(0);
+/// 最後にチャットボックスに空でないテキストを送った時刻 (自動で消すまでの時間はここから数える)
+static LAST_CHATBOX_TEXT: Mutex<Option<Instant>> = Mutex::new(None);
+/// 自動で消す予約の世代 (新しい予約があれば古い予約は何もしない)
+static CLEAR_GENERATION: AtomicU64 = AtomicU64::new(0);
+/// 次のチャットボックスの送信と一緒に送るパラメータ (同じアドレスは最新の値だけ残す)
+static PENDING_PARAMETERS: Mutex<Vec<OscMessage>> = Mutex::new(Vec::new());
+/// VRChat が OSC を受け付けるポート (起動オプション --osc で変えられる)
+static OSC_SEND_PORT: AtomicU16 = AtomicU16::new(DEFAULT_SEND_PORT);
+/// 受信した /avatar/parameters/Voice の履歴 (録音のうち自分が話していた割合を出すため)
+static SELF_VOICE: Mutex<VecDeque<(Instant, f32)>> = Mutex::new(VecDeque::new());
+/// まだ取り出されていない /avatar/parameters/AFK の最新の値 (VRChat はヘッドセットを外したときにも立てる)
+static PENDING_AFK: Mutex<Option<bool>> = Mutex::new(None);
+/// これより古い Voice の値は捨てる
+const SELF_VOICE_WINDOW: Duration = Duration::from_secs(120);
+/// Voice がこれを超えていれば自分が話しているとみなす
+const SELF_VOICE_THRESHOLD: f32 = 0.05;
+
+pub const DEFAULT_SEND_PORT: u16 = 9000;
+pub const DEFAULT_LISTEN_PORT: u16 = 9001;
+
+/// OSC の送信を止める (止めている間もログには残す)
+pub fn set_sends_disabled(disabled: bool) {
+    SENDS_DISABLED.store(disabled, Ordering::Relaxed);
 }
 
-impl std::fmt::Display for VRChatError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            VRChatError::SocketError(msg) => write!(f, "Socket error: {}", msg),
-            VRChatError::SendError(msg) => write!(f, "Send error: {}", msg),
-        }
+/// 送信待ちのページを送らないようにし、新しい世代を返す
+fn drop_pending_pages() -> u64 {
+    PAGE_GENERATION.fetch_add(1, Ordering::SeqCst) + 1
+}
+
+/// 以降に作る VRChatClient の送信先ポートを変える
+pub fn set_send_port(port: u16) {
+    OSC_SEND_PORT.store(port, Ordering::Relaxed);
+}
+
+#[cfg(test)]
+thread_local! {
+    /// テスト用の送信先ポート (並列に走る他のテストに影響しないようスレッドごとに持つ)
+    pub static TEST_SEND_PORT: std::cell::Cell<Option<u16>> = const { std::cell::Cell::new(None) };
+}
+
+fn send_port() -> u16 {
+    #[cfg(test)]
+    if let Some(port) = TEST_SEND_PORT.with(|p| p.get()) {
+        return port;
     }
+    OSC_SEND_PORT.load(Ordering::Relaxed)
 }
 
-impl std::error::Error for VRChatError {}
+#[derive(Debug, thiserror::Error)]
+pub enum VRChatError {
+    #[error("Socket error: {0}")]
+    SocketError(String),
+    #[error("Send error: {0}")]
+    SendError(String),
+}
 
 pub struct VRChatClient {
     pub target_addr: String,
@@ -28,12 +81,17 @@ pub struct VRChatClient {
 impl VRChatClient {
     pub fn new() -> Self {
         Self {
-            target_addr: "127.0.0.1:9000".to_string(), // VRChat OSC default port
+            target_addr: format!("127.0.0.1:{}", send_port()),
         }
     }
 
-    /// Send a message to VRChat via OSC
+    /// Send a message to VRChat via OSC (replacing any pages still waiting from send_paginated)
     pub fn send_message(&self, message: &str) -> Result<(), VRChatError> {
+        drop_pending_pages();
+        self.send_page(message)
+    }
+
+    fn send_page(&self, message: &str) -> Result<(), VRChatError> {
         println!("[VRChat OSC] Preparing to send message");
         println!("[VRChat OSC] Target: {}", self.target_addr);
         println!("[VRChat OSC] Message length: {} bytes", message.len());
@@ -48,14 +106,125 @@ impl VRChatClient {
             .unwrap_or_else(|_| "unknown".to_string());
         println!("[VRChat OSC] Socket bound to: {}", local_addr);
 
-        // Send the message
+        // Send the message together with typing=false and any queued parameters
         println!("[VRChat OSC] Encoding and sending OSC message...");
-        self.send_chatbox_input(&socket, message, true)?;
+        let mut messages = vec![chatbox_input(message, true), typing_message(false)];
+        messages.extend(take_parameters());
+        self.send_packet(&socket, bundle(messages))?;
+        note_chatbox_text(message);
 
         println!("[VRChat OSC] ✓ Message sent successfully");
         Ok(())
     }
 
+    /// Send a long message as several chatbox pages.
+    /// The first page is sent now; the rest follow in the background, each after the
+    /// previous page has been on screen long enough to read at `chars_per_sec`.
+    pub fn send_paginated(&self, message: &str, chars_per_sec: f32) -> Result<(), VRChatError> {
+        let pages = paginate(message, CHATBOX_MAX_CHARS);
+        let generation = drop_pending_pages();
+        let Some((first, rest)) = pages.split_first() else {
+            return self.send_page(message);
+        };
+        self.send_page(first)?;
+        if rest.is_empty() {
+            return Ok(());
+        }
+        let client = VRChatClient {
+            target_addr: self.target_addr.clone(),
+        };
+        let mut delay = display_duration(first, chars_per_sec);
+        let rest = rest.to_vec();
+        std::thread::spawn(move || {
+            for page in rest {
+                std::thread::sleep(delay);
+                if PAGE_GENERATION.load(Ordering::SeqCst) != generation {
+                    println!("[VRChat OSC] Newer message sent, remaining pages dropped");
+                    return;
+                }
+                if let Err(e) = client.send_page(&page) {
+                    eprintln!("[VRChat OSC] Failed to send page: {}", e);
+                    return;
+                }
+                delay = display_duration(&page, chars_per_sec);
+            }
+        });
+        Ok(())
+    }
+
+    /// Replace the chatbox text without the notification sound (for progressive updates)
+    pub fn update_message(&self, message: &str) -> Result<(), VRChatError> {
+        drop_pending_pages();
+        let socket = UdpSocket::bind("0.0.0.0:0")
+            .map_err(|e| VRChatError::SocketError(format!("Failed to bind socket: {}", e)))?;
+        self.send_chatbox_input(&socket, message, false)
+    }
+
+    /// Show or hide the typing indicator above the avatar
+    pub fn set_typing(&self, typing: bool) -> Result<(), VRChatError> {
+        let socket = UdpSocket::bind("0.0.0.0:0")
+            .map_err(|e| VRChatError::SocketError(format!("Failed to bind socket: {}", e)))?;
+        self.send_osc_message(&socket, typing_message(typing))
+    }
+
+    /// Clear the chatbox by sending an empty message (remaining pages are not sent afterwards)
+    pub fn clear_chatbox(&self) -> Result<(), VRChatError> {
+        drop_pending_pages();
+        let socket = UdpSocket::bind("0.0.0.0:0")
+            .map_err(|e| VRChatError::SocketError(format!("Failed to bind socket: {}", e)))?;
+        println!("[VRChat OSC] Clearing chatbox");
+        self.send_chatbox_input(&socket, "", false)
+    }
+
+    /// Clear the chatbox once nothing new has been sent for `delay`.
+    /// Later pages or messages push the clear back; only the latest request is kept.
+    pub fn clear_chatbox_after(&self, delay: Duration) {
+        let generation = CLEAR_GENERATION.fetch_add(1, Ordering::SeqCst) + 1;
+        let client = VRChatClient {
+            target_addr: self.target_addr.clone(),
+        };
+        std::thread::spawn(move || loop {
+            if CLEAR_GENERATION.load(Ordering::SeqCst) != generation {
+                return;
+            }
+            let last_sent = *LAST_CHATBOX_TEXT.lock().unwrap();
+            let wait = clear_wait(last_sent, delay, Instant::now());
+            if wait.is_zero() {
+                if let Err(e) = client.clear_chatbox() {
+                    eprintln!("[VRChat OSC] Failed to clear chatbox: {}", e);
+                }
+                return;
+            }
+            std::thread::sleep(wait);
+        });
+    }
+
+    /// Send several messages in one bundle so VRChat applies them together
+    pub fn send_bundle(&self, messages: Vec<OscMessage>) -> Result<(), VRChatError> {
+        let socket = UdpSocket::bind("0.0.0.0:0")
+            .map_err(|e| VRChatError::SocketError(format!("Failed to bind socket: {}", e)))?;
+        self.send_packet(&socket, bundle(messages))
+    }
+
+    /// Lower the typing indicator and send the queued parameters
+    /// when no chatbox message is going to carry them (e.g. the reply was suppressed)
+    pub fn finish_without_message(&self) -> Result<(), VRChatError> {
+        let mut messages = vec![typing_message(false)];
+        messages.extend(take_parameters());
+        self.send_bundle(messages)
+    }
+
+    /// Send an arbitrary OSC message (e.g. avatar parameters)
+    pub fn send_osc(&self, addr: &str, args: Vec<OscType>) -> Result<(), VRChatError> {
+        let socket = UdpSocket::bind("0.0.0.0:0")
+            .map_err(|e| VRChatError::SocketError(format!("Failed to bind socket: {}", e)))?;
+        let msg = OscMessage {
+            addr: addr.to_string(),
+            args,
+        };
+        self.send_osc_message(&socket, msg)
+    }
+
     /// Send chatbox input message
     fn send_chatbox_input(
         &self,
@@ -63,23 +232,46 @@ impl VRChatClient {
         text: &str,
         notify: bool,
     ) -> Result<(), VRChatError> {
-        let msg = OscMessage {
-            addr: "/chatbox/input".to_string(),
-            args: vec![
-                OscType::String(text.to_string()),
-                OscType::Bool(true),   // immediate
-                OscType::Bool(notify), // notify sound
-            ],
-        };
-
-        self.send_osc_message(socket, msg)
+        self.send_osc_message(socket, chatbox_input(text, notify))?;
+        note_chatbox_text(text);
+        Ok(())
     }
 
     /// Send an OSC message
     fn send_osc_message(&self, socket: &UdpSocket, msg: OscMessage) -> Result<(), VRChatError> {
-        println!("[VRChat OSC] Encoding OSC packet for address: {}", msg.addr);
-        let packet = OscPacket::Message(msg);
-        let msg_buf = encoder::encode(&packet)
+        self.send_packet(socket, OscPacket::Message(msg))
+    }
+
+    /// Send a message or bundle, logging each message it contains
+    fn send_packet(&self, socket: &UdpSocket, packet: OscPacket) -> Result<(), VRChatError> {
+        let messages = packet_messages(&packet);
+        let skipped = if crate::mock::is_enabled() {
+            Some("mock")
+        } else if SENDS_DISABLED.load(Ordering::Relaxed) {
+            Some("disabled")
+        } else {
+            None
+        };
+        if let Some(peer) = skipped {
+            // 送らずにログだけ残す
+            for msg in messages {
+                println!("[VRChat OSC] ({}) {} {:?}", peer, msg.addr, msg.args);
+                osc_log::record(Direction::Sent, peer, &msg.addr, &msg.args, None);
+            }
+            return Ok(());
+        }
+        let result = health::measure(Backend::Osc, || self.encode_and_send(socket, &packet));
+        let error = result.as_ref().err().map(|e| e.to_string());
+        for msg in messages {
+            osc_log::record(Direction::Sent, &self.target_addr, &msg.addr, &msg.args, error.clone());
+        }
+        result
+    }
+
+    fn encode_and_send(&self, socket: &UdpSocket, packet: &OscPacket) -> Result<(), VRChatError> {
+        let addrs: Vec<&str> = packet_messages(packet).iter().map(|m| m.addr.as_str()).collect();
+        println!("[VRChat OSC] Encoding OSC packet for address: {}", addrs.join(", "));
+        let msg_buf = encoder::encode(packet)
             .map_err(|e| VRChatError::SendError(format!("Failed to encode OSC message: {}", e)))?;
 
         println!("[VRChat OSC] Encoded {} bytes, sending to {}", msg_buf.len(), self.target_addr);
@@ -100,41 +292,66 @@ impl Default for VRChatClient {
     }
 }
 
+/// Bool / Int / Float で届くパラメータを bool として読む
+fn osc_bool(arg: Option<&OscType>) -> Option<bool> {
+    match arg? {
+        OscType::Bool(b) => Some(*b),
+        OscType::Int(i) => Some(*i != 0),
+        OscType::Float(f) => Some(*f != 0.0),
+        _ => None,
+    }
+}
+
+/// 前回から AFK が変わっていれば最新の値
+pub fn take_afk_change() -> Option<bool> {
+    PENDING_AFK.lock().unwrap_or_else(|e| e.into_inner()).take()
+}
+
 /// VRChat からの OSC パラメータを受信するリスナー
-/// 9001 ポートで Listen し、/avatar/parameters/MuteSelf を監視する
-pub fn start_mute_listener(sender: Sender<bool>) {
+/// port (既定 9001) で Listen し、/avatar/parameters/MuteSelf と AFK を監視する
+pub fn start_mute_listener(sender: Sender<bool>, port: u16) {
     std::thread::spawn(move || {
-        let socket = match UdpSocket::bind("0.0.0.0:9001") {
+        let socket = match UdpSocket::bind(("0.0.0.0", port)) {
             Ok(s) => s,
             Err(e) => {
-                eprintln!("[VRChat OSC Listener] Failed to bind port 9001: {}", e);
+                eprintln!("[VRChat OSC Listener] Failed to bind port {}: {}", port, e);
                 return;
             }
         };
         socket.set_read_timeout(Some(std::time::Duration::from_millis(500))).ok();
-        println!("[VRChat OSC Listener] Listening on port 9001 for MuteSelf parameter");
+        println!("[VRChat OSC Listener] Listening on port {} for MuteSelf parameter", port);
 
         let mut buf = [0u8; 65535];
         loop {
             match socket.recv_from(&mut buf) {
-                Ok((size, _addr)) => {
+                Ok((size, addr)) => {
                     match decoder::decode_udp(&buf[..size]) {
                         Ok((_, OscPacket::Message(msg))) => {
+                            osc_log::record(Direction::Received, &addr.to_string(), &msg.addr, &msg.args, None);
                             if msg.addr == "/avatar/parameters/MuteSelf" {
-                                let is_muted = match msg.args.first() {
-                                    Some(OscType::Bool(b)) => *b,
-                                    Some(OscType::Int(i)) => *i != 0,
-                                    Some(OscType::Float(f)) => *f != 0.0,
-                                    _ => continue,
+                                let Some(is_muted) = osc_bool(msg.args.first()) else {
+                                    continue;
                                 };
                                 println!("[VRChat OSC Listener] MuteSelf={}", is_muted);
                                 if sender.send(is_muted).is_err() {
                                     // チャンネルが閉じられた → 終了
                                     break;
                                 }
+                            } else if msg.addr == "/avatar/parameters/AFK" {
+                                if let Some(afk) = osc_bool(msg.args.first()) {
+                                    println!("[VRChat OSC Listener] AFK={}", afk);
+                                    *PENDING_AFK.lock().unwrap_or_else(|e| e.into_inner()) = Some(afk);
+                                }
+                            } else if msg.addr == "/avatar/parameters/Voice" {
+                                if let Some(OscType::Float(level)) = msg.args.first() {
+                                    record_self_voice(*level, Instant::now());
+                                }
                             }
                         }
-                        Ok((_, OscPacket::Bundle(_))) => {}
+                        Ok((_, OscPacket::Bundle(bundle))) => {
+                            let args = [OscType::Int(bundle.content.len() as i32)];
+                            osc_log::record(Direction::Received, &addr.to_string(), "#bundle", &args, None);
+                        }
                         Err(_) => {}
                     }
                 }
@@ -154,6 +371,228 @@ pub fn start_mute_listener(sender: Sender<bool>) {
     });
 }
 
+fn record_self_voice(level: f32, at: Instant) {
+    let mut samples = SELF_VOICE.lock().unwrap();
+    while samples.front().is_some_and(|(t, _)| at.duration_since(*t) > SELF_VOICE_WINDOW) {
+        samples.pop_front();
+    }
+    samples.push_back((at, level));
+}
+
+/// from から to までのうち、VRChat が自分の声を検出していた時間の割合 (Voice を受信していなければ None)
+pub fn self_voice_ratio(from: Instant, to: Instant) -> Option<f32> {
+    voice_active_ratio(&SELF_VOICE.lock().unwrap(), from, to)
+}
+
+/// Voice は変化したときだけ届くので、次の値が来るまで同じ値が続いたとみなして数える
+fn voice_active_ratio(samples: &VecDeque<(Instant, f32)>, from: Instant, to: Instant) -> Option<f32> {
+    if to <= from || !samples.iter().any(|(t, _)| *t < to) {
+        return None;
+    }
+    let mut level = samples.iter().rev().find(|(t, _)| *t <= from).map(|(_, l)| *l);
+    let mut cursor = from;
+    let mut active = Duration::ZERO;
+    for &(t, l) in samples.iter().filter(|(t, _)| *t > from && *t < to) {
+        if level.is_some_and(|v| v > SELF_VOICE_THRESHOLD) {
+            active += t - cursor;
+        }
+        cursor = t;
+        level = Some(l);
+    }
+    if level.is_some_and(|v| v > SELF_VOICE_THRESHOLD) {
+        active += to - cursor;
+    }
+    Some(active.as_secs_f32() / (to - from).as_secs_f32())
+}
+
+/// 最後に確認した時点で VRChat が起動していたか
+pub fn is_vrchat_running() -> bool {
+    VRCHAT_RUNNING.load(Ordering::Relaxed) || crate::mock::is_enabled()
+}
+
+/// VRChat.exe がプロセス一覧にあるか確認する。
+/// 確認自体に失敗した場合は送信を止めないよう Some を返さない
+fn check_vrchat_process() -> Option<bool> {
+    #[cfg(windows)]
+    let output = {
+        use std::os::windows::process::CommandExt;
+        const CREATE_NO_WINDOW: u32 = 0x08000000;
+        Command::new("tasklist")
+            .args(["/FI", "IMAGENAME eq VRChat.exe", "/NH"])
+            .creation_flags(CREATE_NO_WINDOW)
+            .output()
+    };
+    #[cfg(not(windows))]
+    let output = Command::new("pgrep").args(["-f", "VRChat"]).output();
+
+    let output = output.ok()?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    if cfg!(windows) {
+        Some(stdout.contains("VRChat.exe"))
+    } else {
+        Some(!stdout.trim().is_empty())
+    }
+}
+
+/// 5 秒ごとに VRChat プロセスの有無を確認するスレッドを起動する
+pub fn start_process_watcher() {
+    std::thread::spawn(move || loop {
+        if let Some(running) = check_vrchat_process() {
+            let was_running = VRCHAT_RUNNING.swap(running, Ordering::Relaxed);
+            if was_running != running {
+                println!("[VRChat] process running={}", running);
+            }
+        }
+        std::thread::sleep(Duration::from_secs(5));
+    });
+}
+
+/// VRChat のチャットボックスに表示できる最大文字数
+pub const CHATBOX_MAX_CHARS: usize = 144;
+/// 切り詰めても発話はこの文字数までは残す
+const MIN_USER_CHARS: usize = 10;
+
+/// 発話と返答を "You: …\nLisa: …" の 1 メッセージにまとめる。
+/// max_chars を超える場合はまず発話を切り詰め、それでも収まらなければ返答も切り詰める
+pub fn format_dialogue(user_label: &str, user: &str, agent_label: &str, reply: &str, max_chars: usize) -> String {
+    let user_prefix = format!("{}: ", user_label);
+    let agent_line = format!("{}: {}", agent_label, reply);
+    let fixed = user_prefix.chars().count() + 1 + agent_line.chars().count();
+
+    let user_budget = max_chars.saturating_sub(fixed).max(MIN_USER_CHARS);
+    let user = truncate_chars(user, user_budget);
+    let agent_budget = max_chars.saturating_sub(user_prefix.chars().count() + user.chars().count() + 1);
+    let agent_line = truncate_chars(&agent_line, agent_budget);
+    format!("{}{}\n{}", user_prefix, user, agent_line)
+}
+
+/// 1 ページを表示しておく最短の時間 (VRChat の連投制限より長く)
+const MIN_PAGE_DURATION: Duration = Duration::from_secs(3);
+
+/// 長い文を max_chars 以下のページに分ける。なるべく句読点・改行の後で区切る
+pub fn paginate(text: &str, max_chars: usize) -> Vec<String> {
+    let mut pages = Vec::new();
+    let mut current = String::new();
+    for piece in split_after_punctuation(text) {
+        if current.chars().count() + piece.chars().count() > max_chars && !current.is_empty() {
+            pages.push(current.trim().to_string());
+            current.clear();
+        }
+        current.push_str(&piece);
+        // 句読点のない長い塊は文字数で切る
+        while current.chars().count() > max_chars {
+            let head: String = current.chars().take(max_chars).collect();
+            current = current.chars().skip(max_chars).collect();
+            pages.push(head.trim().to_string());
+        }
+    }
+    if !current.trim().is_empty() {
+        pages.push(current.trim().to_string());
+    }
+    pages.retain(|p| !p.is_empty());
+    pages
+}
+
+fn split_after_punctuation(text: &str) -> Vec<String> {
+    let mut pieces = Vec::new();
+    let mut piece = String::new();
+    for c in text.chars() {
+        piece.push(c);
+        if matches!(c, '。' | '！' | '？' | '!' | '?' | '、' | '，' | ',' | '.' | '\n' | ' ') {
+            pieces.push(std::mem::take(&mut piece));
+        }
+    }
+    if !piece.is_empty() {
+        pieces.push(piece);
+    }
+    pieces
+}
+
+fn chatbox_input(text: &str, notify: bool) -> OscMessage {
+    OscMessage {
+        addr: "/chatbox/input".to_string(),
+        args: vec![
+            OscType::String(text.to_string()),
+            OscType::Bool(true),   // immediate
+            OscType::Bool(notify), // notify sound
+        ],
+    }
+}
+
+fn typing_message(typing: bool) -> OscMessage {
+    OscMessage {
+        addr: "/chatbox/typing".to_string(),
+        args: vec![OscType::Bool(typing)],
+    }
+}
+
+/// 空でないテキストを送った時刻を覚えておく (自動で消す時間の起点)
+fn note_chatbox_text(text: &str) {
+    if !text.is_empty() {
+        *LAST_CHATBOX_TEXT.lock().unwrap() = Some(Instant::now());
+    }
+}
+
+/// 1 つならそのまま、複数ならすぐに適用するバンドルにまとめる
+fn bundle(mut messages: Vec<OscMessage>) -> OscPacket {
+    if messages.len() == 1 {
+        return OscPacket::Message(messages.remove(0));
+    }
+    OscPacket::Bundle(OscBundle {
+        // (0, 1) は「すぐに」を表す timetag
+        timetag: OscTime { seconds: 0, fractional: 1 },
+        content: messages.into_iter().map(OscPacket::Message).collect(),
+    })
+}
+
+/// パケットに含まれるメッセージ (バンドルは中身を順にたどる)
+fn packet_messages(packet: &OscPacket) -> Vec<&OscMessage> {
+    match packet {
+        OscPacket::Message(msg) => vec![msg],
+        OscPacket::Bundle(bundle) => bundle.content.iter().flat_map(packet_messages).collect(),
+    }
+}
+
+/// アバターのパラメータを次のチャットボックスの送信に乗せて送るよう予約する
+/// (返答の表示と表情などの切り替わりが 1 つのパケットで同時に届く)
+pub fn queue_parameter(addr: &str, args: Vec<OscType>) {
+    coalesce(&mut PENDING_PARAMETERS.lock().unwrap(), OscMessage { addr: addr.to_string(), args });
+}
+
+/// 同じアドレスの古い値は捨てて最新の値だけ残す
+fn coalesce(queue: &mut Vec<OscMessage>, msg: OscMessage) {
+    queue.retain(|queued| queued.addr != msg.addr);
+    queue.push(msg);
+}
+
+fn take_parameters() -> Vec<OscMessage> {
+    std::mem::take(&mut *PENDING_PARAMETERS.lock().unwrap())
+}
+
+/// 最後の送信から delay 経つまでの残り時間 (0 なら今消してよい)
+fn clear_wait(last_sent: Option<Instant>, delay: Duration, now: Instant) -> Duration {
+    match last_sent {
+        Some(sent) => (sent + delay).saturating_duration_since(now),
+        None => Duration::ZERO,
+    }
+}
+
+/// ページを読み終えるのにかかる時間 (chars_per_sec 文字/秒、最短 MIN_PAGE_DURATION)
+pub fn display_duration(page: &str, chars_per_sec: f32) -> Duration {
+    let secs = page.chars().count() as f32 / chars_per_sec.max(1.0);
+    Duration::from_secs_f32(secs).max(MIN_PAGE_DURATION)
+}
+
+/// max_chars を超えていれば末尾を "…" にして切り詰める
+fn truncate_chars(text: &str, max_chars: usize) -> String {
+    if text.chars().count() <= max_chars {
+        return text.to_string();
+    }
+    let mut truncated: String = text.chars().take(max_chars.saturating_sub(1)).collect();
+    truncated.push('…');
+    truncated
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -163,4 +602,102 @@ mod tests {
         let client = VRChatClient::new();
         assert_eq!(client.target_addr, "127.0.0.1:9000");
     }
+
+    #[test]
+    fn test_clear_wait() {
+        let now = Instant::now();
+        let delay = Duration::from_secs(30);
+        assert_eq!(clear_wait(None, delay, now), Duration::ZERO);
+        assert_eq!(clear_wait(Some(now), delay, now + Duration::from_secs(10)), Duration::from_secs(20));
+        assert_eq!(clear_wait(Some(now), delay, now + Duration::from_secs(40)), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_bundle_and_coalesce() {
+        let param = |addr: &str, value: i32| OscMessage {
+            addr: addr.to_string(),
+            args: vec![OscType::Int(value)],
+        };
+        let mut queue = Vec::new();
+        coalesce(&mut queue, param("/avatar/parameters/Mood", 1));
+        coalesce(&mut queue, param("/avatar/parameters/Ears", 1));
+        coalesce(&mut queue, param("/avatar/parameters/Mood", 3));
+        assert_eq!(queue, vec![param("/avatar/parameters/Ears", 1), param("/avatar/parameters/Mood", 3)]);
+
+        assert!(matches!(bundle(vec![param("/a", 1)]), OscPacket::Message(_)));
+        let mut messages = vec![chatbox_input("hi", true)];
+        messages.extend(queue);
+        let packet = bundle(messages);
+        let addrs: Vec<&str> = packet_messages(&packet).iter().map(|m| m.addr.as_str()).collect();
+        assert_eq!(addrs, vec!["/chatbox/input", "/avatar/parameters/Ears", "/avatar/parameters/Mood"]);
+
+        // 1 つのデータグラムで届く
+        let sink = crate::test_support::OscSink::bind();
+        let client = VRChatClient {
+            target_addr: format!("127.0.0.1:{}", sink.port()),
+        };
+        client.send_bundle(vec![chatbox_input("hi", true), param("/a", 2)]).unwrap();
+        assert_eq!(sink.received().len(), 2);
+    }
+
+    #[test]
+    fn test_osc_bool() {
+        assert_eq!(osc_bool(Some(&OscType::Bool(true))), Some(true));
+        assert_eq!(osc_bool(Some(&OscType::Int(0))), Some(false));
+        assert_eq!(osc_bool(Some(&OscType::Float(1.0))), Some(true));
+        assert_eq!(osc_bool(Some(&OscType::String("x".to_string()))), None);
+        assert_eq!(osc_bool(None), None);
+    }
+
+    #[test]
+    fn test_format_dialogue() {
+        assert_eq!(format_dialogue("You", "こんにちは", "Lisa", "やあ", 144), "You: こんにちは\nLisa: やあ");
+
+        // 発話から切り詰める
+        let text = format_dialogue("You", &"あ".repeat(30), "Lisa", &"い".repeat(20), 50);
+        assert_eq!(text, format!("You: {}…\nLisa: {}", "あ".repeat(17), "い".repeat(20)));
+
+        // 返答だけで溢れる場合は発話を最低限残して返答も切り詰める
+        let text = format_dialogue("You", &"あ".repeat(30), "Lisa", &"い".repeat(100), 40);
+        assert_eq!(text.chars().count(), 40);
+        assert!(text.starts_with(&format!("You: {}…\n", "あ".repeat(9))));
+        assert!(text.ends_with('…'));
+    }
+
+    #[test]
+    fn test_paginate() {
+        assert_eq!(paginate("こんにちは。", 144), vec!["こんにちは。"]);
+
+        let text = format!("{}。{}！{}", "あ".repeat(8), "い".repeat(5), "う".repeat(25));
+        let pages = paginate(&text, 12);
+        assert_eq!(
+            pages,
+            vec![
+                format!("{}。", "あ".repeat(8)),
+                format!("{}！", "い".repeat(5)),
+                "う".repeat(12),
+                "う".repeat(12),
+                "う".to_string(),
+            ]
+        );
+        assert!(pages.iter().all(|p| p.chars().count() <= 12));
+    }
+
+    #[test]
+    fn test_display_duration() {
+        assert_eq!(display_duration("短い", 8.0), MIN_PAGE_DURATION);
+        assert_eq!(display_duration(&"あ".repeat(80), 8.0), Duration::from_secs(10));
+    }
+
+    #[test]
+    fn test_voice_active_ratio() {
+        let start = Instant::now();
+        let at = |secs: u64| start + Duration::from_secs(secs);
+        let samples = VecDeque::from([(at(0), 0.0), (at(2), 0.5), (at(6), 0.0)]);
+
+        assert_eq!(voice_active_ratio(&samples, at(0), at(10)), Some(0.4));
+        assert_eq!(voice_active_ratio(&samples, at(3), at(5)), Some(1.0));
+        assert_eq!(voice_active_ratio(&samples, at(7), at(9)), Some(0.0));
+        assert_eq!(voice_active_ratio(&VecDeque::new(), at(0), at(10)), None);
+    }
 }
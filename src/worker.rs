@@ -0,0 +1,104 @@
+//! パイプラインを順番に実行する常駐スレッド
+//!
+//! 発話ごとにスレッドを作らず、1 本のスレッドがジョブを受け取った順に処理する。
+//! まだ始まっていないジョブは取り消せる (実行中のものは止めず、結果を呼び出し側で捨てる)。
+//! 取り消したジョブも `cancelled = true` で呼ぶので、預けたもの (ElizaClient など) を返せる
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Sender};
+use std::sync::Arc;
+
+/// ジョブの取り消しフラグ
+#[derive(Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+type Job = Box<dyn FnOnce(bool) + Send>;
+
+pub struct PipelineWorker {
+    sender: Sender<(Job, CancelToken)>,
+    /// まだ終わっていないかもしれないジョブの取り消しフラグ (cancel_all で使う)
+    tokens: Vec<CancelToken>,
+}
+
+impl PipelineWorker {
+    pub fn start() -> Self {
+        let (sender, receiver) = channel::<(Job, CancelToken)>();
+        std::thread::Builder::new()
+            .name("pipeline-worker".to_string())
+            .spawn(move || {
+                for (job, token) in receiver {
+                    let cancelled = token.is_cancelled();
+                    if cancelled {
+                        println!("[Worker] Skipping cancelled job");
+                    }
+                    job(cancelled);
+                }
+            })
+            .expect("failed to start pipeline worker");
+        Self {
+            sender,
+            tokens: Vec::new(),
+        }
+    }
+
+    /// ジョブを順番待ちに入れる (job の引数は取り消されたか。取り消されていたら後始末だけする)
+    pub fn submit(&mut self, job: impl FnOnce(bool) + Send + 'static) -> CancelToken {
+        let token = CancelToken::default();
+        self.tokens.retain(|t| Arc::strong_count(&t.0) > 1);
+        self.tokens.push(token.clone());
+        if self.sender.send((Box::new(job), token.clone())).is_err() {
+            eprintln!("[Worker] Pipeline worker has stopped");
+        }
+        token
+    }
+
+    /// 順番待ちのジョブをすべて取り消す
+    pub fn cancel_all(&mut self) {
+        for token in self.tokens.drain(..) {
+            token.cancel();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::channel;
+    use std::time::Duration;
+
+    #[test]
+    fn test_worker_order_and_cancel() {
+        let mut worker = PipelineWorker::start();
+        let (sender, receiver) = channel();
+        let (release, gate) = channel::<()>();
+
+        let first = sender.clone();
+        worker.submit(move |_| {
+            let _ = gate.recv();
+            first.send(1).unwrap();
+        });
+        let second = sender.clone();
+        let cancelled = worker.submit(move |cancelled| second.send(if cancelled { -2 } else { 2 }).unwrap());
+        let third = sender.clone();
+        worker.submit(move |_| third.send(3).unwrap());
+
+        cancelled.cancel();
+        release.send(()).unwrap();
+        // 取り消したジョブは後始末だけする
+        let received: Vec<i32> = (0..3)
+            .map(|_| receiver.recv_timeout(Duration::from_secs(5)).unwrap())
+            .collect();
+        assert_eq!(received, vec![1, -2, 3]);
+        assert!(receiver.recv_timeout(Duration::from_millis(100)).is_err());
+    }
+}